@@ -0,0 +1,252 @@
+//! `--api-port`: a read-only JSON API over the live capture, for custom
+//! frontends. It listens on localhost only and answers from the latest
+//! snapshot, the same one the dashboard draws, so a request never holds up
+//! capture or analysis.
+//!
+//! - `/stats`: totals, rates, protocols, top talkers and drop counts
+//! - `/connections`: the flow table, busiest first
+//! - `/alerts`: the retained threat alerts, oldest first
+//! - `/packets?limit=N`: the last N retained packets (all of them without `limit`)
+//!
+//! Binding to localhost doesn't keep out a web page open in the user's
+//! browser, which can send requests there too. So responses carry no CORS
+//! header and browsers keep other sites from reading them, unless
+//! `--api-cors-origin` names the one origin allowed to.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::error::{PacketSnifferError, Result};
+use crate::fingerprint::OsGuess;
+use crate::pipeline::Snapshot;
+use crate::NetworkStats;
+
+/// How long a client gets to send its request and read the answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Talkers listed by `/stats`
+const TOP_TALKERS: usize = 10;
+
+/// Request line and headers longer than this are refused
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// Also the body of the `--summary-only` document.
+#[derive(Serialize)]
+pub struct StatsSummary<'a> {
+    duration_secs: u64,
+    total_packets: usize,
+    total_bytes: usize,
+    /// From and to this host; transit and local traffic is in neither
+    uploaded_bytes: usize,
+    downloaded_bytes: usize,
+    /// IP packets with no direction, all of them for a pcap stream
+    undirected_packets: usize,
+    /// Averaged over `rolling_window_secs`
+    bytes_per_sec: f64,
+    packets_per_sec: f64,
+    upload_bytes_per_sec: f64,
+    download_bytes_per_sec: f64,
+    rolling_window_secs: u64,
+    peak_bytes_per_sec: f64,
+    peak_packets_per_sec: f64,
+    /// Active connections, and those closed after the idle timeout
+    connections: usize,
+    closed_connections: usize,
+    /// Over closed and active connections; `null` before the first
+    connection_duration_avg_secs: Option<f64>,
+    connection_duration_max_secs: Option<f64>,
+    unique_sources: usize,
+    unique_destinations: usize,
+    protocols: &'a HashMap<String, usize>,
+    protocol_bytes: &'a HashMap<String, usize>,
+    top_talkers: Vec<TalkerSummary<'a>>,
+    /// Alerts retained, and every alert raised by level
+    alerts: usize,
+    alerts_by_level: &'a HashMap<String, usize>,
+    dropped: DropSummary,
+    /// `--sample`'s N; the counts above are of sampled packets, unscaled
+    sample_rate: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct TalkerSummary<'a> {
+    ip: &'a str,
+    packets: usize,
+    bytes: usize,
+    /// Heuristic, from the TTL and TCP window the host sends with
+    os_guess: Option<&'a OsGuess>,
+}
+
+#[derive(Serialize)]
+struct DropSummary {
+    rate_limit: usize,
+    overload: usize,
+    /// `null` where the kernel doesn't report drops
+    kernel: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ApiError<'a> {
+    error: &'a str,
+}
+
+impl<'a> StatsSummary<'a> {
+    pub fn new(stats: &'a NetworkStats) -> Self {
+        let (bytes_per_sec, packets_per_sec) = stats.rolling_rate();
+        let (upload_bytes_per_sec, download_bytes_per_sec) = stats.rolling_direction_rate();
+        let mut top_talkers: Vec<TalkerSummary> = stats.top_talkers.iter()
+            .map(|(ip, talker)| TalkerSummary { ip, packets: talker.packets, bytes: talker.bytes, os_guess: stats.os_guesses.get(ip) })
+            .collect();
+        top_talkers.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.ip.cmp(b.ip)));
+        top_talkers.truncate(TOP_TALKERS);
+        let durations = stats.connection_durations();
+
+        StatsSummary {
+            duration_secs: stats.elapsed().as_secs(),
+            total_packets: stats.total_packets,
+            total_bytes: stats.total_bytes,
+            uploaded_bytes: stats.uploaded.bytes,
+            downloaded_bytes: stats.downloaded.bytes,
+            undirected_packets: stats.undirected_packets,
+            bytes_per_sec,
+            packets_per_sec,
+            upload_bytes_per_sec,
+            download_bytes_per_sec,
+            rolling_window_secs: stats.rolling_window_secs,
+            peak_bytes_per_sec: stats.peak_bandwidth,
+            peak_packets_per_sec: stats.peak_packets_per_sec,
+            connections: stats.current_connections,
+            closed_connections: stats.closed_connections.count,
+            connection_duration_avg_secs: durations.map(|(average, _)| average),
+            connection_duration_max_secs: durations.map(|(_, longest)| longest),
+            unique_sources: stats.top_talkers.len(),
+            unique_destinations: stats.top_receivers.len(),
+            protocols: &stats.protocol_counts,
+            protocol_bytes: &stats.protocol_bytes,
+            top_talkers,
+            alerts: stats.threat_alerts.len(),
+            alerts_by_level: &stats.alert_counts,
+            dropped: DropSummary {
+                rate_limit: stats.dropped_packets,
+                overload: stats.capture_overflow,
+                kernel: stats.kernel_dropped,
+            },
+            sample_rate: stats.sample_rate,
+        }
+    }
+}
+
+/// Binds 127.0.0.1:`port` and answers requests on a thread of its own until
+/// the process exits. Clients are served one at a time.
+pub fn serve(port: u16, cors_origin: Option<String>, snapshots: watch::Receiver<Arc<Snapshot>>) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| PacketSnifferError::NetworkError(format!("Failed to listen on 127.0.0.1:{} for --api-port: {}", port, e)))?;
+
+    std::thread::spawn(move || {
+        for client in listener.incoming().flatten() {
+            // A client that hangs up or stalls only loses its own answer
+            let _ = respond(client, cors_origin.as_deref(), &snapshots);
+        }
+    });
+    Ok(())
+}
+
+fn respond(client: TcpStream, cors_origin: Option<&str>, snapshots: &watch::Receiver<Arc<Snapshot>>) -> std::io::Result<()> {
+    client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new((&client).take(MAX_REQUEST_BYTES as u64));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Headers aren't needed, but a client may wait until they've been read
+    let mut complete = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        if header.trim().is_empty() {
+            complete = true;
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, body) = if !complete {
+        ("400 Bad Request", error("incomplete or oversized request"))
+    } else if method != "GET" {
+        ("405 Method Not Allowed", error("only GET is supported"))
+    } else {
+        // Cloning the Arc releases the snapshot lock straight away
+        let snapshot = snapshots.borrow().clone();
+        route(path, query, &snapshot)
+    };
+
+    let cors = cors_origin.map_or(String::new(), |origin| format!("Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n", origin));
+    let mut client = &client;
+    write!(
+        client,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        body.len(),
+        cors
+    )?;
+    client.write_all(&body)?;
+    client.flush()
+}
+
+/// `--api-cors-origin`: a single origin such as `http://localhost:3000`,
+/// scheme, host and optional port. `*` isn't accepted, since any page could
+/// then read the capture.
+pub fn parse_origin(value: &str) -> std::result::Result<String, String> {
+    let invalid = || format!("'{}' is not an origin such as http://localhost:3000", value);
+    let url = reqwest::Url::parse(value).map_err(|_| invalid())?;
+    let origin = url.origin().ascii_serialization();
+    if !matches!(url.scheme(), "http" | "https") || origin != value.trim_end_matches('/') {
+        return Err(invalid());
+    }
+    Ok(origin)
+}
+
+fn route(path: &str, query: &str, snapshot: &Snapshot) -> (&'static str, Vec<u8>) {
+    match path.trim_end_matches('/') {
+        "/stats" => ("200 OK", json(&StatsSummary::new(&snapshot.stats))),
+        "/connections" => ("200 OK", json(&crate::flow_records(&crate::busiest_flows(&snapshot.stats.connections)))),
+        "/alerts" => ("200 OK", json(&crate::alert_records(&snapshot.stats.threat_alerts))),
+        "/packets" => match packet_limit(query) {
+            Ok(limit) => {
+                let packets = &snapshot.packets;
+                ("200 OK", json(&packets[packets.len().saturating_sub(limit.unwrap_or(packets.len()))..]))
+            }
+            Err(message) => ("400 Bad Request", error(&message)),
+        },
+        _ => ("404 Not Found", error("unknown endpoint; try /stats, /connections, /alerts or /packets?limit=N")),
+    }
+}
+
+/// `limit=N` from the query string, if given.
+fn packet_limit(query: &str) -> std::result::Result<Option<usize>, String> {
+    for pair in query.split('&') {
+        if let Some(value) = pair.strip_prefix("limit=") {
+            return value.parse().map(Some).map_err(|_| format!("limit must be a non-negative number, got '{}'", value));
+        }
+    }
+    Ok(None)
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    // Nothing served has non-string map keys, so this can't fail
+    serde_json::to_vec(value).unwrap_or_default()
+}
+
+fn error(message: &str) -> Vec<u8> {
+    json(&ApiError { error: message })
+}
@@ -0,0 +1,60 @@
+use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket};
+use pnet::packet::ethernet::EtherTypes;
+use serde::{Deserialize, Serialize};
+
+/// An Ethernet/IPv4 ARP message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArpInfo {
+    pub operation: String,
+    pub sender_mac: String,
+    pub sender_ip: String,
+    pub target_mac: String,
+    pub target_ip: String,
+}
+
+impl ArpInfo {
+    /// A host announcing its own address, either as a request or reply
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_ip == self.target_ip
+    }
+
+    /// e.g. "Who has 10.0.0.1? Tell 10.0.0.5"
+    pub fn summary(&self) -> String {
+        if self.is_gratuitous() {
+            format!("Gratuitous ARP: {} is at {}", self.sender_ip, self.sender_mac)
+        } else if self.operation == "request" {
+            format!("Who has {}? Tell {}", self.target_ip, self.sender_ip)
+        } else if self.operation == "reply" {
+            format!("{} is at {}", self.sender_ip, self.sender_mac)
+        } else {
+            format!("ARP {} from {} ({})", self.operation, self.sender_ip, self.sender_mac)
+        }
+    }
+}
+
+/// Decodes the payload of an ARP ethernet frame. Non-Ethernet/IPv4 ARP
+/// (e.g. InfiniBand) yields `None`.
+pub fn parse(payload: &[u8]) -> Option<ArpInfo> {
+    let arp = ArpPacket::new(payload)?;
+    if arp.get_hardware_type() != ArpHardwareTypes::Ethernet
+        || arp.get_protocol_type() != EtherTypes::Ipv4
+        || arp.get_hw_addr_len() != 6
+        || arp.get_proto_addr_len() != 4
+    {
+        return None;
+    }
+
+    let operation = match arp.get_operation() {
+        ArpOperations::Request => "request".to_string(),
+        ArpOperations::Reply => "reply".to_string(),
+        other => format!("op {}", other.0),
+    };
+
+    Some(ArpInfo {
+        operation,
+        sender_mac: arp.get_sender_hw_addr().to_string(),
+        sender_ip: arp.get_sender_proto_addr().to_string(),
+        target_mac: arp.get_target_hw_addr().to_string(),
+        target_ip: arp.get_target_proto_addr().to_string(),
+    })
+}
@@ -0,0 +1,78 @@
+//! `--export-bandwidth`: the per-second bandwidth series as CSV. Rows are
+//! appended as each second completes, so the file covers the whole capture
+//! (the in-memory history only keeps the most recent points) and survives a
+//! killed process.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::error::{PacketSnifferError, Result};
+use crate::export::ExportWriter;
+use crate::style;
+use crate::BandwidthPoint;
+
+pub struct BandwidthWriter {
+    path: String,
+    writer: ExportWriter,
+    last_written: Option<DateTime<Utc>>,
+    records: usize,
+}
+
+impl BandwidthWriter {
+    pub fn create(path: &str) -> Result<Self> {
+        let mut writer = ExportWriter::create(path)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to create bandwidth file {}: {}", path, e)))?;
+        // Every field is a number or an RFC 3339 timestamp, so nothing needs quoting
+        writeln!(writer, "timestamp,bytes_per_sec,packets_per_sec")
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV header: {}", e)))?;
+
+        Ok(BandwidthWriter {
+            path: path.to_string(),
+            writer,
+            last_written: None,
+            records: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Appends the points in `history` that haven't been written yet. Called
+    /// after every packet, but only does work when a second has completed.
+    pub fn write_new(&mut self, history: &VecDeque<BandwidthPoint>) -> Result<()> {
+        let new_points = history.iter()
+            .filter(|point| self.last_written.is_none_or(|last| point.timestamp > last))
+            .count();
+        if new_points == 0 {
+            return Ok(());
+        }
+
+        for point in history.iter().skip(history.len() - new_points) {
+            self.write_point(point)?;
+        }
+        self.writer.flush()
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to flush bandwidth file: {}", e)))
+    }
+
+    /// Writes what's left of `history`, then completes the file.
+    pub fn finish(&mut self, history: &VecDeque<BandwidthPoint>) -> Result<()> {
+        self.write_new(history)?;
+        self.writer.finish()
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to finish bandwidth file: {}", e)))
+    }
+
+    fn write_point(&mut self, point: &BandwidthPoint) -> Result<()> {
+        writeln!(self.writer, "{},{:.0},{:.0}", style::rfc3339(&point.timestamp), point.bytes_per_sec, point.packets_per_sec)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write bandwidth record: {}", e)))?;
+
+        self.last_written = Some(point.timestamp);
+        self.records += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,788 @@
+//! Support for `--bpf` capture filters written in pcap-filter syntax.
+//!
+//! The expression is parsed once at startup and compiled to a classic BPF
+//! program. On Linux a live capture attaches it to the packet socket with
+//! `SO_ATTACH_FILTER` (see `packet_socket`), so rejected frames are dropped in
+//! the kernel and never copied to us. Everything is also evaluated here
+//! against the raw frame, after reassembly: that decides for IP fragments,
+//! which the kernel program lets through, and is the only filtering for
+//! `--read-stdin`, other platforms, and a program too long for the kernel.
+//! The supported subset covers what people actually type: protocol names,
+//! `[src|dst] host`, `[src|dst] net`, `[tcp|udp] [src|dst] port`,
+//! `portrange`, `less`/`greater`, combined with `and`/`or`/`not` and
+//! parentheses by the grammar `--filter` uses too.
+
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::error::{PacketSnifferError, Result};
+use crate::expr::{self, Expr, Grammar, Parser};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_ARP: u16 = 0x0806;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+const IPPROTO_SCTP: u8 = 132;
+
+#[derive(Debug, Clone)]
+pub struct BpfFilter {
+    expression: String,
+    program: Expr<Primitive>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Src,
+    Dst,
+    Either,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Proto {
+    Ip,
+    Ip6,
+    Arp,
+    Tcp,
+    Udp,
+    Icmp,
+    Icmp6,
+    Sctp,
+}
+
+#[derive(Debug, Clone)]
+enum Primitive {
+    Proto(Proto),
+    Host(Direction, IpAddr),
+    Net(Direction, IpNetwork),
+    Port(Option<Proto>, Direction, u16, u16),
+    Less(usize),
+    Greater(usize),
+}
+
+/// The few header fields the filter needs, pulled out of the raw frame once.
+struct Frame<'a> {
+    len: usize,
+    ethertype: u16,
+    src: Option<IpAddr>,
+    dst: Option<IpAddr>,
+    ip_proto: Option<u8>,
+    transport: Option<&'a [u8]>,
+}
+
+impl BpfFilter {
+    pub fn compile(expression: &str) -> Result<Self> {
+        let program = expr::parse::<PcapSyntax>(expression)?;
+        Ok(BpfFilter {
+            expression: expression.to_string(),
+            program,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn matches(&self, packet: &[u8]) -> bool {
+        let frame = Frame::parse(packet);
+        self.program.evaluate(&|primitive| evaluate(primitive, &frame))
+    }
+}
+
+/// pcap-filter primitives for the shared grammar
+struct PcapSyntax;
+
+impl Grammar for PcapSyntax {
+    type Primitive = Primitive;
+
+    fn invalid(message: String) -> PacketSnifferError {
+        PacketSnifferError::InvalidBpfFilter(message)
+    }
+
+    fn primitive(parser: &mut Parser<Self>) -> Result<Expr<Primitive>> {
+        let offset = parser.offset();
+
+        let proto = parser.peek_word().and_then(parse_proto);
+        if proto.is_some() {
+            parser.advance();
+        }
+
+        let direction = match parser.peek_word() {
+            Some("src") => Direction::Src,
+            Some("dst") => Direction::Dst,
+            _ => Direction::Either,
+        };
+        if direction != Direction::Either {
+            parser.advance();
+        }
+
+        let keyword = match parser.peek_word() {
+            Some(word @ ("host" | "net" | "port" | "portrange" | "less" | "greater")) => word.to_string(),
+            _ if direction != Direction::Either => {
+                return Err(Self::invalid(format!("expected host, net or port at position {}", parser.offset())));
+            }
+            _ => {
+                return match proto {
+                    Some(proto) => Ok(Expr::Primitive(Primitive::Proto(proto))),
+                    None => {
+                        let (word, _) = parser.value("a filter primitive")?;
+                        Err(Self::invalid(format!("unknown primitive '{}' at position {}", word, offset)))
+                    }
+                };
+            }
+        };
+        parser.advance();
+
+        let (value, value_offset) = parser.value(&format!("a value after '{}'", keyword))?;
+        let bad_value = || Self::invalid(format!("invalid {} '{}' at position {}", keyword, value, value_offset));
+
+        let primitive = match keyword.as_str() {
+            "host" => Primitive::Host(direction, value.parse().map_err(|_| bad_value())?),
+            "net" => Primitive::Net(direction, value.parse().map_err(|_| bad_value())?),
+            "port" => {
+                let port: u16 = value.parse().map_err(|_| bad_value())?;
+                Primitive::Port(proto, direction, port, port)
+            }
+            "portrange" => {
+                let (low, high) = value.split_once('-').ok_or_else(bad_value)?;
+                let low: u16 = low.parse().map_err(|_| bad_value())?;
+                let high: u16 = high.parse().map_err(|_| bad_value())?;
+                if low > high {
+                    return Err(bad_value());
+                }
+                Primitive::Port(proto, direction, low, high)
+            }
+            "less" => Primitive::Less(value.parse().map_err(|_| bad_value())?),
+            _ => Primitive::Greater(value.parse().map_err(|_| bad_value())?),
+        };
+
+        // `ip6 host x` or `tcp net y` also restricts the protocol
+        match (proto, &primitive) {
+            (Some(proto), Primitive::Host(..) | Primitive::Net(..)) => Ok(Expr::and(
+                Expr::Primitive(Primitive::Proto(proto)),
+                Expr::Primitive(primitive),
+            )),
+            _ => Ok(Expr::Primitive(primitive)),
+        }
+    }
+}
+
+fn parse_proto(word: &str) -> Option<Proto> {
+    match word {
+        "ip" => Some(Proto::Ip),
+        "ip6" => Some(Proto::Ip6),
+        "arp" => Some(Proto::Arp),
+        "tcp" => Some(Proto::Tcp),
+        "udp" => Some(Proto::Udp),
+        "icmp" => Some(Proto::Icmp),
+        "icmp6" => Some(Proto::Icmp6),
+        "sctp" => Some(Proto::Sctp),
+        _ => None,
+    }
+}
+
+impl<'a> Frame<'a> {
+    fn parse(packet: &'a [u8]) -> Self {
+        let mut frame = Frame {
+            len: packet.len(),
+            ethertype: 0,
+            src: None,
+            dst: None,
+            ip_proto: None,
+            transport: None,
+        };
+
+        if packet.len() < 14 {
+            return frame;
+        }
+        frame.ethertype = u16::from_be_bytes([packet[12], packet[13]]);
+        let l3 = &packet[14..];
+
+        match frame.ethertype {
+            ETHERTYPE_IPV4 if l3.len() >= 20 => {
+                let header_len = ((l3[0] & 0x0F) as usize) * 4;
+                frame.src = Some(IpAddr::V4(Ipv4Addr::new(l3[12], l3[13], l3[14], l3[15])));
+                frame.dst = Some(IpAddr::V4(Ipv4Addr::new(l3[16], l3[17], l3[18], l3[19])));
+                frame.ip_proto = Some(l3[9]);
+
+                // Like libpcap, only the first fragment carries transport ports
+                let fragment_offset = u16::from_be_bytes([l3[6], l3[7]]) & 0x1FFF;
+                if fragment_offset == 0 && header_len >= 20 && l3.len() >= header_len {
+                    frame.transport = Some(&l3[header_len..]);
+                }
+            }
+            ETHERTYPE_IPV6 if l3.len() >= 40 => {
+                let mut src = [0u8; 16];
+                let mut dst = [0u8; 16];
+                src.copy_from_slice(&l3[8..24]);
+                dst.copy_from_slice(&l3[24..40]);
+                frame.src = Some(IpAddr::V6(Ipv6Addr::from(src)));
+                frame.dst = Some(IpAddr::V6(Ipv6Addr::from(dst)));
+                frame.ip_proto = Some(l3[6]);
+                frame.transport = Some(&l3[40..]);
+            }
+            _ => {}
+        }
+
+        frame
+    }
+
+    fn ports(&self, proto: Option<Proto>) -> Option<(u16, u16)> {
+        let ip_proto = self.ip_proto?;
+        let allowed = match proto {
+            Some(Proto::Tcp) => ip_proto == IPPROTO_TCP,
+            Some(Proto::Udp) => ip_proto == IPPROTO_UDP,
+            Some(Proto::Sctp) => ip_proto == IPPROTO_SCTP,
+            Some(Proto::Ip) => self.ethertype == ETHERTYPE_IPV4 && matches!(ip_proto, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP),
+            Some(Proto::Ip6) => self.ethertype == ETHERTYPE_IPV6 && matches!(ip_proto, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP),
+            Some(_) => false,
+            None => matches!(ip_proto, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP),
+        };
+        if !allowed {
+            return None;
+        }
+
+        let transport = self.transport?;
+        if transport.len() < 4 {
+            return None;
+        }
+        Some((
+            u16::from_be_bytes([transport[0], transport[1]]),
+            u16::from_be_bytes([transport[2], transport[3]]),
+        ))
+    }
+}
+
+fn evaluate(primitive: &Primitive, frame: &Frame) -> bool {
+    match primitive {
+        Primitive::Proto(proto) => match proto {
+            Proto::Ip => frame.ethertype == ETHERTYPE_IPV4,
+            Proto::Ip6 => frame.ethertype == ETHERTYPE_IPV6,
+            Proto::Arp => frame.ethertype == ETHERTYPE_ARP,
+            Proto::Tcp => frame.ip_proto == Some(IPPROTO_TCP),
+            Proto::Udp => frame.ip_proto == Some(IPPROTO_UDP),
+            Proto::Icmp => frame.ethertype == ETHERTYPE_IPV4 && frame.ip_proto == Some(IPPROTO_ICMP),
+            Proto::Icmp6 => frame.ethertype == ETHERTYPE_IPV6 && frame.ip_proto == Some(IPPROTO_ICMPV6),
+            Proto::Sctp => frame.ip_proto == Some(IPPROTO_SCTP),
+        },
+        Primitive::Host(direction, ip) => {
+            matches_direction(*direction, frame.src.as_ref() == Some(ip), frame.dst.as_ref() == Some(ip))
+        }
+        Primitive::Net(direction, net) => matches_direction(
+            *direction,
+            frame.src.is_some_and(|ip| net.contains(ip)),
+            frame.dst.is_some_and(|ip| net.contains(ip)),
+        ),
+        Primitive::Port(proto, direction, low, high) => match frame.ports(*proto) {
+            Some((src, dst)) => {
+                let range = *low..=*high;
+                matches_direction(*direction, range.contains(&src), range.contains(&dst))
+            }
+            None => false,
+        },
+        Primitive::Less(size) => frame.len <= *size,
+        Primitive::Greater(size) => frame.len >= *size,
+    }
+}
+
+fn matches_direction(direction: Direction, src: bool, dst: bool) -> bool {
+    match direction {
+        Direction::Src => src,
+        Direction::Dst => dst,
+        Direction::Either => src || dst,
+    }
+}
+
+/// One classic BPF instruction, laid out like the kernel's `struct sock_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+// Opcodes from linux/filter.h, class | size | mode or class | op | source
+const LD_W_ABS: u16 = 0x20;
+const LD_H_ABS: u16 = 0x28;
+const LD_B_ABS: u16 = 0x30;
+const LD_H_IND: u16 = 0x48;
+const LD_W_LEN: u16 = 0x80;
+const LDX_B_MSH: u16 = 0xb1;
+const AND_K: u16 = 0x54;
+const JA: u16 = 0x05;
+const JEQ_K: u16 = 0x15;
+const JGT_K: u16 = 0x25;
+const JGE_K: u16 = 0x35;
+const JSET_K: u16 = 0x45;
+const RET_K: u16 = 0x06;
+
+/// Longest program the kernel accepts (BPF_MAXINSNS)
+const MAX_INSTRUCTIONS: usize = 4096;
+
+/// Frame offsets of the fields the program reads, behind a 14-byte Ethernet header
+const ETHERTYPE_OFFSET: u32 = 12;
+const IPV4_HEADER_OFFSET: u32 = 14;
+const IPV4_FRAGMENT_OFFSET: u32 = 20;
+const IPV4_PROTOCOL_OFFSET: u32 = 23;
+const IPV4_SRC_OFFSET: u32 = 26;
+const IPV4_DST_OFFSET: u32 = 30;
+const IPV6_NEXT_HEADER_OFFSET: u32 = 20;
+const IPV6_SRC_OFFSET: u32 = 22;
+const IPV6_DST_OFFSET: u32 = 38;
+const IPV6_TRANSPORT_OFFSET: u32 = 54;
+
+const IPV4_MORE_FRAGMENTS: u32 = 0x2000;
+const IPV4_FRAGMENT_MASK: u32 = 0x1FFF;
+const IPV6_FRAGMENT_HEADER: u32 = 44;
+
+#[derive(Debug, Clone, Copy)]
+enum Load {
+    Word(u32),
+    Half(u32),
+    Byte(u32),
+    /// A half-word this far into the transport header behind IPv4's
+    /// variable-length header
+    TransportHalf(u32),
+    Len,
+}
+
+/// What a primitive comes down to: tests on one loaded value each.
+#[derive(Debug, Clone, Copy)]
+enum Check {
+    /// `load & mask` against `k` with the given jump opcode
+    Test { load: Load, mask: Option<u32>, jump: u16, k: u32 },
+    Constant(bool),
+}
+
+impl BpfFilter {
+    /// The expression as a classic BPF program for `SO_ATTACH_FILTER`, or
+    /// `None` when it is too long for the kernel. IP fragments are always
+    /// accepted: before reassembly only the first carries ports, so they are
+    /// left to `matches` once the datagram is whole.
+    pub fn kernel_program(&self) -> Option<Vec<Instruction>> {
+        let fragment = Expr::or(
+            Expr::and(ethertype(ETHERTYPE_IPV4), test(Load::Half(IPV4_FRAGMENT_OFFSET), None, JSET_K, IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_MASK)),
+            Expr::and(ethertype(ETHERTYPE_IPV6), equals(Load::Byte(IPV6_NEXT_HEADER_OFFSET), IPV6_FRAGMENT_HEADER)),
+        );
+        let program = Expr::or(fragment, self.program.expand(&lower));
+
+        let mut assembler = Assembler::default();
+        let (accept, reject) = (assembler.label(), assembler.label());
+        assembler.branch(&program, accept, reject);
+        assembler.place(accept);
+        // Keep the whole frame
+        assembler.emit(RET_K, u32::MAX, Target::Next, Target::Next);
+        assembler.place(reject);
+        assembler.emit(RET_K, 0, Target::Next, Target::Next);
+        assembler.assemble()
+    }
+}
+
+fn test(load: Load, mask: Option<u32>, jump: u16, k: u32) -> Expr<Check> {
+    Expr::Primitive(Check::Test { load, mask, jump, k })
+}
+
+fn equals(load: Load, k: u32) -> Expr<Check> {
+    test(load, None, JEQ_K, k)
+}
+
+fn ethertype(ethertype: u16) -> Expr<Check> {
+    equals(Load::Half(ETHERTYPE_OFFSET), ethertype as u32)
+}
+
+/// Either of the expressions, or never for none at all.
+fn any(checks: impl IntoIterator<Item = Expr<Check>>) -> Expr<Check> {
+    checks.into_iter().reduce(Expr::or).unwrap_or(Expr::Primitive(Check::Constant(false)))
+}
+
+/// All of the expressions, or always for none at all.
+fn all(checks: impl IntoIterator<Item = Expr<Check>>) -> Expr<Check> {
+    checks.into_iter().reduce(Expr::and).unwrap_or(Expr::Primitive(Check::Constant(true)))
+}
+
+fn sided(direction: Direction, check: impl Fn(u32) -> Expr<Check>, src: u32, dst: u32) -> Expr<Check> {
+    match direction {
+        Direction::Src => check(src),
+        Direction::Dst => check(dst),
+        Direction::Either => Expr::or(check(src), check(dst)),
+    }
+}
+
+/// The 16-byte address at `offset` under `mask`, a word at a time.
+fn ipv6_matches(offset: u32, address: Ipv6Addr, mask: Ipv6Addr) -> Expr<Check> {
+    let (address, mask) = (u128::from(address), u128::from(mask));
+    all((0..4).filter_map(|word| {
+        let shift = 96 - 32 * word;
+        let word_mask = (mask >> shift) as u32;
+        let k = (address >> shift) as u32 & word_mask;
+        match word_mask {
+            0 => None,
+            u32::MAX => Some(equals(Load::Word(offset + 4 * word), k)),
+            _ => Some(test(Load::Word(offset + 4 * word), Some(word_mask), JEQ_K, k)),
+        }
+    }))
+}
+
+/// `primitive` in terms of what the program can load, following
+/// `evaluate` field by field.
+fn lower(primitive: &Primitive) -> Expr<Check> {
+    let carries = |ip_proto: u8| Expr::or(
+        Expr::and(ethertype(ETHERTYPE_IPV4), equals(Load::Byte(IPV4_PROTOCOL_OFFSET), ip_proto as u32)),
+        Expr::and(ethertype(ETHERTYPE_IPV6), equals(Load::Byte(IPV6_NEXT_HEADER_OFFSET), ip_proto as u32)),
+    );
+
+    match primitive {
+        Primitive::Proto(proto) => match proto {
+            Proto::Ip => ethertype(ETHERTYPE_IPV4),
+            Proto::Ip6 => ethertype(ETHERTYPE_IPV6),
+            Proto::Arp => ethertype(ETHERTYPE_ARP),
+            Proto::Tcp => carries(IPPROTO_TCP),
+            Proto::Udp => carries(IPPROTO_UDP),
+            Proto::Sctp => carries(IPPROTO_SCTP),
+            Proto::Icmp => Expr::and(ethertype(ETHERTYPE_IPV4), equals(Load::Byte(IPV4_PROTOCOL_OFFSET), IPPROTO_ICMP as u32)),
+            Proto::Icmp6 => Expr::and(ethertype(ETHERTYPE_IPV6), equals(Load::Byte(IPV6_NEXT_HEADER_OFFSET), IPPROTO_ICMPV6 as u32)),
+        },
+        Primitive::Host(direction, IpAddr::V4(ip)) => Expr::and(
+            ethertype(ETHERTYPE_IPV4),
+            sided(*direction, |offset| equals(Load::Word(offset), u32::from(*ip)), IPV4_SRC_OFFSET, IPV4_DST_OFFSET),
+        ),
+        Primitive::Host(direction, IpAddr::V6(ip)) => Expr::and(
+            ethertype(ETHERTYPE_IPV6),
+            sided(*direction, |offset| ipv6_matches(offset, *ip, Ipv6Addr::from(u128::MAX)), IPV6_SRC_OFFSET, IPV6_DST_OFFSET),
+        ),
+        Primitive::Net(direction, IpNetwork::V4(net)) => {
+            let mask = u32::from(net.mask());
+            Expr::and(
+                ethertype(ETHERTYPE_IPV4),
+                sided(*direction, |offset| test(Load::Word(offset), Some(mask), JEQ_K, u32::from(net.network())), IPV4_SRC_OFFSET, IPV4_DST_OFFSET),
+            )
+        }
+        Primitive::Net(direction, IpNetwork::V6(net)) => Expr::and(
+            ethertype(ETHERTYPE_IPV6),
+            sided(*direction, |offset| ipv6_matches(offset, net.network(), net.mask()), IPV6_SRC_OFFSET, IPV6_DST_OFFSET),
+        ),
+        Primitive::Port(proto, direction, low, high) => {
+            let ip_protos: &[u8] = match proto {
+                Some(Proto::Tcp) => &[IPPROTO_TCP],
+                Some(Proto::Udp) => &[IPPROTO_UDP],
+                Some(Proto::Sctp) => &[IPPROTO_SCTP],
+                None | Some(Proto::Ip | Proto::Ip6) => &[IPPROTO_TCP, IPPROTO_UDP, IPPROTO_SCTP],
+                Some(_) => &[],
+            };
+            let in_range = |load: Load| match (*low as u32, *high as u32) {
+                (low, high) if low == high => equals(load, low),
+                (low, high) => Expr::and(test(load, None, JGE_K, low), Expr::Not(Box::new(test(load, None, JGT_K, high)))),
+            };
+
+            let ipv4 = all([
+                ethertype(ETHERTYPE_IPV4),
+                any(ip_protos.iter().map(|&ip_proto| equals(Load::Byte(IPV4_PROTOCOL_OFFSET), ip_proto as u32))),
+                // Like `Frame::parse`, only the first fragment has ports
+                Expr::Not(Box::new(test(Load::Half(IPV4_FRAGMENT_OFFSET), None, JSET_K, IPV4_FRAGMENT_MASK))),
+                sided(*direction, |offset| in_range(Load::TransportHalf(offset)), 0, 2),
+            ]);
+            let ipv6 = all([
+                ethertype(ETHERTYPE_IPV6),
+                any(ip_protos.iter().map(|&ip_proto| equals(Load::Byte(IPV6_NEXT_HEADER_OFFSET), ip_proto as u32))),
+                sided(*direction, |offset| in_range(Load::Half(offset)), IPV6_TRANSPORT_OFFSET, IPV6_TRANSPORT_OFFSET + 2),
+            ]);
+            match proto {
+                Some(Proto::Ip) => ipv4,
+                Some(Proto::Ip6) => ipv6,
+                _ => Expr::or(ipv4, ipv6),
+            }
+        }
+        Primitive::Less(size) => Expr::Not(Box::new(test(Load::Len, None, JGT_K, u32::try_from(*size).unwrap_or(u32::MAX)))),
+        Primitive::Greater(size) => test(Load::Len, None, JGE_K, u32::try_from(*size).unwrap_or(u32::MAX)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Next,
+    Label(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Op {
+    code: u16,
+    k: u32,
+    /// Where a conditional jump goes either way; `JA` goes to `jt`
+    jt: Target,
+    jf: Target,
+}
+
+/// Lays out the program with symbolic jump targets, then resolves them.
+/// Every jump goes forward, to a label placed after it.
+#[derive(Default)]
+struct Assembler {
+    ops: Vec<Op>,
+    /// The op each label points at once placed
+    labels: Vec<usize>,
+}
+
+impl Assembler {
+    fn label(&mut self) -> usize {
+        self.labels.push(usize::MAX);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.labels[label] = self.ops.len();
+    }
+
+    fn emit(&mut self, code: u16, k: u32, jt: Target, jf: Target) {
+        self.ops.push(Op { code, k, jt, jf });
+    }
+
+    /// Code that goes to `yes` where `expr` holds and to `no` where it doesn't.
+    fn branch(&mut self, expr: &Expr<Check>, yes: usize, no: usize) {
+        match expr {
+            Expr::And(left, right) => {
+                let next = self.label();
+                self.branch(left, next, no);
+                self.place(next);
+                self.branch(right, yes, no);
+            }
+            Expr::Or(left, right) => {
+                let next = self.label();
+                self.branch(left, yes, next);
+                self.place(next);
+                self.branch(right, yes, no);
+            }
+            Expr::Not(inner) => self.branch(inner, no, yes),
+            Expr::Primitive(Check::Constant(holds)) => {
+                let target = if *holds { yes } else { no };
+                self.emit(JA, 0, Target::Label(target), Target::Next);
+            }
+            Expr::Primitive(Check::Test { load, mask, jump, k }) => {
+                match *load {
+                    Load::Word(offset) => self.emit(LD_W_ABS, offset, Target::Next, Target::Next),
+                    Load::Half(offset) => self.emit(LD_H_ABS, offset, Target::Next, Target::Next),
+                    Load::Byte(offset) => self.emit(LD_B_ABS, offset, Target::Next, Target::Next),
+                    Load::TransportHalf(offset) => {
+                        // X = IPv4 header length, from its IHL nibble
+                        self.emit(LDX_B_MSH, IPV4_HEADER_OFFSET, Target::Next, Target::Next);
+                        self.emit(LD_H_IND, IPV4_HEADER_OFFSET + offset, Target::Next, Target::Next);
+                    }
+                    Load::Len => self.emit(LD_W_LEN, 0, Target::Next, Target::Next),
+                }
+                if let Some(mask) = mask {
+                    self.emit(AND_K, *mask, Target::Next, Target::Next);
+                }
+                self.emit(*jump, *k, Target::Label(yes), Target::Label(no));
+            }
+        }
+    }
+
+    /// How far `target` is from the op at `index`.
+    fn distance(&self, index: usize, target: Target) -> usize {
+        match target {
+            Target::Next => 0,
+            Target::Label(label) => self.labels[label] - (index + 1),
+        }
+    }
+
+    /// Conditional jumps only reach 255 ops ahead; a longer one goes through
+    /// a `JA` inserted right after it instead, until every jump fits.
+    fn assemble(mut self) -> Option<Vec<Instruction>> {
+        loop {
+            let far = (0..self.ops.len()).find_map(|index| {
+                let op = self.ops[index];
+                if op.code == JA {
+                    return None;
+                }
+                [op.jt, op.jf].into_iter().position(|target| self.distance(index, target) > u8::MAX as usize).map(|side| (index, side))
+            });
+            let Some((index, side)) = far else {
+                break;
+            };
+            if self.ops.len() >= MAX_INSTRUCTIONS {
+                return None;
+            }
+
+            let op = self.ops[index];
+            let (target, trampoline) = (if side == 0 { op.jt } else { op.jf }, index + 1);
+            for position in &mut self.labels {
+                if *position >= trampoline {
+                    *position += 1;
+                }
+            }
+            self.ops.insert(trampoline, Op { code: JA, k: 0, jt: target, jf: Target::Next });
+            let label = self.label();
+            self.labels[label] = trampoline;
+            if side == 0 {
+                self.ops[index].jt = Target::Label(label);
+            } else {
+                self.ops[index].jf = Target::Label(label);
+            }
+        }
+        if self.ops.len() > MAX_INSTRUCTIONS {
+            return None;
+        }
+
+        Some((0..self.ops.len()).map(|index| {
+            let op = self.ops[index];
+            if op.code == JA {
+                return Instruction { code: JA, jt: 0, jf: 0, k: self.distance(index, op.jt) as u32 };
+            }
+            Instruction { code: op.code, jt: self.distance(index, op.jt) as u8, jf: self.distance(index, op.jf) as u8, k: op.k }
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a program the way the kernel does, as far as the opcodes we emit
+    /// go; like the kernel, a load past the end of the frame rejects it.
+    fn run(program: &[Instruction], frame: &[u8]) -> bool {
+        let load = |offset: usize, size: usize| -> Option<u32> {
+            let bytes = frame.get(offset..offset + size)?;
+            Some(bytes.iter().fold(0, |value, &byte| value << 8 | byte as u32))
+        };
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0usize);
+        loop {
+            let Instruction { code, jt, jf, k } = program[pc];
+            pc += 1;
+            let loaded = match code {
+                LD_W_ABS => load(k as usize, 4),
+                LD_H_ABS => load(k as usize, 2),
+                LD_B_ABS => load(k as usize, 1),
+                LD_H_IND => load((x + k) as usize, 2),
+                LD_W_LEN => Some(frame.len() as u32),
+                LDX_B_MSH => {
+                    let Some(byte) = load(k as usize, 1) else { return false };
+                    x = (byte & 0x0F) * 4;
+                    continue;
+                }
+                AND_K => {
+                    a &= k;
+                    continue;
+                }
+                JA => {
+                    pc += k as usize;
+                    continue;
+                }
+                JEQ_K | JGT_K | JGE_K | JSET_K => {
+                    let holds = match code {
+                        JEQ_K => a == k,
+                        JGT_K => a > k,
+                        JGE_K => a >= k,
+                        _ => a & k != 0,
+                    };
+                    pc += if holds { jt } else { jf } as usize;
+                    continue;
+                }
+                RET_K => return k != 0,
+                _ => panic!("unexpected opcode {:#x}", code),
+            };
+            match loaded {
+                Some(value) => a = value,
+                None => return false,
+            }
+        }
+    }
+
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// An IPv4 frame with `options` words of header options; ports go in
+    /// the first four payload bytes.
+    fn ipv4(protocol: u8, src: [u8; 4], dst: [u8; 4], ports: (u16, u16), options: usize, fragment: u16) -> Vec<u8> {
+        let mut header = vec![0x45 + options as u8, 0, 0, 0, 0, 0];
+        header.extend_from_slice(&fragment.to_be_bytes());
+        header.extend_from_slice(&[64, protocol, 0, 0]);
+        header.extend_from_slice(&src);
+        header.extend_from_slice(&dst);
+        header.extend(std::iter::repeat_n(1, options * 4));
+        header.extend_from_slice(&ports.0.to_be_bytes());
+        header.extend_from_slice(&ports.1.to_be_bytes());
+        header.extend_from_slice(&[0; 16]);
+        ethernet(ETHERTYPE_IPV4, &header)
+    }
+
+    fn ipv6(next_header: u8, src: Ipv6Addr, dst: Ipv6Addr, ports: (u16, u16)) -> Vec<u8> {
+        let mut header = vec![0x60, 0, 0, 0, 0, 20, next_header, 64];
+        header.extend_from_slice(&src.octets());
+        header.extend_from_slice(&dst.octets());
+        header.extend_from_slice(&ports.0.to_be_bytes());
+        header.extend_from_slice(&ports.1.to_be_bytes());
+        header.extend_from_slice(&[0; 16]);
+        ethernet(ETHERTYPE_IPV6, &header)
+    }
+
+    fn frames() -> Vec<Vec<u8>> {
+        let a: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let b: Ipv6Addr = "2001:db8:0:1::2".parse().unwrap();
+        vec![
+            ipv4(IPPROTO_TCP, [10, 0, 0, 5], [192, 168, 1, 1], (40000, 443), 0, 0),
+            ipv4(IPPROTO_TCP, [192, 168, 1, 1], [10, 0, 0, 5], (22, 51000), 2, 0),
+            ipv4(IPPROTO_UDP, [10, 0, 0, 7], [8, 8, 8, 8], (5353, 53), 0, 0),
+            ipv4(IPPROTO_SCTP, [10, 0, 1, 9], [10, 0, 0, 5], (2905, 2905), 0, 0),
+            ipv4(IPPROTO_ICMP, [10, 0, 0, 5], [1, 1, 1, 1], (0x0800, 0), 0, 0),
+            ipv4(IPPROTO_ICMPV6, [10, 0, 0, 5], [1, 1, 1, 1], (0, 0), 0, 0),
+            ipv6(IPPROTO_TCP, a, b, (443, 40001)),
+            ipv6(IPPROTO_UDP, b, a, (53, 8053)),
+            ipv6(IPPROTO_ICMPV6, a, b, (0x8000, 0)),
+            ipv6(IPPROTO_ICMP, a, b, (0, 0)),
+            ethernet(ETHERTYPE_ARP, &[0; 28]),
+            ethernet(0x88CC, &[0; 46]),
+        ]
+    }
+
+    #[test]
+    fn kernel_program_agrees_with_matches() {
+        let expressions = [
+            "ip", "ip6", "arp", "tcp", "udp", "sctp", "icmp", "icmp6",
+            "host 10.0.0.5", "src host 10.0.0.5", "dst host 10.0.0.5", "host 2001:db8::1", "dst host 2001:db8::1",
+            "net 10.0.0.0/24", "src net 10.0.0.0/16", "net 0.0.0.0/0", "net 2001:db8::/48", "dst net 2001:db8:0:1::/64",
+            "net 2001:db8::/29", "ip6 net ::/0",
+            "port 443", "src port 22", "dst port 53", "tcp port 443", "udp port 53", "sctp port 2905", "tcp port 53",
+            "ip port 443", "ip6 port 443", "arp port 0", "portrange 20-25", "dst portrange 50000-52000",
+            "less 60", "greater 60", "less 0", "greater 100000",
+            "tcp and not port 22", "not (udp or arp)", "icmp or icmp6 or arp", "ip6 host 2001:db8::1 and tcp",
+            "tcp host 10.0.0.5", "!tcp && (port 53 || src net 10.0.1.0/24)",
+        ];
+        for expression in expressions {
+            let filter = BpfFilter::compile(expression).unwrap();
+            let program = filter.kernel_program().unwrap();
+            for (index, frame) in frames().iter().enumerate() {
+                assert_eq!(run(&program, frame), filter.matches(frame), "{:?} on frame {}", expression, index);
+            }
+        }
+    }
+
+    #[test]
+    fn fragments_pass_the_kernel_program() {
+        let program = BpfFilter::compile("udp port 53").unwrap().kernel_program().unwrap();
+        let first = ipv4(IPPROTO_UDP, [10, 0, 0, 7], [8, 8, 8, 8], (9999, 9999), 0, 0x2000);
+        let later = ipv4(IPPROTO_UDP, [10, 0, 0, 7], [8, 8, 8, 8], (9999, 9999), 0, 0x00B9);
+        let ipv6_fragment = ipv6(IPV6_FRAGMENT_HEADER as u8, Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST, (0x1100, 0));
+        assert!(run(&program, &first));
+        assert!(run(&program, &later));
+        assert!(run(&program, &ipv6_fragment));
+        assert!(!run(&program, &ipv4(IPPROTO_UDP, [10, 0, 0, 7], [8, 8, 8, 8], (9999, 9999), 0, 0)));
+    }
+
+    #[test]
+    fn long_jumps_go_through_ja() {
+        let expression = (1..=60).map(|port| format!("port {}", port)).collect::<Vec<_>>().join(" or ") + " or port 443";
+        let filter = BpfFilter::compile(&expression).unwrap();
+        let program = filter.kernel_program().unwrap();
+        assert!(program.len() > u8::MAX as usize);
+        assert!(program.iter().any(|instruction| instruction.code == JA));
+        for frame in frames() {
+            assert_eq!(run(&program, &frame), filter.matches(&frame));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Reading frames off one or more interfaces. `rx.next()` blocks, so every
+//! interface gets its own reader thread; they all feed one bounded queue, and
+//! a single consumer numbers the frames and hands them on. Several busy NICs
+//! never contend for the stats that way, and packet numbers stay in one sequence.
+//!
+//! `start` blocks a reader on a full queue, leaving the overflow to the
+//! kernel. `start_async` feeds the dashboard's tokio pipeline instead, and
+//! drops and counts frames the queue has no room for. The `start_stdin`
+//! variants read a pcap stream instead of interfaces.
+
+use chrono::{DateTime, Utc};
+use pnet::datalink::{self, Channel, DataLinkReceiver, NetworkInterface};
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+
+#[cfg(target_os = "linux")]
+use crate::bpf::BpfFilter;
+use crate::error::{PacketSnifferError, Result};
+use crate::filter::CaptureFilter;
+use crate::fragments::Defragmenter;
+#[cfg(target_os = "linux")]
+use crate::packet_socket::PacketSocket;
+use crate::pcap::PcapReader;
+
+/// What frames read by `--read-stdin` give as their interface
+pub const STDIN_SOURCE: &str = "stdin";
+
+// Absorbs a burst while the consumer is busy; past that the readers block and
+// the kernel's socket buffer takes the overflow
+const FRAME_QUEUE_CAPACITY: usize = 4096;
+
+/// A frame that passed the capture filter, with the interface it arrived on.
+pub struct CapturedFrame {
+    pub interface: String,
+    /// When the reader got it, so queueing and analysis don't skew packet
+    /// times; from a pcap stream, the time recorded in it
+    pub timestamp: DateTime<Utc>,
+    pub data: Vec<u8>,
+}
+
+/// Frames for the async pipeline, with the counters it reports drops from.
+pub struct AsyncCapture {
+    pub frames: tokio::sync::mpsc::Receiver<CapturedFrame>,
+    /// Frames dropped because the queue was full
+    pub overflow: Arc<AtomicUsize>,
+    pub kernel_drops: KernelDrops,
+}
+
+pub fn start(interfaces: &[NetworkInterface], config: datalink::Config, filter: &CaptureFilter) -> Result<Receiver<CapturedFrame>> {
+    let receivers = open(interfaces, config, filter)?;
+
+    let (sender, frames) = mpsc::sync_channel(FRAME_QUEUE_CAPACITY);
+    for (name, rx) in receivers {
+        let sender = sender.clone();
+        let filter = filter.clone();
+        std::thread::spawn(move || read_frames(&name, rx, &filter, |frame| sender.send(frame).is_ok()));
+    }
+
+    Ok(frames)
+}
+
+pub fn start_async(interfaces: &[NetworkInterface], config: datalink::Config, filter: &CaptureFilter) -> Result<AsyncCapture> {
+    let receivers = open(interfaces, config, filter)?;
+    let kernel_drops = KernelDrops::new(interfaces);
+    let overflow = Arc::new(AtomicUsize::new(0));
+
+    let (sender, frames) = tokio::sync::mpsc::channel(FRAME_QUEUE_CAPACITY);
+    for (name, rx) in receivers {
+        let sender = sender.clone();
+        let filter = filter.clone();
+        let overflow = overflow.clone();
+        std::thread::spawn(move || read_frames(&name, rx, &filter, |frame| match sender.try_send(frame) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                overflow.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }));
+    }
+
+    Ok(AsyncCapture { frames, overflow, kernel_drops })
+}
+
+/// `--read-stdin`: frames from the pcap stream on standard input. The header
+/// is read before this returns, so input that isn't pcap fails up front.
+pub fn start_stdin(filter: &CaptureFilter) -> Result<Receiver<CapturedFrame>> {
+    let reader = PcapReader::new(BufReader::new(io::stdin()))?;
+
+    let (sender, frames) = mpsc::sync_channel(FRAME_QUEUE_CAPACITY);
+    let filter = filter.clone();
+    std::thread::spawn(move || read_pcap(reader, &filter, |frame| sender.send(frame).is_ok()));
+    Ok(frames)
+}
+
+/// `start_stdin` for the async pipeline. A stream can wait for analysis to
+/// catch up, unlike a NIC, so nothing is dropped and no kernel drops apply.
+pub fn start_stdin_async(filter: &CaptureFilter) -> Result<AsyncCapture> {
+    let reader = PcapReader::new(BufReader::new(io::stdin()))?;
+
+    let (sender, frames) = tokio::sync::mpsc::channel(FRAME_QUEUE_CAPACITY);
+    let filter = filter.clone();
+    std::thread::spawn(move || read_pcap(reader, &filter, |frame| sender.blocking_send(frame).is_ok()));
+    Ok(AsyncCapture { frames, overflow: Arc::new(AtomicUsize::new(0)), kernel_drops: KernelDrops::new(&[]) })
+}
+
+/// Opens every interface before spawning any reader, so a bad interface or a
+/// missing permission fails the capture up front instead of halfway through.
+fn open(interfaces: &[NetworkInterface], config: datalink::Config, filter: &CaptureFilter) -> Result<Vec<(String, Box<dyn DataLinkReceiver>)>> {
+    let mut receivers = Vec::new();
+    for interface in interfaces {
+        let rx = match receiver(interface, config, filter) {
+            Ok(rx) => rx,
+            // EPERM/EACCES from opening the raw socket: not root, no CAP_NET_RAW
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(PacketSnifferError::PermissionDenied);
+            }
+            Err(e) => {
+                return Err(PacketSnifferError::NetworkError(format!("Failed to create datalink channel on {}: {}", interface.name, e)));
+            }
+        };
+        receivers.push((interface.name.clone(), rx));
+    }
+    Ok(receivers)
+}
+
+/// pnet's channel, or on Linux with `--bpf` a packet socket of our own with
+/// the program attached, so the kernel drops what the filter rejects.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn receiver(interface: &NetworkInterface, config: datalink::Config, filter: &CaptureFilter) -> io::Result<Box<dyn DataLinkReceiver>> {
+    #[cfg(target_os = "linux")]
+    if let Some(program) = filter.bpf().and_then(BpfFilter::kernel_program) {
+        return Ok(Box::new(PacketSocket::open(interface, &config, &program)?));
+    }
+    match datalink::channel(interface, config)? {
+        Channel::Ethernet(_, rx) => Ok(rx),
+        _ => Err(io::Error::other("unhandled channel type")),
+    }
+}
+
+/// Drops the kernel counted on the capture interfaces since capture began.
+/// pnet keeps its packet socket to itself, so the socket's own drop counter
+/// is out of reach; the interface counters show frames the host lost before
+/// any socket saw them (full NIC ring or backlog), which the capture lost too.
+/// Only Linux exposes them.
+pub struct KernelDrops {
+    baseline: Vec<(String, u64)>,
+}
+
+impl KernelDrops {
+    pub fn new(interfaces: &[NetworkInterface]) -> Self {
+        let baseline = interfaces.iter()
+            .filter_map(|interface| interface_drops(&interface.name).map(|drops| (interface.name.clone(), drops)))
+            .collect();
+        KernelDrops { baseline }
+    }
+
+    /// `None` when no interface's counters can be read.
+    pub fn since_start(&self) -> Option<u64> {
+        if self.baseline.is_empty() {
+            return None;
+        }
+        Some(self.baseline.iter()
+            .map(|(name, start)| interface_drops(name).map_or(0, |now| now.saturating_sub(*start)))
+            .sum())
+    }
+}
+
+fn interface_drops(name: &str) -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let counter = |counter: &str| -> Option<u64> {
+        std::fs::read_to_string(format!("/sys/class/net/{}/statistics/{}", name, counter)).ok()?.trim().parse().ok()
+    };
+    Some(counter("rx_dropped")? + counter("rx_missed_errors").unwrap_or(0))
+}
+
+/// Runs until shutdown, a read error, or `deliver` reporting the consumer
+/// gone. Filtering happens here so rejected frames are never copied, after
+/// reassembly so it sees whole datagrams rather than fragments.
+fn read_frames(interface: &str, mut rx: Box<dyn DataLinkReceiver>, filter: &CaptureFilter, mut deliver: impl FnMut(CapturedFrame) -> bool) {
+    let mut fragments = Defragmenter::default();
+    while !crate::shutdown_requested() {
+        match rx.next() {
+            Ok(packet) => {
+                let timestamp = Utc::now();
+                let Some(packet) = fragments.accept(timestamp, packet) else {
+                    continue;
+                };
+                if !filter.matches(&packet) {
+                    continue;
+                }
+                let frame = CapturedFrame { interface: interface.to_string(), timestamp, data: packet.into_owned() };
+                if !deliver(frame) {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+            Err(_) if crate::shutdown_requested() => return,
+            Err(e) => {
+                errln!("Failed to read packet on {}: {}", interface, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Like `read_frames`, until the stream ends. Frames keep the capture times
+/// recorded in the stream.
+fn read_pcap(mut reader: PcapReader<impl Read>, filter: &CaptureFilter, mut deliver: impl FnMut(CapturedFrame) -> bool) {
+    let mut fragments = Defragmenter::default();
+    while !crate::shutdown_requested() {
+        match reader.next_frame() {
+            Ok(Some((timestamp, data))) => {
+                let Some(data) = fragments.accept(timestamp, &data).map(|data| data.into_owned()) else {
+                    continue;
+                };
+                if !filter.matches(&data) {
+                    continue;
+                }
+                if !deliver(CapturedFrame { interface: STDIN_SOURCE.to_string(), timestamp, data }) {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                errln!("Stopped reading stdin: {}", e);
+                return;
+            }
+        }
+    }
+}
@@ -1,31 +1,174 @@
+use chrono::format::{Item, StrftimeItems};
+use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::net::IpAddr;
 use std::path::Path;
 
+/// Current config file layout. Bump it whenever fields are added, so older
+/// files are rewritten with the new fields filled in.
+pub const CONFIG_VERSION: u32 = 6;
+
+/// Files from before `version` existed
+fn legacy_config_version() -> u32 {
+    1
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default = "legacy_config_version")]
+    pub version: u32,
+    #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
     pub performance: PerformanceConfig,
+    #[serde(default)]
     pub export: ExportConfig,
+    #[serde(default)]
     pub ui: UiConfig,
+    #[serde(default)]
+    pub geoip: GeoIpConfig,
+    #[serde(default)]
+    pub oui: OuiConfig,
+    #[serde(default)]
+    pub threat: ThreatConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    /// Application labels for ports, e.g. `"2222": "SSH"`. Checked before the
+    /// built-in detection, so they also override it.
+    #[serde(default)]
+    pub port_protocols: BTreeMap<String, String>,
+    /// Named filter sets for `--preset`
+    #[serde(default = "default_filter_presets")]
+    pub filter_presets: BTreeMap<String, FilterSpec>,
+    /// Set when loading upgraded an older file
+    #[serde(skip)]
+    pub migration: Option<Migration>,
+}
+
+/// What loading an older config file changed.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub from_version: u32,
+    /// Dotted paths of the fields the file lacked, now at their defaults
+    pub added: Vec<String>,
+    /// Where the original file was kept, or why it couldn't be rewritten
+    pub rewritten: Result<String, String>,
+}
+
+/// Capture filters saved under a name. Each field takes the values of the
+/// command-line flag it is named after; a flag given alongside `--preset`
+/// replaces that field and leaves the rest of the preset in force.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FilterSpec {
+    pub protocol: Vec<String>,
+    pub port: Vec<u16>,
+    pub src_ip: Vec<IpAddr>,
+    pub dst_ip: Vec<IpAddr>,
+    pub host: Vec<IpAddr>,
+    pub subnet: Vec<String>,
+    pub exclude_protocol: Vec<String>,
+    pub exclude_host: Vec<IpAddr>,
+    pub exclude_port: Vec<u16>,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+}
+
+/// Shipped in new config files, as examples as much as for use
+fn default_filter_presets() -> BTreeMap<String, FilterSpec> {
+    BTreeMap::from([
+        ("web".to_string(), FilterSpec {
+            protocol: vec!["tcp".to_string()],
+            port: vec![80, 443, 8080],
+            ..FilterSpec::default()
+        }),
+        ("dns".to_string(), FilterSpec {
+            protocol: vec!["dns".to_string()],
+            ..FilterSpec::default()
+        }),
+    ])
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct LoggingConfig {
     pub level: String,
     pub file: Option<String>,
     pub enable_console: bool,
     pub enable_file: bool,
+    /// Rotate the log file once it would grow past this size; 0 disables rotation
+    pub max_size_bytes: u64,
+    /// Rotated files kept as `<file>.1` (newest) through `<file>.N`
+    pub max_backups: usize,
+}
+
+fn default_log_max_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_max_backups() -> usize {
+    5
+}
+
+fn default_rolling_window_secs() -> u64 {
+    10
+}
+
+fn default_analysis_workers() -> usize {
+    0
 }
 
+fn default_max_retained_packets() -> usize {
+    1000
+}
+
+fn default_max_bandwidth_points() -> usize {
+    100
+}
+
+fn default_max_alerts() -> usize {
+    100
+}
+
+fn default_connection_idle_timeout_secs() -> u64 {
+    300
+}
+
+/// Smallest read buffer that still holds a full VLAN-tagged Ethernet frame
+pub const MIN_CAPTURE_BUFFER_SIZE: usize = 1522;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct PerformanceConfig {
+    /// Datalink read buffer in bytes. On BSD/macOS this is the kernel BPF
+    /// buffer, so a larger value absorbs bursts that would otherwise be
+    /// dropped before we read them; on Linux it bounds the largest frame read,
+    /// and anything longer is truncated. Values below a full Ethernet frame
+    /// are raised to `MIN_CAPTURE_BUFFER_SIZE`.
     pub buffer_size: usize,
     pub max_packets_per_second: usize,
     pub dashboard_refresh_rate: u64,
+    /// Seconds averaged for the "current" bandwidth and the peaks
+    pub rolling_window_secs: u64,
+    /// Threads decoding packets in dashboard mode; 0 means one per CPU core
+    pub analysis_workers: usize,
+    /// Packets kept in memory for the dashboard feed and the final JSON/CSV
+    /// exports; older ones are let go. `--export-jsonl` and `--export-sqlite`
+    /// are written as packets arrive, so they still get every one.
+    pub max_retained_packets: usize,
+    /// Seconds of bandwidth history kept, which also caps `rolling_window_secs`
+    pub max_bandwidth_points: usize,
+    /// Most recent threat alerts kept
+    pub max_alerts: usize,
+    /// Seconds without a packet after which a connection counts as closed
+    /// and is let go; 0 keeps every connection for the whole capture
+    pub connection_idle_timeout_secs: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct ExportConfig {
     pub default_format: String,
     pub default_directory: String,
@@ -33,36 +176,338 @@ pub struct ExportConfig {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct UiConfig {
     pub colors_enabled: bool,
     pub emojis_enabled: bool,
     pub table_style: String,
+    /// `utc` or `local`, for everything printed and exported
+    pub timezone: String,
+    /// strftime layout of packet times on the console and in the dashboard
+    pub timestamp_format: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GeoIpConfig {
+    /// Path to a MaxMind GeoLite2-City `.mmdb` file. Without it only private
+    /// networks are recognized.
+    pub database_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OuiConfig {
+    /// Path to the IEEE `oui.txt` registry or a Wireshark `manuf` file.
+    /// Without it only the built-in table of common vendors is used.
+    pub database_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AlertsConfig {
+    /// Threat alerts are also POSTed here as JSON, in batches
+    pub webhook_url: Option<String>,
+    /// Also write threat alerts to the system log (Unix only)
+    #[serde(default)]
+    pub syslog: bool,
+}
+
+/// Risk scoring used by threat detection. Each matching check adds its score
+/// and the total is mapped to a level through `level_thresholds`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ThreatConfig {
+    pub high_risk_ports: Vec<u16>,
+    pub medium_risk_ports: Vec<u16>,
+    pub high_risk_port_score: u32,
+    pub medium_risk_port_score: u32,
+    /// Ports above this are treated as unusual
+    pub unusual_port_threshold: u16,
+    pub unusual_port_score: u32,
+    pub public_ip_score: u32,
+    /// Link-local destinations (169.254.0.0/16 and fe80::/10): autoconfiguration
+    /// after DHCP failed, or a cloud metadata endpoint. Never scored as public too.
+    pub suspicious_ip_score: u32,
+    pub min_normal_packet_size: usize,
+    pub max_normal_packet_size: usize,
+    /// Packets under `min_normal_packet_size`
+    pub size_anomaly_score: u32,
+    /// Packets over `max_normal_packet_size`. Larger than a standard frame,
+    /// so jumbo frames or crafted packets; this alone reaches Low by default.
+    pub oversize_packet_score: u32,
+    pub icmp_score: u32,
+    pub udp_score: u32,
+    pub level_thresholds: ThreatThresholds,
+    /// IPs/CIDRs that are always Safe, e.g. your own scanners
+    pub allowlist: Vec<String>,
+    /// IPs/CIDRs that are always Critical and raise an alert
+    pub blocklist: Vec<String>,
+    /// Sliding window for counting distinct ports touched by one source
+    pub port_scan_window_secs: u64,
+    /// Distinct ports within the window that count as a scan
+    pub port_scan_threshold: usize,
+    /// Half-open handshakes are forgotten after this long
+    pub syn_flood_window_secs: u64,
+    /// Half-open handshakes to one target that count as a flood
+    pub syn_flood_threshold: usize,
+    /// Sliding window for counting gratuitous ARPs, and for suppressing
+    /// repeat alerts about the same IP/MAC change
+    pub arp_window_secs: u64,
+    /// Gratuitous ARPs within the window that count as a flood
+    pub arp_gratuitous_threshold: usize,
+}
+
+/// Minimum total score for each level; anything below `low` is Safe.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThreatThresholds {
+    pub low: u32,
+    pub medium: u32,
+    pub high: u32,
+    pub critical: u32,
+}
+
+impl Default for ThreatConfig {
+    fn default() -> Self {
+        ThreatConfig {
+            high_risk_ports: vec![1433, 3389, 5900, 23, 135, 139, 445],
+            medium_risk_ports: vec![21, 25, 110, 143, 993, 995],
+            high_risk_port_score: 3,
+            medium_risk_port_score: 2,
+            unusual_port_threshold: 49152,
+            unusual_port_score: 1,
+            public_ip_score: 1,
+            suspicious_ip_score: 2,
+            min_normal_packet_size: 64,
+            max_normal_packet_size: 1500,
+            size_anomaly_score: 1,
+            oversize_packet_score: 2,
+            icmp_score: 1,
+            udp_score: 1,
+            level_thresholds: ThreatThresholds {
+                low: 2,
+                medium: 4,
+                high: 6,
+                critical: 8,
+            },
+            allowlist: Vec::new(),
+            blocklist: Vec::new(),
+            port_scan_window_secs: 10,
+            port_scan_threshold: 20,
+            syn_flood_window_secs: 10,
+            syn_flood_threshold: 100,
+            arp_window_secs: 10,
+            arp_gratuitous_threshold: 20,
+        }
+    }
+}
+
+impl ThreatConfig {
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let t = &self.level_thresholds;
+        if !(0 < t.low && t.low < t.medium && t.medium < t.high && t.high < t.critical) {
+            return Err(crate::error::PacketSnifferError::ConfigError(format!(
+                "threat.level_thresholds must be increasing and above 0 (got low={}, medium={}, high={}, critical={})",
+                t.low, t.medium, t.high, t.critical
+            )));
+        }
+        
+        parse_networks("threat.allowlist", &self.allowlist)?;
+        parse_networks("threat.blocklist", &self.blocklist)?;
+        Ok(())
+    }
+}
+
+/// Accepted `ui.table_style` values
+const TABLE_STYLES: [&str; 3] = ["modern", "classic", "plain"];
+
+/// Accepted `ui.timezone` values, in any case
+const TIMEZONES: [&str; 2] = ["utc", "local"];
+
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%H:%M:%S%.3f";
+
+/// Accepted `export.default_format` values
+const EXPORT_FORMATS: [&str; 4] = ["json", "csv", "jsonl", "sqlite"];
+
+/// Shortest `performance.dashboard_refresh_rate` accepted, in milliseconds.
+/// Each refresh copies the stats for the renderer, so faster ones would
+/// crowd out recording.
+const MIN_DASHBOARD_REFRESH_MS: u64 = 100;
+
+/// Longest `performance.dashboard_refresh_rate` accepted, in milliseconds
+const MAX_DASHBOARD_REFRESH_MS: u64 = 60_000;
+
+/// Collects the paths present in `migrated` but not in `original`. Absent
+/// optional fields (`null`) were never really missing, so they're skipped.
+fn added_fields(original: &serde_json::Value, migrated: &serde_json::Value, prefix: &str, added: &mut Vec<String>) {
+    let serde_json::Value::Object(fields) = migrated else {
+        return;
+    };
+    for (key, value) in fields {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match original.get(key) {
+            Some(existing) => added_fields(existing, value, &path, added),
+            None if !value.is_null() => added.push(path),
+            None => {}
+        }
+    }
+}
+
+/// Parses IP/CIDR strings; a bare address is treated as a single host.
+pub fn parse_networks(field: &str, entries: &[String]) -> crate::error::Result<Vec<IpNetwork>> {
+    entries.iter()
+        .map(|entry| entry.trim().parse::<IpNetwork>().map_err(|e| {
+            crate::error::PacketSnifferError::ConfigError(format!("invalid entry '{}' in {}: {}", entry, field, e))
+        }))
+        .collect()
+}
+
+/// Parses `port_protocols`. Keys are strings because neither JSON nor TOML
+/// has integer keys.
+pub fn parse_port_protocols(entries: &BTreeMap<String, String>) -> crate::error::Result<HashMap<u16, String>> {
+    entries.iter()
+        .map(|(port, label)| {
+            let parsed = port.trim().parse::<u16>().ok().filter(|&port| port != 0).ok_or_else(|| {
+                crate::error::PacketSnifferError::ConfigError(format!("invalid port '{}' in port_protocols: expected 1-65535", port))
+            })?;
+            if label.trim().is_empty() {
+                return Err(crate::error::PacketSnifferError::ConfigError(format!("empty label for port {} in port_protocols", port)));
+            }
+            Ok((parsed, label.trim().to_string()))
+        })
+        .collect()
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            file: Some("packet_sniffer.log".to_string()),
+            enable_console: true,
+            enable_file: true,
+            max_size_bytes: default_log_max_size_bytes(),
+            max_backups: default_log_max_backups(),
+        }
+    }
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        PerformanceConfig {
+            buffer_size: 4096,
+            max_packets_per_second: 1000,
+            dashboard_refresh_rate: 1000, // milliseconds
+            rolling_window_secs: default_rolling_window_secs(),
+            analysis_workers: default_analysis_workers(),
+            max_retained_packets: default_max_retained_packets(),
+            max_bandwidth_points: default_max_bandwidth_points(),
+            max_alerts: default_max_alerts(),
+            connection_idle_timeout_secs: default_connection_idle_timeout_secs(),
+        }
+    }
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig {
+            default_format: "json".to_string(),
+            default_directory: "./exports".to_string(),
+            auto_backup: true,
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            colors_enabled: true,
+            emojis_enabled: true,
+            table_style: "modern".to_string(),
+            timezone: "utc".to_string(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                file: Some("packet_sniffer.log".to_string()),
-                enable_console: true,
-                enable_file: true,
-            },
-            performance: PerformanceConfig {
-                buffer_size: 4096,
-                max_packets_per_second: 1000,
-                dashboard_refresh_rate: 1000, // milliseconds
-            },
-            export: ExportConfig {
-                default_format: "json".to_string(),
-                default_directory: "./exports".to_string(),
-                auto_backup: true,
-            },
-            ui: UiConfig {
-                colors_enabled: true,
-                emojis_enabled: true,
-                table_style: "modern".to_string(),
-            },
+            version: CONFIG_VERSION,
+            logging: LoggingConfig::default(),
+            performance: PerformanceConfig::default(),
+            export: ExportConfig::default(),
+            ui: UiConfig::default(),
+            geoip: GeoIpConfig::default(),
+            oui: OuiConfig::default(),
+            threat: ThreatConfig::default(),
+            alerts: AlertsConfig::default(),
+            port_protocols: BTreeMap::new(),
+            filter_presets: default_filter_presets(),
+            migration: None,
+        }
+    }
+}
+
+/// Environment variables that override config fields, applied after the file
+/// is loaded and before CLI flags. Booleans accept true/false, 1/0, yes/no
+/// and on/off; an empty path variable clears the path.
+const ENV_PREFIX: &str = "PKTSNIFF_";
+
+fn env_text(value: &str) -> Result<String, String> {
+    Ok(value.to_string())
+}
+
+fn env_path(value: &str) -> Result<Option<String>, String> {
+    Ok(Some(value.trim()).filter(|path| !path.is_empty()).map(str::to_string))
+}
+
+fn env_bool(value: &str) -> Result<bool, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+fn env_number<T: std::str::FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| e.to_string())
+}
+
+fn env_log_level(value: &str) -> Result<String, String> {
+    crate::logger::LogLevel::parse(value)
+        .map(|_| value.trim().to_string())
+        .map_err(|_| "expected error, warn, info or debug".to_string())
+}
+
+/// Overwrites `field` when `PKTSNIFF_<name>` is set.
+fn env_override<T>(name: &str, field: &mut T, parse: fn(&str) -> Result<T, String>) -> crate::error::Result<()> {
+    let variable = format!("{}{}", ENV_PREFIX, name);
+    let value = match std::env::var(&variable) {
+        Ok(value) => value,
+        Err(std::env::VarError::NotPresent) => return Ok(()),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            return Err(crate::error::PacketSnifferError::ConfigError(format!("{} is not valid UTF-8", variable)));
+        }
+    };
+    
+    *field = parse(&value).map_err(|e| {
+        crate::error::PacketSnifferError::ConfigError(format!("invalid value '{}' in {}: {}", value, variable, e))
+    })?;
+    Ok(())
+}
+
+/// On-disk format, chosen by file extension; anything but `.toml` is JSON.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
         }
     }
 }
@@ -73,7 +518,14 @@ impl Config {
         
         if path.exists() {
             let content = fs::read_to_string(path)?;
-            let config: Config = serde_json::from_str(&content)?;
+            let format = ConfigFormat::for_path(path);
+            let mut config: Config = match format {
+                ConfigFormat::Json => serde_json::from_str(&content)?,
+                ConfigFormat::Toml => toml::from_str(&content)?,
+            };
+            if config.version < CONFIG_VERSION {
+                config.migrate(path, &content, format)?;
+            }
             Ok(config)
         } else {
             let config = Config::default();
@@ -82,8 +534,122 @@ impl Config {
         }
     }
     
+    /// Upgrades a config loaded from an older file. Missing fields already
+    /// got their defaults while loading; this records which ones, bumps the
+    /// version and rewrites the file, keeping the original as a backup.
+    fn migrate(&mut self, path: &Path, content: &str, format: ConfigFormat) -> Result<(), Box<dyn std::error::Error>> {
+        let original: serde_json::Value = match format {
+            ConfigFormat::Json => serde_json::from_str(content)?,
+            ConfigFormat::Toml => serde_json::to_value(toml::from_str::<toml::Value>(content)?)?,
+        };
+        let from_version = self.version;
+        self.version = CONFIG_VERSION;
+        
+        let mut added = Vec::new();
+        added_fields(&original, &serde_json::to_value(&*self)?, "", &mut added);
+        added.retain(|field| field != "version");
+        
+        // A read-only config still loads; it is just migrated again next time
+        let backup = format!("{}.v{}.bak", path.display(), from_version);
+        let rewritten = match fs::copy(path, &backup) {
+            Ok(_) => self.save(path).map(|()| backup).map_err(|e| e.to_string()),
+            Err(e) => Err(format!("couldn't back it up to {}: {}", backup, e)),
+        };
+        
+        self.migration = Some(Migration { from_version, added, rewritten });
+        Ok(())
+    }
+    
+    pub fn apply_env_overrides(&mut self) -> crate::error::Result<()> {
+        env_override("LOG_LEVEL", &mut self.logging.level, env_log_level)?;
+        env_override("LOG_FILE", &mut self.logging.file, env_path)?;
+        env_override("LOG_CONSOLE", &mut self.logging.enable_console, env_bool)?;
+        env_override("LOG_TO_FILE", &mut self.logging.enable_file, env_bool)?;
+        
+        env_override("BUFFER_SIZE", &mut self.performance.buffer_size, env_number)?;
+        env_override("MAX_PACKETS_PER_SECOND", &mut self.performance.max_packets_per_second, env_number)?;
+        env_override("DASHBOARD_REFRESH_MS", &mut self.performance.dashboard_refresh_rate, env_number)?;
+        env_override("ROLLING_WINDOW_SECS", &mut self.performance.rolling_window_secs, env_number)?;
+        env_override("ANALYSIS_WORKERS", &mut self.performance.analysis_workers, env_number)?;
+        env_override("MAX_RETAINED_PACKETS", &mut self.performance.max_retained_packets, env_number)?;
+        env_override("MAX_BANDWIDTH_POINTS", &mut self.performance.max_bandwidth_points, env_number)?;
+        env_override("MAX_ALERTS", &mut self.performance.max_alerts, env_number)?;
+        env_override("CONNECTION_IDLE_TIMEOUT_SECS", &mut self.performance.connection_idle_timeout_secs, env_number)?;
+        
+        env_override("EXPORT_FORMAT", &mut self.export.default_format, env_text)?;
+        env_override("EXPORT_DIR", &mut self.export.default_directory, env_text)?;
+        env_override("AUTO_BACKUP", &mut self.export.auto_backup, env_bool)?;
+        
+        env_override("COLORS", &mut self.ui.colors_enabled, env_bool)?;
+        env_override("EMOJIS", &mut self.ui.emojis_enabled, env_bool)?;
+        env_override("TIMEZONE", &mut self.ui.timezone, env_text)?;
+        env_override("TIMESTAMP_FORMAT", &mut self.ui.timestamp_format, env_text)?;
+        
+        env_override("GEOIP_DATABASE", &mut self.geoip.database_path, env_path)?;
+        env_override("OUI_DATABASE", &mut self.oui.database_path, env_path)?;
+        env_override("ALERT_WEBHOOK_URL", &mut self.alerts.webhook_url, env_path)?;
+        env_override("ALERT_SYSLOG", &mut self.alerts.syslog, env_bool)?;
+        Ok(())
+    }
+    
+    /// Checks every field with a fixed set of values or a sensible range, and
+    /// reports all the problems at once rather than stopping at the first.
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: String| {
+            if !ok {
+                problems.push(problem);
+            }
+        };
+        
+        check(crate::logger::LogLevel::parse(&self.logging.level).is_ok(),
+              format!("logging.level '{}' is not one of error, warn, info, debug", self.logging.level));
+        check(TABLE_STYLES.contains(&self.ui.table_style.as_str()),
+              format!("ui.table_style '{}' is not one of {}", self.ui.table_style, TABLE_STYLES.join(", ")));
+        check(TIMEZONES.iter().any(|zone| zone.eq_ignore_ascii_case(&self.ui.timezone)),
+              format!("ui.timezone '{}' is not one of {}", self.ui.timezone, TIMEZONES.join(", ")));
+        check(!self.ui.timestamp_format.is_empty() && !StrftimeItems::new(&self.ui.timestamp_format).any(|item| item == Item::Error),
+              format!("ui.timestamp_format '{}' is not a valid strftime format", self.ui.timestamp_format));
+        check(EXPORT_FORMATS.contains(&self.export.default_format.as_str()),
+              format!("export.default_format '{}' is not one of {}", self.export.default_format, EXPORT_FORMATS.join(", ")));
+        
+        let performance = &self.performance;
+        check(performance.buffer_size > 0, "performance.buffer_size must be above 0".to_string());
+        check((MIN_DASHBOARD_REFRESH_MS..=MAX_DASHBOARD_REFRESH_MS).contains(&performance.dashboard_refresh_rate),
+              format!("performance.dashboard_refresh_rate must be {}-{} ms (got {})", MIN_DASHBOARD_REFRESH_MS, MAX_DASHBOARD_REFRESH_MS, performance.dashboard_refresh_rate));
+        check(performance.rolling_window_secs > 0, "performance.rolling_window_secs must be above 0".to_string());
+        check(performance.max_bandwidth_points > 0, "performance.max_bandwidth_points must be above 0".to_string());
+        check(performance.rolling_window_secs as usize <= performance.max_bandwidth_points,
+              format!("performance.rolling_window_secs ({}) can't be longer than the {} seconds of history kept (performance.max_bandwidth_points)",
+                      performance.rolling_window_secs, performance.max_bandwidth_points));
+        
+        let threat = &self.threat;
+        check(threat.port_scan_window_secs > 0, "threat.port_scan_window_secs must be above 0".to_string());
+        check(threat.port_scan_threshold > 0, "threat.port_scan_threshold must be above 0".to_string());
+        check(threat.syn_flood_window_secs > 0, "threat.syn_flood_window_secs must be above 0".to_string());
+        check(threat.min_normal_packet_size <= threat.max_normal_packet_size,
+              format!("threat.min_normal_packet_size ({}) is above threat.max_normal_packet_size ({})",
+                      threat.min_normal_packet_size, threat.max_normal_packet_size));
+        
+        // These report their own errors; collect them with the rest
+        let nested = [threat.validate().err(), parse_port_protocols(&self.port_protocols).err()];
+        problems.extend(nested.into_iter().flatten().map(|e| match e {
+            crate::error::PacketSnifferError::ConfigError(message) => message,
+            other => other.to_string(),
+        }));
+        
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(crate::error::PacketSnifferError::ConfigError(problems.remove(0))),
+            count => Err(crate::error::PacketSnifferError::ConfigError(format!("{} problems: {}", count, problems.join("; ")))),
+        }
+    }
+    
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
-        let content = serde_json::to_string_pretty(self)?;
+        let content = match ConfigFormat::for_path(path.as_ref()) {
+            ConfigFormat::Json => serde_json::to_string_pretty(self)?,
+            ConfigFormat::Toml => toml::to_string_pretty(self)?,
+        };
         
         // Create parent directory if it doesn't exist
         if let Some(parent) = path.as_ref().parent() {
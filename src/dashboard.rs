@@ -0,0 +1,1002 @@
+//! Interactive dashboard on top of ratatui. The capture thread keeps filling
+//! the shared stats; this side snapshots them once per refresh and redraws,
+//! polling the keyboard in between so keys respond without waiting a tick.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Stdout};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use tokio::sync::watch;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Margin, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{BarChart, Block, Clear, List, ListItem, Paragraph, Row, Sparkline, Table, Wrap};
+use ratatui::{Frame, Terminal};
+use regex::{Regex, RegexBuilder};
+
+use crate::export::ExportPaths;
+use crate::pipeline::Snapshot;
+use crate::rdns::NameResolver;
+use crate::services::{self, Transport};
+use crate::style;
+use crate::{format_bytes, rank_talkers, sorted_counts, NetworkStats, PacketInfo, TalkerSort, TalkerStats, ThreatLevel};
+
+/// How long export results stay in the footer
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Activity panel height, borders included
+const ACTIVITY_HEIGHT: u16 = 10;
+
+type Tui = Terminal<CrosstermBackend<Stdout>>;
+
+/// Puts the terminal into raw mode on the alternate screen and restores it on
+/// drop, so an early return or panic doesn't leave the shell unusable.
+struct TerminalGuard {
+    terminal: Tui,
+}
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        if let Err(e) = execute!(stdout, EnterAlternateScreen) {
+            let _ = disable_raw_mode();
+            return Err(e);
+        }
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        Ok(TerminalGuard { terminal })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// What the UI is showing, separate from the live shared state so a paused
+/// dashboard keeps its last frame while capture carries on.
+struct DashboardState {
+    snapshot: Arc<Snapshot>,
+    talker_sort: TalkerSort,
+    names: NameResolver,
+    paused: bool,
+    show_help: bool,
+    search: Option<Search>,
+    /// While scrolled back through the activity list, the number of the
+    /// newest packet shown, so arriving packets don't move the view
+    activity_anchor: Option<usize>,
+    status: Option<(String, Instant)>,
+    last_updated: chrono::DateTime<Utc>,
+}
+
+impl DashboardState {
+    fn set_status(&mut self, message: String) {
+        self.status = Some((message, Instant::now()));
+    }
+
+    /// How many packets back from the newest the activity list starts.
+    fn activity_offset(&self) -> usize {
+        let packets = &self.snapshot.packets;
+        match (self.activity_anchor, packets.last()) {
+            // Retained packets are numbered consecutively; an anchor that
+            // has been dropped from the buffer pins the view to the oldest
+            (Some(anchor), Some(newest)) => newest.packet_number.saturating_sub(anchor).min(packets.len() - 1),
+            _ => 0,
+        }
+    }
+
+    /// Moves the activity list `lines` older (negative: newer). Back at the
+    /// newest packet it follows live traffic again.
+    fn scroll_activity(&mut self, lines: isize) {
+        let Some(newest) = self.snapshot.packets.last() else {
+            return;
+        };
+        let offset = self.activity_offset().saturating_add_signed(lines).min(self.snapshot.packets.len() - 1);
+        self.activity_anchor = (offset > 0).then(|| newest.packet_number - offset);
+    }
+}
+
+/// `/`: the retained packets whose addresses, protocols or description match
+/// a term, newest first. The term is a case-insensitive regex, or plain text
+/// when it isn't valid as one.
+struct Search {
+    query: String,
+    /// Still being typed; nothing is matched until Enter
+    editing: bool,
+    pattern: Option<Regex>,
+    /// The query didn't compile as a regex, so it's matched literally
+    literal: bool,
+    page: usize,
+}
+
+impl Search {
+    fn new() -> Self {
+        Search { query: String::new(), editing: true, pattern: None, literal: false, page: 0 }
+    }
+
+    fn run(&mut self) {
+        let (pattern, literal) = match RegexBuilder::new(&self.query).case_insensitive(true).build() {
+            Ok(pattern) => (pattern, false),
+            Err(_) => (RegexBuilder::new(&regex::escape(&self.query)).case_insensitive(true).build().expect("escaped text is a valid regex"), true),
+        };
+        self.pattern = Some(pattern);
+        self.literal = literal;
+        self.editing = false;
+        self.page = 0;
+    }
+
+    fn matches(&self, packet: &PacketInfo) -> bool {
+        let Some(ref pattern) = self.pattern else {
+            return false;
+        };
+        [packet.src_ip.as_deref(), packet.dst_ip.as_deref(), Some(packet.protocol.as_str()), packet.application_protocol.as_deref(), Some(packet.description.as_str())]
+            .into_iter()
+            .flatten()
+            .any(|field| pattern.is_match(field))
+    }
+
+    fn results<'a>(&'a self, packets: &'a [Arc<PacketInfo>]) -> impl Iterator<Item = &'a PacketInfo> {
+        packets.iter().rev().map(|packet| &**packet).filter(|packet| self.matches(packet))
+    }
+}
+
+/// Search results that fit on one page of the overlay drawn over `screen`.
+fn search_page_size(screen: Rect) -> usize {
+    // Overlay margins, borders and the query line
+    (search_area(screen).height as usize).saturating_sub(4).max(1)
+}
+
+fn search_area(screen: Rect) -> Rect {
+    screen.inner(&Margin { horizontal: screen.width / 10, vertical: screen.height / 8 })
+}
+
+/// A JSON export written from the dashboard, for logging once the terminal
+/// is back to normal.
+pub struct DashboardExport {
+    pub filename: String,
+    pub packets: usize,
+}
+
+/// Runs until the user quits, a shutdown is requested elsewhere, or the
+/// capture thread finishes.
+pub fn run(snapshots: &mut watch::Receiver<Arc<Snapshot>>, refresh_interval: Duration, talker_sort: TalkerSort, names: &NameResolver, export_paths: &ExportPaths) -> io::Result<Vec<DashboardExport>> {
+    let mut guard = TerminalGuard::enter()?;
+    let mut exports = Vec::new();
+
+    let mut state = DashboardState {
+        snapshot: snapshots.borrow_and_update().clone(),
+        talker_sort,
+        names: names.clone(),
+        paused: false,
+        show_help: false,
+        search: None,
+        activity_anchor: None,
+        status: None,
+        last_updated: Utc::now(),
+    };
+
+    // The sender goes away once the capture is over
+    while !crate::shutdown_requested() && snapshots.has_changed().is_ok() {
+        if !state.paused {
+            state.snapshot = snapshots.borrow_and_update().clone();
+            state.last_updated = Utc::now();
+        }
+        if state.status.as_ref().is_some_and(|(_, shown)| shown.elapsed() >= STATUS_TIMEOUT) {
+            state.status = None;
+        }
+
+        guard.terminal.draw(|frame| render(frame, &state))?;
+
+        // Handle keys until the next redraw is due
+        let next_draw = Instant::now() + refresh_interval;
+        loop {
+            let remaining = next_draw.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || crate::shutdown_requested() || !event::poll(remaining)? {
+                break;
+            }
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                crate::request_shutdown();
+                break;
+            }
+            if let Some(ref mut search) = state.search {
+                if search.editing {
+                    match key.code {
+                        KeyCode::Esc => state.search = None,
+                        KeyCode::Enter if search.query.is_empty() => state.search = None,
+                        KeyCode::Enter => search.run(),
+                        KeyCode::Backspace => {
+                            search.query.pop();
+                        }
+                        KeyCode::Char(c) => search.query.push(c),
+                        _ => {}
+                    }
+                    break;
+                }
+
+                let page_size = search_page_size(guard.terminal.size()?);
+                let last_page = search.results(&state.snapshot.packets).count().saturating_sub(1) / page_size;
+                let handled = match key.code {
+                    KeyCode::Esc => {
+                        state.search = None;
+                        true
+                    }
+                    KeyCode::Char('/') => {
+                        search.editing = true;
+                        true
+                    }
+                    KeyCode::PageDown | KeyCode::Right | KeyCode::Char('n') => {
+                        search.page = (search.page + 1).min(last_page);
+                        true
+                    }
+                    KeyCode::PageUp | KeyCode::Left | KeyCode::Char('p') => {
+                        search.page = search.page.saturating_sub(1).min(last_page);
+                        true
+                    }
+                    KeyCode::Home => {
+                        search.page = 0;
+                        true
+                    }
+                    _ => false,
+                };
+                if handled {
+                    break;
+                }
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => crate::request_shutdown(),
+                KeyCode::Char(' ') => {
+                    state.paused = !state.paused;
+                    break;
+                }
+                KeyCode::Char('e') | KeyCode::Char('E') => {
+                    // Export what is on screen, which is the frozen buffer while paused
+                    let filename = Utc::now().format("dashboard_export_%Y%m%d_%H%M%S.json").to_string();
+                    let written = export_paths.prepare(&filename)
+                        .and_then(|filename| crate::write_json(&state.snapshot.packets, &filename).map(|()| filename));
+                    match written {
+                        Ok(filename) => {
+                            state.set_status(format!("✅ Exported {} packets to {}", state.snapshot.packets.len(), filename));
+                            exports.push(DashboardExport { filename, packets: state.snapshot.packets.len() });
+                        }
+                        Err(e) => state.set_status(format!("❌ {}", e)),
+                    }
+                    break;
+                }
+                KeyCode::Char('s') | KeyCode::Char('S') => {
+                    state.talker_sort = match state.talker_sort {
+                        TalkerSort::Bytes => TalkerSort::Packets,
+                        TalkerSort::Packets => TalkerSort::Bytes,
+                    };
+                    break;
+                }
+                KeyCode::Char('h') | KeyCode::Char('H') | KeyCode::Char('?') => {
+                    state.show_help = !state.show_help;
+                    break;
+                }
+                KeyCode::Char('/') => {
+                    state.search = Some(Search::new());
+                    break;
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    state.scroll_activity(1);
+                    break;
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    state.scroll_activity(-1);
+                    break;
+                }
+                KeyCode::PageUp => {
+                    state.scroll_activity(activity_rows() as isize);
+                    break;
+                }
+                KeyCode::PageDown => {
+                    state.scroll_activity(-(activity_rows() as isize));
+                    break;
+                }
+                KeyCode::End | KeyCode::Char('G') => {
+                    state.activity_anchor = None;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(exports)
+}
+
+/// Applies emoji settings to text headed for the terminal.
+fn text(s: &str) -> String {
+    if style::emojis_enabled() {
+        s.to_string()
+    } else {
+        style::strip_emojis(s)
+    }
+}
+
+/// A foreground color, or the terminal default when colors are disabled.
+fn fg(color: Color) -> Style {
+    if style::colors_enabled() {
+        Style::default().fg(color)
+    } else {
+        Style::default()
+    }
+}
+
+fn titled_block(title: &str) -> Block<'static> {
+    Block::bordered()
+        .border_style(fg(Color::Blue))
+        .title(Span::styled(text(title), fg(Color::Yellow).add_modifier(Modifier::BOLD)))
+}
+
+fn threat_icon(level: &ThreatLevel) -> &'static str {
+    match level {
+        ThreatLevel::Safe => "✅",
+        ThreatLevel::Low => "🟡",
+        ThreatLevel::Medium => "🟠",
+        ThreatLevel::High => "🔴",
+        ThreatLevel::Critical => "💀",
+    }
+}
+
+fn threat_color(level: &ThreatLevel) -> Color {
+    match level {
+        ThreatLevel::Safe => Color::Green,
+        ThreatLevel::Low => Color::Yellow,
+        ThreatLevel::Medium => Color::LightRed,
+        ThreatLevel::High | ThreatLevel::Critical => Color::Red,
+    }
+}
+
+fn render(frame: &mut Frame, state: &DashboardState) {
+    // A per-interface line only when capturing on more than one
+    let header_height = if state.snapshot.stats.interface_traffic.len() > 1 { 6 } else { 5 };
+    let [header, traffic, tables, details, activity, footer] = Layout::vertical([
+        Constraint::Length(header_height),
+        Constraint::Length(9),
+        Constraint::Min(8),
+        Constraint::Length(8),
+        Constraint::Length(ACTIVITY_HEIGHT),
+        Constraint::Length(1),
+    ]).areas(frame.size());
+
+    render_header(frame, header, state);
+
+    let [bandwidth, security] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(traffic);
+    render_bandwidth(frame, bandwidth, &state.snapshot.stats);
+    render_security(frame, security, state);
+
+    let [protocols, connections, talkers] = Layout::horizontal([
+        Constraint::Percentage(22),
+        Constraint::Percentage(43),
+        Constraint::Percentage(35),
+    ]).areas(tables);
+    render_protocols(frame, protocols, &state.snapshot.stats);
+    render_connections(frame, connections, &state.snapshot.stats, &state.names);
+    render_talkers(frame, talkers, &state.snapshot.stats, state.talker_sort, &state.names);
+
+    let [ports, sizes, geography, names, multicast] = Layout::horizontal([
+        Constraint::Percentage(14),
+        Constraint::Percentage(18),
+        Constraint::Percentage(14),
+        Constraint::Percentage(36),
+        Constraint::Percentage(18),
+    ]).areas(details);
+    render_ports(frame, ports, &state.snapshot.stats.port_activity);
+    render_packet_sizes(frame, sizes, &state.snapshot.stats.packet_sizes);
+    render_geography(frame, geography, &state.snapshot.packets);
+    render_top_names(frame, names, &state.snapshot.packets, &state.snapshot.stats);
+    render_multicast(frame, multicast, &state.snapshot.stats);
+
+    render_activity(frame, activity, &state.snapshot, state.activity_offset(), &state.names);
+    render_footer(frame, footer, state);
+
+    if let Some(ref search) = state.search {
+        render_search(frame, search, &state.snapshot, &state.names);
+    }
+    if state.show_help {
+        render_help(frame);
+    }
+}
+
+fn render_header(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let stats = &state.snapshot.stats;
+    let duration = stats.elapsed().as_secs();
+    // Estimates for the whole link when sampling
+    let scale = stats.sample_scale();
+    let (total_packets, total_bytes) = (stats.total_packets * scale, stats.total_bytes * scale);
+    let packets_per_sec = if duration > 0 { total_packets as f64 / duration as f64 } else { 0.0 };
+    let bytes_per_sec = if duration > 0 { total_bytes as f64 / duration as f64 } else { 0.0 };
+    let (current_bytes, current_packets) = stats.rolling_rate();
+    let (current_bytes, current_packets) = (current_bytes * scale as f64, current_packets * scale as f64);
+    let (current_upload, current_download) = stats.rolling_direction_rate();
+
+    let label = |s: &str| Span::styled(text(s), fg(Color::Cyan));
+    let value = |s: String, color: Color| Span::styled(s, fg(color).add_modifier(Modifier::BOLD));
+    let kernel_dropped = stats.kernel_dropped.unwrap_or(0);
+    let dropped_color = if stats.dropped_packets > 0 || stats.capture_overflow > 0 || kernel_dropped > 0 { Color::Red } else { Color::Green };
+    let mut dropped = format!("{} rate limit", stats.dropped_packets);
+    if stats.capture_overflow > 0 {
+        dropped.push_str(&format!(", {} overload", stats.capture_overflow));
+    }
+    if let Some(kernel_dropped) = stats.kernel_dropped {
+        dropped.push_str(&format!(", {} kernel", kernel_dropped));
+    }
+    let (sources, destinations) = stats.unique_hosts();
+
+    let mut lines = vec![
+        Line::from(vec![
+            label("⏱ Duration: "), value(format!("{}s", duration), Color::Yellow),
+            label("  📦 Packets: "), value(format!("{} ({:.1}/s now, {:.1}/s avg)", total_packets, current_packets, packets_per_sec), Color::Yellow),
+            label("  📊 Data: "), value(format!("{} ({}/s now, {}/s avg)", format_bytes(total_bytes),
+                format_bytes(current_bytes as usize), format_bytes(bytes_per_sec as usize)), Color::Yellow),
+            label("  🔗 Connections: "), value(stats.current_connections.to_string(), Color::Yellow),
+            label(&stats.sampling_note()),
+        ]),
+        Line::from(vec![
+            label(&format!("⚡ Peak Bandwidth ({}s avg): ", stats.rolling_window_secs)), value(format!("{}/s", format_bytes(stats.peak_bandwidth as usize)), Color::Red),
+            label("  Peak Packets: "), value(format!("{:.1}/s", stats.peak_packets_per_sec), Color::Red),
+            label("  🚫 Dropped: "), value(dropped, dropped_color),
+            label("  🖥 Hosts: "), value(format!("{} src / {} dst", sources, destinations), Color::Yellow),
+            label(&format!(" (+{} in {}s)", stats.recent_new_hosts(), crate::NEW_HOST_WINDOW_SECS)),
+        ]),
+        if stats.direction_known() {
+            Line::from(vec![
+                label("↑ Upload: "), value(format!("{}/s now, {} total", format_bytes(current_upload as usize), format_bytes(stats.uploaded.bytes)), Color::Magenta),
+                label("  ↓ Download: "), value(format!("{}/s now, {} total", format_bytes(current_download as usize), format_bytes(stats.downloaded.bytes)), Color::Green),
+                label(&format!("  ({}s avg; transit and local traffic in neither)", stats.rolling_window_secs)),
+            ])
+        } else {
+            Line::from(vec![label("⇅ Upload / Download: "), value("unknown".to_string(), Color::DarkGray), label(" (no local addresses to judge direction by)")])
+        },
+    ];
+    if stats.interface_traffic.len() > 1 {
+        let mut interfaces: Vec<_> = stats.interface_traffic.iter().collect();
+        interfaces.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut spans = vec![label("📡 Interfaces:")];
+        for (name, traffic) in interfaces {
+            spans.push(label(&format!("  {} ", name)));
+            spans.push(value(format!("{} pkts, {}", traffic.packets, format_bytes(traffic.bytes)), Color::Yellow));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let mut title = String::from("🚀 ADVANCED NETWORK TRAFFIC DASHBOARD");
+    if state.paused {
+        title.push_str(" [PAUSED]");
+    }
+    let block = Block::bordered()
+        .border_style(fg(Color::Blue))
+        .title(Span::styled(text(&title), fg(Color::Green).add_modifier(Modifier::BOLD)));
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_bandwidth(frame: &mut Frame, area: Rect, stats: &NetworkStats) {
+    let title = match stats.bandwidth_history.back() {
+        Some(point) => format!("📈 BANDWIDTH ({}/s)", format_bytes(point.bytes_per_sec as usize)),
+        None => "📈 BANDWIDTH".to_string(),
+    };
+    let block = titled_block(&title);
+
+    if stats.bandwidth_history.is_empty() {
+        let waiting = Paragraph::new(Span::styled("No data available yet...", fg(Color::DarkGray))).block(block);
+        frame.render_widget(waiting, area);
+        return;
+    }
+
+    // Newest samples on the right, as many as fit
+    let width = area.width.saturating_sub(2) as usize;
+    let data: Vec<u64> = stats.bandwidth_history.iter()
+        .rev()
+        .take(width)
+        .rev()
+        .map(|point| point.bytes_per_sec as u64)
+        .collect();
+
+    let sparkline = Sparkline::default()
+        .block(block)
+        .data(&data)
+        .style(fg(Color::Green));
+    frame.render_widget(sparkline, area);
+}
+
+fn render_security(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let stats = &state.snapshot.stats;
+    let threat_counts = state.snapshot.packets.iter().fold([0; 5], |mut acc, packet| {
+        match packet.threat_level {
+            ThreatLevel::Safe => acc[0] += 1,
+            ThreatLevel::Low => acc[1] += 1,
+            ThreatLevel::Medium => acc[2] += 1,
+            ThreatLevel::High => acc[3] += 1,
+            ThreatLevel::Critical => acc[4] += 1,
+        }
+        acc
+    });
+    let total_threats = threat_counts[1] + threat_counts[2] + threat_counts[3] + threat_counts[4];
+
+    let status = if total_threats == 0 {
+        Span::styled(text("✅ SECURE"), fg(Color::Green).add_modifier(Modifier::BOLD))
+    } else {
+        Span::styled(text("⚠ THREATS DETECTED"), fg(Color::Red).add_modifier(Modifier::BOLD))
+    };
+
+    let mut lines = vec![
+        Line::from(vec![status, Span::styled(format!(" ({} alerts)", stats.threat_alerts.len()), fg(Color::DarkGray))]),
+        Line::styled(
+            format!("Safe:{} Low:{} Med:{} High:{} Crit:{}", threat_counts[0], threat_counts[1], threat_counts[2], threat_counts[3], threat_counts[4]),
+            fg(Color::Cyan),
+        ),
+    ];
+
+    let scanners = stats.port_scans.active_scanners();
+    if !scanners.is_empty() {
+        let scanner_list = scanners.iter()
+            .take(5)
+            .map(|(ip, ports)| format!("{} ({} ports)", ip, ports))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(Line::from(vec![
+            Span::styled(text("🔭 Active scanners: "), fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::styled(scanner_list, fg(Color::Yellow)),
+        ]));
+    }
+
+    let flooded = stats.syn_floods.flooded_targets();
+    if !flooded.is_empty() {
+        let target_list = flooded.iter()
+            .take(5)
+            .map(|(ip, half_open)| format!("{} ({} half-open)", ip, half_open))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(Line::from(vec![
+            Span::styled(text("🌊 SYN flood: "), fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::styled(target_list, fg(Color::Yellow)),
+        ]));
+    }
+
+    let arp_bindings = stats.arp_watch.bindings().len();
+    if arp_bindings > 0 {
+        lines.push(Line::from(vec![
+            Span::styled(text("🔁 ARP table: "), fg(Color::Cyan)),
+            Span::styled(format!("{} IP/MAC bindings learned", arp_bindings), fg(Color::DarkGray)),
+        ]));
+    }
+
+    // Recent alerts fill whatever room is left
+    let room = (area.height.saturating_sub(2) as usize).saturating_sub(lines.len());
+    for (timestamp, message, level) in stats.threat_alerts.iter().rev().take(room) {
+        lines.push(Line::from(vec![
+            Span::raw(text(&format!("{} ", threat_icon(level)))),
+            Span::styled(format!("{} ", style::format_time(timestamp, "%H:%M:%S")), fg(Color::DarkGray)),
+            Span::styled(message.clone(), fg(threat_color(level))),
+        ]));
+    }
+
+    frame.render_widget(Paragraph::new(lines).block(titled_block("🚨 SECURITY")), area);
+}
+
+fn render_protocols(frame: &mut Frame, area: Rect, stats: &NetworkStats) {
+    let mut protocols: Vec<_> = stats.protocol_counts.iter().collect();
+    protocols.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let rows = protocols.into_iter().map(|(protocol, count)| {
+        let percentage = (*count as f64 / stats.total_packets.max(1) as f64) * 100.0;
+        let bytes = stats.protocol_bytes.get(protocol).copied().unwrap_or(0);
+        let byte_percentage = (bytes as f64 / stats.total_bytes.max(1) as f64) * 100.0;
+        Row::new(vec![
+            Span::styled(protocol.clone(), fg(Color::Green)),
+            Span::styled(count.to_string(), fg(Color::Yellow)),
+            Span::raw(format!("{:.1}%", percentage)),
+            Span::raw(format!("{:.1}%", byte_percentage)),
+        ])
+    });
+
+    let table = Table::new(rows, [Constraint::Min(8), Constraint::Length(8), Constraint::Length(6), Constraint::Length(6)])
+        .header(Row::new(["Protocol", "Packets", "Pkt%", "Byte%"]).style(fg(Color::Cyan)))
+        .block(titled_block("🔗 PROTOCOLS"));
+    frame.render_widget(table, area);
+}
+
+fn render_connections(frame: &mut Frame, area: Rect, stats: &NetworkStats, names: &NameResolver) {
+    let mut connections: Vec<_> = stats.connections.values().collect();
+    connections.sort_by_key(|c| std::cmp::Reverse(c.packet_count));
+
+    let endpoint = |ip: &str, port: Option<u16>| match port {
+        Some(port) => format!("{}:{}", names.label(ip), port),
+        None => names.label(ip),
+    };
+
+    let rows = connections.into_iter()
+        .take(area.height.saturating_sub(3) as usize)
+        .map(|connection| {
+            Row::new(vec![
+                Span::raw(text(threat_icon(&connection.threat_level))),
+                Span::styled(
+                    format!("{} ↔ {}", endpoint(&connection.src_ip, connection.src_port), endpoint(&connection.dst_ip, connection.dst_port)),
+                    fg(Color::Blue),
+                ),
+                Span::styled(connection.protocol.clone(), fg(Color::Green)),
+                Span::styled(connection.packet_count.to_string(), fg(Color::Yellow)),
+                Span::styled(format_bytes(connection.total_bytes), fg(Color::Cyan)),
+                Span::styled(
+                    connection.retransmissions.to_string(),
+                    fg(if connection.retransmissions > 0 { Color::Red } else { Color::DarkGray }),
+                ),
+                Span::styled(
+                    connection.rtt().map_or("-".to_string(), |rtt| {
+                        format!("{}/{}/{}", rtt_ms(rtt.min_ms), rtt_ms(rtt.avg_ms), rtt_ms(rtt.max_ms))
+                    }),
+                    fg(Color::Magenta),
+                ),
+            ])
+        });
+
+    let widths = [
+        Constraint::Length(if style::emojis_enabled() { 2 } else { 6 }),
+        Constraint::Min(20),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(5),
+        Constraint::Length(14),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["", "Flow", "Proto", "Packets", "Bytes", "Retx", "RTT ms"]).style(fg(Color::Cyan)))
+        .block(titled_block("🌍 TOP CONNECTIONS (RTT min/avg/max)"));
+    frame.render_widget(table, area);
+}
+
+/// Milliseconds with one decimal below 10ms, whole numbers above.
+fn rtt_ms(ms: f64) -> String {
+    if ms < 10.0 { format!("{:.1}", ms) } else { format!("{:.0}", ms) }
+}
+
+fn render_talkers(frame: &mut Frame, area: Rect, stats: &NetworkStats, sort: TalkerSort, names: &NameResolver) {
+    let sort_label = match sort {
+        TalkerSort::Bytes => "by bytes",
+        TalkerSort::Packets => "by packets",
+    };
+    let block = titled_block(&format!("📣 TOP TALKERS ({})", sort_label));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let [sending, receiving] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(inner);
+    render_talker_table(frame, sending, "⬆ Sending", &stats.top_talkers, stats, sort, names);
+    render_talker_table(frame, receiving, "⬇ Receiving", &stats.top_receivers, stats, sort, names);
+}
+
+fn render_talker_table(frame: &mut Frame, area: Rect, title: &str, talkers: &HashMap<String, TalkerStats>, stats: &NetworkStats, sort: TalkerSort, names: &NameResolver) {
+    let limit = area.height.saturating_sub(1) as usize;
+    let rows = rank_talkers(talkers, sort, limit).into_iter().map(|(ip, talker)| {
+        // Only hosts that sent something have a guess
+        let os = stats.os_guesses.get(ip).map_or("", |guess| guess.os);
+        Row::new(vec![
+            Span::styled(names.label(ip), fg(Color::Blue)),
+            Span::styled(os, fg(Color::DarkGray)),
+            Span::styled(format_bytes(talker.bytes), fg(Color::Cyan)),
+            Span::styled(format!("{} pkts", talker.packets), fg(Color::Yellow)),
+        ])
+    });
+
+    let table = Table::new(rows, [Constraint::Min(15), Constraint::Length(14), Constraint::Length(9), Constraint::Length(10)])
+        .header(Row::new([text(title), text("OS (guess)")]).style(fg(Color::Cyan)));
+    frame.render_widget(table, area);
+}
+
+fn render_ports(frame: &mut Frame, area: Rect, port_activity: &HashMap<u16, usize>) {
+    let mut ports: Vec<_> = port_activity.iter().collect();
+    ports.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let rows = ports.into_iter()
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|(port, count)| {
+            let color = match *port {
+                80 | 443 => Color::Green,
+                22 | 23 => Color::Yellow,
+                53 => Color::Blue,
+                _ if *port > 1024 => Color::Cyan,
+                _ => Color::Red,
+            };
+            Row::new(vec![
+                Span::styled(port_label(*port), fg(color)),
+                Span::styled(count.to_string(), fg(Color::DarkGray)),
+            ])
+        });
+
+    let table = Table::new(rows, [Constraint::Min(6), Constraint::Length(7)])
+        .block(titled_block("🚪 PORTS"));
+    frame.render_widget(table, area);
+}
+
+/// `443 (https)`, or just the number for ports without a known service. The
+/// panel counts ports without their transport, so the TCP name goes first.
+fn port_label(port: u16) -> String {
+    match services::name(port, Transport::Tcp).or_else(|| services::name(port, Transport::Udp)) {
+        Some(name) => format!("{} ({})", port, name),
+        None => port.to_string(),
+    }
+}
+
+fn render_packet_sizes(frame: &mut Frame, area: Rect, packet_sizes: &VecDeque<usize>) {
+    let small = packet_sizes.iter().filter(|&&s| s < 100).count() as u64;
+    let medium = packet_sizes.iter().filter(|&&s| (100..500).contains(&s)).count() as u64;
+    let large = packet_sizes.iter().filter(|&&s| s >= 500).count() as u64;
+
+    let title = if packet_sizes.is_empty() {
+        "📏 PACKET SIZES".to_string()
+    } else {
+        let avg_size = packet_sizes.iter().sum::<usize>() / packet_sizes.len();
+        format!("📏 PACKET SIZES (avg {}B)", avg_size)
+    };
+
+    let mut block = titled_block(&title);
+    if let Some([p50, p90, p99]) = crate::size_percentiles(packet_sizes) {
+        // The window is the last PACKET_SIZE_WINDOW packets, not the whole capture
+        block = block.title_bottom(Span::styled(
+            format!("p50 {} p90 {} p99 {}B, last {}", p50, p90, p99, packet_sizes.len()),
+            fg(Color::DarkGray),
+        ));
+    }
+
+    let chart = BarChart::default()
+        .block(block)
+        .data(&[("<100B", small), ("100-500", medium), (">500B", large)])
+        .bar_width(7)
+        .bar_gap(1)
+        .bar_style(fg(Color::Green))
+        .value_style(fg(Color::Black).bg(if style::colors_enabled() { Color::Green } else { Color::Reset }));
+    frame.render_widget(chart, area);
+}
+
+fn render_geography(frame: &mut Frame, area: Rect, packets: &[Arc<PacketInfo>]) {
+    let mut country_counts = HashMap::new();
+    for packet in packets.iter().rev().take(500) {
+        if let Some(country) = packet.geo_info.as_ref().and_then(|geo| geo.country.as_ref()) {
+            *country_counts.entry(country.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    let mut countries: Vec<_> = country_counts.into_iter().collect();
+    countries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let items: Vec<ListItem> = countries.into_iter()
+        .map(|(country, count)| {
+            let icon = if country == "Local Network" { "🏠" } else { "🌐" };
+            ListItem::new(Line::from(vec![
+                Span::raw(text(&format!("{} ", icon))),
+                Span::styled(format!("{} ", country), fg(Color::Cyan)),
+                Span::styled(count.to_string(), fg(Color::Yellow)),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items).block(titled_block("🌍 GEOGRAPHY")), area);
+}
+
+/// Domains looked up, from the retained packets, and the web hosts asked for
+/// over HTTP and TLS, from the whole capture.
+fn render_top_names(frame: &mut Frame, area: Rect, packets: &[Arc<PacketInfo>], stats: &NetworkStats) {
+    let [domains, http_hosts, sni] = Layout::horizontal([
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+    ]).areas(area);
+
+    // Count queries only, so a lookup and its response aren't counted twice
+    let mut queried = HashMap::new();
+    let queries = packets.iter()
+        .filter(|p| p.dst_port == Some(53))
+        .filter_map(|p| p.dns_query.as_ref().and_then(|q| q.split_whitespace().next()));
+    for name in queries {
+        *queried.entry(name).or_insert(0) += 1;
+    }
+    render_name_list(frame, domains, "🔎 TOP DOMAINS", sorted_counts(&queried));
+
+    render_name_list(frame, http_hosts, "🌐 TOP HTTP HOSTS", sorted_counts(&stats.http_hosts));
+    render_name_list(frame, sni, "🔒 TOP TLS SNI", sorted_counts(&stats.tls_server_names));
+}
+
+fn render_name_list<K: AsRef<str>>(frame: &mut Frame, area: Rect, title: &str, sorted: Vec<(&K, usize)>) {
+    let items: Vec<ListItem> = sorted.into_iter()
+        .map(|(name, count)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", count), fg(Color::Yellow)),
+                Span::styled(name.as_ref().to_string(), fg(Color::Cyan)),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items).block(titled_block(title)), area);
+}
+
+/// Each group's member count and traffic, then its first member and how many
+/// more there are.
+fn render_multicast(frame: &mut Frame, area: Rect, stats: &NetworkStats) {
+    let groups = stats.active_multicast_groups();
+    let title = match groups.len() {
+        0 => "📡 MULTICAST GROUPS".to_string(),
+        count => format!("📡 MULTICAST GROUPS ({})", count),
+    };
+
+    let items: Vec<ListItem> = groups.into_iter()
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|(group, info)| {
+            let members = match info.members.iter().next() {
+                Some(first) if info.members.len() > 1 => format!("{} +{}", first, info.members.len() - 1),
+                Some(first) => first.clone(),
+                None => "no members".to_string(),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", group), fg(Color::Cyan)),
+                Span::styled(format!("{}m {}p ", info.members.len(), info.traffic.packets), fg(Color::Yellow)),
+                Span::styled(members, fg(if info.members.is_empty() { Color::DarkGray } else { Color::Green })),
+            ]))
+        })
+        .collect();
+
+    let block = titled_block(&title).title_bottom(Span::styled("m members, p packets to it", fg(Color::DarkGray)));
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn activity_rows() -> usize {
+    ACTIVITY_HEIGHT as usize - 2
+}
+
+/// The newest packets, or older ones `offset` back while scrolled.
+fn render_activity(frame: &mut Frame, area: Rect, snapshot: &Snapshot, offset: usize, names: &NameResolver) {
+    let packets = &snapshot.packets;
+    let block = if offset == 0 {
+        titled_block("📋 LIVE ACTIVITY")
+    } else {
+        let shown = packets.len() - offset;
+        titled_block(&format!(
+            "📋 ACTIVITY: {} back, packets {}-{} of {} retained (frozen; End for live)",
+            offset,
+            shown.saturating_sub(activity_rows()) + 1,
+            shown,
+            packets.len()
+        ))
+    };
+
+    if packets.is_empty() {
+        let waiting = Paragraph::new(Span::styled("Waiting for network activity...", fg(Color::DarkGray))).block(block);
+        frame.render_widget(waiting, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = packets.iter()
+        .rev()
+        .skip(offset)
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|packet| ListItem::new(Line::from(packet_spans(packet, &snapshot.stats, names))))
+        .collect();
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// One packet as the activity list shows it, its addresses named from the
+/// DNS answers seen so far.
+fn packet_spans(packet: &PacketInfo, stats: &NetworkStats, names: &NameResolver) -> Vec<Span<'static>> {
+    let detail = packet.dns_query.clone()
+        .or_else(|| packet.http_info.as_ref().map(|h| h.summary()))
+        .or_else(|| packet.tls_sni.clone());
+    let app_proto = match (&packet.application_protocol, detail) {
+        (Some(proto), Some(detail)) => format!(" ({} {})", proto, detail),
+        (Some(proto), None) => format!(" ({})", proto),
+        _ => String::new(),
+    };
+
+    vec![
+        Span::raw(text(&format!("{} ", threat_icon(&packet.threat_level)))),
+        Span::styled(format!("{} ", style::packet_time(&packet.timestamp)), fg(Color::DarkGray)),
+        Span::styled(packet.protocol.clone(), fg(Color::Green).add_modifier(Modifier::BOLD)),
+        Span::styled(app_proto, fg(Color::Yellow)),
+        Span::styled(
+            format!(" {} → {} ", endpoint_label(packet.src_ip.as_deref(), stats, names), endpoint_label(packet.dst_ip.as_deref(), stats, names)),
+            fg(Color::Blue),
+        ),
+        Span::styled(format_bytes(packet.packet_size), fg(Color::Cyan)),
+    ]
+}
+
+fn render_search(frame: &mut Frame, search: &Search, snapshot: &Snapshot, names: &NameResolver) {
+    let packets = &snapshot.packets;
+    let area = search_area(frame.size());
+    let page_size = search_page_size(frame.size());
+    let matches: Vec<&PacketInfo> = search.results(packets).collect();
+    let pages = matches.len().div_ceil(page_size).max(1);
+    let page = search.page.min(pages - 1);
+
+    let query = if search.editing {
+        Line::from(vec![
+            Span::styled("/", fg(Color::Yellow)),
+            Span::raw(search.query.clone()),
+            Span::styled("█", fg(Color::Yellow)),
+            Span::styled("  Enter to search, Esc to cancel", fg(Color::DarkGray)),
+        ])
+    } else {
+        let kind = if search.literal { "text" } else { "regex" };
+        Line::from(vec![
+            Span::styled(format!("{} /{}/: ", kind, search.query), fg(Color::Yellow)),
+            Span::raw(format!("{} of {} retained packets, page {}/{}", matches.len(), packets.len(), page + 1, pages)),
+            Span::styled("  ←/→ page, / edit, Esc close", fg(Color::DarkGray)),
+        ])
+    };
+
+    let mut lines = vec![query, Line::raw("")];
+    if !search.editing && matches.is_empty() {
+        lines.push(Line::from(Span::styled("No retained packet matches.", fg(Color::DarkGray))));
+    }
+    for packet in matches.iter().skip(page * page_size).take(page_size) {
+        let mut spans = vec![Span::styled(format!("#{} ", packet.packet_number), fg(Color::DarkGray))];
+        spans.extend(packet_spans(packet, &snapshot.stats, names));
+        spans.push(Span::raw(format!("  {}", text(&packet.description))));
+        lines.push(Line::from(spans));
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(titled_block("🔍 SEARCH")), area);
+}
+
+fn endpoint_label(ip: Option<&str>, stats: &NetworkStats, names: &NameResolver) -> String {
+    ip.map_or("?".to_string(), |ip| stats.host_label(ip, names))
+}
+
+fn render_footer(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let mut spans = vec![Span::styled(
+        "[Q] Quit  [Space] Pause  [E] Export JSON  [S] Sort talkers  [/] Search  [PgUp/PgDn] Scroll  [H] Help",
+        fg(Color::Cyan),
+    )];
+
+    match &state.status {
+        Some((message, _)) => spans.push(Span::styled(format!("  {}", text(message)), fg(Color::Yellow))),
+        None => spans.push(Span::styled(
+            format!("  Last updated: {} {}", style::format_time(&state.last_updated, "%H:%M:%S"), style::zone_label(&state.last_updated)),
+            fg(Color::DarkGray),
+        )),
+    }
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn render_help(frame: &mut Frame) {
+    let area = frame.size().inner(&Margin { horizontal: frame.size().width / 4, vertical: frame.size().height / 4 });
+
+    let lines = vec![
+        Line::raw("Space      Pause or resume updates (capture keeps running)"),
+        Line::raw("E          Export the packets on screen to a timestamped JSON file"),
+        Line::raw("S          Rank top talkers by bytes or by packets"),
+        Line::raw("/          Search the retained packets by IP, protocol or description"),
+        Line::raw("PgUp/PgDn  Scroll the activity list a page back or forward (j/k or ↓/↑: a line)"),
+        Line::raw("End / G    Back to the newest packets"),
+        Line::raw("H / ?      Show or hide this help"),
+        Line::raw("Q / Esc    Stop capturing and show the final summary"),
+        Line::raw("Ctrl+C     Same as Q"),
+    ];
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(titled_block("💡 HELP")),
+        area,
+    );
+}
@@ -0,0 +1,304 @@
+//! Application-layer decoders. Each protocol is one `ApplicationDecoder`, and
+//! `TCP` and `UDP` list them in the order they are tried. The first decoder
+//! that claims a port decides: if its payload check fails, the packet is left
+//! unlabeled rather than handed to a decoder further down.
+//!
+//! Ports are offered destination first, then source, so replies from a
+//! service port are recognized too. `port_protocols` from the config is
+//! consulted before any of them.
+
+use std::collections::HashMap;
+
+use crate::dhcp::{self, DhcpInfo};
+use crate::dns::DnsInfo;
+use crate::http::{self, HttpInfo};
+use crate::services::{self, Transport};
+use crate::{dns, quic, tls};
+
+/// What a decoder learned from a payload.
+pub struct AppData {
+    /// Label stored as `PacketInfo::application_protocol`
+    pub protocol: String,
+    pub detail: AppDetail,
+}
+
+pub enum AppDetail {
+    None,
+    Http(HttpInfo),
+    Dns(DnsInfo),
+    TlsSni(String),
+    Dhcp(DhcpInfo),
+}
+
+impl AppData {
+    fn label(protocol: &str) -> Self {
+        AppData { protocol: protocol.to_string(), detail: AppDetail::None }
+    }
+
+    fn with_detail(protocol: &str, detail: Option<AppDetail>) -> Self {
+        AppData { protocol: protocol.to_string(), detail: detail.unwrap_or(AppDetail::None) }
+    }
+}
+
+pub trait ApplicationDecoder: Sync {
+    /// Whether this decoder handles traffic on `port`. Cheap; no parsing.
+    fn matches(&self, port: u16, payload: &[u8]) -> bool;
+
+    /// Decodes a payload on a port `matches` accepted. `None` means the
+    /// payload isn't this protocol after all.
+    fn decode(&self, port: u16, payload: &[u8]) -> Option<AppData>;
+}
+
+pub static TCP: &[&dyn ApplicationDecoder] = &[&HttpDecoder, &TlsDecoder, &PortDecoder(Transport::Tcp)];
+
+pub static UDP: &[&dyn ApplicationDecoder] = &[
+    &QuicDecoder,
+    &DnsDecoder,
+    &MdnsDecoder,
+    &NtpDecoder,
+    &SnmpDecoder,
+    &DhcpDecoder,
+    &PortDecoder(Transport::Udp),
+];
+
+pub fn decode(custom: &CustomPorts, decoders: &[&dyn ApplicationDecoder], src_port: u16, dst_port: u16, payload: &[u8]) -> Option<AppData> {
+    let custom: &dyn ApplicationDecoder = custom;
+    for decoder in std::iter::once(&custom).chain(decoders) {
+        for port in [dst_port, src_port] {
+            if decoder.matches(port, payload) {
+                return decoder.decode(port, payload);
+            }
+        }
+    }
+    None
+}
+
+/// Labels from `port_protocols`, for services on non-standard ports. The
+/// label is taken as given; nothing is parsed.
+#[derive(Clone)]
+pub struct CustomPorts {
+    labels: HashMap<u16, String>,
+}
+
+impl CustomPorts {
+    pub fn new(labels: HashMap<u16, String>) -> Self {
+        CustomPorts { labels }
+    }
+}
+
+impl ApplicationDecoder for CustomPorts {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        self.labels.contains_key(&port)
+    }
+
+    fn decode(&self, port: u16, _payload: &[u8]) -> Option<AppData> {
+        self.labels.get(&port).map(|label| AppData::label(label))
+    }
+}
+
+/// Cleartext HTTP on the usual web ports
+struct HttpDecoder;
+
+impl ApplicationDecoder for HttpDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        matches!(port, 80 | 8080)
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        if let Some(request) = http::parse_request(payload) {
+            return Some(AppData { protocol: "HTTP".to_string(), detail: AppDetail::Http(request) });
+        }
+        let start = String::from_utf8_lossy(&payload[..payload.len().min(100)]);
+        if start.starts_with("HTTP") || start.contains("Host:") {
+            return Some(AppData::label("HTTP"));
+        }
+        Some(AppData::label("Web Traffic"))
+    }
+}
+
+/// TLS on 443, with the SNI when the packet carries a ClientHello
+struct TlsDecoder;
+
+impl ApplicationDecoder for TlsDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        port == 443
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        Some(AppData::with_detail("HTTPS", tls::parse_sni(payload).map(AppDetail::TlsSni)))
+    }
+}
+
+/// UDP/443 is QUIC (HTTP/3) or nothing we know, never TLS
+struct QuicDecoder;
+
+impl ApplicationDecoder for QuicDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        port == 443
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        quic::is_quic(payload).then(|| AppData::label("QUIC"))
+    }
+}
+
+struct DnsDecoder;
+
+impl ApplicationDecoder for DnsDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        port == 53
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        Some(AppData::with_detail("DNS", dns::parse(payload).map(AppDetail::Dns)))
+    }
+}
+
+const DNS_HEADER_LEN: usize = 12;
+
+struct MdnsDecoder;
+
+impl ApplicationDecoder for MdnsDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        port == 5353
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        (payload.len() >= DNS_HEADER_LEN).then(|| AppData::label("mDNS"))
+    }
+}
+
+struct NtpDecoder;
+
+impl ApplicationDecoder for NtpDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        port == 123
+    }
+
+    /// Fixed NTP header with a version from 1 to 4
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        let version_ok = payload.first().is_some_and(|first| (1..=4).contains(&((first >> 3) & 0x07)));
+        (payload.len() >= 48 && version_ok).then(|| AppData::label("NTP"))
+    }
+}
+
+struct SnmpDecoder;
+
+impl ApplicationDecoder for SnmpDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        matches!(port, 161 | 162)
+    }
+
+    /// Every SNMP message is a BER SEQUENCE
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        (payload.first() == Some(&0x30)).then(|| AppData::label("SNMP"))
+    }
+}
+
+struct DhcpDecoder;
+
+impl ApplicationDecoder for DhcpDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        matches!(port, 67 | 68)
+    }
+
+    fn decode(&self, _port: u16, payload: &[u8]) -> Option<AppData> {
+        dhcp::parse(payload).map(|info| AppData { protocol: "DHCP".to_string(), detail: AppDetail::Dhcp(info) })
+    }
+}
+
+/// Port-only labels: a few classic services, then the system services table
+/// for this transport. Always last, since it claims nearly every registered port.
+struct PortDecoder(Transport);
+
+impl PortDecoder {
+    fn label(&self, port: u16) -> Option<String> {
+        match port {
+            53 => Some("DNS".to_string()),
+            22 => Some("SSH".to_string()),
+            21 => Some("FTP".to_string()),
+            25 => Some("SMTP".to_string()),
+            110 => Some("POP3".to_string()),
+            143 => Some("IMAP".to_string()),
+            993 => Some("IMAPS".to_string()),
+            995 => Some("POP3S".to_string()),
+            // Dynamic ports are never assigned, so a name there would be a coincidence
+            0..=49151 => services::name(port, self.0).map(str::to_uppercase),
+            _ => None,
+        }
+    }
+}
+
+impl ApplicationDecoder for PortDecoder {
+    fn matches(&self, port: u16, _payload: &[u8]) -> bool {
+        self.label(port).is_some()
+    }
+
+    fn decode(&self, port: u16, _payload: &[u8]) -> Option<AppData> {
+        self.label(port).map(|protocol| AppData { protocol, detail: AppDetail::None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(custom: &CustomPorts, decoders: &[&dyn ApplicationDecoder], src_port: u16, dst_port: u16, payload: &[u8]) -> Option<String> {
+        decode(custom, decoders, src_port, dst_port, payload).map(|app| app.protocol)
+    }
+
+    fn no_custom_ports() -> CustomPorts {
+        CustomPorts::new(HashMap::new())
+    }
+
+    #[test]
+    fn custom_ports_win_over_builtin_decoders() {
+        let custom = CustomPorts::new(HashMap::from([(80, "Intranet".to_string()), (53, "Resolver".to_string())]));
+        let request = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+        assert_eq!(label(&custom, TCP, 50000, 80, request).as_deref(), Some("Intranet"));
+        assert_eq!(label(&custom, UDP, 50000, 53, &[0; 12]).as_deref(), Some("Resolver"));
+        assert_eq!(label(&no_custom_ports(), TCP, 50000, 80, request).as_deref(), Some("HTTP"));
+    }
+
+    #[test]
+    fn port_decoder_is_the_fallback() {
+        let custom = no_custom_ports();
+
+        assert_eq!(label(&custom, TCP, 50000, 22, b"SSH-2.0-OpenSSH_9.6\r\n").as_deref(), Some("SSH"));
+        assert_eq!(label(&custom, TCP, 50000, 25, b"EHLO mail\r\n").as_deref(), Some("SMTP"));
+        // Dynamic ports have no names, so nothing claims them
+        assert_eq!(label(&custom, TCP, 50000, 60000, b"data"), None);
+        assert!(!PortDecoder(Transport::Tcp).matches(60000, b""));
+    }
+
+    #[test]
+    fn first_decoder_to_claim_a_port_decides() {
+        let custom = no_custom_ports();
+
+        // Not QUIC, and the port decoder further down doesn't get a say
+        assert_eq!(label(&custom, UDP, 50000, 443, b"not quic"), None);
+        assert_eq!(label(&custom, TCP, 50000, 80, b"\x00\x01binary").as_deref(), Some("Web Traffic"));
+        assert_eq!(label(&custom, TCP, 50000, 443, b"").as_deref(), Some("HTTPS"));
+    }
+
+    #[test]
+    fn destination_port_is_tried_before_source() {
+        let custom = no_custom_ports();
+
+        assert_eq!(label(&custom, TCP, 22, 80, b"GET / HTTP/1.1\r\n\r\n").as_deref(), Some("HTTP"));
+        assert_eq!(label(&custom, TCP, 80, 22, b"SSH-2.0-OpenSSH_9.6\r\n").as_deref(), Some("Web Traffic"));
+        // A reply from a service port is still recognised
+        assert_eq!(label(&custom, TCP, 22, 50000, b"SSH-2.0-OpenSSH_9.6\r\n").as_deref(), Some("SSH"));
+    }
+
+    #[test]
+    fn decoders_match_only_their_ports() {
+        assert!(HttpDecoder.matches(80, b"") && HttpDecoder.matches(8080, b""));
+        assert!(!HttpDecoder.matches(443, b""));
+        assert!(TlsDecoder.matches(443, b"") && !TlsDecoder.matches(8443, b""));
+        assert!(DnsDecoder.matches(53, b"") && !DnsDecoder.matches(5353, b""));
+        assert!(DhcpDecoder.matches(67, b"") && DhcpDecoder.matches(68, b""));
+        assert!(SnmpDecoder.matches(161, b"") && SnmpDecoder.matches(162, b""));
+    }
+}
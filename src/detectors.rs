@@ -0,0 +1,303 @@
+//! Stateful attack detectors fed from the capture loop. Each keeps only a
+//! bounded, time-windowed view of recent traffic.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+
+use crate::arp::ArpInfo;
+use crate::config::ThreatConfig;
+use crate::{ConnectionFlow, HandshakeState, ThreatLevel};
+
+// Hard cap so a spoofed-source flood can't exhaust memory
+const MAX_TRACKED_SOURCES: usize = 10_000;
+
+/// Flags a source that touches many distinct destination ports within a
+/// sliding window. Only connection attempts are counted (TCP SYN without ACK
+/// and UDP to well-known ports) so busy servers replying to ephemeral client
+/// ports don't look like scanners.
+#[derive(Debug, Clone)]
+pub struct PortScanDetector {
+    window: Duration,
+    threshold: usize,
+    sources: HashMap<String, ScanState>,
+    last_sweep: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ScanState {
+    ports: HashMap<u16, DateTime<Utc>>,
+    alerted: Option<ThreatLevel>,
+}
+
+impl PortScanDetector {
+    pub fn new(window_secs: u64, threshold: usize) -> Self {
+        PortScanDetector {
+            window: Duration::seconds(window_secs as i64),
+            threshold: threshold.max(1),
+            sources: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+
+    /// Records a connection attempt and returns an alert when the source
+    /// first crosses the threshold (High) or three times it (Critical).
+    pub fn observe(&mut self, src_ip: &str, dst_port: u16, now: DateTime<Utc>) -> Option<(String, ThreatLevel)> {
+        self.sweep(now);
+
+        if !self.sources.contains_key(src_ip) && self.sources.len() >= MAX_TRACKED_SOURCES {
+            return None;
+        }
+
+        let window = self.window;
+        let state = self.sources.entry(src_ip.to_string()).or_default();
+        state.ports.retain(|_, seen| now - *seen <= window);
+        state.ports.insert(dst_port, now);
+
+        let distinct_ports = state.ports.len();
+        let level = if distinct_ports >= self.threshold * 3 {
+            ThreatLevel::Critical
+        } else if distinct_ports >= self.threshold {
+            ThreatLevel::High
+        } else {
+            return None;
+        };
+
+        if state.alerted.as_ref().is_some_and(|alerted| *alerted >= level) {
+            return None;
+        }
+        state.alerted = Some(level.clone());
+
+        let message = format!(
+            "Port scan from {}: {} distinct ports in {}s",
+            src_ip, distinct_ports, self.window.num_seconds()
+        );
+        Some((message, level))
+    }
+
+    /// Sources currently over the threshold with their distinct port counts.
+    pub fn active_scanners(&self) -> Vec<(String, usize)> {
+        let mut scanners: Vec<_> = self.sources.iter()
+            .filter(|(_, state)| state.ports.len() >= self.threshold)
+            .map(|(ip, state)| (ip.clone(), state.ports.len()))
+            .collect();
+        scanners.sort_by_key(|scanner| std::cmp::Reverse(scanner.1));
+        scanners
+    }
+
+    /// Drops ports and sources that fell out of the window, at most once per window.
+    fn sweep(&mut self, now: DateTime<Utc>) {
+        if self.last_sweep.is_some_and(|last| now - last < self.window) {
+            return;
+        }
+        self.last_sweep = Some(now);
+
+        let window = self.window;
+        self.sources.retain(|_, state| {
+            state.ports.retain(|_, seen| now - *seen <= window);
+            !state.ports.is_empty()
+        });
+    }
+}
+
+/// Default thresholds, for a saved session to load into before the resuming
+/// run swaps in detectors built from its own configuration.
+impl Default for PortScanDetector {
+    fn default() -> Self {
+        let threat = ThreatConfig::default();
+        PortScanDetector::new(threat.port_scan_window_secs, threat.port_scan_threshold)
+    }
+}
+
+/// Counts half-open TCP handshakes per target. Handshakes younger than a
+/// second are still in flight and ignored; ones older than the window will
+/// never complete and are evicted so a spoofed flood can't grow the table.
+#[derive(Debug, Clone)]
+pub struct SynFloodDetector {
+    window: Duration,
+    threshold: usize,
+    alerted: HashMap<String, ThreatLevel>,
+    half_open: Vec<(String, usize)>,
+    last_sweep: Option<DateTime<Utc>>,
+}
+
+impl SynFloodDetector {
+    pub fn new(window_secs: u64, threshold: usize) -> Self {
+        SynFloodDetector {
+            window: Duration::seconds(window_secs as i64),
+            threshold: threshold.max(1),
+            alerted: HashMap::new(),
+            half_open: Vec::new(),
+            last_sweep: None,
+        }
+    }
+
+    /// Re-counts half-open connections at most once a second and returns an
+    /// alert for each target that newly crossed the threshold.
+    pub fn sweep(&mut self, connections: &mut HashMap<String, ConnectionFlow>, now: DateTime<Utc>) -> Vec<(String, ThreatLevel)> {
+        if self.last_sweep.is_some_and(|last| now - last < Duration::seconds(1)) {
+            return Vec::new();
+        }
+        self.last_sweep = Some(now);
+
+        let window = self.window;
+        connections.retain(|_, flow| !(is_half_open(flow) && now - flow.last_seen > window));
+
+        let mut per_target: HashMap<&str, usize> = HashMap::new();
+        for flow in connections.values() {
+            if is_half_open(flow) && now - flow.first_seen >= Duration::seconds(1) {
+                *per_target.entry(flow.dst_ip.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut alerts = Vec::new();
+        for (target, &count) in &per_target {
+            let level = if count >= self.threshold * 3 {
+                ThreatLevel::Critical
+            } else if count >= self.threshold {
+                ThreatLevel::High
+            } else {
+                continue;
+            };
+
+            if self.alerted.get(*target).is_some_and(|alerted| *alerted >= level) {
+                continue;
+            }
+            self.alerted.insert(target.to_string(), level.clone());
+            alerts.push((format!("SYN flood against {}: {} half-open connections", target, count), level));
+        }
+
+        // Re-arm targets once they drop back under the threshold
+        let threshold = self.threshold;
+        self.alerted.retain(|target, _| per_target.get(target.as_str()).is_some_and(|&count| count >= threshold));
+
+        self.half_open = per_target.into_iter()
+            .filter(|(_, count)| *count >= threshold)
+            .map(|(target, count)| (target.to_string(), count))
+            .collect();
+        self.half_open.sort_by_key(|target| std::cmp::Reverse(target.1));
+
+        alerts
+    }
+
+    /// Targets currently over the threshold with their half-open counts.
+    pub fn flooded_targets(&self) -> &[(String, usize)] {
+        &self.half_open
+    }
+}
+
+impl Default for SynFloodDetector {
+    fn default() -> Self {
+        let threat = ThreatConfig::default();
+        SynFloodDetector::new(threat.syn_flood_window_secs, threat.syn_flood_threshold)
+    }
+}
+
+/// Learns IP-to-MAC bindings from ARP senders and flags an IP that moves to
+/// a different MAC (cache poisoning) or a burst of gratuitous ARPs.
+#[derive(Debug, Clone)]
+pub struct ArpSpoofDetector {
+    window: Duration,
+    gratuitous_threshold: usize,
+    bindings: HashMap<String, ArpBinding>,
+    gratuitous: VecDeque<DateTime<Utc>>,
+    gratuitous_alerted: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArpBinding {
+    pub mac: String,
+    pub last_seen: DateTime<Utc>,
+    last_alert: Option<DateTime<Utc>>,
+}
+
+impl ArpSpoofDetector {
+    pub fn new(window_secs: u64, gratuitous_threshold: usize) -> Self {
+        ArpSpoofDetector {
+            window: Duration::seconds(window_secs as i64),
+            gratuitous_threshold: gratuitous_threshold.max(1),
+            bindings: HashMap::new(),
+            gratuitous: VecDeque::new(),
+            gratuitous_alerted: false,
+        }
+    }
+
+    pub fn observe(&mut self, arp: &ArpInfo, now: DateTime<Utc>) -> Vec<(String, ThreatLevel)> {
+        let mut alerts = Vec::new();
+
+        if arp.is_gratuitous() {
+            if let Some(alert) = self.observe_gratuitous(now) {
+                alerts.push(alert);
+            }
+        }
+
+        // 0.0.0.0 is an address probe from a host that has no IP yet
+        if arp.sender_ip == "0.0.0.0" {
+            return alerts;
+        }
+
+        let table_full = self.bindings.len() >= MAX_TRACKED_SOURCES;
+        match self.bindings.get_mut(&arp.sender_ip) {
+            Some(binding) if binding.mac != arp.sender_mac => {
+                let suppressed = binding.last_alert.is_some_and(|last| now - last < self.window);
+                if !suppressed {
+                    alerts.push((
+                        format!("ARP spoofing suspected: {} moved from {} to {}", arp.sender_ip, binding.mac, arp.sender_mac),
+                        ThreatLevel::High,
+                    ));
+                    binding.last_alert = Some(now);
+                }
+                binding.mac = arp.sender_mac.clone();
+                binding.last_seen = now;
+            }
+            Some(binding) => binding.last_seen = now,
+            None if !table_full => {
+                self.bindings.insert(arp.sender_ip.clone(), ArpBinding {
+                    mac: arp.sender_mac.clone(),
+                    last_seen: now,
+                    last_alert: None,
+                });
+            }
+            None => {}
+        }
+
+        alerts
+    }
+
+    /// The learned IP-to-MAC table.
+    pub fn bindings(&self) -> &HashMap<String, ArpBinding> {
+        &self.bindings
+    }
+
+    fn observe_gratuitous(&mut self, now: DateTime<Utc>) -> Option<(String, ThreatLevel)> {
+        self.gratuitous.push_back(now);
+        while self.gratuitous.front().is_some_and(|seen| now - *seen > self.window) {
+            self.gratuitous.pop_front();
+        }
+
+        let count = self.gratuitous.len();
+        if count < self.gratuitous_threshold {
+            self.gratuitous_alerted = false;
+            return None;
+        }
+        if self.gratuitous_alerted {
+            return None;
+        }
+        self.gratuitous_alerted = true;
+
+        Some((
+            format!("Gratuitous ARP flood: {} announcements in {}s", count, self.window.num_seconds()),
+            ThreatLevel::High,
+        ))
+    }
+}
+
+impl Default for ArpSpoofDetector {
+    fn default() -> Self {
+        let threat = ThreatConfig::default();
+        ArpSpoofDetector::new(threat.arp_window_secs, threat.arp_gratuitous_threshold)
+    }
+}
+
+fn is_half_open(flow: &ConnectionFlow) -> bool {
+    matches!(flow.handshake_state, HandshakeState::SynSent | HandshakeState::SynReceived)
+}
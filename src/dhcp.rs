@@ -0,0 +1,124 @@
+//! DHCPv4 decoding: the BOOTP fixed header plus the options that matter when
+//! following a lease exchange. Options are walked with bounds checks, so a
+//! truncated packet yields whatever was decoded before the cut.
+
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+/// Options start right after the magic cookie
+const OPTIONS_OFFSET: usize = 240;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_HOSTNAME: u8 = 12;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_END: u8 = 255;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DhcpInfo {
+    pub message_type: String,
+    pub client_mac: String,
+    /// `yiaddr`: the address offered or assigned by the server
+    pub your_ip: Option<String>,
+    /// Option 50: the address a client asks for
+    pub requested_ip: Option<String>,
+    /// Option 12
+    pub hostname: Option<String>,
+}
+
+impl DhcpInfo {
+    /// e.g. "DHCP OFFER of 192.168.1.50 to aa:bb:cc:dd:ee:ff"
+    pub fn summary(&self) -> String {
+        let mut summary = format!("DHCP {}", self.message_type);
+        if let Some(ref ip) = self.your_ip {
+            summary.push_str(&format!(" of {}", ip));
+        } else if let Some(ref ip) = self.requested_ip {
+            summary.push_str(&format!(" for {}", ip));
+        }
+        summary.push_str(&format!(" {} {}", if self.your_ip.is_some() { "to" } else { "from" }, self.client_mac));
+        if let Some(ref hostname) = self.hostname {
+            summary.push_str(&format!(" ({})", hostname));
+        }
+        summary
+    }
+}
+
+/// BOOTP header followed by the DHCP magic cookie
+pub fn is_dhcp(payload: &[u8]) -> bool {
+    payload.get(236..OPTIONS_OFFSET) == Some(&MAGIC_COOKIE[..])
+}
+
+pub fn parse(payload: &[u8]) -> Option<DhcpInfo> {
+    if !is_dhcp(payload) {
+        return None;
+    }
+
+    // Only Ethernet hardware addresses are shown as MACs
+    let hardware_len = payload[2] as usize;
+    let client_mac = if payload[1] == 1 && hardware_len == 6 {
+        payload[28..34].iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+    } else {
+        "unknown".to_string()
+    };
+    let your_ip = ipv4(&payload[16..20]).filter(|ip| !ip.is_unspecified()).map(|ip| ip.to_string());
+
+    let mut message_type = None;
+    let mut requested_ip = None;
+    let mut hostname = None;
+    let mut options = &payload[OPTIONS_OFFSET..];
+    while let Some((&code, rest)) = options.split_first() {
+        if code == OPTION_END {
+            break;
+        }
+        if code == OPTION_PAD {
+            options = rest;
+            continue;
+        }
+        let Some((&len, rest)) = rest.split_first() else {
+            break;
+        };
+        let Some(value) = rest.get(..len as usize) else {
+            break;
+        };
+        match code {
+            OPTION_MESSAGE_TYPE => message_type = value.first().copied(),
+            OPTION_REQUESTED_IP => requested_ip = ipv4(value).map(|ip| ip.to_string()),
+            OPTION_HOSTNAME => {
+                hostname = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_string())
+                    .filter(|name| !name.is_empty());
+            }
+            _ => {}
+        }
+        options = &rest[len as usize..];
+    }
+
+    Some(DhcpInfo {
+        message_type: message_type_name(message_type),
+        client_mac,
+        your_ip,
+        requested_ip,
+        hostname,
+    })
+}
+
+fn ipv4(bytes: &[u8]) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = bytes.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+fn message_type_name(message_type: Option<u8>) -> String {
+    match message_type {
+        Some(1) => "DISCOVER".to_string(),
+        Some(2) => "OFFER".to_string(),
+        Some(3) => "REQUEST".to_string(),
+        Some(4) => "DECLINE".to_string(),
+        Some(5) => "ACK".to_string(),
+        Some(6) => "NAK".to_string(),
+        Some(7) => "RELEASE".to_string(),
+        Some(8) => "INFORM".to_string(),
+        Some(other) => format!("type {}", other),
+        // Plain BOOTP, no message type option
+        None => "BOOTP".to_string(),
+    }
+}
@@ -0,0 +1,83 @@
+//! Which way a packet travels relative to this host, judged against the
+//! addresses of the capture interfaces. Broadcast and multicast count as
+//! addressed to us, so a neighbour's mDNS announcement is inbound.
+
+use ipnetwork::IpNetwork;
+use pnet::datalink::NetworkInterface;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+    /// Neither end is this host: mirrored, bridged or routed traffic
+    Transit,
+    /// Both ends are this host, as on loopback
+    Local,
+}
+
+impl Direction {
+    /// Same spelling as the serialized form, for CSV columns
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+            Direction::Transit => "transit",
+            Direction::Local => "local",
+        }
+    }
+}
+
+/// The capture interfaces' addresses and networks.
+#[derive(Debug, Clone, Default)]
+pub struct LocalAddresses {
+    networks: Vec<IpNetwork>,
+}
+
+impl LocalAddresses {
+    pub fn of(interfaces: &[NetworkInterface]) -> Self {
+        LocalAddresses { networks: interfaces.iter().flat_map(|interface| interface.ips.iter().copied()).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    pub fn classify(&self, src: IpAddr, dst: IpAddr) -> Direction {
+        match (self.is_ours(src), self.is_ours(dst)) {
+            (true, true) => Direction::Local,
+            (true, false) => Direction::Outbound,
+            (false, true) => Direction::Inbound,
+            (false, false) if self.reaches_us(dst) => Direction::Inbound,
+            (false, false) => Direction::Transit,
+        }
+    }
+
+    /// `classify` for the address strings kept on `PacketInfo`. `None` without
+    /// any local addresses, as for a pcap stream, since then every packet
+    /// would look like transit or broadcast traffic.
+    pub fn classify_text(&self, src: &str, dst: &str) -> Option<Direction> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.classify(src.parse().ok()?, dst.parse().ok()?))
+    }
+
+    fn is_ours(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.ip() == ip)
+    }
+
+    /// Broadcast and multicast destinations, which every host on the link gets
+    fn reaches_us(&self, ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(v4) if v4.is_broadcast() => true,
+            IpAddr::V4(_) => ip.is_multicast() || self.networks.iter().any(|network| match network {
+                IpNetwork::V4(network) => network.prefix() < 31 && network.broadcast() == ip,
+                IpNetwork::V6(_) => false,
+            }),
+            IpAddr::V6(_) => ip.is_multicast(),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Minimal DNS message decoding. Every offset is bounds-checked so truncated
+//! or malformed payloads simply yield `None`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const HEADER_LEN: usize = 12;
+
+// Longest legal name is 255 bytes; more labels than this means a pointer loop
+const MAX_LABELS: usize = 128;
+
+/// Set in the flags of a response
+const FLAG_RESPONSE: u16 = 0x8000;
+
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+
+/// Longer CNAME chains than this are treated as a loop
+const MAX_CNAME_HOPS: usize = 16;
+
+/// What a DNS message asks, and for a response, the addresses it gives.
+pub struct DnsInfo {
+    pub query: Option<String>,
+    pub answers: Vec<DnsAnswer>,
+}
+
+/// An A or AAAA answer, under the name that was looked up: CNAME chains are
+/// followed back, so the CDN host behind `www.example.com` is labeled
+/// `www.example.com`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsAnswer {
+    pub name: String,
+    pub address: String,
+}
+
+/// `None` unless the payload has a DNS header.
+pub fn parse(payload: &[u8]) -> Option<DnsInfo> {
+    if payload.len() < HEADER_LEN {
+        return None;
+    }
+    let query = parse_query(payload);
+    let answers = if read_u16(payload, 2)? & FLAG_RESPONSE != 0 { parse_answers(payload) } else { Vec::new() };
+    Some(DnsInfo { query, answers })
+}
+
+/// Returns the first question as "name TYPE", e.g. "example.com A".
+fn parse_query(payload: &[u8]) -> Option<String> {
+    if payload.len() < HEADER_LEN {
+        return None;
+    }
+
+    let question_count = read_u16(payload, 4)?;
+    if question_count == 0 {
+        return None;
+    }
+
+    let (name, offset) = read_name(payload, HEADER_LEN)?;
+    let query_type = read_u16(payload, offset)?;
+
+    Some(format!("{} {}", name, query_type_name(query_type)))
+}
+
+/// The A and AAAA records in a response's answer section. Answers up to a
+/// malformed record are kept.
+fn parse_answers(payload: &[u8]) -> Vec<DnsAnswer> {
+    let mut addresses = Vec::new();
+    // Alias to the name it stands in for, lowercased as names are case-insensitive
+    let mut aliased_by = HashMap::new();
+
+    let (Some(question_count), Some(answer_count)) = (read_u16(payload, 4), read_u16(payload, 6)) else {
+        return Vec::new();
+    };
+    let mut offset = HEADER_LEN;
+    for _ in 0..question_count {
+        let Some((_, end)) = read_name(payload, offset) else {
+            return Vec::new();
+        };
+        // Type and class
+        offset = end + 4;
+    }
+
+    for _ in 0..answer_count {
+        let Some((owner, end)) = read_name(payload, offset) else {
+            break;
+        };
+        let (Some(record_type), Some(data_len)) = (read_u16(payload, end), read_u16(payload, end + 8)) else {
+            break;
+        };
+        // Type, class, TTL and data length come before the data
+        let data_start = end + 10;
+        let Some(data) = payload.get(data_start..data_start + data_len as usize) else {
+            break;
+        };
+        offset = data_start + data.len();
+
+        match (record_type, data.len()) {
+            (TYPE_A, 4) => addresses.push((owner, Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string())),
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().expect("sixteen bytes");
+                addresses.push((owner, Ipv6Addr::from(octets).to_string()));
+            }
+            (TYPE_CNAME, _) => {
+                if let Some((target, _)) = read_name(payload, data_start) {
+                    aliased_by.insert(target.to_ascii_lowercase(), owner.to_ascii_lowercase());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    addresses.into_iter()
+        .map(|(owner, address)| {
+            let mut name = owner.to_ascii_lowercase();
+            for _ in 0..MAX_CNAME_HOPS {
+                match aliased_by.get(&name) {
+                    Some(alias) => name = alias.clone(),
+                    None => break,
+                }
+            }
+            DnsAnswer { name, address }
+        })
+        .collect()
+}
+
+fn read_u16(payload: &[u8], offset: usize) -> Option<u16> {
+    let bytes = payload.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a possibly-compressed name starting at `offset`. Returns the name and
+/// the offset just past it in the original (uncompressed) position.
+fn read_name(payload: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut position = offset;
+    let mut end_of_name = None;
+
+    for _ in 0..MAX_LABELS {
+        let length = *payload.get(position)? as usize;
+
+        match length & 0xC0 {
+            0x00 if length == 0 => {
+                let end = end_of_name.unwrap_or(position + 1);
+                let name = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+                return Some((name, end));
+            }
+            0x00 => {
+                let label = payload.get(position + 1..position + 1 + length)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + length;
+            }
+            0xC0 => {
+                let pointer = (read_u16(payload, position)? & 0x3FFF) as usize;
+                if end_of_name.is_none() {
+                    end_of_name = Some(position + 2);
+                }
+                position = pointer;
+            }
+            // 0x40 and 0x80 label types are obsolete/reserved
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn query_type_name(query_type: u16) -> String {
+    match query_type {
+        1 => "A".to_string(),
+        2 => "NS".to_string(),
+        5 => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        12 => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        64 => "SVCB".to_string(),
+        65 => "HTTPS".to_string(),
+        255 => "ANY".to_string(),
+        other => format!("TYPE{}", other),
+    }
+}
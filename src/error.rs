@@ -8,6 +8,9 @@ pub enum PacketSnifferError {
     ConfigError(String),
     ExportError(String),
     InvalidFilter(String),
+    InvalidBpfFilter(String),
+    PcapError(String),
+    SessionError(String),
     IoError(std::io::Error),
 }
 
@@ -24,13 +27,22 @@ impl fmt::Display for PacketSnifferError {
                 write!(f, "Network error: {}. Check your network connection and interface status.", msg)
             }
             PacketSnifferError::ConfigError(msg) => {
-                write!(f, "Configuration error: {}. Check your config.json file.", msg)
+                write!(f, "Configuration error: {}. Check your config file and any PKTSNIFF_* environment variables.", msg)
             }
             PacketSnifferError::ExportError(msg) => {
                 write!(f, "Export error: {}. Check file permissions and disk space.", msg)
             }
             PacketSnifferError::InvalidFilter(filter) => {
-                write!(f, "Invalid filter '{}'. Supported filters: tcp, udp, icmp, http, dns", filter)
+                write!(f, "Invalid filter: {}", filter)
+            }
+            PacketSnifferError::InvalidBpfFilter(msg) => {
+                write!(f, "Invalid BPF filter expression: {}", msg)
+            }
+            PacketSnifferError::PcapError(msg) => {
+                write!(f, "Invalid pcap input: {}", msg)
+            }
+            PacketSnifferError::SessionError(msg) => {
+                write!(f, "Session error: {}", msg)
             }
             PacketSnifferError::IoError(e) => {
                 write!(f, "I/O error: {}. Check file permissions and disk space.", e)
@@ -69,33 +81,47 @@ impl From<csv::Error> for PacketSnifferError {
 pub type Result<T> = std::result::Result<T, PacketSnifferError>;
 
 pub fn handle_error(error: &PacketSnifferError) -> ! {
-    eprintln!("❌ Error: {}", error);
+    errln!("❌ Error: {}", error);
     
     // Provide helpful suggestions based on error type
     match error {
         PacketSnifferError::PermissionDenied => {
-            eprintln!("💡 Suggestion: Try running with 'sudo' on Linux/macOS or as Administrator on Windows");
-            eprintln!("   Example: sudo cargo run -- --interface eth0");
+            errln!("💡 Suggestion: Try running with 'sudo' on Linux/macOS or as Administrator on Windows");
+            errln!("   Example: sudo cargo run -- --interface eth0");
         }
         PacketSnifferError::InterfaceNotFound(_) => {
-            eprintln!("💡 Suggestion: Use '--list-interfaces' to see available network interfaces");
-            eprintln!("   Example: cargo run -- --list-interfaces");
+            errln!("💡 Suggestion: Use '--list-interfaces' to see available network interfaces");
+            errln!("   Example: cargo run -- --list-interfaces");
         }
         PacketSnifferError::NetworkError(_) => {
-            eprintln!("💡 Suggestion: Check if the network interface is up and connected");
-            eprintln!("   You can use 'ip addr' (Linux) or 'ipconfig' (Windows) to check interface status");
+            errln!("💡 Suggestion: Check if the network interface is up and connected");
+            errln!("   You can use 'ip addr' (Linux) or 'ipconfig' (Windows) to check interface status");
         }
         PacketSnifferError::ConfigError(_) => {
-            eprintln!("💡 Suggestion: Delete config.json to regenerate default configuration");
+            errln!("💡 Suggestion: Delete config.json to regenerate default configuration");
         }
         PacketSnifferError::ExportError(_) => {
-            eprintln!("💡 Suggestion: Ensure you have write permissions and sufficient disk space");
+            errln!("💡 Suggestion: Ensure you have write permissions and sufficient disk space");
         }
         PacketSnifferError::InvalidFilter(_) => {
-            eprintln!("💡 Suggestion: Use one of these protocol filters: tcp, udp, icmp, http, dns");
+            errln!("💡 Suggestion: Use one of these protocol filters: tcp, udp, sctp, icmp, icmp6, igmp, http, dns, arp");
+            errln!("   Subnets use CIDR notation, e.g. --subnet 10.0.0.0/24");
+            errln!("   TCP flags are comma-separated names, e.g. --tcp-flags SYN,ACK");
+            errln!("   --preset names a preset under filter_presets in the config file");
+        }
+        PacketSnifferError::InvalidBpfFilter(_) => {
+            errln!("💡 Suggestion: Use pcap-filter syntax, e.g. --bpf \"tcp port 443 and host 10.0.0.5\"");
+        }
+        PacketSnifferError::PcapError(_) => {
+            errln!("💡 Suggestion: Pipe in a classic pcap stream, flushed per packet");
+            errln!("   Example: ssh host tcpdump -U -w - | packet_sniffer --read-stdin");
+        }
+        PacketSnifferError::SessionError(_) => {
+            errln!("💡 Suggestion: Check the --load-session path, or start a new session by leaving --load-session out");
+            errln!("   Sessions only load into the version that saved them, at the --sample rate they were saved with");
         }
         PacketSnifferError::IoError(_) => {
-            eprintln!("💡 Suggestion: Check file permissions and available disk space");
+            errln!("💡 Suggestion: Check file permissions and available disk space");
         }
     }
     
@@ -0,0 +1,158 @@
+//! Shared handling of export targets. Every exporter asks for its path here
+//! right before writing, so placeholders and the settings from `ExportConfig`
+//! apply to all of them the same way, and writes through `ExportWriter` so a
+//! `.gz` path is compressed whatever the format.
+
+use chrono::{DateTime, Local};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::ExportConfig;
+use crate::error::{PacketSnifferError, Result};
+
+#[derive(Debug, Clone)]
+pub struct ExportPaths {
+    /// Relative export filenames land here; empty means the working directory
+    directory: PathBuf,
+    auto_backup: bool,
+    /// `--compress`: gzip every file export, adding `.gz` where missing
+    compress: bool,
+    /// Capture start, for the time placeholders
+    started: DateTime<Local>,
+    /// Interface names joined with `+`, for `{interface}`
+    interface: String,
+}
+
+impl ExportPaths {
+    /// Call when capture starts; that moment is what `{timestamp}` and
+    /// friends expand to, however late the export is written.
+    pub fn new(config: &ExportConfig, interfaces: &[&str], compress: bool) -> Self {
+        ExportPaths {
+            directory: PathBuf::from(&config.default_directory),
+            auto_backup: config.auto_backup,
+            compress,
+            started: Local::now(),
+            interface: interfaces.join("+"),
+        }
+    }
+
+    /// Expands `{timestamp}` (20240131_154500), `{date}` (2024-01-31),
+    /// `{time}` (154500) and `{interface}`. Anything else in braces is left
+    /// alone rather than failing an export at the end of a long capture.
+    pub fn expand(&self, filename: &str) -> String {
+        filename
+            .replace("{timestamp}", &self.started.format("%Y%m%d_%H%M%S").to_string())
+            .replace("{date}", &self.started.format("%Y-%m-%d").to_string())
+            .replace("{time}", &self.started.format("%H%M%S").to_string())
+            .replace("{interface}", &self.interface)
+    }
+
+    /// Expands placeholders and places a relative `filename` under the export
+    /// directory, creating the directory if needed; absolute paths are used as
+    /// given.
+    pub fn resolve(&self, filename: &str) -> Result<String> {
+        let filename = self.expand(filename);
+        let path = Path::new(&filename);
+        if path.is_absolute() || self.directory.as_os_str().is_empty() {
+            return Ok(filename);
+        }
+
+        let path = self.directory.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                PacketSnifferError::ExportError(format!("Failed to create export directory {}: {}", parent.display(), e))
+            })?;
+        }
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// `resolve`, for exports that replace the file. With `auto_backup`, a
+    /// file already there is first renamed to `<name>.<modified time>.bak`,
+    /// so reusing a filename never silently replaces an earlier capture.
+    pub fn prepare(&self, filename: &str) -> Result<String> {
+        let mut filename = self.resolve(filename)?;
+        if self.compress && !is_gzip_path(&filename) {
+            filename.push_str(".gz");
+        }
+        let path = Path::new(&filename);
+        if self.auto_backup && path.is_file() {
+            backup(path)?;
+        }
+        Ok(filename)
+    }
+}
+
+fn backup(path: &Path) -> Result<()> {
+    let modified: DateTime<Local> = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(DateTime::from)
+        .unwrap_or_else(|_| Local::now());
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(modified.format(".%Y%m%d_%H%M%S.bak").to_string());
+
+    fs::rename(path, &backup).map_err(|e| {
+        PacketSnifferError::ExportError(format!("Failed to back up {} before overwriting it: {}", path.display(), e))
+    })
+}
+
+fn is_gzip_path(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".gz")
+}
+
+/// True for `.csv` and `.csv.gz`, for exports that pick CSV or JSON by name.
+pub fn is_csv_path(filename: &str) -> bool {
+    let filename = filename.to_lowercase();
+    filename.strip_suffix(".gz").unwrap_or(&filename).ends_with(".csv")
+}
+
+/// A buffered export file, gzip-compressed when its path ends in `.gz`. The
+/// decompressed bytes are exactly what the plain file would hold.
+pub enum ExportWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl ExportWriter {
+    pub fn create(filename: &str) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(filename)?);
+        if is_gzip_path(filename) {
+            Ok(ExportWriter::Gzip(GzEncoder::new(file, Compression::default())))
+        } else {
+            Ok(ExportWriter::Plain(file))
+        }
+    }
+
+    /// Flushes everything and, for gzip, writes the trailer. Dropping the
+    /// writer instead may leave a truncated `.gz` file behind.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            ExportWriter::Plain(file) => file.flush(),
+            ExportWriter::Gzip(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+        }
+    }
+}
+
+impl Write for ExportWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ExportWriter::Plain(file) => file.write(buf),
+            ExportWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    /// For gzip this is a sync flush, so everything written so far can be
+    /// decompressed even before `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ExportWriter::Plain(file) => file.flush(),
+            ExportWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
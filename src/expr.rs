@@ -0,0 +1,329 @@
+//! The expression grammar `--filter` and `--bpf` share: primitives combined
+//! with `and`, `or`, `not` and parentheses, where `and` binds tighter than
+//! `or` and `&&`, `||` and `!` work too. Each filter brings its own
+//! primitives through [`Grammar`]; tokenizing, precedence and the positions in
+//! error messages live here once.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::error::{PacketSnifferError, Result};
+
+#[derive(Debug, Clone)]
+pub enum Expr<P> {
+    And(Box<Expr<P>>, Box<Expr<P>>),
+    Or(Box<Expr<P>>, Box<Expr<P>>),
+    Not(Box<Expr<P>>),
+    Primitive(P),
+}
+
+impl<P> Expr<P> {
+    pub fn and(left: Expr<P>, right: Expr<P>) -> Self {
+        Expr::And(Box::new(left), Box::new(right))
+    }
+
+    pub fn or(left: Expr<P>, right: Expr<P>) -> Self {
+        Expr::Or(Box::new(left), Box::new(right))
+    }
+
+    /// Whether the expression holds, given whether each primitive does.
+    pub fn evaluate(&self, test: &impl Fn(&P) -> bool) -> bool {
+        match self {
+            Expr::And(left, right) => left.evaluate(test) && right.evaluate(test),
+            Expr::Or(left, right) => left.evaluate(test) || right.evaluate(test),
+            Expr::Not(inner) => !inner.evaluate(test),
+            Expr::Primitive(primitive) => test(primitive),
+        }
+    }
+
+    /// The same expression with each primitive replaced by an expression of
+    /// its own.
+    pub fn expand<Q>(&self, replace: &impl Fn(&P) -> Expr<Q>) -> Expr<Q> {
+        match self {
+            Expr::And(left, right) => Expr::and(left.expand(replace), right.expand(replace)),
+            Expr::Or(left, right) => Expr::or(left.expand(replace), right.expand(replace)),
+            Expr::Not(inner) => Expr::Not(Box::new(inner.expand(replace))),
+            Expr::Primitive(primitive) => replace(primitive),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Compare(Comparison),
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Compare(comparison) => write!(f, "{}", comparison),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Comparison {
+    pub fn holds(self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Equal => left == right,
+        }
+    }
+}
+
+/// What one filter adds to the shared grammar.
+pub trait Grammar: Sized {
+    type Primitive;
+
+    /// The filter's own error for a mistake in its expression.
+    fn invalid(message: String) -> PacketSnifferError;
+
+    /// Parses the primitive starting at the parser's current word.
+    fn primitive(parser: &mut Parser<Self>) -> Result<Expr<Self::Primitive>>;
+}
+
+/// Parses a whole expression; anything left over is an error.
+pub fn parse<G: Grammar>(expression: &str) -> Result<Expr<G::Primitive>> {
+    let tokens = tokenize::<G>(expression)?;
+    if tokens.is_empty() {
+        return Err(G::invalid("empty expression".to_string()));
+    }
+
+    let mut parser = Parser::<G> { tokens, pos: 0, end: expression.len(), grammar: PhantomData };
+    let root = parser.parse_or()?;
+    if let Some((token, offset)) = parser.tokens.get(parser.pos) {
+        return Err(G::invalid(format!("unexpected '{}' at position {}", token, offset)));
+    }
+    Ok(root)
+}
+
+fn tokenize<G: Grammar>(expression: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '!' => Token::Not,
+            '&' | '|' => {
+                if chars.next_if(|&(_, next)| next == c).is_none() {
+                    return Err(G::invalid(format!("unexpected '{}' at position {}; use '{}{}'", c, offset, c, c)));
+                }
+                if c == '&' { Token::And } else { Token::Or }
+            }
+            '<' | '>' => {
+                let or_equal = chars.next_if(|&(_, next)| next == '=').is_some();
+                Token::Compare(match (c, or_equal) {
+                    ('<', false) => Comparison::Less,
+                    ('<', true) => Comparison::LessOrEqual,
+                    (_, false) => Comparison::Greater,
+                    (_, true) => Comparison::GreaterOrEqual,
+                })
+            }
+            '=' => {
+                // `==` reads the same as `=`
+                chars.next_if(|&(_, next)| next == '=');
+                Token::Compare(Comparison::Equal)
+            }
+            _ => {
+                let mut end = offset + c.len_utf8();
+                while let Some((next_offset, next)) = chars.next_if(|&(_, next)| !next.is_whitespace() && !"()!&|<>=".contains(next)) {
+                    end = next_offset + next.len_utf8();
+                }
+                let word = expression[offset..end].to_lowercase();
+                match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                }
+            }
+        };
+        tokens.push((token, offset));
+    }
+
+    Ok(tokens)
+}
+
+pub struct Parser<G> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Length of the expression, the position reported for a missing token
+    end: usize,
+    grammar: PhantomData<G>,
+}
+
+impl<G: Grammar> Parser<G> {
+    pub fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Steps past the token just peeked at.
+    pub fn advance(&mut self) {
+        self.pos += 1;
+    }
+
+    /// Where the next token starts, or the end of the expression.
+    pub fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, offset)| *offset)
+    }
+
+    /// The next token as a word, with where it starts.
+    pub fn value(&mut self, what: &str) -> Result<(String, usize)> {
+        match self.tokens.get(self.pos).cloned() {
+            Some((Token::Word(word), offset)) => {
+                self.pos += 1;
+                Ok((word, offset))
+            }
+            Some((token, offset)) => Err(G::invalid(format!("expected {} but found '{}' at position {}", what, token, offset))),
+            None => Err(G::invalid(format!("expected {} at position {} (end of expression)", what, self.end))),
+        }
+    }
+
+    /// The next token if it is `<`, `<=`, `>`, `>=` or `=`.
+    pub fn comparison(&mut self) -> Option<Comparison> {
+        match self.peek() {
+            Some(Token::Compare(comparison)) => {
+                let comparison = *comparison;
+                self.pos += 1;
+                Some(comparison)
+            }
+            _ => None,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr<G::Primitive>> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expr::or(left, self.parse_and()?);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr<G::Primitive>> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expr::and(left, self.parse_unary()?);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr<G::Primitive>> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::LParen) => {
+                let open = self.offset();
+                self.pos += 1;
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(G::invalid(format!("missing ')' at position {} for the '(' at position {}", self.offset(), open)));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(Token::Word(_)) => G::primitive(self),
+            Some(token) => Err(G::invalid(format!("unexpected '{}' at position {}", token, self.offset()))),
+            None => Err(G::invalid(format!("expected an expression at position {} (end of expression)", self.end))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every word is a primitive that holds when it is in the list
+    struct Words;
+
+    impl Grammar for Words {
+        type Primitive = String;
+
+        fn invalid(message: String) -> PacketSnifferError {
+            PacketSnifferError::InvalidFilter(message)
+        }
+
+        fn primitive(parser: &mut Parser<Self>) -> Result<Expr<String>> {
+            Ok(Expr::Primitive(parser.value("a word")?.0))
+        }
+    }
+
+    fn holds(expression: &str, true_words: &[&str]) -> bool {
+        parse::<Words>(expression).unwrap().evaluate(&|word| true_words.contains(&word.as_str()))
+    }
+
+    fn error(expression: &str) -> String {
+        match parse::<Words>(expression) {
+            Err(PacketSnifferError::InvalidFilter(message)) => message,
+            other => panic!("expected an error for {:?}, got {:?}", expression, other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(holds("a or b and c", &["a"]));
+        assert!(!holds("(a or b) and c", &["a"]));
+        assert!(holds("!a && b || c", &["c"]));
+        assert!(!holds("not (a or b)", &["b"]));
+        assert!(holds("A AND b", &["a", "b"]));
+    }
+
+    #[test]
+    fn errors_point_at_the_position() {
+        assert_eq!(error(""), "empty expression");
+        assert_eq!(error("a & b"), "unexpected '&' at position 2; use '&&'");
+        assert_eq!(error("(a or b"), "missing ')' at position 7 for the '(' at position 0");
+        assert_eq!(error("a b"), "unexpected 'b' at position 2");
+        assert_eq!(error("a and"), "expected an expression at position 5 (end of expression)");
+        assert_eq!(error("a and <"), "unexpected '<' at position 6");
+    }
+}
@@ -0,0 +1,381 @@
+//! Capture filters from the command line. Everything is parsed and validated
+//! once at startup, so a typo fails before capture instead of mid-stream.
+//! Each filter class ORs its own values; a packet must pass every active class
+//! and match none of the `--exclude-*` values.
+
+use ipnetwork::IpNetwork;
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
+use pnet::packet::Packet;
+use regex::Regex;
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::bpf::BpfFilter;
+use crate::direction::{Direction, LocalAddresses};
+use crate::error::{PacketSnifferError, Result};
+use crate::filter_expr::FilterExpr;
+use crate::sctp;
+use crate::vlan;
+use crate::{Args, DirectionFilter, TcpFlagMatch};
+
+pub const PROTOCOLS: &[&str] = &["tcp", "udp", "sctp", "icmp", "icmp6", "igmp", "http", "dns", "arp"];
+
+/// How much of a payload `--payload-match` looks at. The regex engine runs in
+/// linear time, so this only bounds the cost per packet; matches further in
+/// are missed.
+pub const PAYLOAD_MATCH_BYTES: usize = 2048;
+
+/// TCP flag names by bit, lowest first
+pub const TCP_FLAGS: [&str; 8] = ["FIN", "SYN", "RST", "PSH", "ACK", "URG", "ECE", "CWR"];
+
+#[derive(Clone)]
+pub struct CaptureFilter {
+    bpf: Option<BpfFilter>,
+    protocols: Vec<String>,
+    /// Any of these, as source or destination
+    ports: Vec<u16>,
+    src_ips: Vec<IpAddr>,
+    dst_ips: Vec<IpAddr>,
+    hosts: Vec<IpAddr>,
+    subnets: Vec<IpNetwork>,
+    vlans: Vec<u16>,
+    /// Frame length bounds, inclusive
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+    tcp_flags: Option<TcpFlagFilter>,
+    payload_match: Option<Regex>,
+    exclude_protocols: Vec<String>,
+    exclude_hosts: Vec<IpAddr>,
+    exclude_ports: Vec<u16>,
+    direction: Option<DirectionFilter>,
+    local: LocalAddresses,
+    expression: Option<FilterExpr>,
+}
+
+/// What the protocol checks and `--filter` expressions look at in a frame.
+pub struct PacketFields {
+    pub len: usize,
+    pub src: Option<IpAddr>,
+    pub dst: Option<IpAddr>,
+    /// `None` for ARP
+    pub ip_protocol: Option<IpNextHeaderProtocol>,
+    /// Source and destination, for TCP and UDP
+    pub ports: Option<(u16, u16)>,
+    pub tcp_flags: Option<u8>,
+}
+
+/// `--tcp-flags`: the flag bits asked for and how strictly they must match.
+#[derive(Clone)]
+pub struct TcpFlagFilter {
+    mask: u8,
+    mode: TcpFlagMatch,
+}
+
+impl TcpFlagFilter {
+    fn parse(names: &[String], mode: TcpFlagMatch) -> Result<Option<Self>> {
+        if names.is_empty() {
+            return Ok(None);
+        }
+        let mut mask = 0;
+        for name in names {
+            let name = name.trim().to_uppercase();
+            let Some(bit) = TCP_FLAGS.iter().position(|flag| *flag == name) else {
+                return Err(PacketSnifferError::InvalidFilter(format!(
+                    "unknown TCP flag '{}' (supported: {})", name, TCP_FLAGS.join(", ")
+                )));
+            };
+            mask |= 1 << bit;
+        }
+        Ok(Some(TcpFlagFilter { mask, mode }))
+    }
+
+    fn matches(&self, flags: u8) -> bool {
+        match self.mode {
+            TcpFlagMatch::Exact => flags == self.mask,
+            TcpFlagMatch::Superset => flags & self.mask == self.mask,
+        }
+    }
+}
+
+impl fmt::Display for TcpFlagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = TCP_FLAGS.iter().enumerate()
+            .filter(|(bit, _)| self.mask & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        let mode = match self.mode {
+            TcpFlagMatch::Exact => "exactly",
+            TcpFlagMatch::Superset => "at least",
+        };
+        write!(f, "{} {}", mode, names.join(","))
+    }
+}
+
+impl CaptureFilter {
+    /// `local` holds the capture interfaces' addresses, which `--direction`
+    /// is judged against.
+    pub fn from_args(args: &Args, local: &LocalAddresses) -> Result<Self> {
+        let protocols = parse_protocols(&args.protocol)?;
+        let exclude_protocols = parse_protocols(&args.exclude_protocol)?;
+
+        let subnets = args.subnet.iter()
+            .map(|cidr| cidr.parse::<IpNetwork>().map_err(|e| {
+                PacketSnifferError::InvalidFilter(format!("--subnet '{}' is not a valid CIDR: {}", cidr, e))
+            }))
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(id) = args.vlan.iter().find(|id| **id > 4094) {
+            return Err(PacketSnifferError::InvalidFilter(format!("--vlan {} is out of range (0-4094)", id)));
+        }
+
+        if let (Some(min), Some(max)) = (args.min_size, args.max_size) {
+            if min > max {
+                return Err(PacketSnifferError::InvalidFilter(format!("--min-size {} is larger than --max-size {}", min, max)));
+            }
+        }
+
+        if args.direction.is_some() && local.is_empty() {
+            return Err(PacketSnifferError::InvalidFilter("--direction needs an interface with an IP address to tell directions apart".to_string()));
+        }
+
+        let payload_match = args.payload_match.as_deref()
+            .map(|pattern| Regex::new(pattern).map_err(|e| {
+                PacketSnifferError::InvalidFilter(format!("--payload-match '{}' is not a valid regex: {}", pattern, e))
+            }))
+            .transpose()?;
+
+        Ok(CaptureFilter {
+            bpf: args.bpf.as_deref().map(BpfFilter::compile).transpose()?,
+            protocols,
+            ports: args.port.clone(),
+            src_ips: args.src_ip.clone(),
+            dst_ips: args.dst_ip.clone(),
+            hosts: args.host.clone(),
+            subnets,
+            vlans: args.vlan.clone(),
+            min_size: args.min_size,
+            max_size: args.max_size,
+            tcp_flags: TcpFlagFilter::parse(&args.tcp_flags, args.tcp_flags_match)?,
+            payload_match,
+            exclude_protocols,
+            exclude_hosts: args.exclude_host.clone(),
+            exclude_ports: args.exclude_port.clone(),
+            direction: args.direction,
+            local: local.clone(),
+            expression: args.filter.as_deref().map(FilterExpr::parse).transpose()?,
+        })
+    }
+
+    pub fn bpf(&self) -> Option<&BpfFilter> {
+        self.bpf.as_ref()
+    }
+
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    pub fn subnets(&self) -> &[IpNetwork] {
+        &self.subnets
+    }
+
+    pub fn vlans(&self) -> &[u16] {
+        &self.vlans
+    }
+
+    pub fn tcp_flags(&self) -> Option<&TcpFlagFilter> {
+        self.tcp_flags.as_ref()
+    }
+
+    pub fn payload_match(&self) -> Option<&Regex> {
+        self.payload_match.as_ref()
+    }
+
+    pub fn exclude_protocols(&self) -> &[String] {
+        &self.exclude_protocols
+    }
+
+    pub fn direction(&self) -> Option<DirectionFilter> {
+        self.direction
+    }
+
+    pub fn expression(&self) -> Option<&FilterExpr> {
+        self.expression.as_ref()
+    }
+
+    pub fn matches(&self, packet: &[u8]) -> bool {
+        if self.min_size.is_some_and(|min| packet.len() < min) || self.max_size.is_some_and(|max| packet.len() > max) {
+            return false;
+        }
+        if self.bpf.as_ref().is_some_and(|bpf| !bpf.matches(packet)) {
+            return false;
+        }
+
+        let Some(frame) = vlan::untag(packet) else {
+            return true;
+        };
+        if !self.vlans.is_empty() && !frame.vlan_ids.iter().any(|id| self.vlans.contains(id)) {
+            return false;
+        }
+
+        match frame.ethertype {
+            EtherTypes::Ipv4 => match Ipv4Packet::new(frame.payload) {
+                Some(ipv4_packet) => self.matches_ip(
+                    packet.len(),
+                    IpAddr::V4(ipv4_packet.get_source()),
+                    IpAddr::V4(ipv4_packet.get_destination()),
+                    ipv4_packet.get_next_level_protocol(),
+                    ipv4_packet.payload(),
+                ),
+                None => true,
+            },
+            EtherTypes::Ipv6 => match Ipv6Packet::new(frame.payload) {
+                Some(ipv6_packet) => self.matches_ip(
+                    packet.len(),
+                    IpAddr::V6(ipv6_packet.get_source()),
+                    IpAddr::V6(ipv6_packet.get_destination()),
+                    ipv6_packet.get_next_header(),
+                    ipv6_packet.payload(),
+                ),
+                None => true,
+            },
+            EtherTypes::Arp => match ArpPacket::new(frame.payload) {
+                Some(arp_packet) => self.matches_arp(packet.len(), &arp_packet),
+                None => true,
+            },
+            _ => false,
+        }
+    }
+
+    /// ARP has no ports, TCP flags or payload, so any of those filters
+    /// excludes it; a port exclusion never does.
+    fn matches_arp(&self, len: usize, arp_packet: &ArpPacket) -> bool {
+        let sender = IpAddr::V4(arp_packet.get_sender_proto_addr());
+        let target = IpAddr::V4(arp_packet.get_target_proto_addr());
+        let fields = PacketFields { len, src: Some(sender), dst: Some(target), ip_protocol: None, ports: None, tcp_flags: None };
+
+        self.matches_hosts(sender, target)
+            && (self.protocols.is_empty() || self.protocols.iter().any(|protocol| protocol == "arp"))
+            && !self.exclude_protocols.iter().any(|protocol| protocol == "arp")
+            && self.ports.is_empty()
+            && self.tcp_flags.is_none()
+            && self.payload_match.is_none()
+            && self.expression.as_ref().is_none_or(|expression| expression.matches(&fields))
+    }
+
+    fn matches_ip(&self, len: usize, src: IpAddr, dst: IpAddr, next_protocol: IpNextHeaderProtocol, payload: &[u8]) -> bool {
+        if !self.matches_hosts(src, dst) {
+            return false;
+        }
+
+        let tcp = match next_protocol {
+            IpNextHeaderProtocols::Tcp => TcpPacket::new(payload),
+            _ => None,
+        };
+        let udp = match next_protocol {
+            IpNextHeaderProtocols::Udp => UdpPacket::new(payload),
+            _ => None,
+        };
+        // A flag filter only ever matches TCP
+        if let Some(ref tcp_flags) = self.tcp_flags {
+            if !tcp.as_ref().is_some_and(|tcp| tcp_flags.matches(tcp.get_flags())) {
+                return false;
+            }
+        }
+
+        let ports = tcp.as_ref().map(|tcp| (tcp.get_source(), tcp.get_destination()))
+            .or_else(|| udp.as_ref().map(|udp| (udp.get_source(), udp.get_destination())))
+            .or_else(|| match next_protocol {
+                IpNextHeaderProtocols::Sctp => sctp::ports(payload),
+                _ => None,
+            });
+        let uses_port = |port: u16| ports.is_some_and(|(src_port, dst_port)| src_port == port || dst_port == port);
+        let fields = PacketFields {
+            len,
+            src: Some(src),
+            dst: Some(dst),
+            ip_protocol: Some(next_protocol),
+            ports,
+            tcp_flags: tcp.as_ref().map(|tcp| tcp.get_flags()),
+        };
+
+        let protocol_match = |protocol: &String| protocol_matches(protocol, &fields);
+        if !self.protocols.is_empty() && !self.protocols.iter().any(protocol_match) {
+            return false;
+        }
+        if self.exclude_protocols.iter().any(protocol_match) {
+            return false;
+        }
+
+        if !(self.ports.is_empty() || self.ports.iter().any(|&port| uses_port(port))) || self.exclude_ports.iter().any(|&port| uses_port(port)) {
+            return false;
+        }
+        if self.expression.as_ref().is_some_and(|expression| !expression.matches(&fields)) {
+            return false;
+        }
+
+        // Last, as the priciest check: TCP/UDP payload, or what follows the IP header otherwise
+        self.payload_match.as_ref().is_none_or(|regex| {
+            let data = tcp.as_ref().map(|tcp| tcp.payload())
+                .or_else(|| udp.as_ref().map(|udp| udp.payload()))
+                .unwrap_or(payload);
+            !data.is_empty() && regex.is_match(&String::from_utf8_lossy(&data[..data.len().min(PAYLOAD_MATCH_BYTES)]))
+        })
+    }
+
+    fn matches_hosts(&self, src: IpAddr, dst: IpAddr) -> bool {
+        (self.src_ips.is_empty() || self.src_ips.contains(&src))
+            && (self.dst_ips.is_empty() || self.dst_ips.contains(&dst))
+            && (self.hosts.is_empty() || self.hosts.iter().any(|host| *host == src || *host == dst))
+            && (self.subnets.is_empty() || self.subnets.iter().any(|net| net.contains(src) || net.contains(dst)))
+            && !self.exclude_hosts.iter().any(|host| *host == src || *host == dst)
+            && self.direction.is_none_or(|direction| {
+                let wanted = match direction {
+                    DirectionFilter::In => Direction::Inbound,
+                    DirectionFilter::Out => Direction::Outbound,
+                };
+                self.local.classify(src, dst) == wanted
+            })
+    }
+}
+
+/// Whether a frame is `protocol`, one of `PROTOCOLS`. HTTP and DNS are told
+/// apart by their well-known ports.
+pub fn protocol_matches(protocol: &str, fields: &PacketFields) -> bool {
+    let uses_port = |port: u16| fields.ports.is_some_and(|(src_port, dst_port)| src_port == port || dst_port == port);
+    match (protocol, fields.ip_protocol) {
+        ("arp", None) => true,
+        (_, None) => false,
+        ("tcp", Some(next)) => next == IpNextHeaderProtocols::Tcp,
+        ("udp", Some(next)) => next == IpNextHeaderProtocols::Udp,
+        ("sctp", Some(next)) => next == IpNextHeaderProtocols::Sctp,
+        ("icmp", Some(next)) => next == IpNextHeaderProtocols::Icmp,
+        ("icmp6", Some(next)) => next == IpNextHeaderProtocols::Icmpv6,
+        ("igmp", Some(next)) => next == IpNextHeaderProtocols::Igmp,
+        ("http", Some(next)) => next == IpNextHeaderProtocols::Tcp && (uses_port(80) || uses_port(8080)),
+        ("dns", Some(next)) => next == IpNextHeaderProtocols::Udp && uses_port(53),
+        _ => false,
+    }
+}
+
+/// Lowercased and deduplicated, each one checked against `PROTOCOLS`.
+fn parse_protocols(names: &[String]) -> Result<Vec<String>> {
+    let mut protocols = Vec::new();
+    for protocol in names {
+        let protocol = protocol.trim().to_lowercase();
+        if !PROTOCOLS.contains(&protocol.as_str()) {
+            return Err(PacketSnifferError::InvalidFilter(format!(
+                "unknown protocol '{}' (supported: {})", protocol, PROTOCOLS.join(", ")
+            )));
+        }
+        if !protocols.contains(&protocol) {
+            protocols.push(protocol);
+        }
+    }
+    Ok(protocols)
+}
@@ -0,0 +1,146 @@
+//! `--filter` expressions: the checks behind the individual filter flags,
+//! combined freely with `and`, `or`, `not` and parentheses, e.g.
+//! `tcp and (port 443 or port 8443) and not host 10.0.0.1`.
+//!
+//! - protocols, with the meaning `--protocol` gives them: `tcp`, `udp`,
+//!   `sctp`, `icmp`, `icmp6`, `igmp`, `http`, `dns`, `arp`
+//! - `[src|dst] host <ip>`, `[src|dst] net <cidr>`, `[src|dst] port <n>`
+//! - `size <op> <bytes>` with `<`, `<=`, `>`, `>=` or `=`, on the frame length
+//! - `flags <names>`: TCP packets with at least these flags set, e.g. `flags syn,ack`
+//!
+//! The combining grammar is the one `--bpf` uses too (see `expr`). Like the
+//! other filters the expression is parsed once at startup and runs on each
+//! frame before it is analyzed.
+
+use ipnetwork::IpNetwork;
+use std::net::IpAddr;
+
+use crate::error::{PacketSnifferError, Result};
+use crate::expr::{self, Comparison, Expr, Grammar, Parser};
+use crate::filter::{self, PacketFields};
+
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    expression: String,
+    root: Expr<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Protocol(String),
+    Host(Side, IpAddr),
+    Net(Side, IpNetwork),
+    Port(Side, u16),
+    Size(Comparison, usize),
+    /// TCP flag bits that must all be set
+    Flags(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Src,
+    Dst,
+    Either,
+}
+
+impl FilterExpr {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let root = expr::parse::<FilterSyntax>(expression)?;
+        Ok(FilterExpr { expression: expression.to_string(), root })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn matches(&self, fields: &PacketFields) -> bool {
+        self.root.evaluate(&|predicate| evaluate(predicate, fields))
+    }
+}
+
+/// `--filter` predicates for the shared grammar
+struct FilterSyntax;
+
+impl Grammar for FilterSyntax {
+    type Primitive = Predicate;
+
+    fn invalid(message: String) -> PacketSnifferError {
+        PacketSnifferError::InvalidFilter(format!("--filter: {}", message))
+    }
+
+    fn primitive(parser: &mut Parser<Self>) -> Result<Expr<Predicate>> {
+        let invalid = Self::invalid;
+        let (word, offset) = parser.value("a predicate")?;
+        let side = match word.as_str() {
+            "src" => Some(Side::Src),
+            "dst" => Some(Side::Dst),
+            _ => None,
+        };
+        let (keyword, keyword_offset) = match side {
+            Some(_) => parser.value("host, net or port")?,
+            None => (word, offset),
+        };
+
+        let predicate = match keyword.as_str() {
+            "host" | "net" | "port" => {
+                let side = side.unwrap_or(Side::Either);
+                let (value, value_offset) = parser.value(&format!("a value after '{}'", keyword))?;
+                let bad_value = |expected: &str| invalid(format!("invalid {} '{}' at position {}: expected {}", keyword, value, value_offset, expected));
+                match keyword.as_str() {
+                    "host" => Predicate::Host(side, value.parse().map_err(|_| bad_value("an IP address"))?),
+                    "net" => Predicate::Net(side, value.parse().map_err(|_| bad_value("a CIDR network such as 10.0.0.0/24"))?),
+                    _ => Predicate::Port(side, value.parse().map_err(|_| bad_value("a port from 0 to 65535"))?),
+                }
+            }
+            _ if side.is_some() => return Err(invalid(format!("expected host, net or port after '{}' at position {}", if side == Some(Side::Src) { "src" } else { "dst" }, keyword_offset))),
+            "size" => {
+                let Some(comparison) = parser.comparison() else {
+                    return Err(invalid(format!("expected <, <=, >, >= or = after 'size' at position {}", parser.offset())));
+                };
+                let (value, value_offset) = parser.value("a size in bytes")?;
+                let bytes = value.parse().map_err(|_| invalid(format!("invalid size '{}' at position {}: expected a number of bytes", value, value_offset)))?;
+                Predicate::Size(comparison, bytes)
+            }
+            "flags" => {
+                let (value, value_offset) = parser.value("TCP flag names")?;
+                let mut mask = 0;
+                for name in value.split(',') {
+                    let name = name.trim().to_uppercase();
+                    let Some(bit) = filter::TCP_FLAGS.iter().position(|flag| *flag == name) else {
+                        return Err(invalid(format!(
+                            "unknown TCP flag '{}' at position {} (supported: {})", name, value_offset, filter::TCP_FLAGS.join(", ")
+                        )));
+                    };
+                    mask |= 1 << bit;
+                }
+                Predicate::Flags(mask)
+            }
+            protocol if filter::PROTOCOLS.contains(&protocol) => Predicate::Protocol(protocol.to_string()),
+            _ => return Err(invalid(format!(
+                "unknown predicate '{}' at position {} (expected a protocol such as tcp, host, net, port, size or flags)", keyword, keyword_offset
+            ))),
+        };
+        Ok(Expr::Primitive(predicate))
+    }
+}
+
+fn evaluate(predicate: &Predicate, fields: &PacketFields) -> bool {
+    let on_side = |side: Side, test: &dyn Fn(&IpAddr) -> bool| match side {
+        Side::Src => fields.src.as_ref().is_some_and(test),
+        Side::Dst => fields.dst.as_ref().is_some_and(test),
+        Side::Either => fields.src.as_ref().is_some_and(test) || fields.dst.as_ref().is_some_and(test),
+    };
+
+    match predicate {
+        Predicate::Protocol(protocol) => filter::protocol_matches(protocol, fields),
+        Predicate::Host(side, host) => on_side(*side, &|ip| ip == host),
+        Predicate::Net(side, net) => on_side(*side, &|ip| net.contains(*ip)),
+        Predicate::Port(side, port) => fields.ports.is_some_and(|(src, dst)| match side {
+            Side::Src => src == *port,
+            Side::Dst => dst == *port,
+            Side::Either => src == *port || dst == *port,
+        }),
+        Predicate::Size(comparison, bytes) => comparison.holds(fields.len, *bytes),
+        Predicate::Flags(mask) => fields.tcp_flags.is_some_and(|flags| flags & mask == *mask),
+    }
+}
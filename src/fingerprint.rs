@@ -0,0 +1,64 @@
+//! Passive OS guesses from what a host puts in its own headers. Every stack
+//! starts its packets at a fixed TTL (64 for Linux and macOS, 128 for
+//! Windows, 255 for most network gear), which each router on the way lowers
+//! by one, so rounding the observed TTL up to the nearest of those recovers
+//! it. The TCP window offered in a SYN tells Linux and macOS apart.
+//!
+//! This is a heuristic: tuned stacks, NAT and tunnels all change what is
+//! seen, so the result is a hint for inventory, never an identification.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Starting TTLs in use, lowest first
+const INITIAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+
+/// What macOS, iOS and the BSDs offer in a SYN; Linux offers less
+const BSD_SYN_WINDOW: u16 = 65535;
+
+/// Every `os` that `OsGuess::new` gives
+const OS_NAMES: [&str; 6] = ["macOS/BSD", "Linux/Android", "Linux/macOS", "Windows", "Network device", "Embedded/legacy"];
+
+/// Spelled as an alias so serde doesn't take `os` for a string borrowed
+/// from the input
+type OsName = &'static str;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsGuess {
+    #[serde(deserialize_with = "os_name")]
+    pub os: OsName,
+    pub initial_ttl: u8,
+    /// Routers between the host and the capture point
+    pub hops: u8,
+    /// Whether a SYN's window went into the guess, not just the TTL
+    #[serde(skip)]
+    from_syn: bool,
+}
+
+impl OsGuess {
+    /// `syn_window` is the TCP window when the packet is a SYN or SYN-ACK;
+    /// windows later in a connection depend on its state, not the OS.
+    pub fn new(ttl: u8, syn_window: Option<u16>) -> Self {
+        let initial_ttl = INITIAL_TTLS.into_iter().find(|&initial| ttl <= initial).unwrap_or(u8::MAX);
+        let os = match (initial_ttl, syn_window) {
+            (64, Some(BSD_SYN_WINDOW)) => "macOS/BSD",
+            (64, Some(_)) => "Linux/Android",
+            (64, None) => "Linux/macOS",
+            (128, _) => "Windows",
+            (255, _) => "Network device",
+            _ => "Embedded/legacy",
+        };
+        OsGuess { os, initial_ttl, hops: initial_ttl - ttl, from_syn: syn_window.is_some() }
+    }
+
+    /// A guess backed by a SYN is only ever replaced by another such guess.
+    pub fn replaces(&self, older: &OsGuess) -> bool {
+        self.from_syn || !older.from_syn
+    }
+}
+
+/// Reads a saved guess back, for `--load-session`.
+fn os_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OsName, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    OS_NAMES.into_iter().find(|&known| known == name).ok_or_else(|| D::Error::custom(format!("unknown OS guess '{}'", name)))
+}
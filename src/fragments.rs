@@ -0,0 +1,407 @@
+//! IP fragment reassembly. Only the first fragment of a datagram carries the
+//! transport header, so fragments are held back until their datagram is
+//! complete and analysis sees one rebuilt frame instead. Fragments are keyed
+//! by addresses, identification and protocol; IPv6 ones are recognised when
+//! the fragment header directly follows the fixed header, as it does in
+//! practice. Sets still incomplete after `FRAGMENT_TIMEOUT_SECS` are dropped,
+//! and so is a set whose pieces add up to more than a whole datagram, as
+//! overlapping fragments at many offsets would, so memory stays bounded by
+//! `MAX_PENDING_DATAGRAMS` datagrams.
+
+use chrono::{DateTime, Utc};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pnet::packet::ethernet::EtherTypes;
+
+use crate::vlan;
+
+/// How long a datagram's fragments wait for the rest, as Linux's ipfrag_time
+const FRAGMENT_TIMEOUT_SECS: i64 = 30;
+
+/// Datagrams being reassembled at once; fragments of further ones are dropped
+const MAX_PENDING_DATAGRAMS: usize = 1024;
+
+/// Largest datagram the 16-bit length fields can describe
+const MAX_DATAGRAM_BYTES: usize = 65_535;
+
+const IPV6_HEADER_LEN: usize = 40;
+const IPV6_FRAGMENT_HEADER: u8 = 44;
+const IPV6_FRAGMENT_HEADER_LEN: usize = 8;
+
+/// Across every capture thread, for the final summary
+static REASSEMBLED: AtomicUsize = AtomicUsize::new(0);
+static DISCARDED: AtomicUsize = AtomicUsize::new(0);
+
+/// Datagrams rebuilt from fragments, and incomplete ones given up on.
+pub fn totals() -> (usize, usize) {
+    (REASSEMBLED.load(Ordering::Relaxed), DISCARDED.load(Ordering::Relaxed))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    src: IpAddr,
+    dst: IpAddr,
+    id: u32,
+    protocol: u8,
+}
+
+/// What one frame says about its datagram.
+struct Fragment<'a> {
+    key: FragmentKey,
+    /// Byte offset of `data` in the datagram's payload
+    offset: usize,
+    more_fragments: bool,
+    /// The frame up to the end of its IP header, fragment header excluded
+    head: &'a [u8],
+    ip_header_len: usize,
+    data: &'a [u8],
+    ipv6: bool,
+}
+
+/// The first fragment's headers, which the rebuilt frame starts with.
+struct Head {
+    bytes: Vec<u8>,
+    ip_header_len: usize,
+    ipv6: bool,
+}
+
+struct PendingDatagram {
+    first_seen: DateTime<Utc>,
+    head: Option<Head>,
+    pieces: BTreeMap<usize, Vec<u8>>,
+    /// Bytes held in `pieces`
+    buffered: usize,
+    /// Known once the last fragment has arrived
+    payload_len: Option<usize>,
+}
+
+/// One per capture thread, so fragments need no locking.
+#[derive(Default)]
+pub struct Defragmenter {
+    pending: HashMap<FragmentKey, PendingDatagram>,
+    last_sweep: Option<DateTime<Utc>>,
+}
+
+impl Defragmenter {
+    /// `frame` untouched when it isn't a fragment, the rebuilt datagram when
+    /// it completes one, and `None` while its datagram is still incomplete.
+    pub fn accept<'a>(&mut self, timestamp: DateTime<Utc>, frame: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        self.sweep(timestamp);
+        let Some(fragment) = parse_fragment(frame) else {
+            return Some(Cow::Borrowed(frame));
+        };
+
+        let too_many = !self.pending.contains_key(&fragment.key) && self.pending.len() >= MAX_PENDING_DATAGRAMS;
+        if too_many || fragment.offset + fragment.data.len() > MAX_DATAGRAM_BYTES {
+            // Counted once per datagram, when its set is dropped or never started
+            if self.pending.remove(&fragment.key).is_some() || too_many {
+                DISCARDED.fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+        let datagram = self.pending.entry(fragment.key).or_insert_with(|| PendingDatagram {
+            first_seen: timestamp,
+            head: None,
+            pieces: BTreeMap::new(),
+            buffered: 0,
+            payload_len: None,
+        });
+        if fragment.offset == 0 {
+            datagram.head = Some(Head { bytes: fragment.head.to_vec(), ip_header_len: fragment.ip_header_len, ipv6: fragment.ipv6 });
+        }
+        if !fragment.more_fragments {
+            datagram.payload_len = Some(fragment.offset + fragment.data.len());
+        }
+        // A retransmitted fragment replaces the earlier copy
+        if let Some(earlier) = datagram.pieces.insert(fragment.offset, fragment.data.to_vec()) {
+            datagram.buffered -= earlier.len();
+        }
+        datagram.buffered += fragment.data.len();
+        if datagram.buffered > MAX_DATAGRAM_BYTES {
+            self.pending.remove(&fragment.key);
+            DISCARDED.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let rebuilt = datagram.rebuild(fragment.key.protocol)?;
+        self.pending.remove(&fragment.key);
+        REASSEMBLED.fetch_add(1, Ordering::Relaxed);
+        Some(Cow::Owned(rebuilt))
+    }
+
+    /// Drops sets that have waited too long, at most once a second.
+    fn sweep(&mut self, now: DateTime<Utc>) {
+        if self.last_sweep.is_some_and(|last| (now - last).num_seconds() < 1) {
+            return;
+        }
+        self.last_sweep = Some(now);
+
+        let before = self.pending.len();
+        self.pending.retain(|_, datagram| (now - datagram.first_seen).num_seconds() < FRAGMENT_TIMEOUT_SECS);
+        DISCARDED.fetch_add(before - self.pending.len(), Ordering::Relaxed);
+    }
+}
+
+impl PendingDatagram {
+    /// The whole frame, once the first and last fragments are in and nothing
+    /// between them is missing. Where fragments overlap, the one with the lower
+    /// offset wins.
+    fn rebuild(&self, protocol: u8) -> Option<Vec<u8>> {
+        let head = self.head.as_ref()?;
+        let payload_len = self.payload_len?;
+
+        let mut payload = Vec::with_capacity(payload_len);
+        for (&offset, data) in &self.pieces {
+            if offset > payload.len() {
+                return None;
+            }
+            let end = (offset + data.len()).min(payload_len);
+            if end > payload.len() {
+                payload.extend_from_slice(&data[payload.len() - offset..end - offset]);
+            }
+        }
+        if payload.len() < payload_len {
+            return None;
+        }
+
+        let mut frame = head.bytes.clone();
+        let ip_start = frame.len() - head.ip_header_len;
+        let header = &mut frame[ip_start..];
+        if head.ipv6 {
+            // The fragment header goes, so the next header is what it named
+            header[4..6].copy_from_slice(&(payload_len as u16).to_be_bytes());
+            header[6] = protocol;
+        } else {
+            let total_len = header.len() + payload_len;
+            if total_len > MAX_DATAGRAM_BYTES {
+                return None;
+            }
+            header[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+            // Keep don't-fragment, clear more-fragments and the offset
+            header[6] &= 0x40;
+            header[7] = 0;
+            header[10..12].copy_from_slice(&[0, 0]);
+            let checksum = ipv4_checksum(header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        frame.extend_from_slice(&payload);
+        Some(frame)
+    }
+}
+
+fn parse_fragment(frame: &[u8]) -> Option<Fragment<'_>> {
+    let untagged = vlan::untag(frame)?;
+    let link_len = frame.len() - untagged.payload.len();
+    let packet = untagged.payload;
+
+    match untagged.ethertype {
+        EtherTypes::Ipv4 => {
+            if packet.len() < 20 || packet[0] >> 4 != 4 {
+                return None;
+            }
+            let header_len = ((packet[0] & 0x0F) as usize) * 4;
+            if header_len < 20 {
+                return None;
+            }
+            let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+            let flags_offset = u16::from_be_bytes([packet[6], packet[7]]);
+            let more_fragments = flags_offset & 0x2000 != 0;
+            let offset = ((flags_offset & 0x1FFF) as usize) * 8;
+            if !more_fragments && offset == 0 {
+                return None;
+            }
+            // Ethernet pads short frames, so go by the IP length
+            let data = packet.get(header_len..total_len)?;
+            let key = FragmentKey {
+                src: IpAddr::V4(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15])),
+                dst: IpAddr::V4(Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19])),
+                id: u16::from_be_bytes([packet[4], packet[5]]) as u32,
+                protocol: packet[9],
+            };
+            Some(Fragment { key, offset, more_fragments, head: &frame[..link_len + header_len], ip_header_len: header_len, data, ipv6: false })
+        }
+        EtherTypes::Ipv6 => {
+            if packet.len() < IPV6_HEADER_LEN + IPV6_FRAGMENT_HEADER_LEN || packet[6] != IPV6_FRAGMENT_HEADER {
+                return None;
+            }
+            let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+            let fragment_header = &packet[IPV6_HEADER_LEN..IPV6_HEADER_LEN + IPV6_FRAGMENT_HEADER_LEN];
+            let offset_flags = u16::from_be_bytes([fragment_header[2], fragment_header[3]]);
+            let data = packet.get(IPV6_HEADER_LEN + IPV6_FRAGMENT_HEADER_LEN..IPV6_HEADER_LEN + payload_len)?;
+            let address = |start: usize| -> Ipv6Addr {
+                let octets: [u8; 16] = packet[start..start + 16].try_into().expect("sixteen bytes");
+                Ipv6Addr::from(octets)
+            };
+            let key = FragmentKey {
+                src: IpAddr::V6(address(8)),
+                dst: IpAddr::V6(address(24)),
+                id: u32::from_be_bytes([fragment_header[4], fragment_header[5], fragment_header[6], fragment_header[7]]),
+                protocol: fragment_header[0],
+            };
+            Some(Fragment {
+                key,
+                offset: ((offset_flags >> 3) as usize) * 8,
+                more_fragments: offset_flags & 0x0001 != 0,
+                head: &frame[..link_len + IPV6_HEADER_LEN],
+                ip_header_len: IPV6_HEADER_LEN,
+                data,
+                ipv6: true,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header.chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn ethernet(ethertype: u16) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 0x02, 0x02, 0, 0, 0, 0, 0x01];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame
+    }
+
+    /// A UDP fragment from 10.0.0.1 to 10.0.0.2, don't-fragment unset
+    fn ipv4_fragment(id: u16, offset: usize, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+        let mut frame = ethernet(0x0800);
+        let flags_offset = (offset / 8) as u16 | if more_fragments { 0x2000 } else { 0 };
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((20 + data.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&flags_offset.to_be_bytes());
+        frame.extend_from_slice(&[64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    fn ipv6_fragment(id: u32, offset: usize, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+        let mut frame = ethernet(0x86DD);
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&((IPV6_FRAGMENT_HEADER_LEN + data.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[IPV6_FRAGMENT_HEADER, 64]);
+        frame.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        frame.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        // The offset is in 8-byte units above three flag bits, so it sits as is
+        let offset_flags = offset as u16 | more_fragments as u16;
+        frame.extend_from_slice(&[17, 0]);
+        frame.extend_from_slice(&offset_flags.to_be_bytes());
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    /// Feeds every frame but the last, expecting each to be held back.
+    fn feed(defragmenter: &mut Defragmenter, frames: &[Vec<u8>]) -> Vec<u8> {
+        let (last, held) = frames.split_last().unwrap();
+        for frame in held {
+            assert!(defragmenter.accept(at(0), frame).is_none());
+        }
+        defragmenter.accept(at(0), last).expect("the datagram is complete").into_owned()
+    }
+
+    #[test]
+    fn fragments_reassemble_in_any_order() {
+        let payload: Vec<u8> = (0..24).collect();
+        let frames = [
+            ipv4_fragment(7, 0, true, &payload[..8]),
+            ipv4_fragment(7, 8, true, &payload[8..16]),
+            ipv4_fragment(7, 16, false, &payload[16..]),
+        ];
+        let in_order = feed(&mut Defragmenter::default(), &frames);
+        let out_of_order = feed(&mut Defragmenter::default(), &[frames[2].clone(), frames[0].clone(), frames[1].clone()]);
+        assert_eq!(in_order, out_of_order);
+
+        let header = &in_order[14..34];
+        assert_eq!(u16::from_be_bytes([header[2], header[3]]), 44);
+        assert_eq!(&header[6..8], &[0, 0]);
+        assert_eq!(ipv4_checksum(header), 0);
+        assert_eq!(&in_order[34..], &payload[..]);
+
+        let whole = ipv4_fragment(8, 0, false, &payload);
+        assert!(matches!(Defragmenter::default().accept(at(0), &whole), Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn lower_offset_wins_where_fragments_overlap() {
+        let rebuilt = feed(&mut Defragmenter::default(), &[
+            ipv4_fragment(7, 8, false, &[0xBB; 16]),
+            ipv4_fragment(7, 0, true, &[0xAA; 16]),
+        ]);
+        assert_eq!(&rebuilt[34..50], &[0xAA; 16]);
+        assert_eq!(&rebuilt[50..], &[0xBB; 8]);
+    }
+
+    #[test]
+    fn incomplete_datagrams_time_out() {
+        let mut defragmenter = Defragmenter::default();
+        assert!(defragmenter.accept(at(0), &ipv4_fragment(7, 0, true, &[0; 8])).is_none());
+        assert!(defragmenter.accept(at(0), &ipv4_fragment(7, 16, false, &[0; 8])).is_none());
+        assert_eq!(defragmenter.pending.len(), 1);
+
+        let later = at(0) + Duration::seconds(FRAGMENT_TIMEOUT_SECS - 1);
+        defragmenter.accept(later, &ipv4_fragment(9, 0, false, &[0; 8]));
+        assert_eq!(defragmenter.pending.len(), 1);
+
+        let expired = at(0) + Duration::seconds(FRAGMENT_TIMEOUT_SECS);
+        defragmenter.accept(expired, &ipv4_fragment(9, 0, false, &[0; 8]));
+        assert!(defragmenter.pending.is_empty());
+        // Filling the gap afterwards no longer completes anything
+        assert!(defragmenter.accept(expired, &ipv4_fragment(7, 8, true, &[0; 8])).is_none());
+    }
+
+    #[test]
+    fn ipv6_fragment_header_is_removed() {
+        let payload: Vec<u8> = (0..16).collect();
+        let rebuilt = feed(&mut Defragmenter::default(), &[
+            ipv6_fragment(0x1234_5678, 8, false, &payload[8..]),
+            ipv6_fragment(0x1234_5678, 0, true, &payload[..8]),
+        ]);
+        assert_eq!(rebuilt.len(), 14 + IPV6_HEADER_LEN + payload.len());
+        let header = &rebuilt[14..14 + IPV6_HEADER_LEN];
+        assert_eq!(u16::from_be_bytes([header[4], header[5]]), 16);
+        assert_eq!(header[6], 17);
+        assert_eq!(&rebuilt[14 + IPV6_HEADER_LEN..], &payload[..]);
+    }
+
+    #[test]
+    fn overlapping_fragments_cannot_outgrow_a_datagram() {
+        let mut defragmenter = Defragmenter::default();
+        let mut offset = 0;
+        while offset == 0 || !defragmenter.pending.is_empty() {
+            let fragment = ipv4_fragment(7, offset, true, &[0; 1400]);
+            assert!(defragmenter.accept(at(0), &fragment).is_none());
+            offset += 8;
+            assert!(offset + 1400 <= MAX_DATAGRAM_BYTES, "the set was never dropped");
+        }
+        // About a datagram's worth went in before it was
+        assert_eq!(offset / 8, MAX_DATAGRAM_BYTES / 1400 + 1);
+    }
+
+    #[test]
+    fn checksum_of_a_known_header() {
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+            0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(ipv4_checksum(&header), 0xb861);
+    }
+}
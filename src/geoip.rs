@@ -0,0 +1,122 @@
+use maxminddb::{geoip2, Reader};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::GeoIpConfig;
+use crate::logger::Logger;
+use crate::{is_local_ip, GeoInfo};
+
+// Enough for a busy capture without letting the cache grow forever
+const MAX_CACHED_LOOKUPS: usize = 10_000;
+
+/// Clones share the database but each keeps its own cache.
+#[derive(Clone)]
+pub struct GeoIpResolver {
+    reader: Option<Arc<Reader<Vec<u8>>>>,
+    cache: HashMap<String, Option<GeoInfo>>,
+}
+
+impl GeoIpResolver {
+    pub fn from_config(config: &GeoIpConfig, logger: &mut Logger) -> Self {
+        let reader = match config.database_path {
+            Some(ref path) if Path::new(path).exists() => match Reader::open_readfile(path) {
+                Ok(reader) => {
+                    logger.log_info(&format!("Loaded GeoIP database: {}", path));
+                    Some(Arc::new(reader))
+                }
+                Err(e) => {
+                    logger.log_warn(&format!("Failed to open GeoIP database {}: {}", path, e));
+                    None
+                }
+            },
+            Some(ref path) => {
+                logger.log_warn(&format!("GeoIP database {} not found, using basic geolocation", path));
+                None
+            }
+            None => None,
+        };
+
+        GeoIpResolver {
+            reader,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn get_geo_info(&mut self, ip: &str) -> Option<GeoInfo> {
+        if let Some(cached) = self.cache.get(ip) {
+            return cached.clone();
+        }
+
+        let geo_info = match self.reader {
+            Some(ref reader) if !is_local_ip(ip) => lookup_city(reader, ip),
+            _ => fallback_geo_info(ip),
+        };
+
+        if self.cache.len() >= MAX_CACHED_LOOKUPS {
+            self.cache.clear();
+        }
+        self.cache.insert(ip.to_string(), geo_info.clone());
+
+        geo_info
+    }
+}
+
+fn lookup_city(reader: &Reader<Vec<u8>>, ip: &str) -> Option<GeoInfo> {
+    let address: IpAddr = ip.parse().ok()?;
+
+    let city: geoip2::City = match reader.lookup(address) {
+        Ok(city) => city,
+        Err(_) => return Some(unknown_location()),
+    };
+
+    let english_name = |names: Option<std::collections::BTreeMap<&str, &str>>| {
+        names.and_then(|names| names.get("en").map(|name| name.to_string()))
+    };
+
+    Some(GeoInfo {
+        country: english_name(city.country.and_then(|c| c.names)).or_else(|| Some("Unknown".to_string())),
+        city: english_name(city.city.and_then(|c| c.names)),
+        latitude: city.location.as_ref().and_then(|l| l.latitude),
+        longitude: city.location.as_ref().and_then(|l| l.longitude),
+    })
+}
+
+fn fallback_geo_info(ip: &str) -> Option<GeoInfo> {
+    // Without a GeoLite2 database we can only tell local traffic apart
+    if is_local_ip(ip) {
+        return Some(GeoInfo {
+            country: Some("Local Network".to_string()),
+            city: Some("Local".to_string()),
+            latitude: None,
+            longitude: None,
+        });
+    }
+
+    // For demo purposes, return some sample data based on IP patterns
+    match ip {
+        ip if ip.starts_with("8.8.") => Some(GeoInfo {
+            country: Some("United States".to_string()),
+            city: Some("Mountain View".to_string()),
+            latitude: Some(37.4056),
+            longitude: Some(-122.0775),
+        }),
+        ip if ip.starts_with("1.1.") => Some(GeoInfo {
+            country: Some("Australia".to_string()),
+            city: Some("Sydney".to_string()),
+            latitude: Some(-33.8688),
+            longitude: Some(151.2093),
+        }),
+        _ => Some(unknown_location()),
+    }
+}
+
+fn unknown_location() -> GeoInfo {
+    GeoInfo {
+        country: Some("Unknown".to_string()),
+        city: Some("Unknown".to_string()),
+        latitude: None,
+        longitude: None,
+    }
+}
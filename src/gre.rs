@@ -0,0 +1,100 @@
+//! GRE headers (RFC 2784/2890, and the PPTP variant of RFC 2637), so the
+//! packet a tunnel carries can be decoded in place of the tunnel itself.
+
+use pnet::packet::ethernet::EtherType;
+use serde::{Deserialize, Serialize};
+
+const CHECKSUM_PRESENT: u16 = 0x8000;
+const KEY_PRESENT: u16 = 0x2000;
+const SEQUENCE_PRESENT: u16 = 0x1000;
+/// PPTP's enhanced GRE may carry an acknowledgment number too
+const ACK_PRESENT: u16 = 0x0080;
+const VERSION_MASK: u16 = 0x0007;
+
+/// Transparent Ethernet bridging: a whole Ethernet frame is inside
+pub const TRANSPARENT_ETHERNET: u16 = 0x6558;
+
+/// The tunnel a packet arrived through, as seen in its outer headers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GreInfo {
+    /// Outer IP addresses: the tunnel endpoints
+    pub src_ip: String,
+    pub dst_ip: String,
+    /// e.g. "IPv4", "Ethernet" or "PPP"
+    pub inner_protocol: String,
+    pub key: Option<u32>,
+}
+
+impl GreInfo {
+    /// e.g. "GRE 198.51.100.1 -> 203.0.113.9 (key 42)"
+    pub fn summary(&self) -> String {
+        match self.key {
+            Some(key) => format!("GRE {} -> {} (key {})", self.src_ip, self.dst_ip, key),
+            None => format!("GRE {} -> {}", self.src_ip, self.dst_ip),
+        }
+    }
+}
+
+/// A decoded GRE header and what follows it.
+pub struct GrePacket<'a> {
+    pub protocol_type: EtherType,
+    pub key: Option<u32>,
+    pub payload: &'a [u8],
+}
+
+/// Returns `None` for unknown versions and headers cut short.
+pub fn parse(packet: &[u8]) -> Option<GrePacket<'_>> {
+    let flags = u16::from_be_bytes([*packet.first()?, *packet.get(1)?]);
+    let protocol_type = EtherType(u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]));
+
+    let mut offset = 4;
+    let key = match flags & VERSION_MASK {
+        0 => {
+            if flags & CHECKSUM_PRESENT != 0 {
+                // Checksum and a reserved field
+                offset += 4;
+            }
+            let mut key = None;
+            if flags & KEY_PRESENT != 0 {
+                key = Some(word_at(packet, offset)?);
+                offset += 4;
+            }
+            if flags & SEQUENCE_PRESENT != 0 {
+                offset += 4;
+            }
+            key
+        }
+        // PPTP: the key field is always there, but holds payload length and call ID
+        1 => {
+            offset += 4;
+            if flags & SEQUENCE_PRESENT != 0 {
+                offset += 4;
+            }
+            if flags & ACK_PRESENT != 0 {
+                offset += 4;
+            }
+            None
+        }
+        _ => return None,
+    };
+
+    Some(GrePacket { protocol_type, key, payload: packet.get(offset..)? })
+}
+
+/// Name for a GRE protocol type, as what the tunnel carries.
+pub fn protocol_name(protocol_type: EtherType) -> String {
+    match protocol_type.0 {
+        0x0800 => "IPv4".to_string(),
+        0x86dd => "IPv6".to_string(),
+        TRANSPARENT_ETHERNET => "Ethernet".to_string(),
+        0x880b => "PPP".to_string(),
+        0x88be => "ERSPAN".to_string(),
+        0x8847 => "MPLS".to_string(),
+        other => format!("protocol 0x{:04x}", other),
+    }
+}
+
+fn word_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(u32::from_be_bytes(word))
+}
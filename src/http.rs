@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+// Only the request line and headers matter, and they rarely exceed this
+const MAX_HEADER_BYTES: usize = 4096;
+
+const METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpInfo {
+    pub method: String,
+    pub host: Option<String>,
+    pub path: String,
+}
+
+impl HttpInfo {
+    /// e.g. "GET example.com/login"
+    pub fn summary(&self) -> String {
+        format!("{} {}{}", self.method, self.host.as_deref().unwrap_or(""), self.path)
+    }
+}
+
+/// Parses the request line and `Host:` header of a cleartext HTTP request.
+/// Requests split across packets are parsed as far as the data goes.
+pub fn parse_request(payload: &[u8]) -> Option<HttpInfo> {
+    let payload = &payload[..payload.len().min(MAX_HEADER_BYTES)];
+    let text = String::from_utf8_lossy(payload);
+    let header_block = text.split("\r\n\r\n").next().unwrap_or("");
+
+    let mut lines = header_block.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+
+    let method = request_line.next()?;
+    if !METHODS.contains(&method) {
+        return None;
+    }
+    let path = request_line.next().filter(|p| !p.is_empty())?;
+
+    let host = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    Some(HttpInfo {
+        method: method.to_string(),
+        host,
+        path: path.to_string(),
+    })
+}
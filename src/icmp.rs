@@ -0,0 +1,82 @@
+//! Human-readable names for ICMP and ICMPv6 type/code pairs.
+
+pub fn describe_v4(icmp_type: u8, code: u8) -> String {
+    let detail = match (icmp_type, code) {
+        (3, 0) => Some("network unreachable"),
+        (3, 1) => Some("host unreachable"),
+        (3, 2) => Some("protocol unreachable"),
+        (3, 3) => Some("port unreachable"),
+        (3, 4) => Some("fragmentation needed"),
+        (3, 5) => Some("source route failed"),
+        (3, 6) => Some("destination network unknown"),
+        (3, 7) => Some("destination host unknown"),
+        (3, 9) | (3, 10) | (3, 13) => Some("administratively prohibited"),
+        (5, 0) => Some("network"),
+        (5, 1) => Some("host"),
+        (11, 0) => Some("TTL exceeded in transit"),
+        (11, 1) => Some("fragment reassembly time exceeded"),
+        _ => None,
+    };
+
+    let name = match icmp_type {
+        0 => "Echo Reply",
+        3 => "Destination Unreachable",
+        4 => "Source Quench",
+        5 => "Redirect",
+        8 => "Echo Request",
+        9 => "Router Advertisement",
+        10 => "Router Solicitation",
+        11 => "Time Exceeded",
+        12 => "Parameter Problem",
+        13 => "Timestamp Request",
+        14 => "Timestamp Reply",
+        _ => return format!("ICMP type {} code {}", icmp_type, code),
+    };
+
+    with_detail(name, detail, code)
+}
+
+pub fn describe_v6(icmp_type: u8, code: u8) -> String {
+    let detail = match (icmp_type, code) {
+        (1, 0) => Some("no route to destination"),
+        (1, 1) => Some("administratively prohibited"),
+        (1, 2) => Some("beyond scope of source address"),
+        (1, 3) => Some("address unreachable"),
+        (1, 4) => Some("port unreachable"),
+        (1, 5) => Some("source address failed policy"),
+        (1, 6) => Some("reject route"),
+        (3, 0) => Some("hop limit exceeded in transit"),
+        (3, 1) => Some("fragment reassembly time exceeded"),
+        _ => None,
+    };
+
+    let name = match icmp_type {
+        1 => "Destination Unreachable",
+        2 => "Packet Too Big",
+        3 => "Time Exceeded",
+        4 => "Parameter Problem",
+        128 => "Echo Request",
+        129 => "Echo Reply",
+        130 => "Multicast Listener Query",
+        131 => "Multicast Listener Report",
+        132 => "Multicast Listener Done",
+        133 => "Router Solicitation",
+        134 => "Router Advertisement",
+        135 => "Neighbor Solicitation",
+        136 => "Neighbor Advertisement",
+        137 => "Redirect",
+        143 => "Multicast Listener Report v2",
+        _ => return format!("ICMPv6 type {} code {}", icmp_type, code),
+    };
+
+    with_detail(name, detail, code)
+}
+
+/// Types whose code isn't covered above only mention it when non-zero
+fn with_detail(name: &str, detail: Option<&str>, code: u8) -> String {
+    match detail {
+        Some(detail) => format!("{} ({})", name, detail),
+        None if code != 0 => format!("{} (code {})", name, code),
+        None => name.to_string(),
+    }
+}
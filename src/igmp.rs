@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+const MEMBERSHIP_QUERY: u8 = 0x11;
+const V1_MEMBERSHIP_REPORT: u8 = 0x12;
+const V2_MEMBERSHIP_REPORT: u8 = 0x16;
+const V2_LEAVE_GROUP: u8 = 0x17;
+const V3_MEMBERSHIP_REPORT: u8 = 0x22;
+
+/// IGMPv3 group record types (RFC 3376 4.2.12)
+const MODE_IS_INCLUDE: u8 = 1;
+const MODE_IS_EXCLUDE: u8 = 2;
+const CHANGE_TO_INCLUDE: u8 = 3;
+const CHANGE_TO_EXCLUDE: u8 = 4;
+const ALLOW_NEW_SOURCES: u8 = 5;
+
+/// An IGMP membership message, v1 to v3.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IgmpInfo {
+    /// "query", "report" or "leave"
+    pub message: String,
+    pub version: u8,
+    /// What a group-specific query asks about; `None` for a general query
+    pub queried: Option<String>,
+    /// Groups the sender reports membership of
+    pub joined: Vec<String>,
+    /// Groups the sender is leaving: v2 leaves, and v3 records that switch
+    /// to receiving from no source
+    pub left: Vec<String>,
+}
+
+impl IgmpInfo {
+    /// e.g. "IGMPv2 join 239.1.1.1"
+    pub fn summary(&self) -> String {
+        let version = format!("IGMPv{}", self.version);
+        match self.message.as_str() {
+            "query" => match self.queried {
+                Some(ref group) => format!("{} membership query for {}", version, group),
+                None => format!("{} general membership query", version),
+            },
+            "leave" => format!("{} leave {}", version, self.left.join(", ")),
+            _ => {
+                let mut parts = Vec::new();
+                if !self.joined.is_empty() {
+                    parts.push(format!("join {}", self.joined.join(", ")));
+                }
+                if !self.left.is_empty() {
+                    parts.push(format!("leave {}", self.left.join(", ")));
+                }
+                if parts.is_empty() {
+                    format!("{} membership report", version)
+                } else {
+                    format!("{} {}", version, parts.join("; "))
+                }
+            }
+        }
+    }
+}
+
+/// Decodes an IGMP message, the payload of an IPv4 packet with protocol 2.
+pub fn parse(payload: &[u8]) -> Option<IgmpInfo> {
+    if payload.len() < 8 {
+        return None;
+    }
+    let group = group_at(payload, 4)?;
+
+    let (message, version, queried, joined, left) = match payload[0] {
+        MEMBERSHIP_QUERY => {
+            // v3 queries are longer; v1 ones leave the response time at zero
+            let version = if payload.len() >= 12 { 3 } else if payload[1] == 0 { 1 } else { 2 };
+            let queried = (!group.is_unspecified()).then(|| group.to_string());
+            ("query", version, queried, Vec::new(), Vec::new())
+        }
+        V1_MEMBERSHIP_REPORT => ("report", 1, None, vec![group.to_string()], Vec::new()),
+        V2_MEMBERSHIP_REPORT => ("report", 2, None, vec![group.to_string()], Vec::new()),
+        V2_LEAVE_GROUP => ("leave", 2, None, Vec::new(), vec![group.to_string()]),
+        V3_MEMBERSHIP_REPORT => {
+            let (joined, left) = v3_records(payload);
+            ("report", 3, None, joined, left)
+        }
+        _ => return None,
+    };
+
+    Some(IgmpInfo { message: message.to_string(), version, queried, joined, left })
+}
+
+/// Joined and left groups from a v3 report's group records. Listening to
+/// some sources counts as joined; excluding none of them (include-nothing)
+/// as left.
+fn v3_records(payload: &[u8]) -> (Vec<String>, Vec<String>) {
+    let (mut joined, mut left) = (Vec::new(), Vec::new());
+    let records = u16::from_be_bytes([payload[6], payload[7]]);
+    let mut offset = 8;
+
+    for _ in 0..records {
+        let Some(record) = payload.get(offset..offset + 8) else {
+            break;
+        };
+        let (record_type, aux_words) = (record[0], record[1] as usize);
+        let sources = u16::from_be_bytes([record[2], record[3]]) as usize;
+        let Some(group) = group_at(record, 4) else {
+            break;
+        };
+
+        match record_type {
+            MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE => joined.push(group.to_string()),
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE | ALLOW_NEW_SOURCES if sources > 0 => joined.push(group.to_string()),
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE => left.push(group.to_string()),
+            // BLOCK_OLD_SOURCES narrows a membership without ending it
+            _ => {}
+        }
+        offset += 8 + sources * 4 + aux_words * 4;
+    }
+
+    (joined, left)
+}
+
+fn group_at(bytes: &[u8], offset: usize) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
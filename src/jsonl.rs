@@ -0,0 +1,74 @@
+//! `--export-jsonl`: one JSON object per line, appended while capturing so
+//! the file survives a killed process and can be followed with `tail -f`.
+
+use serde::Serialize;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::error::{PacketSnifferError, Result};
+use crate::export::ExportWriter;
+
+// Flushing per line costs a syscall per packet; either limit keeps a
+// follower no more than a moment behind
+const FLUSH_EVERY_RECORDS: usize = 100;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct JsonlWriter {
+    path: String,
+    writer: ExportWriter,
+    records: usize,
+    unflushed: usize,
+    last_flush: Instant,
+}
+
+impl JsonlWriter {
+    /// Truncates any existing file, like the other exports.
+    pub fn create(path: &str) -> Result<Self> {
+        let writer = ExportWriter::create(path)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to create JSONL file {}: {}", path, e)))?;
+
+        Ok(JsonlWriter {
+            path: path.to_string(),
+            writer,
+            records: 0,
+            unflushed: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write JSONL record: {}", e)))?;
+        self.writer.write_all(b"\n")
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write JSONL record: {}", e)))?;
+
+        self.records += 1;
+        self.unflushed += 1;
+        if self.unflushed >= FLUSH_EVERY_RECORDS || self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to flush JSONL file: {}", e)))?;
+        self.unflushed = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Completes the file; a gzip stream needs this to be readable to the end.
+    pub fn finish(&mut self) -> Result<()> {
+        self.writer.finish()
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to finish JSONL file: {}", e)))
+    }
+}
@@ -1,74 +1,180 @@
-use log::{error, warn, info, debug};
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use chrono::Utc;
 
+use crate::error::PacketSnifferError;
+
+/// Ordered from least to most verbose, so a message is emitted when its
+/// level is `<=` the configured threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Result<Self, PacketSnifferError> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            other => Err(PacketSnifferError::ConfigError(format!(
+                "Invalid logging.level '{}': expected error, warn, info or debug", other
+            ))),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
 pub struct Logger {
     file_logger: Option<std::fs::File>,
+    file_path: Option<PathBuf>,
+    file_size: u64,
+    max_size_bytes: u64,
+    max_backups: usize,
     console_enabled: bool,
+    level: LogLevel,
 }
 
 impl Logger {
     pub fn new(config: &crate::config::LoggingConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let file_logger = if config.enable_file {
-            if let Some(ref file_path) = config.file {
-                let file = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(file_path)?;
-                Some(file)
-            } else {
-                None
-            }
-        } else {
-            None
+        let level = LogLevel::parse(&config.level)?;
+
+        let file_path = config.file.as_ref().filter(|_| config.enable_file).map(PathBuf::from);
+        let file_logger = match file_path {
+            Some(ref path) => Some(open_log_file(path)?),
+            None => None,
+        };
+        let file_size = match file_logger {
+            Some(ref file) => file.metadata()?.len(),
+            None => 0,
         };
 
         Ok(Logger {
             file_logger,
+            file_path,
+            file_size,
+            max_size_bytes: config.max_size_bytes,
+            max_backups: config.max_backups,
             console_enabled: config.enable_console,
+            level,
         })
     }
 
     pub fn log_error(&mut self, message: &str) {
-        self.write_log("ERROR", message);
+        if !self.enabled(LogLevel::Error) {
+            return;
+        }
+        self.write_log(LogLevel::Error, message);
         if self.console_enabled {
-            eprintln!("❌ {}", message);
+            errln!("❌ {}", message);
         }
     }
 
     pub fn log_warn(&mut self, message: &str) {
-        self.write_log("WARN", message);
+        if !self.enabled(LogLevel::Warn) {
+            return;
+        }
+        self.write_log(LogLevel::Warn, message);
         if self.console_enabled {
-            println!("⚠️  {}", message);
+            outln!("⚠️  {}", message);
         }
     }
 
     pub fn log_info(&mut self, message: &str) {
-        self.write_log("INFO", message);
+        if !self.enabled(LogLevel::Info) {
+            return;
+        }
+        self.write_log(LogLevel::Info, message);
         if self.console_enabled {
-            println!("ℹ️  {}", message);
+            outln!("ℹ️  {}", message);
         }
     }
 
+    #[allow(dead_code)]
     pub fn log_debug(&mut self, message: &str) {
-        self.write_log("DEBUG", message);
+        if !self.enabled(LogLevel::Debug) {
+            return;
+        }
+        self.write_log(LogLevel::Debug, message);
         if self.console_enabled {
-            println!("🔍 {}", message);
+            outln!("🐛 {}", message);
         }
     }
 
-    fn write_log(&mut self, level: &str, message: &str) {
+    fn enabled(&self, level: LogLevel) -> bool {
+        level <= self.level
+    }
+
+    fn write_log(&mut self, level: LogLevel, message: &str) {
+        if self.file_logger.is_none() {
+            return;
+        }
+
+        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f UTC");
+        let log_line = format!("[{}] {} - {}\n", timestamp, level.as_str(), message);
+
+        let line_len = log_line.len() as u64;
+        if self.max_size_bytes > 0 && self.file_size > 0 && self.file_size + line_len > self.max_size_bytes {
+            if let Err(e) = self.rotate() {
+                errln!("Failed to rotate log file: {}", e);
+            }
+        }
+
         if let Some(ref mut file) = self.file_logger {
-            let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f UTC");
-            let log_line = format!("[{}] {} - {}\n", timestamp, level, message);
             if let Err(e) = file.write_all(log_line.as_bytes()) {
-                eprintln!("Failed to write to log file: {}", e);
+                errln!("Failed to write to log file: {}", e);
+            } else {
+                self.file_size += line_len;
             }
             if let Err(e) = file.flush() {
-                eprintln!("Failed to flush log file: {}", e);
+                errln!("Failed to flush log file: {}", e);
+            }
+        }
+    }
+
+    /// Shifts `<file>.N-1` to `<file>.N` down to `<file>` -> `<file>.1`, then
+    /// reopens an empty log. On failure the current file stays open so
+    /// messages keep flowing to it.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.file_path.clone() else {
+            return Ok(());
+        };
+
+        if self.max_backups == 0 {
+            fs::remove_file(&path)?;
+        } else {
+            let backup = |index: usize| {
+                let mut name = path.clone().into_os_string();
+                name.push(format!(".{}", index));
+                PathBuf::from(name)
+            };
+
+            for index in (1..self.max_backups).rev() {
+                let from = backup(index);
+                if from.exists() {
+                    fs::rename(&from, backup(index + 1))?;
+                }
             }
+            fs::rename(&path, backup(1))?;
         }
+
+        // The old handle still points at the renamed file until replaced
+        self.file_logger = Some(open_log_file(&path)?);
+        self.file_size = 0;
+        Ok(())
     }
 
     pub fn log_packet_capture_start(&mut self, interface: &str) {
@@ -86,4 +192,8 @@ impl Logger {
     pub fn log_error_with_context(&mut self, context: &str, error: &dyn std::error::Error) {
         self.log_error(&format!("{}: {}", context, error));
     }
-}
\ No newline at end of file
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<std::fs::File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
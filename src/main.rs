@@ -1,8 +1,52 @@
+#[macro_use]
+mod style;
 mod config;
 mod logger;
 mod error;
+mod bpf;
+mod geoip;
+mod oui;
+mod services;
+mod rdns;
+mod arp;
+mod dns;
+mod dhcp;
+mod icmp;
+mod igmp;
+mod gre;
+mod sctp;
+mod http;
+mod tls;
+mod quic;
+mod decoders;
+mod reassembly;
+mod tcp_sequence;
+mod rtt;
+mod fingerprint;
+mod direction;
+mod detectors;
+mod throttle;
+mod vlan;
+mod filter;
+mod expr;
+mod filter_expr;
+mod fragments;
+mod session;
+mod capture;
+#[cfg(target_os = "linux")]
+mod packet_socket;
+mod pcap;
+mod pipeline;
+mod jsonl;
+mod bandwidth;
+mod sqlite;
+mod api;
+mod webhook;
+mod syslog;
+mod export;
+mod dashboard;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use colored::*;
 use pnet::datalink::{self, NetworkInterface};
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
@@ -10,21 +54,57 @@ use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::tcp::TcpPacket;
 use pnet::packet::udp::UdpPacket;
 use pnet::packet::icmp::IcmpPacket;
+use pnet::packet::icmpv6::Icmpv6Packet;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv6::Ipv6Packet;
 use pnet::packet::Packet;
 use prettytable::{Table, Row, Cell};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use chrono::{DateTime, Utc};
 use std::path::PathBuf;
-use dirs;
 
-use config::Config;
+use config::{parse_networks, parse_port_protocols, Config, FilterSpec, Migration, PerformanceConfig, ThreatConfig, ThreatThresholds, MIN_CAPTURE_BUFFER_SIZE};
+use detectors::{ArpSpoofDetector, PortScanDetector, SynFloodDetector};
+use ipnetwork::IpNetwork;
+use geoip::GeoIpResolver;
+use oui::VendorLookup;
+use rdns::NameResolver;
+use webhook::AlertWebhook;
+use syslog::AlertSyslog;
+use arp::ArpInfo;
+use igmp::IgmpInfo;
+use gre::GreInfo;
+use sctp::SctpInfo;
+use decoders::{AppData, AppDetail, CustomPorts};
+use dhcp::DhcpInfo;
+use dns::DnsAnswer;
+use reassembly::{Segment, StreamSelector, TcpReassembler};
+use tcp_sequence::{SegmentKind, SequenceTracker};
+use rtt::{RttLeg, RttStats};
+use fingerprint::OsGuess;
+use direction::{Direction, LocalAddresses};
+use http::HttpInfo;
+use throttle::{RateLimiter, Sampler};
+use filter::CaptureFilter;
+use capture::{CapturedFrame, KernelDrops};
+use pipeline::{Pipeline, Snapshot};
+use session::Session;
+use jsonl::JsonlWriter;
+use bandwidth::BandwidthWriter;
+use sqlite::SqliteWriter;
+use export::{is_csv_path, ExportPaths, ExportWriter};
 use logger::Logger;
+use style::OutputStyle;
 use error::{PacketSnifferError, Result, handle_error};
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(
     name = "packet_sniffer",
     author = "Packet Sniffer Team",
@@ -33,30 +113,118 @@ use error::{PacketSnifferError, Result, handle_error};
     long_about = "A powerful, user-friendly network packet analyzer that captures and analyzes network traffic in real-time. Designed to make network analysis accessible to both technical experts and everyday users."
 )]
 struct Args {
-    /// Network interface to sniff on
-    #[arg(short, long)]
-    interface: Option<String>,
+    /// Network interface to sniff on; repeat or comma-separate to capture on several at once
+    #[arg(short, long, value_delimiter = ',')]
+    interface: Vec<String>,
     
-    /// Filter by protocol (tcp, udp, icmp, http, dns)
-    #[arg(short, long)]
-    protocol: Option<String>,
+    /// Read a pcap stream from standard input instead of an interface, e.g. ssh host tcpdump -U -w - | packet_sniffer --read-stdin
+    #[arg(long, conflicts_with_all = ["interface", "promiscuous"])]
+    read_stdin: bool,
+    
+    /// Filter by protocol (tcp, udp, sctp, icmp, icmp6, igmp, http, dns, arp); repeat or comma-separate to match any of several
+    #[arg(short, long, value_delimiter = ',')]
+    protocol: Vec<String>,
+    
+    /// Filter by port number; repeat or comma-separate to match any of several
+    #[arg(short = 'P', long, value_delimiter = ',')]
+    port: Vec<u16>,
+    
+    /// Only capture packets from these source IPs (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    src_ip: Vec<IpAddr>,
+    
+    /// Only capture packets to these destination IPs (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    dst_ip: Vec<IpAddr>,
+    
+    /// Only capture packets to or from these IPs (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    host: Vec<IpAddr>,
+    
+    /// Only capture packets tagged with these 802.1Q VLAN IDs (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    vlan: Vec<u16>,
+    
+    /// Only capture packets to or from these CIDR networks, e.g. 10.0.0.0/24 (repeatable)
+    #[arg(long, value_delimiter = ',')]
+    subnet: Vec<String>,
+    
+    /// Drop packets of these protocols, even if other filters match them (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    exclude_protocol: Vec<String>,
+    
+    /// Drop packets to or from these IPs (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    exclude_host: Vec<IpAddr>,
+    
+    /// Drop packets to or from these ports (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    exclude_port: Vec<u16>,
+    
+    /// Only capture traffic to this host (in) or from it (out), judged by the capture interfaces' addresses
+    #[arg(long, value_enum)]
+    direction: Option<DirectionFilter>,
+    
+    /// Only capture frames of at least this many bytes
+    #[arg(long, value_name = "BYTES")]
+    min_size: Option<usize>,
+    
+    /// Only capture frames of at most this many bytes
+    #[arg(long, value_name = "BYTES")]
+    max_size: Option<usize>,
+    
+    /// Only capture TCP packets with these flags set (FIN, SYN, RST, PSH, ACK, URG, ECE, CWR; comma-separated)
+    #[arg(long, value_delimiter = ',', value_name = "FLAGS")]
+    tcp_flags: Vec<String>,
     
-    /// Filter by port number
-    #[arg(short = 'P', long)]
-    port: Option<u16>,
+    /// How --tcp-flags compares: exact needs exactly those flags, superset allows others alongside them
+    #[arg(long, value_enum, default_value = "exact", requires = "tcp_flags")]
+    tcp_flags_match: TcpFlagMatch,
+    
+    /// Only capture packets whose payload (the first 2048 bytes, as lossy UTF-8) matches this regex, e.g. "Host: .*example\.com"
+    #[arg(long, value_name = "REGEX")]
+    payload_match: Option<String>,
+    
+    /// Capture filter in pcap/BPF syntax, e.g. "tcp port 443 and host 10.0.0.5"
+    #[arg(long)]
+    bpf: Option<String>,
+    
+    /// Filter expression combining protocols, hosts, nets, ports, sizes and TCP flags with and/or/not, e.g. "tcp and (port 80 or port 443) and not host 10.0.0.1"
+    #[arg(long, value_name = "EXPR")]
+    filter: Option<String>,
+    
+    /// Apply the filters saved under this name in the config's filter_presets; filter flags given as well replace the preset's value for that filter
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
     
     /// Number of packets to capture (0 = unlimited)
     #[arg(short, long, default_value = "0")]
     count: usize,
     
+    /// Stop capturing after N seconds; with --count, whichever limit is hit first wins
+    #[arg(long)]
+    duration: Option<u64>,
+    
+    /// Analyze only one in every N packets that pass the filters, scaling the packet and byte totals by N; port scan, SYN flood and ARP spoofing detection are off, as they need every packet
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    sample: Option<usize>,
+    
     /// Show available network interfaces
     #[arg(short, long)]
     list_interfaces: bool,
     
+    /// Print the available interfaces as a JSON array on stdout, for scripts
+    #[arg(long, conflicts_with = "list_interfaces")]
+    list_interfaces_json: bool,
+    
     /// Enable interactive dashboard mode
     #[arg(short, long)]
     dashboard: bool,
     
+    /// Rank top talkers in the dashboard by bytes or packets
+    #[arg(long, value_enum, default_value = "bytes")]
+    sort_talkers: TalkerSort,
+    
     /// Export captured data to JSON file
     #[arg(long)]
     export_json: Option<String>,
@@ -65,44 +233,227 @@ struct Args {
     #[arg(long)]
     export_csv: Option<String>,
     
+    /// Export the aggregated connection flows; CSV for a .csv path, JSON otherwise
+    #[arg(long)]
+    export_flows: Option<String>,
+    
+    /// Export threat alerts; CSV for a .csv path, JSON otherwise
+    #[arg(long)]
+    export_alerts: Option<String>,
+    
+    /// Print nothing per packet, and write a JSON summary of the capture (totals, protocols, top talkers, connections, alert counts) to PATH when it ends
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["dashboard", "verbose", "hex_dump", "format"])]
+    summary_only: Option<String>,
+    
+    /// Save the accumulated stats to PATH every 30 seconds and when capture ends, for --load-session to resume from
+    #[arg(long, value_name = "PATH")]
+    save_session: Option<String>,
+    
+    /// Include the retained packets in the saved session, for the exports and packet views of the resuming run
+    #[arg(long, requires = "save_session")]
+    session_packets: bool,
+    
+    /// Resume a session saved with --save-session: its totals carry on, with new packets added to them
+    #[arg(long, value_name = "PATH")]
+    load_session: Option<String>,
+    
+    /// Stream each packet to a JSON Lines file as it is captured
+    #[arg(long)]
+    export_jsonl: Option<String>,
+    
+    /// Insert each packet into a `packets` table in this SQLite database as it is captured
+    #[arg(long)]
+    export_sqlite: Option<String>,
+    
+    /// After capture, print the reassembled payload of TCP streams: a stream number, or ADDR:PORT ([ADDR]:PORT for IPv6) to follow every stream with that endpoint
+    #[arg(long, value_name = "STREAM", conflicts_with = "quiet")]
+    follow_stream: Option<StreamSelector>,
+    
     /// Show detailed packet analysis
     #[arg(short, long)]
     verbose: bool,
     
+    /// Print a hex + ASCII dump of each captured frame
+    #[arg(long)]
+    hex_dump: bool,
+    
+    /// Print only one line per packet on stdout: no banners, stats or summary
+    #[arg(short, long, conflicts_with_all = ["dashboard", "verbose", "hex_dump"])]
+    quiet: bool,
+    
+    /// Per-packet output format; ndjson prints one JSON object per line and implies --quiet
+    #[arg(long, value_enum, default_value = "text", conflicts_with_all = ["dashboard", "verbose", "hex_dump"])]
+    format: OutputFormat,
+    
+    /// Maximum bytes shown per frame by --hex-dump
+    #[arg(long, default_value = "64")]
+    hex_dump_bytes: usize,
+    
+    /// Put the interfaces in promiscuous mode, so frames for other hosts on the segment are seen too; a switch still only forwards those to a SPAN/mirror port
+    #[arg(long)]
+    promiscuous: bool,
+    
+    /// Show reverse-DNS names next to IPs; lookups run in the background and generate DNS traffic of their own
+    #[arg(long)]
+    resolve_names: bool,
+    
     /// Show statistics summary every N seconds
     #[arg(long, default_value = "10")]
     stats_interval: u64,
     
-    /// Configuration file path (default: ~/.config/packet_sniffer/config.json)
+    /// Serve live stats as JSON on 127.0.0.1:PORT: /stats, /connections, /alerts and /packets?limit=N
+    #[arg(long, value_name = "PORT")]
+    api_port: Option<u16>,
+    
+    /// Let pages from this origin read the API from a browser, e.g. "http://localhost:3000"; by default browsers keep other sites from reading the capture
+    #[arg(long, value_name = "ORIGIN", requires = "api_port", value_parser = api::parse_origin)]
+    api_cors_origin: Option<String>,
+    
+    /// Configuration file path, TOML for a .toml path and JSON otherwise (default: ~/.config/packet_sniffer/config.json)
     #[arg(long)]
     config: Option<PathBuf>,
     
+    /// Log level for this run; overrides PKTSNIFF_LOG_LEVEL, which overrides logging.level in the config file
+    #[arg(long, value_parser = ["error", "warn", "info", "debug"])]
+    log_level: Option<String>,
+    
+    /// Append one CSV row per second of capture: timestamp, bytes_per_sec, packets_per_sec
+    #[arg(long)]
+    export_bandwidth: Option<String>,
+    
+    /// Gzip JSON, CSV and JSONL exports, adding .gz to their paths (a .gz path compresses without it)
+    #[arg(long)]
+    compress: bool,
+    
+    /// Directory that relative export paths are written under; overrides PKTSNIFF_EXPORT_DIR, which overrides export.default_directory in the config file
+    #[arg(long)]
+    export_dir: Option<String>,
+    
     /// Generate default configuration file and exit
     #[arg(long)]
     generate_config: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum TalkerSort {
+    Bytes,
+    Packets,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum DirectionFilter {
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum TcpFlagMatch {
+    Exact,
+    Superset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Ndjson,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct PacketInfo {
+    #[serde(serialize_with = "style::serialize_time")]
     timestamp: DateTime<Utc>,
     packet_number: usize,
+    interface: String,
     src_mac: String,
     dst_mac: String,
+    src_vendor: Option<String>,
+    dst_vendor: Option<String>,
     src_ip: Option<String>,
     dst_ip: Option<String>,
+    /// IPv4 TTL or IPv6 hop limit
+    ttl: Option<u8>,
+    /// Relative to this host; `None` without both addresses
+    direction: Option<Direction>,
     protocol: String,
     src_port: Option<u16>,
     dst_port: Option<u16>,
     packet_size: usize,
     flags: Option<String>,
+    tcp_window: Option<u16>,
+    tcp_sequence: Option<u32>,
+    /// Only when the ACK flag is set
+    tcp_ack: Option<u32>,
     payload_size: usize,
     application_protocol: Option<String>,
+    dns_query: Option<String>,
+    /// Addresses a DNS response gave, under the names looked up
+    #[serde(default)]
+    dns_answers: Vec<DnsAnswer>,
+    http_info: Option<HttpInfo>,
+    tls_sni: Option<String>,
+    arp: Option<ArpInfo>,
+    igmp: Option<IgmpInfo>,
+    #[serde(default)]
+    sctp: Option<SctpInfo>,
+    /// Set for packets that came through a GRE tunnel; the other fields
+    /// describe the packet inside it
+    gre: Option<GreInfo>,
+    dhcp: Option<DhcpInfo>,
+    vlan_id: Option<u16>,
+    icmp_type: Option<u8>,
+    icmp_code: Option<u8>,
     description: String,
     threat_level: ThreatLevel,
     geo_info: Option<GeoInfo>,
 }
 
+impl PacketInfo {
+    /// Nothing decoded yet. `packet_number` stays 0 until `NetworkStats::record`
+    /// numbers the packet.
+    fn new(timestamp: DateTime<Utc>, interface: &str, packet_size: usize) -> Self {
+        PacketInfo {
+            timestamp,
+            packet_number: 0,
+            interface: interface.to_string(),
+            src_mac: String::new(),
+            dst_mac: String::new(),
+            src_vendor: None,
+            dst_vendor: None,
+            src_ip: None,
+            dst_ip: None,
+            ttl: None,
+            direction: None,
+            protocol: "Unknown".to_string(),
+            src_port: None,
+            dst_port: None,
+            packet_size,
+            flags: None,
+            tcp_window: None,
+            tcp_sequence: None,
+            tcp_ack: None,
+            payload_size: 0,
+            application_protocol: None,
+            dns_query: None,
+            dns_answers: Vec::new(),
+            http_info: None,
+            tls_sni: None,
+            arp: None,
+            igmp: None,
+            sctp: None,
+            gre: None,
+            dhcp: None,
+            vlan_id: None,
+            icmp_type: None,
+            icmp_code: None,
+            description: "Unknown packet".to_string(),
+            threat_level: ThreatLevel::Safe,
+            geo_info: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
 enum ThreatLevel {
     Safe,
     Low,
@@ -111,6 +462,19 @@ enum ThreatLevel {
     Critical,
 }
 
+impl ThreatLevel {
+    /// Same spelling as the serialized form, for CSV and SQLite columns
+    fn as_str(&self) -> &'static str {
+        match self {
+            ThreatLevel::Safe => "safe",
+            ThreatLevel::Low => "low",
+            ThreatLevel::Medium => "medium",
+            ThreatLevel::High => "high",
+            ThreatLevel::Critical => "critical",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct GeoInfo {
     country: Option<String>,
@@ -119,7 +483,7 @@ struct GeoInfo {
     longitude: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ConnectionFlow {
     src_ip: String,
     dst_ip: String,
@@ -128,963 +492,1721 @@ struct ConnectionFlow {
     protocol: String,
     packet_count: usize,
     total_bytes: usize,
+    /// Bytes sent by `src_ip`, the side seen first
+    bytes_from_src: usize,
+    bytes_from_dst: usize,
+    #[serde(serialize_with = "style::serialize_time")]
     first_seen: DateTime<Utc>,
+    #[serde(serialize_with = "style::serialize_time")]
     last_seen: DateTime<Utc>,
     threat_level: ThreatLevel,
+    handshake_state: HandshakeState,
+    /// TCP segments repeating data already seen in their direction
+    retransmissions: usize,
+    /// TCP segments filling a gap left by later data
+    out_of_order: usize,
+    #[serde(skip)]
+    sequence_from_src: SequenceTracker,
+    #[serde(skip)]
+    sequence_from_dst: SequenceTracker,
+    /// Segments from `src_ip` time the leg between the capture point and
+    /// `dst_ip`, and the other way round
+    #[serde(skip)]
+    rtt_to_dst: RttLeg,
+    #[serde(skip)]
+    rtt_to_src: RttLeg,
+}
+
+/// Connections let go after `performance.connection_idle_timeout_secs`
+/// without a packet, reduced to what the duration figures need.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ClosedConnections {
+    count: usize,
+    total_duration_secs: f64,
+    longest_secs: f64,
+}
+
+impl ClosedConnections {
+    fn add(&mut self, flow: &ConnectionFlow) {
+        let duration = flow.duration_secs();
+        self.count += 1;
+        self.total_duration_secs += duration;
+        self.longest_secs = self.longest_secs.max(duration);
+    }
+}
+
+impl ConnectionFlow {
+    fn duration_secs(&self) -> f64 {
+        (self.last_seen - self.first_seen).num_milliseconds() as f64 / 1000.0
+    }
+    
+    /// End-to-end TCP round trip, once either leg has been timed
+    fn rtt(&self) -> Option<RttStats> {
+        RttStats::end_to_end(self.rtt_to_dst.stats.as_ref(), self.rtt_to_src.stats.as_ref())
+    }
+    
+    fn avg_bytes_per_packet(&self) -> f64 {
+        self.total_bytes as f64 / self.packet_count.max(1) as f64
+    }
+}
+
+/// Orders the endpoints so both directions of a conversation get the same key.
+fn connection_key(protocol: &str, src_ip: &str, src_port: Option<u16>, dst_ip: &str, dst_port: Option<u16>) -> String {
+    let src = format!("{}:{}", src_ip, src_port.unwrap_or(0));
+    let dst = format!("{}:{}", dst_ip, dst_port.unwrap_or(0));
+    
+    if src <= dst {
+        format!("{} {}-{}", protocol, src, dst)
+    } else {
+        format!("{} {}-{}", protocol, dst, src)
+    }
+}
+
+/// TCP handshake progress as seen on the wire; non-TCP flows stay `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum HandshakeState {
+    None,
+    SynSent,
+    SynReceived,
+    Established,
+    Closed,
 }
 
-#[derive(Debug, Clone)]
+/// Traffic during the one second of capture starting at `timestamp`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct BandwidthPoint {
     timestamp: DateTime<Utc>,
     bytes_per_sec: f64,
     packets_per_sec: f64,
+    /// The outbound and inbound part of `bytes_per_sec`
+    upload_bytes_per_sec: f64,
+    download_bytes_per_sec: f64,
+}
+
+/// Traffic attributed to one host, as sender or receiver, to one interface, or
+/// to one direction.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TalkerStats {
+    packets: usize,
+    bytes: usize,
+}
+
+/// A multicast group: the hosts whose IGMP reports put them in it, and the
+/// traffic sent to it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct MulticastGroup {
+    members: BTreeSet<String>,
+    traffic: TalkerStats,
 }
 
-#[derive(Debug, Clone)]
+/// Most recent packet sizes kept for the size breakdown and percentiles
+const PACKET_SIZE_WINDOW: usize = 1000;
+
+/// How far back "new hosts" looks
+const NEW_HOST_WINDOW_SECS: i64 = 60;
+
+/// Addresses the passive DNS map holds, the same bound as the reverse-DNS
+/// cache; past it the address learned earliest is forgotten
+const MAX_DNS_NAMES: usize = 10_000;
+
+/// Serializable for `--save-session`. Detectors, thresholds and alert
+/// outputs are skipped: they come from the configuration of the run that
+/// resumes the session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct NetworkStats {
     total_packets: usize,
     total_bytes: usize,
     protocol_counts: HashMap<String, usize>,
-    top_talkers: HashMap<String, usize>,
+    /// Frame bytes per protocol, keyed like `protocol_counts`
+    protocol_bytes: HashMap<String, usize>,
+    top_talkers: HashMap<String, TalkerStats>,
+    top_receivers: HashMap<String, TalkerStats>,
+    /// Best OS guess per source address, from `fingerprint`
+    os_guesses: HashMap<String, OsGuess>,
+    /// When each host was first seen, as source or destination, for the last
+    /// `NEW_HOST_WINDOW_SECS`. The distinct counts are the talker maps' sizes.
+    new_hosts: VecDeque<DateTime<Utc>>,
+    interface_traffic: HashMap<String, TalkerStats>,
+    /// Keyed by group address; joins and leaves come from IGMP, traffic from
+    /// packets to the group
+    multicast_groups: HashMap<String, MulticastGroup>,
+    /// Outbound and inbound traffic; transit and local traffic is in neither
+    uploaded: TalkerStats,
+    downloaded: TalkerStats,
+    /// IP packets with no direction, because the input had no local addresses
+    /// to judge by
+    #[serde(default)]
+    undirected_packets: usize,
+    /// Rebuilt from `started_at` when a saved session is resumed
+    #[serde(skip, default = "Instant::now")]
     start_time: Instant,
-    bandwidth_history: Vec<BandwidthPoint>,
+    /// Wall-clock time of `start_time`, for timestamping bandwidth points; the
+    /// first packet's timestamp on the packet clock
+    started_at: DateTime<Utc>,
+    /// Set for a pcap stream: time is read from packet timestamps rather than
+    /// the wall clock, since a replay takes far less time than the capture did
+    #[serde(skip)]
+    packet_clock: bool,
+    /// Latest packet timestamp, which is "now" on the packet clock
+    #[serde(default)]
+    last_packet_at: Option<DateTime<Utc>>,
+    bandwidth_history: VecDeque<BandwidthPoint>,
+    /// Bandwidth points and threat alerts kept, from `PerformanceConfig`
+    #[serde(skip)]
+    max_bandwidth_points: usize,
+    #[serde(skip)]
+    max_alerts: usize,
+    /// Second of capture being accumulated, and its traffic so far
+    bandwidth_second: u64,
+    second_bytes: usize,
+    second_packets: usize,
+    second_uploaded: usize,
+    second_downloaded: usize,
+    /// Active connections; idle ones move to `closed_connections`
     connections: HashMap<String, ConnectionFlow>,
-    threat_alerts: Vec<(DateTime<Utc>, String, ThreatLevel)>,
+    #[serde(default)]
+    closed_connections: ClosedConnections,
+    /// From `PerformanceConfig`; `None` keeps connections indefinitely
+    #[serde(skip)]
+    connection_idle_timeout: Option<chrono::Duration>,
+    #[serde(skip)]
+    last_idle_sweep: Option<DateTime<Utc>>,
+    threat_alerts: VecDeque<(DateTime<Utc>, String, ThreatLevel)>,
+    /// Every alert raised, by level, including those `threat_alerts` no
+    /// longer retains
+    alert_counts: HashMap<String, usize>,
     port_activity: HashMap<u16, usize>,
-    packet_sizes: Vec<usize>,
+    /// Requests per HTTP `Host`, and TLS handshakes per SNI name: which sites
+    /// are being visited, by name rather than address
+    #[serde(default)]
+    http_hosts: HashMap<String, usize>,
+    #[serde(default)]
+    tls_server_names: HashMap<String, usize>,
+    /// Passive DNS: the name each address was last given as an answer for,
+    /// learned from the DNS responses captured
+    #[serde(default)]
+    dns_names: HashMap<String, String>,
+    /// The addresses in `dns_names`, in the order they were first learned
+    #[serde(default)]
+    dns_order: VecDeque<String>,
+    packet_sizes: VecDeque<usize>,
     current_connections: usize,
+    /// Highest rolling rates seen, so a short burst still registers
     peak_bandwidth: f64,
     peak_packets_per_sec: f64,
+    /// Seconds in the rolling rates, at most `max_bandwidth_points`
+    #[serde(skip)]
+    rolling_window_secs: u64,
+    #[serde(skip)]
+    port_scans: PortScanDetector,
+    #[serde(skip)]
+    syn_floods: SynFloodDetector,
+    #[serde(skip)]
+    arp_watch: ArpSpoofDetector,
+    dropped_packets: usize,
+    /// Drops on the capture interfaces reported by the kernel; `None` where unavailable
+    kernel_dropped: Option<u64>,
+    /// Frames the dashboard pipeline's queue had no room for
+    capture_overflow: usize,
+    /// Where new alerts are also posted, from `alerts.webhook_url`
+    #[serde(skip)]
+    alert_webhook: Option<AlertWebhook>,
+    /// Set with `alerts.syslog`
+    #[serde(skip)]
+    alert_syslog: Option<AlertSyslog>,
+    /// `--sample`'s N: one packet in this many was recorded. Saved with the
+    /// session, since it scales every total the session carries
+    sample_rate: Option<usize>,
 }
 
-fn main() {
-    // Initialize environment logger
-    env_logger::init();
-    
-    let args = Args::parse();
-    
-    // Handle configuration generation
-    if args.generate_config {
-        generate_default_config(&args);
-        return;
-    }
-    
-    // Load configuration
-    let config = load_configuration(&args).unwrap_or_else(|e| {
-        handle_error(&e);
-    });
-    
-    // Initialize logger
-    let mut logger = Logger::new(&config.logging).unwrap_or_else(|e| {
-        eprintln!("Failed to initialize logger: {}", e);
-        std::process::exit(1);
-    });
-    
-    logger.log_info("Starting Advanced Network Packet Sniffer v1.0.0");
-    
-    if args.list_interfaces {
-        list_interfaces(&config, &mut logger);
-        return;
+impl NetworkStats {
+    fn new(threat: &ThreatConfig, performance: &PerformanceConfig) -> Self {
+        // The current second's rate is worked out from the history, so keep at least one
+        let max_bandwidth_points = performance.max_bandwidth_points.max(1);
+        NetworkStats {
+            total_packets: 0,
+            total_bytes: 0,
+            protocol_counts: HashMap::new(),
+            protocol_bytes: HashMap::new(),
+            top_talkers: HashMap::new(),
+            top_receivers: HashMap::new(),
+            os_guesses: HashMap::new(),
+            new_hosts: VecDeque::new(),
+            interface_traffic: HashMap::new(),
+            multicast_groups: HashMap::new(),
+            uploaded: TalkerStats::default(),
+            downloaded: TalkerStats::default(),
+            undirected_packets: 0,
+            start_time: Instant::now(),
+            started_at: Utc::now(),
+            packet_clock: false,
+            last_packet_at: None,
+            bandwidth_history: VecDeque::new(),
+            max_bandwidth_points,
+            max_alerts: performance.max_alerts,
+            bandwidth_second: 0,
+            second_bytes: 0,
+            second_packets: 0,
+            second_uploaded: 0,
+            second_downloaded: 0,
+            connections: HashMap::new(),
+            closed_connections: ClosedConnections::default(),
+            connection_idle_timeout: (performance.connection_idle_timeout_secs > 0)
+                .then(|| chrono::Duration::seconds(performance.connection_idle_timeout_secs as i64)),
+            last_idle_sweep: None,
+            threat_alerts: VecDeque::new(),
+            alert_counts: HashMap::new(),
+            port_activity: HashMap::new(),
+            http_hosts: HashMap::new(),
+            tls_server_names: HashMap::new(),
+            dns_names: HashMap::new(),
+            dns_order: VecDeque::new(),
+            packet_sizes: VecDeque::new(),
+            current_connections: 0,
+            peak_bandwidth: 0.0,
+            peak_packets_per_sec: 0.0,
+            rolling_window_secs: performance.rolling_window_secs.clamp(1, max_bandwidth_points as u64),
+            port_scans: PortScanDetector::new(threat.port_scan_window_secs, threat.port_scan_threshold),
+            syn_floods: SynFloodDetector::new(threat.syn_flood_window_secs, threat.syn_flood_threshold),
+            arp_watch: ArpSpoofDetector::new(threat.arp_window_secs, threat.arp_gratuitous_threshold),
+            dropped_packets: 0,
+            kernel_dropped: None,
+            capture_overflow: 0,
+            alert_webhook: None,
+            alert_syslog: None,
+            sample_rate: None,
+        }
     }
     
-    let interface_name = match &args.interface {
-        Some(name) => name.clone(),
-        None => {
-            let error = PacketSnifferError::InterfaceNotFound("No interface specified".to_string());
-            logger.log_error_with_context("Interface selection", &error);
-            handle_error(&error);
+    /// Stats saved by `--save-session`, carried on with this run's thresholds
+    /// and limits in place of the skipped fields.
+    fn resume(saved: NetworkStats, threat: &ThreatConfig, performance: &PerformanceConfig) -> Self {
+        let fresh = NetworkStats::new(threat, performance);
+        let mut stats = NetworkStats {
+            max_bandwidth_points: fresh.max_bandwidth_points,
+            max_alerts: fresh.max_alerts,
+            rolling_window_secs: fresh.rolling_window_secs,
+            connection_idle_timeout: fresh.connection_idle_timeout,
+            port_scans: fresh.port_scans,
+            syn_floods: fresh.syn_floods,
+            arp_watch: fresh.arp_watch,
+            ..saved
+        };
+        
+        // Backdate the clock to the wall-clock anchor, so the time between
+        // runs reads as idle seconds rather than shifting the history. A
+        // session on the packet clock carries on from its last packet instead.
+        let since_start = (Utc::now() - stats.started_at).to_std().unwrap_or_default();
+        match fresh.start_time.checked_sub(since_start) {
+            _ if stats.last_packet_at.is_some() => {}
+            Some(start_time) => stats.start_time = start_time,
+            None => {
+                // Further back than this clock goes (a reboot since), so restart it
+                stats.push_bandwidth_point(stats.current_bandwidth_point());
+                stats.start_time = fresh.start_time;
+                stats.started_at = fresh.started_at;
+                stats.bandwidth_second = 0;
+                stats.second_bytes = 0;
+                stats.second_packets = 0;
+                stats.second_uploaded = 0;
+                stats.second_downloaded = 0;
+            }
         }
-    };
-    
-    let interface = match find_interface(&interface_name) {
-        Some(iface) => iface,
-        None => {
-            let error = PacketSnifferError::InterfaceNotFound(interface_name);
-            logger.log_error_with_context("Interface discovery", &error);
-            handle_error(&error);
+        
+        // This run's limits may be lower than the saving run's
+        while stats.bandwidth_history.len() > stats.max_bandwidth_points {
+            stats.bandwidth_history.pop_front();
         }
-    };
-    
-    // Validate protocol filter
-    if let Some(ref protocol) = args.protocol {
-        if !is_valid_protocol(protocol) {
-            let error = PacketSnifferError::InvalidFilter(protocol.clone());
-            logger.log_error_with_context("Protocol filter validation", &error);
-            handle_error(&error);
+        while stats.threat_alerts.len() > stats.max_alerts {
+            stats.threat_alerts.pop_front();
         }
+        stats
     }
     
-    logger.log_packet_capture_start(&interface.name);
+    /// The current time on the capture's clock: the wall clock live, the
+    /// latest packet's timestamp for a pcap stream.
+    fn now(&self) -> DateTime<Utc> {
+        if self.packet_clock {
+            self.last_packet_at.unwrap_or(self.started_at)
+        } else {
+            Utc::now()
+        }
+    }
     
-    let result = if args.dashboard {
-        start_dashboard_mode(interface, args, config, logger)
-    } else {
-        start_sniffing(interface, args, config, logger)
-    };
+    /// How long the capture has run on its clock. Durations, rates and the
+    /// bandwidth history all go by this, as the idle sweep goes by `now`.
+    fn elapsed(&self) -> Duration {
+        if self.packet_clock {
+            (self.now() - self.started_at).to_std().unwrap_or_default()
+        } else {
+            self.start_time.elapsed()
+        }
+    }
     
-    if let Err(e) = result {
-        handle_error(&e);
+    /// `count` per second of `elapsed`; 0 before a second has passed on the
+    /// packet clock, as for a stream of one packet.
+    fn per_second(&self, count: usize) -> f64 {
+        let elapsed = self.elapsed().as_secs_f64();
+        if elapsed > 0.0 { count as f64 / elapsed } else { 0.0 }
     }
-}
-
-fn generate_default_config(args: &Args) -> ! {
-    let config_path = get_config_path(args);
     
-    match Config::default().save(&config_path) {
-        Ok(_) => {
-            println!("✅ Default configuration generated at: {}", config_path.display());
-            println!("💡 You can now edit this file to customize the packet sniffer behavior.");
-            std::process::exit(0);
+    /// Moves the packet clock to `timestamp`. The first packet starts it, so
+    /// time before the stream's first packet isn't counted.
+    fn advance_packet_clock(&mut self, timestamp: DateTime<Utc>) {
+        if !self.packet_clock {
+            return;
         }
-        Err(e) => {
-            eprintln!("❌ Failed to generate configuration: {}", e);
-            std::process::exit(1);
+        if self.last_packet_at.is_none() {
+            self.started_at = timestamp;
         }
+        // Records can be slightly out of order across capture interfaces
+        self.last_packet_at = Some(self.last_packet_at.map_or(timestamp, |last| last.max(timestamp)));
     }
-}
-
-fn load_configuration(args: &Args) -> Result<Config> {
-    let config_path = get_config_path(args);
     
-    Config::load_or_create(config_path).map_err(|e| {
-        PacketSnifferError::ConfigError(format!("Failed to load configuration: {}", e))
-    })
-}
-
-fn get_config_path(args: &Args) -> PathBuf {
-    if let Some(ref path) = args.config {
-        path.clone()
-    } else {
-        let config_dir = dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("packet_sniffer");
-        config_dir.join("config.json")
+    /// What recorded packet and byte counts are multiplied by to estimate the
+    /// link's, 1 unless sampling.
+    fn sample_scale(&self) -> usize {
+        self.sample_rate.unwrap_or(1)
     }
-}
-
-fn is_valid_protocol(protocol: &str) -> bool {
-    matches!(protocol.to_lowercase().as_str(), "tcp" | "udp" | "icmp" | "http" | "dns")
-}
-
-fn list_interfaces(config: &Config, logger: &mut Logger) {
-    println!("{}", "🌐 Available Network Interfaces:".green().bold());
-    println!();
     
-    let mut table = Table::new();
-    table.add_row(Row::new(vec![
-        Cell::new("Interface").style_spec("Fb"),
-        Cell::new("Description").style_spec("Fb"),
-        Cell::new("IP Addresses").style_spec("Fb"),
-        Cell::new("Status").style_spec("Fb"),
-    ]));
+    /// " (sampled 1:N)" after totals scaled by `sample_scale`, empty otherwise.
+    fn sampling_note(&self) -> String {
+        self.sample_rate.map_or(String::new(), |rate| format!(" (sampled 1:{})", rate))
+    }
     
-    for interface in datalink::interfaces() {
-        let ips = interface.ips.iter()
-            .map(|ip| ip.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
-        
-        let status = if interface.is_up() { "UP".green() } else { "DOWN".red() };
+    /// Moves connections idle for longer than `connection_idle_timeout` into
+    /// `closed_connections`, at most once a second. `now` is on the capture's
+    /// clock, the one durations and rates use too.
+    fn close_idle_connections(&mut self, now: DateTime<Utc>) {
+        let Some(timeout) = self.connection_idle_timeout else {
+            return;
+        };
+        if self.last_idle_sweep.is_some_and(|last| now - last < chrono::Duration::seconds(1)) {
+            return;
+        }
+        self.last_idle_sweep = Some(now);
         
-        table.add_row(Row::new(vec![
-            Cell::new(&interface.name),
-            Cell::new(&interface.description),
-            Cell::new(&ips),
-            Cell::new(&status.to_string()),
-        ]));
+        let closed = &mut self.closed_connections;
+        self.connections.retain(|_, flow| {
+            let idle = now - flow.last_seen > timeout;
+            if idle {
+                closed.add(flow);
+            }
+            !idle
+        });
     }
     
-    table.printstd();
-    println!();
-    println!("{}", "💡 Usage example:".yellow().bold());
-    println!("{}", "  sudo cargo run -- --interface eth0 --dashboard".cyan());
-    println!("{}", "  sudo cargo run -- --interface wlan0 --protocol http --verbose".cyan());
-}
-
-fn find_interface(name: &str) -> Option<NetworkInterface> {
-    datalink::interfaces()
-        .into_iter()
-        .find(|iface| iface.name == name)
-}
-
-fn start_dashboard_mode(interface: NetworkInterface, args: Args, config: Config, mut logger: Logger) -> Result<()> {
-    println!("{}", "🚀 Starting Interactive Dashboard Mode".green().bold());
-    println!("{}", format!("📡 Interface: {}", interface.name).cyan());
-    println!("{}", "Press Ctrl+C to stop".yellow());
-    println!();
-    
-    let stats = Arc::new(Mutex::new(NetworkStats {
-        total_packets: 0,
-        total_bytes: 0,
-        protocol_counts: HashMap::new(),
-        top_talkers: HashMap::new(),
-        start_time: Instant::now(),
-        bandwidth_history: Vec::new(),
-        connections: HashMap::new(),
-        threat_alerts: Vec::new(),
-        port_activity: HashMap::new(),
-        packet_sizes: Vec::new(),
-        current_connections: 0,
-        peak_bandwidth: 0.0,
-        peak_packets_per_sec: 0.0,
-    }));
-    
-    let captured_packets = Arc::new(Mutex::new(Vec::<PacketInfo>::new()));
-    
-    // Start packet capture in a separate thread
-    let stats_clone = stats.clone();
-    let captured_clone = captured_packets.clone();
-    
-    std::thread::spawn(move || {
-        capture_packets_with_stats(interface, args, stats_clone, captured_clone);
-    });
+    /// Average and longest connection duration in seconds, closed and
+    /// active connections alike; `None` before the first connection.
+    fn connection_durations(&self) -> Option<(f64, f64)> {
+        let count = self.closed_connections.count + self.connections.len();
+        if count == 0 {
+            return None;
+        }
+        let (total, longest) = self.connections.values()
+            .map(ConnectionFlow::duration_secs)
+            .fold((self.closed_connections.total_duration_secs, self.closed_connections.longest_secs),
+                  |(total, longest), duration| (total + duration, longest.max(duration)));
+        Some((total / count as f64, longest))
+    }
     
-    // Display dashboard updates
-    loop {
-        std::thread::sleep(std::time::Duration::from_secs(1));
-        
-        print!("\x1B[2J\x1B[1;1H"); // Clear screen and move cursor to top
-        display_dashboard(&stats, &captured_packets);
+    /// Hostnames by HTTP requests plus TLS handshakes, busiest first, with
+    /// each count.
+    fn top_web_destinations(&self, limit: usize) -> Vec<(&str, usize, usize)> {
+        let mut names: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for (host, &requests) in &self.http_hosts {
+            names.entry(host).or_default().0 += requests;
+        }
+        for (name, &handshakes) in &self.tls_server_names {
+            names.entry(name).or_default().1 += handshakes;
+        }
         
-        // Break on Ctrl+C (simplified version)
-        // In a real implementation, you'd use signal handling
-        // For now, this is an infinite loop that can only be stopped with Ctrl+C
+        let mut ranked: Vec<_> = names.into_iter().map(|(name, (requests, handshakes))| (name, requests, handshakes)).collect();
+        // Stable, so ties stay in name order
+        ranked.sort_by_key(|&(_, requests, handshakes)| std::cmp::Reverse(requests + handshakes));
+        ranked.truncate(limit);
+        ranked
     }
     
-    // This line will never be reached due to infinite loop above
-    // but is needed for compilation
-    #[allow(unreachable_code)]
-    Ok(())
-}
-
-fn capture_packets_with_stats(interface: NetworkInterface, args: Args, stats: std::sync::Arc<std::sync::Mutex<NetworkStats>>, captured_packets: std::sync::Arc<std::sync::Mutex<Vec<PacketInfo>>>) {
-    use pnet::datalink::Channel::Ethernet;
+    /// `name (ip)` with the name DNS answered for the address, or else the
+    /// one `--resolve-names` looked up; the bare IP without either.
+    fn host_label(&self, ip: &str, names: &NameResolver) -> String {
+        match self.dns_names.get(ip) {
+            Some(name) => format!("{} ({})", name, ip),
+            None => names.label(ip),
+        }
+    }
     
-    let (_, mut rx) = match datalink::channel(&interface, Default::default()) {
-        Ok(Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => {
-            eprintln!("Unhandled channel type");
+    /// Distinct source and destination addresses seen so far.
+    fn unique_hosts(&self) -> (usize, usize) {
+        (self.top_talkers.len(), self.top_receivers.len())
+    }
+    
+    /// Group membership from IGMP reports and leaves, and traffic to any
+    /// multicast address, IPv6 ones included.
+    fn track_multicast(&mut self, packet_info: &PacketInfo) {
+        if let (Some(igmp), Some(src_ip)) = (&packet_info.igmp, &packet_info.src_ip) {
+            for group in &igmp.joined {
+                self.multicast_groups.entry(group.clone()).or_default().members.insert(src_ip.clone());
+            }
+            for group in &igmp.left {
+                if let Some(entry) = self.multicast_groups.get_mut(group) {
+                    entry.members.remove(src_ip);
+                }
+            }
             return;
         }
-        Err(e) => {
-            eprintln!("Failed to create datalink channel: {}", e);
+        
+        let Some(dst_ip) = &packet_info.dst_ip else {
             return;
+        };
+        if dst_ip.parse::<IpAddr>().is_ok_and(|ip| ip.is_multicast()) {
+            let traffic = &mut self.multicast_groups.entry(dst_ip.clone()).or_default().traffic;
+            traffic.packets += 1;
+            traffic.bytes += packet_info.packet_size;
         }
-    };
+    }
     
-    let mut packet_count = 0;
+    /// Groups with members or traffic, the most joined first, then the busiest.
+    fn active_multicast_groups(&self) -> Vec<(&String, &MulticastGroup)> {
+        let mut groups: Vec<_> = self.multicast_groups.iter()
+            .filter(|(_, group)| !group.members.is_empty() || group.traffic.packets > 0)
+            .collect();
+        groups.sort_by(|a, b| b.1.members.len().cmp(&a.1.members.len())
+            .then(b.1.traffic.packets.cmp(&a.1.traffic.packets))
+            .then(a.0.cmp(b.0)));
+        groups
+    }
     
-    loop {
-        if args.count > 0 && packet_count >= args.count {
-            break;
+    /// Hosts seen for the first time in the last `NEW_HOST_WINDOW_SECS`.
+    fn recent_new_hosts(&self) -> usize {
+        let cutoff = self.now() - chrono::Duration::seconds(NEW_HOST_WINDOW_SECS);
+        self.new_hosts.iter().filter(|&&seen| seen > cutoff).count()
+    }
+    
+    /// Must run before the talker maps are updated, since an address missing
+    /// from both of them is what makes it new.
+    fn track_new_hosts(&mut self, packet_info: &PacketInfo) {
+        let cutoff = packet_info.timestamp - chrono::Duration::seconds(NEW_HOST_WINDOW_SECS);
+        while self.new_hosts.front().is_some_and(|&seen| seen <= cutoff) {
+            self.new_hosts.pop_front();
         }
         
-        match rx.next() {
-            Ok(packet) => {
-                if should_capture_packet(packet, &args) {
-                    let packet_info = analyze_packet_advanced(packet, packet_count + 1);
-                    
-                    // Update stats
-                    {
-                        let mut stats = stats.lock().unwrap();
-                        stats.total_packets += 1;
-                        stats.total_bytes += packet_info.packet_size;
-                        *stats.protocol_counts.entry(packet_info.protocol.clone()).or_insert(0) += 1;
-                        
-                        // Track packet sizes for analysis
-                        stats.packet_sizes.push(packet_info.packet_size);
-                        if stats.packet_sizes.len() > 1000 {
-                            stats.packet_sizes.remove(0);
-                        }
-                        
-                        // Track port activity
-                        if let Some(port) = packet_info.dst_port.or(packet_info.src_port) {
-                            *stats.port_activity.entry(port).or_insert(0) += 1;
-                        }
-                        
-                        // Track top talkers
-                        if let Some(src_ip) = &packet_info.src_ip {
-                            *stats.top_talkers.entry(src_ip.clone()).or_insert(0) += 1;
-                        }
-                        
-                        // Track threat alerts
-                        if packet_info.threat_level != ThreatLevel::Safe {
-                            let alert_msg = format!("Suspicious {} traffic from {} to {}", 
-                                packet_info.protocol,
-                                packet_info.src_ip.as_ref().unwrap_or(&"unknown".to_string()),
-                                packet_info.dst_ip.as_ref().unwrap_or(&"unknown".to_string())
-                            );
-                            stats.threat_alerts.push((packet_info.timestamp, alert_msg, packet_info.threat_level.clone()));
-                            
-                            // Keep only last 100 alerts
-                            if stats.threat_alerts.len() > 100 {
-                                stats.threat_alerts.remove(0);
-                            }
-                        }
-                        
-                        // Track connections
-                        if let (Some(src_ip), Some(dst_ip)) = (&packet_info.src_ip, &packet_info.dst_ip) {
-                            let connection_key = format!("{}:{}-{}:{}", 
-                                src_ip, packet_info.src_port.unwrap_or(0),
-                                dst_ip, packet_info.dst_port.unwrap_or(0)
-                            );
-                            
-                            let connection = stats.connections.entry(connection_key.clone()).or_insert(ConnectionFlow {
-                                src_ip: src_ip.clone(),
-                                dst_ip: dst_ip.clone(),
-                                src_port: packet_info.src_port,
-                                dst_port: packet_info.dst_port,
-                                protocol: packet_info.protocol.clone(),
-                                packet_count: 0,
-                                total_bytes: 0,
-                                first_seen: packet_info.timestamp,
-                                last_seen: packet_info.timestamp,
-                                threat_level: packet_info.threat_level.clone(),
-                            });
-                            
-                            connection.packet_count += 1;
-                            connection.total_bytes += packet_info.packet_size;
-                            connection.last_seen = packet_info.timestamp;
-                            
-                            // Update threat level if higher
-                            if packet_info.threat_level > connection.threat_level {
-                                connection.threat_level = packet_info.threat_level.clone();
-                            }
-                        }
-                        
-                        // Calculate bandwidth stats every few seconds
-                        let elapsed = stats.start_time.elapsed().as_secs();
-                        if elapsed > 0 && stats.total_packets % 100 == 0 {
-                            let bytes_per_sec = stats.total_bytes as f64 / elapsed as f64;
-                            let packets_per_sec = stats.total_packets as f64 / elapsed as f64;
-                            
-                            stats.bandwidth_history.push(BandwidthPoint {
-                                timestamp: packet_info.timestamp,
-                                bytes_per_sec,
-                                packets_per_sec,
-                            });
-                            
-                            // Update peaks
-                            if bytes_per_sec > stats.peak_bandwidth {
-                                stats.peak_bandwidth = bytes_per_sec;
-                            }
-                            if packets_per_sec > stats.peak_packets_per_sec {
-                                stats.peak_packets_per_sec = packets_per_sec;
-                            }
-                            
-                            // Keep only last 100 bandwidth points
-                            if stats.bandwidth_history.len() > 100 {
-                                stats.bandwidth_history.remove(0);
-                            }
-                        }
-                        
-                        // Update current connections count
-                        stats.current_connections = stats.connections.len();
-                    }
-                    
-                    // Store packet info
-                    {
-                        let mut packets = captured_packets.lock().unwrap();
-                        packets.push(packet_info);
-                        
-                        // Keep only last 1000 packets to avoid memory issues
-                        if packets.len() > 1000 {
-                            packets.remove(0);
-                        }
-                    }
-                    
-                    packet_count += 1;
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to read packet: {}", e);
-                break;
+        let mut addresses = vec![packet_info.src_ip.as_ref(), packet_info.dst_ip.as_ref()];
+        addresses.dedup();
+        for ip in addresses.into_iter().flatten() {
+            if !self.top_talkers.contains_key(ip) && !self.top_receivers.contains_key(ip) {
+                self.new_hosts.push_back(packet_info.timestamp);
             }
         }
     }
-}
-
-fn display_dashboard(stats: &std::sync::Arc<std::sync::Mutex<NetworkStats>>, captured_packets: &std::sync::Arc<std::sync::Mutex<Vec<PacketInfo>>>) {
-    let stats = stats.lock().unwrap();
-    let packets = captured_packets.lock().unwrap();
-    
-    // Clear screen and display header
-    println!("{}", "\x1B[2J\x1B[1;1H");
-    println!("{}", "🚀 ADVANCED NETWORK TRAFFIC DASHBOARD".green().bold());
-    println!("{}", "═".repeat(100).blue());
-    
-    let duration = stats.start_time.elapsed().as_secs();
-    let packets_per_sec = if duration > 0 { stats.total_packets as f64 / duration as f64 } else { 0.0 };
-    let bytes_per_sec = if duration > 0 { stats.total_bytes as f64 / duration as f64 } else { 0.0 };
     
-    // Main statistics overview
-    println!("⏱️  {} {} {} {} {} {} {} {}", 
-             "Duration:".cyan(), format!("{}s", duration).yellow().bold(),
-             "| 📦 Packets:".cyan(), format!("{} ({:.1}/s)", stats.total_packets, packets_per_sec).yellow().bold(),
-             "| 📊 Data:".cyan(), format!("{} ({:.1}/s)", format_bytes(stats.total_bytes), bytes_per_sec).yellow().bold(),
-             "| 🔗 Connections:".cyan(), format!("{}", stats.current_connections).yellow().bold()
-    );
-    
-    // Performance metrics
-    println!("⚡ {} {} {} {}", 
-             "Peak Bandwidth:".cyan(), format!("{}/s", format_bytes(stats.peak_bandwidth as usize)).red().bold(),
-             "| Peak Packets:".cyan(), format!("{:.1}/s", stats.peak_packets_per_sec).red().bold()
-    );
-    println!();
+    fn guess_os(&mut self, src_ip: &str, ttl: u8, packet_info: &PacketInfo) {
+        let syn_window = packet_info.tcp_window.filter(|_| has_tcp_flag(packet_info, "SYN"));
+        let guess = OsGuess::new(ttl, syn_window);
+        match self.os_guesses.get_mut(src_ip) {
+            Some(known) if guess.replaces(known) => *known = guess,
+            Some(_) => {}
+            None => {
+                self.os_guesses.insert(src_ip.to_string(), guess);
+            }
+        }
+    }
     
-    // Real-time bandwidth graph (ASCII art)
-    display_bandwidth_graph(&stats.bandwidth_history);
-    
-    // Security threat indicators
-    display_threat_dashboard(&stats.threat_alerts, &packets);
+    /// Folds one analyzed packet into the aggregates. Both capture modes go
+    /// through here so the dashboard and the final summary see the same data.
+    /// It also numbers the packet: `total_packets` is the one session-wide
+    /// counter, so numbers never repeat or restart, whichever mode captured.
+    fn record(&mut self, packet_info: &mut PacketInfo, threat: &ThreatRules) {
+        self.advance_packet_clock(packet_info.timestamp);
+        self.total_packets += 1;
+        packet_info.packet_number = self.total_packets;
+        self.total_bytes += packet_info.packet_size;
+        *self.protocol_counts.entry(packet_info.protocol.clone()).or_insert(0) += 1;
+        *self.protocol_bytes.entry(packet_info.protocol.clone()).or_insert(0) += packet_info.packet_size;
+        
+        let interface = self.interface_traffic.entry(packet_info.interface.clone()).or_default();
+        interface.packets += 1;
+        interface.bytes += packet_info.packet_size;
+        
+        let direction = match packet_info.direction {
+            Some(Direction::Outbound) => Some(&mut self.uploaded),
+            Some(Direction::Inbound) => Some(&mut self.downloaded),
+            None if packet_info.src_ip.is_some() => {
+                self.undirected_packets += 1;
+                None
+            }
+            _ => None,
+        };
+        if let Some(direction) = direction {
+            direction.packets += 1;
+            direction.bytes += packet_info.packet_size;
+        }
+        
+        // Track packet sizes for analysis
+        self.packet_sizes.push_back(packet_info.packet_size);
+        if self.packet_sizes.len() > PACKET_SIZE_WINDOW {
+            self.packet_sizes.pop_front();
+        }
+        
+        // Track port activity
+        if let Some(port) = packet_info.dst_port.or(packet_info.src_port) {
+            *self.port_activity.entry(port).or_insert(0) += 1;
+        }
+        
+        // Names are case-insensitive, so fold case to count each site once
+        if let Some(host) = packet_info.http_info.as_ref().and_then(|http| http.host.as_ref()) {
+            *self.http_hosts.entry(host.to_ascii_lowercase()).or_insert(0) += 1;
+        }
+        if let Some(ref sni) = packet_info.tls_sni {
+            *self.tls_server_names.entry(sni.to_ascii_lowercase()).or_insert(0) += 1;
+        }
+        
+        for answer in &packet_info.dns_answers {
+            if self.dns_names.insert(answer.address.clone(), answer.name.clone()).is_none() {
+                self.dns_order.push_back(answer.address.clone());
+                if self.dns_order.len() > MAX_DNS_NAMES {
+                    if let Some(oldest) = self.dns_order.pop_front() {
+                        self.dns_names.remove(&oldest);
+                    }
+                }
+            }
+        }
+        
+        // Track top talkers on both sides
+        self.track_new_hosts(packet_info);
+        if let Some(src_ip) = &packet_info.src_ip {
+            let talker = self.top_talkers.entry(src_ip.clone()).or_default();
+            talker.packets += 1;
+            talker.bytes += packet_info.packet_size;
+            if let Some(ttl) = packet_info.ttl {
+                self.guess_os(src_ip, ttl, packet_info);
+            }
+        }
+        if let Some(dst_ip) = &packet_info.dst_ip {
+            let receiver = self.top_receivers.entry(dst_ip.clone()).or_default();
+            receiver.packets += 1;
+            receiver.bytes += packet_info.packet_size;
+        }
+        
+        // Track threat alerts
+        if packet_info.threat_level != ThreatLevel::Safe {
+            let src = packet_info.src_ip.as_deref().unwrap_or("unknown");
+            let dst = packet_info.dst_ip.as_deref().unwrap_or("unknown");
+            let alert_msg = match threat.blocklisted_ip(packet_info) {
+                Some(ip) => format!("Blocklisted host {} in {} traffic from {} to {}", ip, packet_info.protocol, src, dst),
+                None => format!("Suspicious {} traffic from {} to {}", packet_info.protocol, src, dst),
+            };
+            self.push_alert(packet_info.timestamp, alert_msg, packet_info.threat_level.clone());
+        }
+        
+        // The detectors count packets within a window, which sampling would thin out
+        let detect = self.sample_rate.is_none();
+        
+        // Track connection attempts for port-scan detection
+        if let (Some(src_ip), Some(dst_port)) = (&packet_info.src_ip, packet_info.dst_port) {
+            if detect && is_connection_attempt(packet_info) {
+                if let Some((alert_msg, level)) = self.port_scans.observe(src_ip, dst_port, packet_info.timestamp) {
+                    self.push_alert(packet_info.timestamp, alert_msg, level);
+                }
+            }
+        }
+        
+        // Track connections; both directions of a conversation land in one flow
+        if let (Some(src_ip), Some(dst_ip)) = (&packet_info.src_ip, &packet_info.dst_ip) {
+            let connection_key = connection_key(&packet_info.protocol, src_ip, packet_info.src_port, dst_ip, packet_info.dst_port);
+            
+            let connection = self.connections.entry(connection_key).or_insert(ConnectionFlow {
+                src_ip: src_ip.clone(),
+                dst_ip: dst_ip.clone(),
+                src_port: packet_info.src_port,
+                dst_port: packet_info.dst_port,
+                protocol: packet_info.protocol.clone(),
+                packet_count: 0,
+                total_bytes: 0,
+                bytes_from_src: 0,
+                bytes_from_dst: 0,
+                first_seen: packet_info.timestamp,
+                last_seen: packet_info.timestamp,
+                threat_level: packet_info.threat_level.clone(),
+                handshake_state: HandshakeState::None,
+                retransmissions: 0,
+                out_of_order: 0,
+                sequence_from_src: SequenceTracker::default(),
+                sequence_from_dst: SequenceTracker::default(),
+                rtt_to_dst: RttLeg::default(),
+                rtt_to_src: RttLeg::default(),
+            });
+            
+            if packet_info.protocol == "TCP" {
+                connection.handshake_state = advance_handshake(&connection.handshake_state, packet_info);
+            }
+            connection.packet_count += 1;
+            connection.total_bytes += packet_info.packet_size;
+            let from_src = connection.src_ip == *src_ip && connection.src_port == packet_info.src_port;
+            if from_src {
+                connection.bytes_from_src += packet_info.packet_size;
+            } else {
+                connection.bytes_from_dst += packet_info.packet_size;
+            }
+            if let Some(sequence) = packet_info.tcp_sequence {
+                // SYN and FIN each take up one sequence number
+                let len = packet_info.payload_size as u32
+                    + has_tcp_flag(packet_info, "SYN") as u32
+                    + has_tcp_flag(packet_info, "FIN") as u32;
+                let tracker = if from_src { &mut connection.sequence_from_src } else { &mut connection.sequence_from_dst };
+                let kind = tracker.observe(sequence, len, packet_info.timestamp);
+                match kind {
+                    SegmentKind::InOrder => {}
+                    SegmentKind::Retransmission => connection.retransmissions += 1,
+                    SegmentKind::OutOfOrder => connection.out_of_order += 1,
+                }
+                
+                let (outbound, inbound) = if from_src {
+                    (&mut connection.rtt_to_dst, &mut connection.rtt_to_src)
+                } else {
+                    (&mut connection.rtt_to_src, &mut connection.rtt_to_dst)
+                };
+                if let Some(ack) = packet_info.tcp_ack {
+                    inbound.acknowledged(ack, packet_info.timestamp);
+                }
+                outbound.sent(sequence, len, kind != SegmentKind::InOrder, packet_info.timestamp);
+            }
+            connection.last_seen = packet_info.timestamp;
+            
+            // Update threat level if higher
+            if packet_info.threat_level > connection.threat_level {
+                connection.threat_level = packet_info.threat_level.clone();
+            }
+        }
+        
+        // Look for targets piling up half-open handshakes
+        if detect {
+            for (alert_msg, level) in self.syn_floods.sweep(&mut self.connections, packet_info.timestamp) {
+                self.push_alert(packet_info.timestamp, alert_msg, level);
+            }
+        }
+        
+        self.close_idle_connections(self.now());
+        self.track_multicast(packet_info);
+        
+        // Watch for IPs changing MAC and gratuitous ARP bursts
+        if let Some(arp) = packet_info.arp.as_ref().filter(|_| detect) {
+            for (alert_msg, level) in self.arp_watch.observe(arp, packet_info.timestamp) {
+                self.push_alert(packet_info.timestamp, alert_msg, level);
+            }
+        }
+        
+        // Bandwidth history gets a point as each second of capture completes
+        let second = self.elapsed().as_secs();
+        if second > self.bandwidth_second {
+            self.close_bandwidth_second(second);
+        }
+        self.second_bytes += packet_info.packet_size;
+        self.second_packets += 1;
+        match packet_info.direction {
+            Some(Direction::Outbound) => self.second_uploaded += packet_info.packet_size,
+            Some(Direction::Inbound) => self.second_downloaded += packet_info.packet_size,
+            _ => {}
+        }
+        
+        // Update current connections count
+        self.current_connections = self.connections.len();
+    }
+
+    /// Records the finished second, plus empty points for any idle seconds
+    /// since, then starts accumulating `second`.
+    fn close_bandwidth_second(&mut self, second: u64) {
+        self.push_bandwidth_point(self.current_bandwidth_point());
+        
+        // Older idle seconds would be trimmed straight away
+        let first_idle = (self.bandwidth_second + 1).max(second.saturating_sub(self.max_bandwidth_points as u64));
+        for idle in first_idle..second {
+            self.push_bandwidth_point(BandwidthPoint {
+                timestamp: self.started_at + chrono::Duration::seconds(idle as i64),
+                bytes_per_sec: 0.0,
+                packets_per_sec: 0.0,
+                upload_bytes_per_sec: 0.0,
+                download_bytes_per_sec: 0.0,
+            });
+        }
+        
+        self.bandwidth_second = second;
+        self.second_bytes = 0;
+        self.second_packets = 0;
+        self.second_uploaded = 0;
+        self.second_downloaded = 0;
+        
+        let (bytes_per_sec, packets_per_sec) = self.rolling_rate_at(second);
+        if bytes_per_sec > self.peak_bandwidth {
+            self.peak_bandwidth = bytes_per_sec;
+        }
+        if packets_per_sec > self.peak_packets_per_sec {
+            self.peak_packets_per_sec = packets_per_sec;
+        }
+    }
     
-    // Split dashboard into columns
-    println!("{}", "┌─────────────────────────────────────────────────┬─────────────────────────────────────────────────┐".blue());
-    print!("{}", "│".blue());
-    print!("{:^49}", "🔗 PROTOCOL ANALYSIS".yellow().bold());
-    print!("{}", "│".blue());
-    print!("{:^49}", "🌍 TOP CONNECTIONS".yellow().bold());
-    println!("{}", "│".blue());
-    println!("{}", "├─────────────────────────────────────────────────┼─────────────────────────────────────────────────┤".blue());
+    /// Bytes and packets per second over the last `rolling_window_secs`
+    /// complete seconds, or fewer right after capture starts.
+    fn rolling_rate(&self) -> (f64, f64) {
+        self.rolling_rate_at(self.elapsed().as_secs())
+    }
     
-    // Display protocol stats and top connections side by side
-    display_protocol_and_connections(&stats);
+    /// Upload and download bytes per second, over the same window as `rolling_rate`.
+    fn rolling_direction_rate(&self) -> (f64, f64) {
+        let second = self.elapsed().as_secs();
+        self.rolling_average_at(second, |point| (point.upload_bytes_per_sec, point.download_bytes_per_sec))
+    }
     
-    println!("{}", "└─────────────────────────────────────────────────┴─────────────────────────────────────────────────┘".blue());
+    /// False when IP traffic was seen but none of it could be given a
+    /// direction, as for a pcap stream, so upload and download mean nothing.
+    fn direction_known(&self) -> bool {
+        self.undirected_packets == 0 || self.uploaded.packets + self.downloaded.packets > 0
+    }
     
-    // Port activity analysis
-    display_port_activity(&stats.port_activity);
+    /// `rolling_rate` as of the start of `second`.
+    fn rolling_rate_at(&self, second: u64) -> (f64, f64) {
+        self.rolling_average_at(second, |point| (point.bytes_per_sec, point.packets_per_sec))
+    }
     
-    // Packet size distribution
-    display_packet_size_analysis(&stats.packet_sizes);
+    /// Averages a pair of per-second values over the rolling window ending at
+    /// `second`. A finished second that no packet has closed yet still counts;
+    /// idle seconds count as zero.
+    fn rolling_average_at(&self, second: u64, values: impl Fn(&BandwidthPoint) -> (f64, f64)) -> (f64, f64) {
+        let window = self.rolling_window_secs.min(second);
+        if window == 0 {
+            return (0.0, 0.0);
+        }
+        
+        let from = self.started_at + chrono::Duration::seconds((second - window) as i64);
+        let until = self.started_at + chrono::Duration::seconds(second as i64);
+        let unclosed = (self.bandwidth_second < second).then(|| self.current_bandwidth_point());
+        let (first_total, second_total) = self.bandwidth_history.iter().chain(unclosed.as_ref())
+            .filter(|point| point.timestamp >= from && point.timestamp < until)
+            .map(values)
+            .fold((0.0, 0.0), |(first_total, second_total), (first, second)| (first_total + first, second_total + second));
+        (first_total / window as f64, second_total / window as f64)
+    }
     
-    // Geographic distribution
-    display_geographic_analysis(&packets);
+    /// The second still being accumulated, as a point.
+    fn current_bandwidth_point(&self) -> BandwidthPoint {
+        BandwidthPoint {
+            timestamp: self.started_at + chrono::Duration::seconds(self.bandwidth_second as i64),
+            bytes_per_sec: self.second_bytes as f64,
+            packets_per_sec: self.second_packets as f64,
+            upload_bytes_per_sec: self.second_uploaded as f64,
+            download_bytes_per_sec: self.second_downloaded as f64,
+        }
+    }
     
-    // Recent activity stream
-    display_recent_activity(&packets);
+    fn push_bandwidth_point(&mut self, point: BandwidthPoint) {
+        self.bandwidth_history.push_back(point);
+        if self.bandwidth_history.len() > self.max_bandwidth_points {
+            self.bandwidth_history.pop_front();
+        }
+    }
     
-    // Footer with controls
-    println!("\n{}", "═".repeat(100).blue());
-    println!("{}", "💡 CONTROLS: [Ctrl+C] Exit | [Space] Pause | [F] Filter | [E] Export | [H] Help".cyan());
-    println!("{}", format!("📡 Last Updated: {}", Utc::now().format("%H:%M:%S UTC")).bright_black());
+    fn push_alert(&mut self, timestamp: DateTime<Utc>, message: String, level: ThreatLevel) {
+        if let Some(ref webhook) = self.alert_webhook {
+            webhook.send(timestamp, &message, &level);
+        }
+        if let Some(ref syslog) = self.alert_syslog {
+            syslog.send(&message, &level);
+        }
+        *self.alert_counts.entry(level.as_str().to_string()).or_insert(0) += 1;
+        self.threat_alerts.push_back((timestamp, message, level));
+        if self.threat_alerts.len() > self.max_alerts {
+            self.threat_alerts.pop_front();
+        }
+    }
 }
 
-fn format_bytes(bytes: usize) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
-    let mut size = bytes as f64;
-    let mut unit_index = 0;
-    
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
+/// The settings and lookups decoding a single packet consults. Cheap to
+/// clone, so each analysis worker gets its own; anything that depends on
+/// packet order (rate limiting, stream reassembly) stays with whoever numbers
+/// the frames.
+#[derive(Clone)]
+struct AnalysisContext {
+    geoip: GeoIpResolver,
+    vendors: VendorLookup,
+    threat: ThreatRules,
+    names: NameResolver,
+    custom_ports: CustomPorts,
+    local: LocalAddresses,
+}
+
+impl AnalysisContext {
+    fn new(config: &Config, args: &Args, interfaces: &[NetworkInterface], logger: &mut Logger) -> Result<Self> {
+        Ok(AnalysisContext {
+            geoip: GeoIpResolver::from_config(&config.geoip, logger),
+            vendors: VendorLookup::from_config(&config.oui, logger),
+            threat: ThreatRules::new(&config.threat)?,
+            names: NameResolver::new(args.resolve_names),
+            custom_ports: CustomPorts::new(parse_port_protocols(&config.port_protocols)?),
+            local: LocalAddresses::of(interfaces),
+        })
     }
-    
-    format!("{:.1} {}", size, UNITS[unit_index])
 }
 
-fn display_bandwidth_graph(bandwidth_history: &Vec<BandwidthPoint>) {
-    println!("{}", "📈 REAL-TIME BANDWIDTH GRAPH".yellow().bold());
-    
-    if bandwidth_history.is_empty() {
-        println!("   {}", "No data available yet...".bright_black());
-        println!();
-        return;
+/// Threat scoring settings with the allow/block lists parsed up front.
+#[derive(Clone)]
+struct ThreatRules {
+    config: ThreatConfig,
+    allowlist: Vec<IpNetwork>,
+    blocklist: Vec<IpNetwork>,
+}
+
+impl ThreatRules {
+    fn new(config: &ThreatConfig) -> Result<Self> {
+        Ok(ThreatRules {
+            config: config.clone(),
+            allowlist: parse_networks("threat.allowlist", &config.allowlist)?,
+            blocklist: parse_networks("threat.blocklist", &config.blocklist)?,
+        })
     }
     
-    let max_bytes = bandwidth_history.iter()
-        .map(|p| p.bytes_per_sec)
-        .fold(0.0, f64::max)
-        .max(1.0); // Prevent division by zero
+    /// Returns whichever endpoint of the packet is on the list, if any.
+    fn listed_ip<'a>(list: &[IpNetwork], packet_info: &'a PacketInfo) -> Option<&'a str> {
+        [&packet_info.src_ip, &packet_info.dst_ip].into_iter()
+            .flatten()
+            .find(|ip| ip.parse().is_ok_and(|addr| list.iter().any(|net| net.contains(addr))))
+            .map(|ip| ip.as_str())
+    }
     
-    println!("   {} {}/s", "Peak:".cyan(), format_bytes(max_bytes as usize).red().bold());
+    fn blocklisted_ip<'a>(&self, packet_info: &'a PacketInfo) -> Option<&'a str> {
+        Self::listed_ip(&self.blocklist, packet_info)
+    }
     
-    // ASCII graph
-    for point in bandwidth_history.iter().rev().take(20).rev() {
-        let bar_length = ((point.bytes_per_sec / max_bytes) * 40.0) as usize;
-        let bar = "█".repeat(bar_length);
-        let time = point.timestamp.format("%H:%M:%S").to_string();
-        println!("   {} │{:<40}│ {}", 
-                 time.bright_black(), 
-                 bar.green(), 
-                 format_bytes(point.bytes_per_sec as usize).cyan());
+    fn is_allowlisted(&self, packet_info: &PacketInfo) -> bool {
+        Self::listed_ip(&self.allowlist, packet_info).is_some()
     }
-    println!();
 }
 
-fn display_threat_dashboard(threat_alerts: &Vec<(DateTime<Utc>, String, ThreatLevel)>, packets: &Vec<PacketInfo>) {
-    let threat_counts = packets.iter().fold([0; 5], |mut acc, packet| {
-        match packet.threat_level {
-            ThreatLevel::Safe => acc[0] += 1,
-            ThreatLevel::Low => acc[1] += 1,
-            ThreatLevel::Medium => acc[2] += 1,
-            ThreatLevel::High => acc[3] += 1,
-            ThreatLevel::Critical => acc[4] += 1,
+/// How long a capture read may block before the loop re-checks the shutdown
+/// flag, time limits and periodic stats
+const CAPTURE_READ_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How often the kernel's interface drop counters are re-read
+const KERNEL_DROPS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set by the Ctrl+C handler or the dashboard's quit key; both capture loops
+/// poll it so they can stop cleanly and still run the final summary and exports.
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+fn install_shutdown_handler(logger: &mut Logger) {
+    let result = ctrlc::set_handler(|| {
+        // A second Ctrl+C means the user doesn't want to wait for a blocked read
+        if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+            out!("\x1B[?25h");
+            std::process::exit(130);
         }
-        acc
     });
     
-    let total_threats = threat_counts[1] + threat_counts[2] + threat_counts[3] + threat_counts[4];
-    
-    println!("{} {} {}", 
-             "🛡️  SECURITY STATUS:".yellow().bold(),
-             if total_threats == 0 { "✅ SECURE".green().bold() } else { "⚠️  THREATS DETECTED".red().bold() },
-             format!("({} alerts)", threat_alerts.len()).bright_black()
-    );
-    
-    // Threat level bars
-    let threat_bar = format!("Safe:{} Low:{} Med:{} High:{} Crit:{}", 
-                            threat_counts[0], threat_counts[1], threat_counts[2], threat_counts[3], threat_counts[4]);
-    println!("   {}", threat_bar.cyan());
-    
-    // Recent threat alerts
-    if !threat_alerts.is_empty() {
-        println!("   {} Recent Alerts:", "🚨".red());
-        for (timestamp, message, level) in threat_alerts.iter().rev().take(3) {
-            let level_icon = match level {
-                ThreatLevel::Low => "🟡",
-                ThreatLevel::Medium => "🟠", 
-                ThreatLevel::High => "🔴",
-                ThreatLevel::Critical => "💀",
-                _ => "⚪",
-            };
-            println!("   {} {} {}", 
-                     level_icon, 
-                     timestamp.format("%H:%M:%S").to_string().bright_black(),
-                     message.yellow());
-        }
+    if let Err(e) = result {
+        logger.log_warn(&format!("Failed to install Ctrl+C handler, summary will not run on interrupt: {}", e));
     }
-    println!();
 }
 
-fn display_protocol_and_connections(stats: &NetworkStats) {
-    // Prepare protocol data
-    let mut protocols: Vec<_> = stats.protocol_counts.iter().collect();
-    protocols.sort_by(|a, b| b.1.cmp(a.1));
+fn main() {
+    // Initialize environment logger
+    env_logger::init();
     
-    // Prepare connection data
-    let mut connections: Vec<_> = stats.connections.iter().collect();
-    connections.sort_by(|a, b| b.1.packet_count.cmp(&a.1.packet_count));
+    let mut args = Args::parse();
     
-    let max_rows = std::cmp::max(protocols.len(), connections.len().min(8));
+    // Handle configuration generation
+    if args.generate_config {
+        generate_default_config(&args);
+    }
     
-    for i in 0..max_rows.max(5) {
-        print!("{}", "│".blue());
-        
-        // Protocol column
-        if i < protocols.len() {
-            let (protocol, count) = protocols[i];
-            let percentage = (*count as f64 / stats.total_packets as f64) * 100.0;
-            print!(" {:<12} {:>8} {:>6.1}%{:>18}", 
-                   protocol.green(), 
-                   count.to_string().yellow(), 
-                   percentage,
-                   "");
-        } else {
-            print!("{:49}", "");
+    // Load configuration
+    let config = load_configuration(&args).unwrap_or_else(|e| {
+        handle_error(&e);
+    });
+    
+    let mut style = OutputStyle::from_config(&config.ui);
+    style.quiet = args.quiet || args.format == OutputFormat::Ndjson || args.list_interfaces_json;
+    style::init(style);
+    style::init_timestamps(&config.ui);
+    
+    // Initialize logger
+    let mut logger = Logger::new(&config.logging).unwrap_or_else(|e| {
+        errln!("Failed to initialize logger: {}", e);
+        std::process::exit(1);
+    });
+    
+    logger.log_info("Starting Advanced Network Packet Sniffer v1.0.0");
+    if let Some(ref migration) = config.migration {
+        log_config_migration(migration, &mut logger);
+    }
+    services::init(&mut logger);
+    
+    if args.list_interfaces {
+        list_interfaces(&config, &mut logger);
+        return;
+    }
+    if args.list_interfaces_json {
+        list_interfaces_json(&mut logger);
+        return;
+    }
+    
+    if args.interface.is_empty() && !args.read_stdin {
+        let error = PacketSnifferError::InterfaceNotFound("No interface specified".to_string());
+        logger.log_error_with_context("Interface selection", &error);
+        handle_error(&error);
+    }
+    
+    let mut interfaces = Vec::new();
+    for name in &args.interface {
+        if interfaces.iter().any(|iface: &NetworkInterface| iface.name == *name) {
+            continue;
         }
-        
-        print!("{}", "│".blue());
-        
-        // Connection column
-        if i < connections.len() && i < 8 {
-            let (_, connection) = connections[i];
-            let threat_icon = match connection.threat_level {
-                ThreatLevel::Safe => "✅",
-                ThreatLevel::Low => "🟡",
-                ThreatLevel::Medium => "🟠",
-                ThreatLevel::High => "🔴", 
-                ThreatLevel::Critical => "💀",
-            };
-            
-            let conn_display = format!("{}→{}", 
-                connection.src_ip.split('.').last().unwrap_or("?"),
-                connection.dst_ip.split('.').last().unwrap_or("?"));
-            
-            print!(" {} {:<15} {:>8} {:>8}", 
-                   threat_icon,
-                   conn_display.blue(),
-                   connection.packet_count.to_string().yellow(),
-                   format_bytes(connection.total_bytes).cyan());
-        } else {
-            print!("{:49}", "");
+        match find_interface(name) {
+            Some(iface) => interfaces.push(iface),
+            None => {
+                let error = PacketSnifferError::InterfaceNotFound(name.clone());
+                logger.log_error_with_context("Interface discovery", &error);
+                handle_error(&error);
+            }
         }
-        
-        println!("{}", "│".blue());
     }
-}
-
-fn display_port_activity(port_activity: &HashMap<u16, usize>) {
-    println!("{}", "🚪 TOP PORT ACTIVITY".yellow().bold());
     
-    if port_activity.is_empty() {
-        println!("   {}", "No port activity recorded yet...".bright_black());
-        println!();
-        return;
+    if let Err(error) = apply_filter_preset(&mut args, &config.filter_presets) {
+        logger.log_error_with_context("Filter preset", &error);
+        handle_error(&error);
     }
     
-    let mut ports: Vec<_> = port_activity.iter().collect();
-    ports.sort_by(|a, b| b.1.cmp(a.1));
+    // Validate filters and compile the BPF expression before capture starts
+    let filter = match CaptureFilter::from_args(&args, &LocalAddresses::of(&interfaces)) {
+        Ok(filter) => filter,
+        Err(error) => {
+            logger.log_error_with_context("Capture filter validation", &error);
+            handle_error(&error);
+        }
+    };
     
-    print!("   ");
-    for (port, count) in ports.iter().take(10) {
-        let port_color = match **port {
-            80 | 443 => "green",
-            22 | 23 => "yellow", 
-            53 => "blue",
-            _ if **port > 1024 => "cyan",
-            _ => "red",
-        };
-        
-        print!("{}:{} ", 
-               match port_color {
-                   "green" => format!("{}", port).green(),
-                   "yellow" => format!("{}", port).yellow(),
-                   "blue" => format!("{}", port).blue(),
-                   "cyan" => format!("{}", port).cyan(),
-                   _ => format!("{}", port).red(),
-               },
-               count.to_string().bright_black());
+    if config.alerts.syslog && !AlertSyslog::supported() {
+        logger.log_warn("alerts.syslog is ignored: this platform has no syslog");
+    }
+    
+    install_shutdown_handler(&mut logger);
+    logger.log_packet_capture_start(&interface_names(&interfaces));
+    
+    let result = if args.dashboard {
+        start_dashboard_mode(&interfaces, args, filter, config, logger)
+    } else {
+        start_sniffing(&interfaces, args, filter, config, logger)
+    };
+    
+    if let Err(e) = result {
+        handle_error(&e);
     }
-    println!("\n");
 }
 
-fn display_packet_size_analysis(packet_sizes: &Vec<usize>) {
-    println!("{}", "📏 PACKET SIZE DISTRIBUTION".yellow().bold());
+fn generate_default_config(args: &Args) -> ! {
+    let config_path = get_config_path(args);
     
-    if packet_sizes.is_empty() {
-        println!("   {}", "No packet size data available...".bright_black());
-        println!();
-        return;
+    match Config::default().save(&config_path) {
+        Ok(_) => {
+            outln!("✅ Default configuration generated at: {}", config_path.display());
+            outln!("💡 You can now edit this file to customize the packet sniffer behavior.");
+            std::process::exit(0);
+        }
+        Err(e) => {
+            errln!("❌ Failed to generate configuration: {}", e);
+            std::process::exit(1);
+        }
     }
+}
+
+fn load_configuration(args: &Args) -> Result<Config> {
+    let config_path = get_config_path(args);
     
-    let avg_size = packet_sizes.iter().sum::<usize>() as f64 / packet_sizes.len() as f64;
-    let min_size = packet_sizes.iter().min().unwrap_or(&0);
-    let max_size = packet_sizes.iter().max().unwrap_or(&0);
-    
-    // Size categories
-    let small = packet_sizes.iter().filter(|&&s| s < 100).count();
-    let medium = packet_sizes.iter().filter(|&&s| s >= 100 && s < 500).count();
-    let large = packet_sizes.iter().filter(|&&s| s >= 500 && s < 1500).count();
-    let jumbo = packet_sizes.iter().filter(|&&s| s >= 1500).count();
+    let mut config = Config::load_or_create(config_path).map_err(|e| {
+        PacketSnifferError::ConfigError(format!("Failed to load configuration: {}", e))
+    })?;
+    config.apply_env_overrides()?;
     
-    println!("   {} {} {} {} {} {} {} {}", 
-             "Avg:".cyan(), format!("{}B", avg_size as usize).yellow(),
-             "Range:".cyan(), format!("{}-{}B", min_size, max_size).yellow(),
-             "Small:".cyan(), small.to_string().green(),
-             "Large:".cyan(), (large + jumbo).to_string().red());
+    // CLI flags come last, so they win over the file and the environment
+    if let Some(ref level) = args.log_level {
+        config.logging.level = level.clone();
+    }
+    if let Some(ref dir) = args.export_dir {
+        config.export.default_directory = dir.clone();
+    }
     
-    // Simple histogram
-    let total = packet_sizes.len();
-    let small_bar = "█".repeat((small * 30 / total.max(1)).min(30));
-    let medium_bar = "█".repeat((medium * 30 / total.max(1)).min(30));
-    let large_bar = "█".repeat(((large + jumbo) * 30 / total.max(1)).min(30));
+    config.validate()?;
+    Ok(config)
+}
+
+/// Fills in the filters of the `--preset` preset that weren't given as flags.
+fn apply_filter_preset(args: &mut Args, presets: &BTreeMap<String, FilterSpec>) -> Result<()> {
+    let Some(ref name) = args.preset else {
+        return Ok(());
+    };
+    let Some(preset) = presets.get(name) else {
+        let known: Vec<&str> = presets.keys().map(String::as_str).collect();
+        let known = if known.is_empty() { "none are defined".to_string() } else { format!("defined: {}", known.join(", ")) };
+        return Err(PacketSnifferError::InvalidFilter(format!("unknown --preset '{}' ({})", name, known)));
+    };
     
-    println!("   <100B  │{:<30}│ {}%", small_bar.green(), (small * 100 / total.max(1)));
-    println!("   100-500│{:<30}│ {}%", medium_bar.yellow(), (medium * 100 / total.max(1)));
-    println!("   >500B  │{:<30}│ {}%", large_bar.red(), ((large + jumbo) * 100 / total.max(1)));
-    println!();
+    fn fill<T: Clone>(flag: &mut Vec<T>, preset: &[T]) {
+        if flag.is_empty() {
+            *flag = preset.to_vec();
+        }
+    }
+    fill(&mut args.protocol, &preset.protocol);
+    fill(&mut args.port, &preset.port);
+    fill(&mut args.src_ip, &preset.src_ip);
+    fill(&mut args.dst_ip, &preset.dst_ip);
+    fill(&mut args.host, &preset.host);
+    fill(&mut args.subnet, &preset.subnet);
+    fill(&mut args.exclude_protocol, &preset.exclude_protocol);
+    fill(&mut args.exclude_host, &preset.exclude_host);
+    fill(&mut args.exclude_port, &preset.exclude_port);
+    args.min_size = args.min_size.or(preset.min_size);
+    args.max_size = args.max_size.or(preset.max_size);
+    Ok(())
+}
+
+fn log_config_migration(migration: &Migration, logger: &mut Logger) {
+    let added = if migration.added.is_empty() {
+        "no fields were missing".to_string()
+    } else {
+        format!("added with defaults: {}", migration.added.join(", "))
+    };
+    logger.log_info(&format!("Migrated the configuration from version {} to {}; {}", migration.from_version, config::CONFIG_VERSION, added));
+    match migration.rewritten {
+        Ok(ref backup) => logger.log_info(&format!("Rewrote the config file; the previous version is kept at {}", backup)),
+        Err(ref e) => logger.log_warn(&format!("Could not rewrite the config file ({}); it will be migrated again next run", e)),
+    }
 }
 
-fn display_geographic_analysis(packets: &Vec<PacketInfo>) {
-    println!("{}", "🌍 GEOGRAPHIC DISTRIBUTION".yellow().bold());
+fn get_config_path(args: &Args) -> PathBuf {
+    if let Some(ref path) = args.config {
+        path.clone()
+    } else {
+        let config_dir = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("packet_sniffer");
+        config_dir.join("config.json")
+    }
+}
+
+fn list_interfaces(_config: &Config, _logger: &mut Logger) {
+    outln!("{}", "🌐 Available Network Interfaces:".green().bold());
+    outln!();
     
-    let mut country_counts = HashMap::new();
-    for packet in packets.iter().rev().take(500) {
-        if let Some(ref geo) = packet.geo_info {
-            if let Some(ref country) = geo.country {
-                *country_counts.entry(country.clone()).or_insert(0) += 1;
-            }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Interface").style_spec("Fb"),
+        Cell::new("Description").style_spec("Fb"),
+        Cell::new("MAC Address").style_spec("Fb"),
+        Cell::new("IP Addresses").style_spec("Fb"),
+        Cell::new("MTU").style_spec("Fb"),
+        Cell::new("Status").style_spec("Fb"),
+    ]));
+    
+    for interface in datalink::interfaces() {
+        let ips = interface.ips.iter()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        
+        let mac = interface.mac.map_or_else(|| "-".to_string(), |mac| mac.to_string());
+        let mtu = interface_mtu(&interface.name).map_or_else(|| "-".to_string(), |mtu| mtu.to_string());
+        
+        let mut status = if interface.is_up() { "UP".green().to_string() } else { "DOWN".red().to_string() };
+        if interface.is_loopback() {
+            status.push_str(&format!(" {}", "LOOPBACK".yellow()));
         }
+        
+        table.add_row(Row::new(vec![
+            Cell::new(&interface.name),
+            Cell::new(&interface.description),
+            Cell::new(&mac),
+            Cell::new(&ips),
+            Cell::new(&mtu),
+            Cell::new(&status),
+        ]));
     }
     
-    if country_counts.is_empty() {
-        println!("   {}", "No geographic data available...".bright_black());
-        println!();
-        return;
+    table.printstd();
+    outln!();
+    outln!("{}", "💡 Usage example:".yellow().bold());
+    outln!("{}", "  sudo cargo run -- --interface eth0 --dashboard".cyan());
+    outln!("{}", "  sudo cargo run -- --interface wlan0 --protocol http --verbose".cyan());
+}
+
+#[derive(Serialize)]
+struct InterfaceSummary {
+    name: String,
+    description: String,
+    mac: Option<String>,
+    /// Addresses with their prefix length, e.g. "192.168.1.10/24"
+    ips: Vec<String>,
+    up: bool,
+    loopback: bool,
+    mtu: Option<u32>,
+}
+
+fn list_interfaces_json(logger: &mut Logger) {
+    let interfaces: Vec<InterfaceSummary> = datalink::interfaces().into_iter()
+        .map(|interface| InterfaceSummary {
+            mtu: interface_mtu(&interface.name),
+            mac: interface.mac.map(|mac| mac.to_string()),
+            ips: interface.ips.iter().map(|ip| ip.to_string()).collect(),
+            up: interface.is_up(),
+            loopback: interface.is_loopback(),
+            name: interface.name,
+            description: interface.description,
+        })
+        .collect();
+    
+    match serde_json::to_string_pretty(&interfaces) {
+        // A closed pipe (e.g. `| head`) just means nobody wants the rest
+        Ok(json) => {
+            let _ = writeln!(std::io::stdout(), "{}", json);
+        }
+        Err(e) => logger.log_error(&format!("Failed to serialize interfaces: {}", e)),
+    }
+}
+
+/// pnet doesn't report the MTU; Linux has it in sysfs, elsewhere it's left out.
+fn interface_mtu(name: &str) -> Option<u32> {
+    if cfg!(target_os = "linux") {
+        std::fs::read_to_string(format!("/sys/class/net/{}/mtu", name)).ok()?.trim().parse().ok()
+    } else {
+        None
     }
+}
+
+fn find_interface(name: &str) -> Option<NetworkInterface> {
+    datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.name == name)
+}
+
+/// The interface names, or the stdin source under `--read-stdin`.
+fn interface_list(interfaces: &[NetworkInterface]) -> Vec<&str> {
+    if interfaces.is_empty() {
+        return vec![capture::STDIN_SOURCE];
+    }
+    interfaces.iter().map(|iface| iface.name.as_str()).collect()
+}
+
+fn interface_names(interfaces: &[NetworkInterface]) -> String {
+    interface_list(interfaces).join(", ")
+}
+
+fn start_dashboard_mode(interfaces: &[NetworkInterface], args: Args, filter: CaptureFilter, config: Config, mut logger: Logger) -> Result<()> {
+    outln!("{}", "🚀 Starting Interactive Dashboard Mode".green().bold());
+    outln!("{}", format!("📡 Interface: {}", interface_names(interfaces)).cyan());
+    outln!("{}", "Press Q or Ctrl+C to stop, H for help".yellow());
+    outln!();
+    
+    let runtime = tokio::runtime::Runtime::new()?;
+    let context = AnalysisContext::new(&config, &args, interfaces, &mut logger)?;
+    let names = context.names.clone();
+    let export_paths = ExportPaths::new(&config.export, &interface_list(interfaces), args.compress);
+    let streams = StreamingExports::open(&args, &export_paths)?;
+    let capture = if args.read_stdin {
+        capture::start_stdin_async(&filter)?
+    } else {
+        capture::start_async(interfaces, channel_config(&config.performance, args.promiscuous), &filter)?
+    };
     
-    let mut countries: Vec<_> = country_counts.iter().collect();
-    countries.sort_by(|a, b| b.1.cmp(a.1));
+    let session = start_session(&config, &args)?;
+    if let Some(ref path) = args.load_session {
+        logger.log_info(&format!("Resumed the session in {} at {} packets", path, session.stats.total_packets));
+    }
+    let (pipeline, mut snapshots) = Pipeline::spawn(&runtime, capture, args.clone(), context, streams, session, &config);
+    if let Some(port) = args.api_port {
+        api::serve(port, args.api_cors_origin.clone(), snapshots.clone())?;
+        logger.log_info(&format!("Serving the JSON API on http://127.0.0.1:{}", port));
+    }
     
-    print!("   ");
-    for (country, count) in countries.iter().take(6) {
-        let flag = match country.as_str() {
-            "United States" => "🇺🇸",
-            "United Kingdom" => "🇬🇧", 
-            "Australia" => "🇦🇺",
-            "Germany" => "🇩🇪",
-            "France" => "🇫🇷",
-            "Local Network" => "🏠",
-            _ => "🌐",
-        };
-        
-        print!("{} {}: {} ", flag, country.cyan(), count.to_string().yellow());
+    // Runs until Q/Ctrl+C or the capture hits its limits
+    let exports = dashboard::run(&mut snapshots, dashboard_refresh_interval(&config.performance), args.sort_talkers, &names, &export_paths)?;
+    for export in &exports {
+        logger.log_export("JSON", &export.filename, export.packets);
     }
-    println!("\n");
+    
+    // Frame reads time out, so the dispatch task notices the shutdown flag promptly
+    let outcome = pipeline.finish(&runtime);
+    
+    finish_capture(&outcome.packets, &outcome.stats, outcome.streams, outcome.reassembly.as_ref(), &args, &export_paths, &mut logger)
+}
+
+fn dashboard_refresh_interval(performance: &PerformanceConfig) -> Duration {
+    Duration::from_millis(performance.dashboard_refresh_rate)
 }
 
-fn display_recent_activity(packets: &Vec<PacketInfo>) {
-    println!("{}", "📋 LIVE ACTIVITY STREAM".yellow().bold());
+/// Datalink settings derived from the performance config. We never transmit,
+/// so the write buffer keeps pnet's default. Reads always time out, otherwise
+/// an idle link would block the capture loop indefinitely.
+fn channel_config(performance: &PerformanceConfig, promiscuous: bool) -> datalink::Config {
+    datalink::Config {
+        read_buffer_size: performance.buffer_size.max(MIN_CAPTURE_BUFFER_SIZE),
+        read_timeout: Some(CAPTURE_READ_TIMEOUT),
+        // pnet defaults to on; only --promiscuous asks for it here
+        promiscuous,
+        ..Default::default()
+    }
+}
+
+/// True once either `--count` or `--duration` has been reached.
+fn capture_limit_reached(args: &Args, packet_count: usize, started: Instant) -> bool {
+    if args.count > 0 && packet_count >= args.count {
+        return true;
+    }
+    args.duration.is_some_and(|secs| started.elapsed() >= Duration::from_secs(secs))
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
     
-    if packets.is_empty() {
-        println!("   {}", "Waiting for network activity...".bright_black());
-        println!();
-        return;
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
     }
     
-    for packet in packets.iter().rev().take(8) {
-        let timestamp = packet.timestamp.format("%H:%M:%S%.1f").to_string();
-        let threat_icon = match packet.threat_level {
-            ThreatLevel::Safe => "✅",
-            ThreatLevel::Low => "🟡",
-            ThreatLevel::Medium => "🟠", 
-            ThreatLevel::High => "🔴",
-            ThreatLevel::Critical => "💀",
-        };
-        
-        let app_proto = packet.application_protocol.as_ref()
-            .map(|s| format!(" ({})", s))
-            .unwrap_or_default();
-            
-        let geo_info = packet.geo_info.as_ref()
-            .and_then(|g| g.country.as_ref())
-            .map(|c| if c == "Local Network" { "🏠" } else { "🌐" })
-            .unwrap_or("");
-        
-        println!("   {} {} {} {} {} → {} {} {}{}", 
-                 threat_icon,
-                 timestamp.bright_black(),
-                 packet.protocol.green().bold(),
-                 app_proto.yellow(),
-                 packet.src_ip.as_ref().unwrap_or(&"?".to_string()).blue(),
-                 packet.dst_ip.as_ref().unwrap_or(&"?".to_string()).blue(),
-                 geo_info,
-                 format_bytes(packet.packet_size).cyan(),
-                 if packet.packet_size > 1000 { " 📈" } else { "" });
-    }
-    println!();
-}
-
-fn analyze_packet_advanced(packet: &[u8], packet_num: usize) -> PacketInfo {
-    let timestamp = Utc::now();
+    format!("{:.1} {}", size, UNITS[unit_index])
+}
+
+/// Nearest-rank p50, p90 and p99 of `sizes`, or `None` when there are none.
+fn size_percentiles(sizes: &VecDeque<usize>) -> Option<[usize; 3]> {
+    if sizes.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<usize> = sizes.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = |percentile: usize| sorted[(percentile * sorted.len()).div_ceil(100) - 1];
+    Some([rank(50), rank(90), rank(99)])
+}
+
+fn analyze_packet_advanced(frame: &CapturedFrame, context: &mut AnalysisContext) -> PacketInfo {
+    let packet = frame.data.as_slice();
     let packet_size = packet.len();
     
-    let mut packet_info = PacketInfo {
-        timestamp,
-        packet_number: packet_num,
-        src_mac: String::new(),
-        dst_mac: String::new(),
-        src_ip: None,
-        dst_ip: None,
-        protocol: "Unknown".to_string(),
-        src_port: None,
-        dst_port: None,
-        packet_size,
-        flags: None,
-        payload_size: 0,
-        application_protocol: None,
-        description: "Unknown packet".to_string(),
-        threat_level: ThreatLevel::Safe,
-        geo_info: None,
-    };
+    let mut packet_info = PacketInfo::new(frame.timestamp, &frame.interface, packet_size);
     
     if let Some(ethernet_packet) = EthernetPacket::new(packet) {
         packet_info.src_mac = ethernet_packet.get_source().to_string();
         packet_info.dst_mac = ethernet_packet.get_destination().to_string();
+        packet_info.src_vendor = context.vendors.vendor(&packet_info.src_mac);
+        packet_info.dst_vendor = context.vendors.vendor(&packet_info.dst_mac);
+    }
+    
+    if let Some(frame) = vlan::untag(packet) {
+        packet_info.vlan_id = frame.vlan_ids.first().copied();
         
-        match ethernet_packet.get_ethertype() {
+        match frame.ethertype {
             EtherTypes::Ipv4 => {
-                if let Some(ipv4_packet) = Ipv4Packet::new(ethernet_packet.payload()) {
-                    packet_info.src_ip = Some(ipv4_packet.get_source().to_string());
-                    packet_info.dst_ip = Some(ipv4_packet.get_destination().to_string());
-                    
-                    match ipv4_packet.get_next_level_protocol() {
-                        pnet::packet::ip::IpNextHeaderProtocols::Tcp => {
-                            packet_info.protocol = "TCP".to_string();
-                            if let Some(tcp_packet) = TcpPacket::new(ipv4_packet.payload()) {
-                                packet_info.src_port = Some(tcp_packet.get_source());
-                                packet_info.dst_port = Some(tcp_packet.get_destination());
-                                packet_info.payload_size = tcp_packet.payload().len();
-                                
-                                let flags = tcp_packet.get_flags();
-                                let mut flag_str = String::new();
-                                if flags & 0x01 != 0 { flag_str.push_str("FIN "); }
-                                if flags & 0x02 != 0 { flag_str.push_str("SYN "); }
-                                if flags & 0x04 != 0 { flag_str.push_str("RST "); }
-                                if flags & 0x08 != 0 { flag_str.push_str("PSH "); }
-                                if flags & 0x10 != 0 { flag_str.push_str("ACK "); }
-                                if flags & 0x20 != 0 { flag_str.push_str("URG "); }
-                                packet_info.flags = Some(flag_str.trim().to_string());
-                                
-                                // Detect application protocols
-                                packet_info.application_protocol = detect_application_protocol(tcp_packet.get_destination(), tcp_packet.payload());
-                                packet_info.description = format_packet_description(&packet_info);
-                            }
-                        }
-                        pnet::packet::ip::IpNextHeaderProtocols::Udp => {
-                            packet_info.protocol = "UDP".to_string();
-                            if let Some(udp_packet) = UdpPacket::new(ipv4_packet.payload()) {
-                                packet_info.src_port = Some(udp_packet.get_source());
-                                packet_info.dst_port = Some(udp_packet.get_destination());
-                                packet_info.payload_size = udp_packet.payload().len();
-                                
-                                packet_info.application_protocol = detect_application_protocol(udp_packet.get_destination(), udp_packet.payload());
-                                packet_info.description = format_packet_description(&packet_info);
-                            }
-                        }
-                        pnet::packet::ip::IpNextHeaderProtocols::Icmp => {
-                            packet_info.protocol = "ICMP".to_string();
-                            if let Some(_icmp_packet) = IcmpPacket::new(ipv4_packet.payload()) {
-                                packet_info.description = "ICMP ping/echo message".to_string();
-                            }
-                        }
-                        _ => {
-                            packet_info.protocol = format!("IPv4-{:?}", ipv4_packet.get_next_level_protocol());
-                        }
-                    }
+                decode_ipv4(&mut packet_info, context, frame.payload);
+            }
+            EtherTypes::Arp => {
+                packet_info.protocol = "ARP".to_string();
+                if let Some(arp) = arp::parse(frame.payload) {
+                    packet_info.src_ip = Some(arp.sender_ip.clone());
+                    packet_info.dst_ip = Some(arp.target_ip.clone());
+                    packet_info.description = arp.summary();
+                    packet_info.arp = Some(arp);
                 }
             }
             EtherTypes::Ipv6 => {
-                packet_info.protocol = "IPv6".to_string();
-                packet_info.description = "IPv6 packet (parsing not fully implemented)".to_string();
+                decode_ipv6(&mut packet_info, context, frame.payload);
             }
             _ => {
-                packet_info.protocol = format!("{:?}", ethernet_packet.get_ethertype());
+                packet_info.protocol = format!("{:?}", frame.ethertype);
             }
         }
     }
     
+    // A tunneled packet crossed the interface between the tunnel endpoints
+    let endpoints = match packet_info.gre {
+        Some(ref gre) => (Some(&gre.src_ip), Some(&gre.dst_ip)),
+        None => (packet_info.src_ip.as_ref(), packet_info.dst_ip.as_ref()),
+    };
+    if let (Some(src_ip), Some(dst_ip)) = endpoints {
+        packet_info.direction = context.local.classify_text(src_ip, dst_ip);
+    }
+    
     // Add threat detection
-    packet_info.threat_level = detect_threat_level(&packet_info);
+    packet_info.threat_level = detect_threat_level(&packet_info, &context.threat);
     
-    // Add geographical information (simplified for demo)
+    // Add geographical information
     if let Some(ref dst_ip) = packet_info.dst_ip {
-        packet_info.geo_info = get_geo_info(dst_ip);
+        packet_info.geo_info = context.geoip.get_geo_info(dst_ip);
+    }
+    
+    // Queue PTR lookups; names show up once the background workers answer
+    for ip in [&packet_info.src_ip, &packet_info.dst_ip].into_iter().flatten() {
+        context.names.resolve(ip);
     }
     
     packet_info
 }
 
-fn detect_application_protocol(port: u16, payload: &[u8]) -> Option<String> {
-    match port {
-        80 | 8080 => {
-            // Check for HTTP
-            if payload.len() > 0 {
-                let payload_str = String::from_utf8_lossy(&payload[..std::cmp::min(100, payload.len())]);
-                if payload_str.starts_with("GET") || payload_str.starts_with("POST") || 
-                   payload_str.starts_with("HTTP") || payload_str.contains("Host:") {
-                    return Some("HTTP".to_string());
-                }
+/// `false` when `payload` is too short for an IPv4 header.
+fn decode_ipv4(packet_info: &mut PacketInfo, context: &mut AnalysisContext, payload: &[u8]) -> bool {
+    let Some(ipv4_packet) = Ipv4Packet::new(payload) else {
+        return false;
+    };
+    packet_info.src_ip = Some(ipv4_packet.get_source().to_string());
+    packet_info.dst_ip = Some(ipv4_packet.get_destination().to_string());
+    packet_info.ttl = Some(ipv4_packet.get_ttl());
+    
+    decode_transport(packet_info, context, "IPv4", ipv4_packet.get_next_level_protocol(), ipv4_packet.payload());
+    true
+}
+
+fn decode_ipv6(packet_info: &mut PacketInfo, context: &mut AnalysisContext, payload: &[u8]) -> bool {
+    packet_info.protocol = "IPv6".to_string();
+    let Some(ipv6_packet) = Ipv6Packet::new(payload) else {
+        return false;
+    };
+    packet_info.src_ip = Some(ipv6_packet.get_source().to_string());
+    packet_info.dst_ip = Some(ipv6_packet.get_destination().to_string());
+    packet_info.ttl = Some(ipv6_packet.get_hop_limit());
+    decode_transport(packet_info, context, "IPv6", ipv6_packet.get_next_header(), ipv6_packet.payload());
+    true
+}
+
+/// Records the tunnel and decodes the IP packet inside it, directly or in a
+/// bridged Ethernet frame, over the outer addresses. Only one level is
+/// decoded: a tunnel inside the tunnel stays a GRE packet.
+fn decode_gre(packet_info: &mut PacketInfo, context: &mut AnalysisContext, payload: &[u8]) {
+    packet_info.protocol = "GRE".to_string();
+    let Some(gre) = gre::parse(payload) else {
+        packet_info.description = "GRE tunnel packet".to_string();
+        return;
+    };
+    let inner_protocol = gre::protocol_name(gre.protocol_type);
+    if packet_info.gre.is_some() {
+        packet_info.description = format!("Nested GRE tunnel carrying {}", inner_protocol);
+        return;
+    }
+    
+    let tunnel = GreInfo {
+        src_ip: packet_info.src_ip.clone().unwrap_or_default(),
+        dst_ip: packet_info.dst_ip.clone().unwrap_or_default(),
+        inner_protocol,
+        key: gre.key,
+    };
+    let summary = tunnel.summary();
+    packet_info.description = format!("GRE tunnel carrying {}", tunnel.inner_protocol);
+    packet_info.gre = Some(tunnel);
+    
+    let inner = match gre.protocol_type.0 {
+        gre::TRANSPARENT_ETHERNET => vlan::untag(gre.payload).map(|frame| (frame.ethertype, frame.payload)),
+        _ => Some((gre.protocol_type, gre.payload)),
+    };
+    let decoded = match inner {
+        Some((EtherTypes::Ipv4, inner)) => decode_ipv4(packet_info, context, inner),
+        Some((EtherTypes::Ipv6, inner)) => decode_ipv6(packet_info, context, inner),
+        _ => false,
+    };
+    if decoded {
+        packet_info.description = format!("Tunneled via {}: {}", summary, packet_info.description);
+    } else {
+        packet_info.protocol = "GRE".to_string();
+    }
+}
+
+/// Feeds a TCP segment to `--follow-stream`. Segments have to arrive in
+/// capture order, so this runs where frames are numbered, not in the
+/// analysis workers.
+fn follow_stream(reassembly: &mut TcpReassembler, packet: &[u8]) {
+    let Some(frame) = vlan::untag(packet) else {
+        return;
+    };
+    match frame.ethertype {
+        EtherTypes::Ipv4 => {
+            if let Some(ipv4) = Ipv4Packet::new(frame.payload).filter(|ipv4| ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Tcp) {
+                observe_segment(reassembly, IpAddr::V4(ipv4.get_source()), IpAddr::V4(ipv4.get_destination()), ipv4.payload());
+            }
+        }
+        EtherTypes::Ipv6 => {
+            if let Some(ipv6) = Ipv6Packet::new(frame.payload).filter(|ipv6| ipv6.get_next_header() == IpNextHeaderProtocols::Tcp) {
+                observe_segment(reassembly, IpAddr::V6(ipv6.get_source()), IpAddr::V6(ipv6.get_destination()), ipv6.payload());
             }
-            Some("Web Traffic".to_string())
-        }
-        443 => Some("HTTPS".to_string()),
-        53 => Some("DNS".to_string()),
-        22 => Some("SSH".to_string()),
-        21 => Some("FTP".to_string()),
-        25 => Some("SMTP".to_string()),
-        110 => Some("POP3".to_string()),
-        143 => Some("IMAP".to_string()),
-        993 => Some("IMAPS".to_string()),
-        995 => Some("POP3S".to_string()),
-        _ => None,
-    }
-}
-
-fn detect_threat_level(packet_info: &PacketInfo) -> ThreatLevel {
-    // Sophisticated threat detection based on multiple factors
-    let mut risk_score = 0;
-    
-    // Check for suspicious ports
-    if let Some(port) = packet_info.dst_port.or(packet_info.src_port) {
-        match port {
-            // High-risk ports
-            1433 | 3389 | 5900 | 23 | 135 | 139 | 445 => risk_score += 3,
-            // Medium-risk ports  
-            21 | 25 | 110 | 143 | 993 | 995 => risk_score += 2,
-            // Unusual high ports
-            p if p > 49152 => risk_score += 1,
-            _ => {}
         }
+        _ => {}
     }
-    
-    // Check for suspicious IP patterns
-    if let Some(ref ip) = packet_info.dst_ip {
-        // Private IP ranges are generally safer
-        if !is_private_ip(ip) {
-            risk_score += 1;
+}
+
+fn observe_segment(reassembly: &mut TcpReassembler, src_ip: IpAddr, dst_ip: IpAddr, payload: &[u8]) {
+    let Some(tcp_packet) = TcpPacket::new(payload) else {
+        return;
+    };
+    let flags = tcp_packet.get_flags();
+    reassembly.observe(&Segment {
+        src_ip,
+        src_port: tcp_packet.get_source(),
+        dst_ip,
+        dst_port: tcp_packet.get_destination(),
+        sequence: tcp_packet.get_sequence(),
+        syn: flags & 0x02 != 0,
+        ack: flags & 0x10 != 0,
+        fin: flags & 0x01 != 0,
+        rst: flags & 0x04 != 0,
+        payload: tcp_packet.payload(),
+    });
+}
+
+/// Decodes the layer-4 header and anything recognisable above it. Shared by
+/// IPv4 and IPv6; extension headers aren't walked, so IPv6 packets that use
+/// them show up as e.g. "IPv6-Ipv6Frag".
+fn decode_transport(packet_info: &mut PacketInfo, context: &mut AnalysisContext, ip_version: &str, next_protocol: IpNextHeaderProtocol, payload: &[u8]) {
+    match next_protocol {
+        pnet::packet::ip::IpNextHeaderProtocols::Tcp => {
+            packet_info.protocol = "TCP".to_string();
+            if let Some(tcp_packet) = TcpPacket::new(payload) {
+                packet_info.src_port = Some(tcp_packet.get_source());
+                packet_info.dst_port = Some(tcp_packet.get_destination());
+                packet_info.payload_size = tcp_packet.payload().len();
+                
+                let flags = tcp_packet.get_flags();
+                let mut flag_str = String::new();
+                if flags & 0x01 != 0 { flag_str.push_str("FIN "); }
+                if flags & 0x02 != 0 { flag_str.push_str("SYN "); }
+                if flags & 0x04 != 0 { flag_str.push_str("RST "); }
+                if flags & 0x08 != 0 { flag_str.push_str("PSH "); }
+                if flags & 0x10 != 0 { flag_str.push_str("ACK "); }
+                if flags & 0x20 != 0 { flag_str.push_str("URG "); }
+                packet_info.flags = Some(flag_str.trim().to_string());
+                packet_info.tcp_window = Some(tcp_packet.get_window());
+                packet_info.tcp_sequence = Some(tcp_packet.get_sequence());
+                packet_info.tcp_ack = (flags & 0x10 != 0).then(|| tcp_packet.get_acknowledgement());
+                
+                // Detect application protocols
+                let app = decoders::decode(&context.custom_ports, decoders::TCP, tcp_packet.get_source(), tcp_packet.get_destination(), tcp_packet.payload());
+                apply_app_data(packet_info, app);
+                packet_info.description = format_packet_description(packet_info);
+            }
         }
-        
-        // Check for known malicious patterns (simplified)
-        if ip.starts_with("10.0.0.") || ip.starts_with("169.254.") {
-            risk_score += 2;
+        pnet::packet::ip::IpNextHeaderProtocols::Udp => {
+            packet_info.protocol = "UDP".to_string();
+            if let Some(udp_packet) = UdpPacket::new(payload) {
+                packet_info.src_port = Some(udp_packet.get_source());
+                packet_info.dst_port = Some(udp_packet.get_destination());
+                packet_info.payload_size = udp_packet.payload().len();
+                
+                let app = decoders::decode(&context.custom_ports, decoders::UDP, udp_packet.get_source(), udp_packet.get_destination(), udp_packet.payload());
+                apply_app_data(packet_info, app);
+                packet_info.description = format_packet_description(packet_info);
+            }
+        }
+        pnet::packet::ip::IpNextHeaderProtocols::Sctp => {
+            packet_info.protocol = "SCTP".to_string();
+            if let Some(sctp) = sctp::parse(payload) {
+                (packet_info.src_port, packet_info.dst_port) = sctp::ports(payload).unzip();
+                packet_info.payload_size = payload.len() - sctp::COMMON_HEADER_LEN;
+                packet_info.description = sctp.summary();
+                packet_info.sctp = Some(sctp);
+            }
+        }
+        pnet::packet::ip::IpNextHeaderProtocols::Icmp => {
+            packet_info.protocol = "ICMP".to_string();
+            if let Some(icmp_packet) = IcmpPacket::new(payload) {
+                let (icmp_type, code) = (icmp_packet.get_icmp_type().0, icmp_packet.get_icmp_code().0);
+                packet_info.icmp_type = Some(icmp_type);
+                packet_info.icmp_code = Some(code);
+                packet_info.description = icmp::describe_v4(icmp_type, code);
+            }
+        }
+        pnet::packet::ip::IpNextHeaderProtocols::Gre => decode_gre(packet_info, context, payload),
+        pnet::packet::ip::IpNextHeaderProtocols::Igmp => {
+            packet_info.protocol = "IGMP".to_string();
+            packet_info.description = "IGMP message".to_string();
+            if let Some(igmp) = igmp::parse(payload) {
+                packet_info.description = igmp.summary();
+                packet_info.igmp = Some(igmp);
+            }
+        }
+        pnet::packet::ip::IpNextHeaderProtocols::Icmpv6 => {
+            packet_info.protocol = "ICMPv6".to_string();
+            if let Some(icmpv6_packet) = Icmpv6Packet::new(payload) {
+                let (icmp_type, code) = (icmpv6_packet.get_icmpv6_type().0, icmpv6_packet.get_icmpv6_code().0);
+                packet_info.icmp_type = Some(icmp_type);
+                packet_info.icmp_code = Some(code);
+                packet_info.description = icmp::describe_v6(icmp_type, code);
+            }
+        }
+        _ => {
+            packet_info.protocol = format!("{}-{:?}", ip_version, next_protocol);
+        }
+    }
+}
+
+/// Stores what the decoders made of a payload on `packet_info`.
+fn apply_app_data(packet_info: &mut PacketInfo, app: Option<AppData>) {
+    let Some(app) = app else {
+        return;
+    };
+    packet_info.application_protocol = Some(app.protocol);
+    match app.detail {
+        AppDetail::None => {}
+        AppDetail::Http(http) => packet_info.http_info = Some(http),
+        AppDetail::Dns(dns) => {
+            packet_info.dns_query = dns.query;
+            packet_info.dns_answers = dns.answers;
         }
+        AppDetail::TlsSni(sni) => packet_info.tls_sni = Some(sni),
+        AppDetail::Dhcp(dhcp) => packet_info.dhcp = Some(dhcp),
     }
+}
+
+fn has_tcp_flag(packet_info: &PacketInfo, flag: &str) -> bool {
+    packet_info.flags.as_deref().is_some_and(|flags| flags.split_whitespace().any(|f| f == flag))
+}
+
+/// Moves a flow's handshake state forward based on this packet's TCP flags.
+fn advance_handshake(state: &HandshakeState, packet_info: &PacketInfo) -> HandshakeState {
+    let syn = has_tcp_flag(packet_info, "SYN");
+    let ack = has_tcp_flag(packet_info, "ACK");
     
-    // Check packet size anomalies
-    if packet_info.packet_size > 1500 || packet_info.packet_size < 64 {
-        risk_score += 1;
+    if has_tcp_flag(packet_info, "RST") || has_tcp_flag(packet_info, "FIN") {
+        return HandshakeState::Closed;
     }
     
-    // Check for suspicious protocols
+    match (state, syn, ack) {
+        (_, true, false) => HandshakeState::SynSent,
+        (_, true, true) => HandshakeState::SynReceived,
+        // A bare ACK completes a handshake, or means we joined mid-stream
+        (HandshakeState::Closed, false, _) => HandshakeState::Closed,
+        (_, false, true) => HandshakeState::Established,
+        (state, false, false) => state.clone(),
+    }
+}
+
+/// TCP SYNs without ACK, or UDP to well-known ports. Replies to ephemeral
+/// client ports are excluded so they don't look like scans.
+fn is_connection_attempt(packet_info: &PacketInfo) -> bool {
     match packet_info.protocol.as_str() {
-        "ICMP" => risk_score += 1, // Could be scanning
-        "UDP" if packet_info.dst_port == Some(53) => {}, // DNS is normal
-        "UDP" => risk_score += 1, // Other UDP could be suspicious
-        _ => {}
+        "TCP" => has_tcp_flag(packet_info, "SYN") && !has_tcp_flag(packet_info, "ACK"),
+        "UDP" => packet_info.dst_port.is_some_and(|port| port <= 1024),
+        _ => false,
+    }
+}
+
+fn detect_threat_level(packet_info: &PacketInfo, rules: &ThreatRules) -> ThreatLevel {
+    // Known-bad hosts win over everything, then explicitly trusted ones
+    if rules.blocklisted_ip(packet_info).is_some() {
+        return ThreatLevel::Critical;
+    }
+    if rules.is_allowlisted(packet_info) {
+        return ThreatLevel::Safe;
     }
     
-    // Convert risk score to threat level
-    match risk_score {
-        0..=1 => ThreatLevel::Safe,
-        2..=3 => ThreatLevel::Low,
-        4..=5 => ThreatLevel::Medium,
-        6..=7 => ThreatLevel::High,
-        _ => ThreatLevel::Critical,
+    threat_level_for(risk_score(packet_info, &rules.config), &rules.config.level_thresholds)
+}
+
+/// Sum of every check that matches the packet.
+fn risk_score(packet_info: &PacketInfo, threat: &ThreatConfig) -> u32 {
+    port_risk(packet_info.dst_port.or(packet_info.src_port), threat)
+        + packet_info.dst_ip.as_deref().map_or(0, |ip| address_risk(ip, threat))
+        + size_risk(packet_info.packet_size, threat)
+        + protocol_risk(packet_info, threat)
+}
+
+fn port_risk(port: Option<u16>, threat: &ThreatConfig) -> u32 {
+    match port {
+        Some(port) if threat.high_risk_ports.contains(&port) => threat.high_risk_port_score,
+        Some(port) if threat.medium_risk_ports.contains(&port) => threat.medium_risk_port_score,
+        Some(port) if port > threat.unusual_port_threshold => threat.unusual_port_score,
+        _ => 0,
     }
 }
 
-fn is_private_ip(ip: &str) -> bool {
-    ip.starts_with("10.") || 
-    ip.starts_with("192.168.") || 
-    ip.starts_with("172.16.") ||
-    ip.starts_with("127.") ||
-    ip.starts_with("::1") ||
-    ip.starts_with("fe80::")
+/// Private ranges and multicast groups score nothing, link-local IPv4 is
+/// unusual, public is risky. The three are exclusive, so no address is both
+/// trusted and suspicious.
+fn address_risk(ip: &str, threat: &ThreatConfig) -> u32 {
+    let Ok(ip) = ip.parse::<IpAddr>() else {
+        return threat.public_ip_score;
+    };
+    if is_link_local_addr(ip) {
+        threat.suspicious_ip_score
+    } else if is_private_addr(ip) || ip.is_multicast() {
+        0
+    } else {
+        threat.public_ip_score
+    }
 }
 
-fn get_geo_info(ip: &str) -> Option<GeoInfo> {
-    // Simplified geolocation (in production, use MaxMind GeoIP2 or similar)
-    if is_private_ip(ip) {
-        return Some(GeoInfo {
-            country: Some("Local Network".to_string()),
-            city: Some("Local".to_string()),
-            latitude: None,
-            longitude: None,
-        });
+fn size_risk(packet_size: usize, threat: &ThreatConfig) -> u32 {
+    if packet_size > threat.max_normal_packet_size {
+        threat.oversize_packet_score
+    } else if packet_size < threat.min_normal_packet_size {
+        threat.size_anomaly_score
+    } else {
+        0
     }
-    
-    // For demo purposes, return some sample data based on IP patterns
+}
+
+fn protocol_risk(packet_info: &PacketInfo, threat: &ThreatConfig) -> u32 {
+    match packet_info.protocol.as_str() {
+        "ICMP" => threat.icmp_score, // Could be scanning
+        "UDP" if packet_info.dst_port == Some(53) => 0, // DNS is normal
+        "UDP" => threat.udp_score, // Other UDP could be suspicious
+        _ => 0,
+    }
+}
+
+fn threat_level_for(risk_score: u32, thresholds: &ThreatThresholds) -> ThreatLevel {
+    if risk_score >= thresholds.critical {
+        ThreatLevel::Critical
+    } else if risk_score >= thresholds.high {
+        ThreatLevel::High
+    } else if risk_score >= thresholds.medium {
+        ThreatLevel::Medium
+    } else if risk_score >= thresholds.low {
+        ThreatLevel::Low
+    } else {
+        ThreatLevel::Safe
+    }
+}
+
+/// Whether traffic to `ip` stays on the local network: private or link-local.
+/// Anything that doesn't parse as an address is not local.
+fn is_local_ip(ip: &str) -> bool {
+    ip.parse().is_ok_and(|ip| is_private_addr(ip) || is_link_local_addr(ip))
+}
+
+/// 169.254.0.0/16 and fe80::/10, IPv4-mapped ones included. Never private,
+/// and scored as suspicious rather than public.
+fn is_link_local_addr(ip: IpAddr) -> bool {
     match ip {
-        ip if ip.starts_with("8.8.") => Some(GeoInfo {
-            country: Some("United States".to_string()),
-            city: Some("Mountain View".to_string()),
-            latitude: Some(37.4056),
-            longitude: Some(-122.0775),
-        }),
-        ip if ip.starts_with("1.1.") => Some(GeoInfo {
-            country: Some("Australia".to_string()),
-            city: Some("Sydney".to_string()),
-            latitude: Some(-33.8688),
-            longitude: Some(151.2093),
-        }),
-        _ => Some(GeoInfo {
-            country: Some("Unknown".to_string()),
-            city: Some("Unknown".to_string()),
-            latitude: None,
-            longitude: None,
-        }),
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => ip.is_link_local(),
+            None => ip.segments()[0] & 0xffc0 == 0xfe80,
+        },
+    }
+}
+
+/// Whether `ip` is in a private range: RFC 1918 and loopback for IPv4;
+/// loopback and unique-local (fc00::/7) for IPv6. IPv4-mapped IPv6 addresses
+/// are judged as the IPv4 address they carry. Link-local addresses are a class
+/// of their own.
+fn is_private_addr(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_loopback(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_addr(IpAddr::V4(ip)),
+            None => ip.is_loopback() || ip.segments()[0] & 0xfe00 == 0xfc00,
+        },
     }
 }
 
@@ -1092,9 +2214,26 @@ fn format_packet_description(packet_info: &PacketInfo) -> String {
     match packet_info.application_protocol.as_ref() {
         Some(app_proto) => {
             match app_proto.as_str() {
-                "HTTP" => "Web browsing (HTTP request/response)".to_string(),
-                "HTTPS" => "Secure web browsing (encrypted)".to_string(),
-                "DNS" => "Domain name lookup".to_string(),
+                "HTTP" => match packet_info.http_info {
+                    Some(ref http) => format!("Web request: {}", http.summary()),
+                    None => "Web browsing (HTTP request/response)".to_string(),
+                },
+                "HTTPS" => match packet_info.tls_sni {
+                    Some(ref sni) => format!("Secure web browsing to {} (encrypted)", sni),
+                    None => "Secure web browsing (encrypted)".to_string(),
+                },
+                "QUIC" => "Secure web browsing over QUIC (HTTP/3, encrypted)".to_string(),
+                "DNS" => match packet_info.dns_query {
+                    Some(ref query) => format!("Domain name lookup: {}", query),
+                    None => "Domain name lookup".to_string(),
+                },
+                "mDNS" => "Local service discovery (multicast DNS)".to_string(),
+                "NTP" => "Time synchronization".to_string(),
+                "SNMP" => "Network device management".to_string(),
+                "DHCP" => match packet_info.dhcp {
+                    Some(ref dhcp) => dhcp.summary(),
+                    None => "Network address assignment".to_string(),
+                },
                 "SSH" => "Secure shell connection".to_string(),
                 "FTP" => "File transfer".to_string(),
                 "SMTP" => "Email sending".to_string(),
@@ -1125,99 +2264,419 @@ fn format_packet_description(packet_info: &PacketInfo) -> String {
     }
 }
 
-fn start_sniffing(interface: NetworkInterface, args: Args, config: Config, mut logger: Logger) -> Result<()> {
-    use pnet::datalink::Channel::Ethernet;
-    
-    let start_time = Instant::now();
-    
-    println!("{}", "🚀 Starting Advanced Packet Capture".green().bold());
-    println!("{}", format!("📡 Interface: {}", interface.name).cyan());
-    if let Some(ref protocol) = args.protocol {
-        println!("{}", format!("🔍 Protocol Filter: {}", protocol).yellow());
+fn start_sniffing(interfaces: &[NetworkInterface], args: Args, filter: CaptureFilter, config: Config, mut logger: Logger) -> Result<()> {
+    outln!("{}", "🚀 Starting Advanced Packet Capture".green().bold());
+    outln!("{}", format!("📡 Interface: {}", interface_names(interfaces)).cyan());
+    if !filter.protocols().is_empty() {
+        outln!("{}", format!("🔍 Protocol Filter: {}", filter.protocols().join(", ")).yellow());
+    }
+    if let Some(ref preset) = args.preset {
+        outln!("{}", format!("🎛️  Filter Preset: {}", preset).yellow());
+    }
+    if !args.port.is_empty() {
+        let ports: Vec<String> = args.port.iter().map(|port| port.to_string()).collect();
+        outln!("{}", format!("🚪 Port Filter: {}", ports.join(", ")).yellow());
+    }
+    for (label, ips) in [("Source IP", &args.src_ip), ("Destination IP", &args.dst_ip), ("Host", &args.host)] {
+        if !ips.is_empty() {
+            let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+            outln!("{}", format!("🖥️  {} Filter: {}", label, ips.join(", ")).yellow());
+        }
+    }
+    if !filter.subnets().is_empty() {
+        let subnets: Vec<String> = filter.subnets().iter().map(|net| net.to_string()).collect();
+        outln!("{}", format!("🕸️  Subnet Filter: {}", subnets.join(", ")).yellow());
+    }
+    if !filter.vlans().is_empty() {
+        let vlans: Vec<String> = filter.vlans().iter().map(|id| id.to_string()).collect();
+        outln!("{}", format!("🏷️  VLAN Filter: {}", vlans.join(", ")).yellow());
+    }
+    if !filter.exclude_protocols().is_empty() {
+        outln!("{}", format!("🚫 Excluded Protocols: {}", filter.exclude_protocols().join(", ")).yellow());
+    }
+    if !args.exclude_host.is_empty() {
+        let ips: Vec<String> = args.exclude_host.iter().map(|ip| ip.to_string()).collect();
+        outln!("{}", format!("🚫 Excluded Hosts: {}", ips.join(", ")).yellow());
+    }
+    if !args.exclude_port.is_empty() {
+        let ports: Vec<String> = args.exclude_port.iter().map(|port| port.to_string()).collect();
+        outln!("{}", format!("🚫 Excluded Ports: {}", ports.join(", ")).yellow());
+    }
+    match (args.min_size, args.max_size) {
+        (Some(min), Some(max)) => outln!("{}", format!("📏 Size Filter: {}-{} bytes", min, max).yellow()),
+        (Some(min), None) => outln!("{}", format!("📏 Size Filter: at least {} bytes", min).yellow()),
+        (None, Some(max)) => outln!("{}", format!("📏 Size Filter: at most {} bytes", max).yellow()),
+        (None, None) => {}
+    }
+    if let Some(flags) = filter.tcp_flags() {
+        outln!("{}", format!("🏁 TCP Flags Filter: {}", flags).yellow());
+    }
+    match filter.direction() {
+        Some(DirectionFilter::In) => outln!("{}", "🧭 Direction Filter: inbound only".yellow()),
+        Some(DirectionFilter::Out) => outln!("{}", "🧭 Direction Filter: outbound only".yellow()),
+        None => {}
+    }
+    if let Some(regex) = filter.payload_match() {
+        outln!("{}", format!("🔎 Payload Filter: /{}/ (first {} bytes)", regex.as_str(), filter::PAYLOAD_MATCH_BYTES).yellow());
+    }
+    if let Some(bpf) = filter.bpf() {
+        outln!("{}", format!("🧬 BPF Filter: {}", bpf.expression()).yellow());
+    }
+    if let Some(expression) = filter.expression() {
+        outln!("{}", format!("🧮 Filter Expression: {}", expression.expression()).yellow());
+    }
+    if args.count > 0 {
+        outln!("{}", format!("📊 Capture Limit: {} packets", args.count).blue());
+    }
+    if let Some(secs) = args.duration {
+        outln!("{}", format!("⏱️  Time Limit: {}s", secs).blue());
+    }
+    if let Some(every) = args.sample.filter(|&every| every > 1) {
+        outln!("{}", format!("🎲 Sampling: 1 in {} packets; totals are estimated, and port scan, SYN flood and ARP spoofing detection are off", every).yellow());
+    }
+    if args.promiscuous {
+        outln!("{}", "🕵️  Promiscuous Mode: on (switched networks still only deliver broadcast, multicast and your own traffic without a SPAN/mirror port)".blue());
+    }
+    if let Some(ref url) = config.alerts.webhook_url {
+        outln!("{}", format!("🪝 Alert Webhook: {}", url).blue());
+    }
+    if config.alerts.syslog {
+        outln!("{}", "📜 Alert Syslog: on".blue());
+    }
+    if args.resolve_names {
+        outln!("{}", "📇 Reverse DNS: on (lookups generate DNS traffic of their own)".blue());
+    }
+    
+    let Session { mut stats, packets } = start_session(&config, &args)?;
+    // Shared with the API's snapshots rather than copied into each
+    let mut captured_packets: VecDeque<Arc<PacketInfo>> = packets.into_iter().map(Arc::new).collect();
+    if let Some(ref path) = args.load_session {
+        outln!("{}", format!("💾 Session: resumed from {} ({} packets so far)", path, stats.total_packets).blue());
+    }
+    if let Some(ref path) = args.save_session {
+        outln!("{}", format!("💾 Saving Session: {} every {}s", path, session::SAVE_INTERVAL.as_secs()).blue());
+    }
+    let mut context = AnalysisContext::new(&config, &args, interfaces, &mut logger)?;
+    let mut throttle = RateLimiter::new(config.performance.max_packets_per_second);
+    let mut sampler = Sampler::new(args.sample);
+    let mut reassembly = args.follow_stream.clone().map(TcpReassembler::new);
+    let export_paths = ExportPaths::new(&config.export, &interface_list(interfaces), args.compress);
+    let mut streams = StreamingExports::open(&args, &export_paths)?;
+    
+    let frames = if args.read_stdin {
+        capture::start_stdin(&filter)?
+    } else {
+        capture::start(interfaces, channel_config(&config.performance, args.promiscuous), &filter)?
+    };
+    let kernel_drops = KernelDrops::new(interfaces);
+    
+    // Text mode has no snapshots of its own, so publish some for the API alone
+    let api_snapshots = match args.api_port {
+        Some(port) => {
+            let (sender, receiver) = tokio::sync::watch::channel(Snapshot::of(&stats, &captured_packets));
+            api::serve(port, args.api_cors_origin.clone(), receiver)?;
+            outln!("{}", format!("🌐 JSON API: http://127.0.0.1:{} (/stats, /connections, /alerts, /packets?limit=N)", port).blue());
+            logger.log_info(&format!("Serving the JSON API on http://127.0.0.1:{}", port));
+            Some(sender)
+        }
+        None => None,
+    };
+    let api_refresh_interval = dashboard_refresh_interval(&config.performance);
+    
+    outln!("{}", "🎯 Capturing packets... (Press Ctrl+C to stop)".green());
+    outln!();
+    
+    // --summary-only goes by the stats alone, unless an export or the API wants the packets
+    let retain_packets = args.summary_only.is_none() || args.export_json.is_some() || args.export_csv.is_some() || args.api_port.is_some() || args.session_packets;
+    if !retain_packets {
+        captured_packets.clear();
+    }
+    let mut packet_count = 0;
+    // The limits apply to this run, not the session's whole span
+    let capture_start = Instant::now();
+    let mut last_stats_time = Instant::now();
+    let mut last_drops_poll = Instant::now();
+    let mut last_api_publish = Instant::now();
+    let mut last_session_save = Instant::now();
+    
+    loop {
+        if shutdown_requested() {
+            outln!();
+            outln!("{}", "🛑 Capture interrupted, finishing up...".yellow());
+            break;
+        }
+        
+        if capture_limit_reached(&args, packet_count, capture_start) {
+            break;
+        }
+        
+        // Checked on every wakeup, so quiet links still get periodic stats
+        if last_stats_time.elapsed().as_secs() >= args.stats_interval {
+            display_interim_stats(&stats);
+            last_stats_time = Instant::now();
+        }
+        
+        if last_drops_poll.elapsed() >= KERNEL_DROPS_POLL_INTERVAL {
+            stats.kernel_dropped = kernel_drops.since_start();
+            last_drops_poll = Instant::now();
+        }
+        
+        if let Some(ref api_snapshots) = api_snapshots {
+            if last_api_publish.elapsed() >= api_refresh_interval {
+                let _ = api_snapshots.send(Snapshot::of(&stats, &captured_packets));
+                last_api_publish = Instant::now();
+            }
+        }
+        
+        if last_session_save.elapsed() >= session::SAVE_INTERVAL {
+            if let Err(e) = save_session(&args, &stats, captured_packets.make_contiguous()) {
+                logger.log_error(&e.to_string());
+            }
+            last_session_save = Instant::now();
+        }
+        
+        match frames.recv_timeout(CAPTURE_READ_TIMEOUT) {
+            Ok(frame) => {
+                if !sampler.take() {
+                    continue;
+                }
+                if !throttle.allow() {
+                    stats.dropped_packets += 1;
+                    continue;
+                }
+                if let Some(ref mut reassembly) = reassembly {
+                    follow_stream(reassembly, &frame.data);
+                }
+                
+                let mut packet_info = analyze_packet_advanced(&frame, &mut context);
+                stats.record(&mut packet_info, &context.threat);
+                
+                if args.summary_only.is_some() {
+                    // The summary document is the only output about packets
+                } else if args.format == OutputFormat::Ndjson {
+                    print_ndjson(&packet_info);
+                } else if args.verbose {
+                    display_packet_verbose(&packet_info, &stats, &context.names);
+                } else {
+                    display_packet_simple(&packet_info, &stats, &context.names);
+                }
+                if args.hex_dump {
+                    display_hex_dump(&frame.data, args.hex_dump_bytes);
+                }
+                
+                if let Err(e) = streams.write(&packet_info, &stats) {
+                    logger.log_error(&format!("{}; that export has been stopped", e));
+                }
+                
+                if retain_packets {
+                    captured_packets.push_back(Arc::new(packet_info));
+                    if captured_packets.len() > config.performance.max_retained_packets {
+                        captured_packets.pop_front();
+                    }
+                }
+                packet_count += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) if args.read_stdin => {
+                outln!("{}", "✅ Reached the end of the pcap stream".green());
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                outln!("{}", "❌ Capture stopped: no interface is readable any more".red());
+                break;
+            }
+        }
+    }
+    stats.kernel_dropped = kernel_drops.since_start();
+    
+    let captured_packets: Vec<PacketInfo> = captured_packets.into_iter().map(Arc::unwrap_or_clone).collect();
+    finish_capture(&captured_packets, &stats, streams, reassembly.as_ref(), &args, &export_paths, &mut logger)
+}
+
+/// The stats and packets a capture starts from: those `--load-session` saved,
+/// or none yet. Alerts go to this run's webhook and syslog either way.
+fn start_session(config: &Config, args: &Args) -> Result<Session> {
+    let sample_rate = args.sample.filter(|&every| every > 1);
+    let mut session = match args.load_session {
+        Some(ref path) => session::load(path, &config.threat, &config.performance, sample_rate)?,
+        None => Session { stats: NetworkStats::new(&config.threat, &config.performance), packets: VecDeque::new() },
+    };
+    session.stats.alert_webhook = config.alerts.webhook_url.as_deref().map(AlertWebhook::start).transpose()?;
+    session.stats.alert_syslog = config.alerts.syslog.then(AlertSyslog::open);
+    session.stats.sample_rate = sample_rate;
+    session.stats.packet_clock = args.read_stdin;
+    Ok(session)
+}
+
+/// Writes `--save-session`, if given; the packets only with `--session-packets`.
+fn save_session<P: Serialize>(args: &Args, stats: &NetworkStats, packets: &[P]) -> Result<()> {
+    match args.save_session {
+        Some(ref path) => session::save(path, stats, if args.session_packets { packets } else { &[] }),
+        None => Ok(()),
+    }
+}
+
+/// Exports written packet by packet during capture rather than at the end,
+/// so they cover every packet and survive a killed process.
+#[derive(Default)]
+struct StreamingExports {
+    jsonl: Option<JsonlWriter>,
+    sqlite: Option<SqliteWriter>,
+    bandwidth: Option<BandwidthWriter>,
+}
+
+impl StreamingExports {
+    /// The SQLite database is appended to, so only the JSONL file is backed up.
+    fn open(args: &Args, export_paths: &ExportPaths) -> Result<Self> {
+        Ok(StreamingExports {
+            jsonl: match args.export_jsonl {
+                Some(ref path) => Some(JsonlWriter::create(&export_paths.prepare(path)?)?),
+                None => None,
+            },
+            sqlite: match args.export_sqlite {
+                Some(ref path) => Some(SqliteWriter::open(&export_paths.resolve(path)?)?),
+                None => None,
+            },
+            bandwidth: match args.export_bandwidth {
+                Some(ref path) => Some(BandwidthWriter::create(&export_paths.prepare(path)?)?),
+                None => None,
+            },
+        })
+    }
+    
+    /// Call after `stats` has recorded the packet. A failed write closes only
+    /// that export, so the capture carries on.
+    fn write(&mut self, packet_info: &PacketInfo, stats: &NetworkStats) -> Result<()> {
+        let mut result = Ok(());
+        
+        if let Some(writer) = self.jsonl.as_mut() {
+            if let Err(e) = writer.write(packet_info) {
+                self.jsonl = None;
+                result = Err(e);
+            }
+        }
+        
+        if let Some(writer) = self.sqlite.as_mut() {
+            if let Err(e) = writer.write(packet_info) {
+                self.sqlite = None;
+                result = Err(e);
+            }
+        }
+        
+        if let Some(writer) = self.bandwidth.as_mut() {
+            if let Err(e) = writer.write_new(&stats.bandwidth_history) {
+                self.bandwidth = None;
+                result = Err(e);
+            }
+        }
+        
+        result
+    }
+    
+    fn finish(self, stats: &NetworkStats, logger: &mut Logger) -> Result<()> {
+        if let Some(mut writer) = self.jsonl {
+            writer.finish()?;
+            outln!("{}", format!("✅ Streamed {} packets to {}", writer.records(), writer.path()).green());
+            logger.log_export("JSONL", writer.path(), writer.records());
+        }
+        
+        if let Some(mut writer) = self.sqlite {
+            writer.commit()?;
+            outln!("{}", format!("✅ Inserted {} packets into {}", writer.records(), writer.path()).green());
+            logger.log_export("SQLite", writer.path(), writer.records());
+        }
+        
+        if let Some(mut writer) = self.bandwidth {
+            // Close out the second in progress and any idle ones since the last packet
+            let mut closing = stats.clone();
+            closing.close_bandwidth_second(stats.elapsed().as_secs().max(stats.bandwidth_second + 1));
+            writer.finish(&closing.bandwidth_history)?;
+            outln!("{}", format!("✅ Exported {} seconds of bandwidth to {}", writer.records(), writer.path()).green());
+            logger.log_info(&format!("Exported {} seconds of bandwidth to {}", writer.records(), writer.path()));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Final summary plus any requested exports. Shared by both capture modes so
+/// a capture stopped with Ctrl+C still produces its output.
+fn finish_capture(packets: &[PacketInfo], stats: &NetworkStats, streams: StreamingExports, reassembly: Option<&TcpReassembler>, args: &Args, export_paths: &ExportPaths, logger: &mut Logger) -> Result<()> {
+    // The tables print straight to stdout, past the --quiet check in outln!
+    if !style::quiet() {
+        display_final_summary(packets, stats, args.sort_talkers);
     }
-    if let Some(port) = args.port {
-        println!("{}", format!("🚪 Port Filter: {}", port).yellow());
+    if let Some(reassembly) = reassembly {
+        reassembly.print();
     }
-    if args.count > 0 {
-        println!("{}", format!("📊 Capture Limit: {} packets", args.count).blue());
+    if stats.dropped_packets > 0 {
+        logger.log_warn(&format!("Dropped {} packets over performance.max_packets_per_second", stats.dropped_packets));
     }
     
-    let mut captured_packets = Vec::<PacketInfo>::new();
-    let stats_start = Instant::now();
-    
-    let (_, mut rx) = match datalink::channel(&interface, Default::default()) {
-        Ok(Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => {
-            return Err(PacketSnifferError::NetworkError("Unhandled channel type".to_string()));
-        }
-        Err(e) => {
-            return Err(PacketSnifferError::NetworkError(format!("Failed to create datalink channel: {}", e)));
-        }
-    };
+    // Export if requested
+    if let Some(ref json_file) = args.export_json {
+        let json_file = export_paths.prepare(json_file)?;
+        export_to_json(packets, &json_file)?;
+        logger.log_export("JSON", &json_file, packets.len());
+    }
     
-    println!("{}", "🎯 Capturing packets... (Press Ctrl+C to stop)".green());
-    println!();
+    if let Some(ref csv_file) = args.export_csv {
+        let csv_file = export_paths.prepare(csv_file)?;
+        export_to_csv(packets, &csv_file)?;
+        logger.log_export("CSV", &csv_file, packets.len());
+    }
     
-    let mut packet_count = 0;
-    let mut last_stats_time = Instant::now();
+    if let Some(ref flows_file) = args.export_flows {
+        let flows_file = export_paths.prepare(flows_file)?;
+        export_flows(&stats.connections, &flows_file)?;
+        logger.log_info(&format!("Exported {} flows to {}", stats.connections.len(), flows_file));
+    }
     
-    loop {
-        if args.count > 0 && packet_count >= args.count {
-            break;
-        }
-        
-        match rx.next() {
-            Ok(packet) => {
-                if should_capture_packet(packet, &args) {
-                    let packet_info = analyze_packet_advanced(packet, packet_count + 1);
-                    
-                    if args.verbose {
-                        display_packet_verbose(&packet_info);
-                    } else {
-                        display_packet_simple(&packet_info);
-                    }
-                    
-                    captured_packets.push(packet_info);
-                    packet_count += 1;
-                    
-                    // Show periodic stats
-                    if last_stats_time.elapsed().as_secs() >= args.stats_interval {
-                        display_interim_stats(&captured_packets, stats_start.elapsed());
-                        last_stats_time = Instant::now();
-                    }
-                }
-            }
-            Err(e) => {
-                println!("{}", format!("❌ Failed to read packet: {}", e).red());
-                break;
-            }
-        }
+    if let Some(ref alerts_file) = args.export_alerts {
+        let alerts_file = export_paths.prepare(alerts_file)?;
+        export_alerts(&stats.threat_alerts, &alerts_file)?;
+        logger.log_info(&format!("Exported {} alerts to {}", stats.threat_alerts.len(), alerts_file));
     }
     
-    // Final summary
-    display_final_summary(&captured_packets, stats_start.elapsed());
+    if let Some(ref summary_file) = args.summary_only {
+        let summary_file = export_paths.prepare(summary_file)?;
+        write_json_file(&CaptureSummary::new(stats), &summary_file, "summary")?;
+        outln!("{}", format!("✅ Wrote the capture summary to {}", summary_file).green());
+        logger.log_info(&format!("Wrote the summary of {} packets to {}", stats.total_packets, summary_file));
+    }
     
-    // Export if requested
-    if let Some(ref json_file) = args.export_json {
-        export_to_json(&captured_packets, json_file)?;
-        logger.log_export("JSON", json_file, captured_packets.len());
+    if let Some(ref session_file) = args.save_session {
+        save_session(args, stats, packets)?;
+        logger.log_info(&format!("Saved the session of {} packets to {}", stats.total_packets, session_file));
     }
     
-    if let Some(ref csv_file) = args.export_csv {
-        export_to_csv(&captured_packets, csv_file)?;
-        logger.log_export("CSV", csv_file, captured_packets.len());
+    streams.finish(stats, logger)?;
+    
+    if let Some(ref webhook) = stats.alert_webhook {
+        match webhook.finish() {
+            Some(summary) => {
+                logger.log_info(&format!("Posted {} alerts to the webhook", summary.posted));
+                if summary.suppressed > 0 {
+                    logger.log_warn(&format!("{} alerts were not posted to stay within the webhook rate limit", summary.suppressed));
+                }
+                if summary.failed_posts > 0 {
+                    logger.log_warn(&format!("{} webhook posts failed", summary.failed_posts));
+                }
+            }
+            None => logger.log_warn("The alert webhook did not answer in time; recent alerts may not have been posted"),
+        }
     }
     
-    logger.log_packet_capture_stop(captured_packets.len(), start_time.elapsed().as_secs());
+    logger.log_packet_capture_stop(stats.total_packets, stats.elapsed().as_secs());
     Ok(())
 }
 
-fn display_packet_simple(packet_info: &PacketInfo) {
-    let timestamp = packet_info.timestamp.format("%H:%M:%S%.3f").to_string();
-    let src = packet_info.src_ip.as_ref().map(|s| s.as_str()).unwrap_or("N/A");
-    let dst = packet_info.dst_ip.as_ref().map(|s| s.as_str()).unwrap_or("N/A");
+fn display_packet_simple(packet_info: &PacketInfo, stats: &NetworkStats, names: &NameResolver) {
+    let timestamp = style::packet_time(&packet_info.timestamp);
+    let src = packet_info.src_ip.as_deref().map_or("N/A".to_string(), |ip| stats.host_label(ip, names));
+    let dst = packet_info.dst_ip.as_deref().map_or("N/A".to_string(), |ip| stats.host_label(ip, names));
     
-    println!("🕐 {} | {} {} | {} -> {} | {}", 
+    recordln!("🕐 {} | {} {} | {} -> {} | {}", 
              timestamp.cyan(),
              packet_info.protocol.green().bold(),
              packet_info.application_protocol.as_ref().unwrap_or(&"".to_string()).yellow(),
@@ -1226,105 +2685,283 @@ fn display_packet_simple(packet_info: &PacketInfo) {
              packet_info.description.white());
 }
 
-fn display_packet_verbose(packet_info: &PacketInfo) {
-    println!("{}", format!("[Packet #{}]", packet_info.packet_number).bold().green());
-    println!("🕐 Timestamp: {}", packet_info.timestamp.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string().cyan());
-    println!("📟 Ethernet: {} -> {}", packet_info.src_mac.blue(), packet_info.dst_mac.blue());
+/// Compact JSON, one object per line. A closed pipe (`| head`) ends the
+/// capture instead of panicking the way `println!` would.
+fn print_ndjson(packet_info: &PacketInfo) {
+    let mut stdout = std::io::stdout().lock();
+    let written = serde_json::to_writer(&mut stdout, packet_info)
+        .map_err(std::io::Error::from)
+        .and_then(|_| stdout.write_all(b"\n"));
+    if written.is_err() {
+        request_shutdown();
+    }
+}
+
+fn display_packet_verbose(packet_info: &PacketInfo, stats: &NetworkStats, names: &NameResolver) {
+    outln!("{}", format!("[Packet #{}]", packet_info.packet_number).bold().green());
+    outln!("📡 Interface: {}", packet_info.interface.cyan());
+    outln!("🕐 Timestamp: {}", format!("{} {} {}", style::format_time(&packet_info.timestamp, "%Y-%m-%d"), style::packet_time(&packet_info.timestamp), style::zone_label(&packet_info.timestamp)).cyan());
+    let with_vendor = |mac: &str, vendor: &Option<String>| match vendor {
+        Some(vendor) => format!("{} ({})", mac, vendor),
+        None => mac.to_string(),
+    };
+    outln!("📟 Ethernet: {} -> {}", with_vendor(&packet_info.src_mac, &packet_info.src_vendor).blue(),
+             with_vendor(&packet_info.dst_mac, &packet_info.dst_vendor).blue());
+    if let Some(vlan_id) = packet_info.vlan_id {
+        outln!("🏷️  VLAN: {}", vlan_id.to_string().magenta());
+    }
+    
+    if let Some(ref arp) = packet_info.arp {
+        outln!("🔁 ARP {}: {} ({}) -> {} ({})", arp.operation.yellow(), arp.sender_ip.green(), arp.sender_mac.blue(),
+                 arp.target_ip.green(), arp.target_mac.blue());
+    } else if let (Some(src_ip), Some(dst_ip)) = (&packet_info.src_ip, &packet_info.dst_ip) {
+        outln!("🌐 IP: {} -> {} ({})", stats.host_label(src_ip, names).green(), stats.host_label(dst_ip, names).green(), packet_info.protocol.yellow());
+    }
     
-    if let (Some(src_ip), Some(dst_ip)) = (&packet_info.src_ip, &packet_info.dst_ip) {
-        println!("🌐 IP: {} -> {} ({})", src_ip.green(), dst_ip.green(), packet_info.protocol.yellow());
+    if let Some(ref gre) = packet_info.gre {
+        outln!("🚇 Tunnel: {} carrying {}", gre.summary().cyan(), gre.inner_protocol.yellow());
     }
     
     if let (Some(src_port), Some(dst_port)) = (packet_info.src_port, packet_info.dst_port) {
-        println!("🚪 Ports: {} -> {}", src_port.to_string().magenta(), dst_port.to_string().magenta());
+        outln!("🚪 Ports: {} -> {}", src_port.to_string().magenta(), dst_port.to_string().magenta());
     }
     
     if let Some(ref flags) = packet_info.flags {
-        println!("🏁 Flags: {}", flags.red());
+        outln!("🏁 Flags: {}", flags.red());
+    }
+    
+    if let (Some(icmp_type), Some(icmp_code)) = (packet_info.icmp_type, packet_info.icmp_code) {
+        outln!("📶 {} Type/Code: {}/{}", packet_info.protocol, icmp_type.to_string().magenta(), icmp_code.to_string().magenta());
     }
     
     if let Some(ref app_proto) = packet_info.application_protocol {
-        println!("📱 Application: {}", app_proto.bright_yellow().bold());
+        outln!("📱 Application: {}", app_proto.bright_yellow().bold());
     }
     
-    println!("📊 Size: {} bytes (payload: {} bytes)", packet_info.packet_size, packet_info.payload_size);
-    println!("💬 Description: {}", packet_info.description.italic());
-    println!("{}", "─".repeat(80).bright_black());
-}
-
-fn display_interim_stats(packets: &[PacketInfo], duration: Duration) {
-    println!("\n{}", "📈 Interim Statistics".bright_green().bold());
-    println!("{}", "═".repeat(50).blue());
+    if let Some(ref query) = packet_info.dns_query {
+        outln!("🔎 DNS Query: {}", query.bright_yellow());
+    }
+    for answer in &packet_info.dns_answers {
+        outln!("   ↳ {} is {}", answer.name.bright_yellow(), answer.address.green());
+    }
     
-    let duration_secs = duration.as_secs();
-    let total_packets = packets.len();
-    let total_bytes: usize = packets.iter().map(|p| p.packet_size).sum();
+    if let Some(ref sni) = packet_info.tls_sni {
+        outln!("🔒 TLS SNI: {}", sni.bright_yellow());
+    }
     
-    println!("⏱️  Duration: {}s | 📦 Packets: {} ({:.1}/s)", 
-             duration_secs, total_packets, 
-             total_packets as f64 / duration_secs as f64);
-    println!("📊 Total Data: {}", format_bytes(total_bytes));
+    if let Some(ref igmp) = packet_info.igmp {
+        outln!("👥 IGMPv{} {}", igmp.version, igmp.message.bright_yellow().bold());
+        if let Some(ref group) = igmp.queried {
+            outln!("   Queried group: {}", group.green());
+        }
+        if !igmp.joined.is_empty() {
+            outln!("   Joined: {}", igmp.joined.join(", ").green());
+        }
+        if !igmp.left.is_empty() {
+            outln!("   Left: {}", igmp.left.join(", ").green());
+        }
+    }
     
-    // Protocol breakdown
-    let mut protocol_counts = HashMap::new();
-    for packet in packets {
-        *protocol_counts.entry(packet.protocol.clone()).or_insert(0) += 1;
+    if let Some(ref sctp) = packet_info.sctp {
+        outln!("🧵 SCTP Verification Tag: {}", format!("0x{:08x}", sctp.verification_tag).magenta());
+        if !sctp.chunks.is_empty() {
+            outln!("   Chunks: {}", sctp.chunks.join(", ").bright_yellow());
+        }
+    }
+    
+    if let Some(ref dhcp) = packet_info.dhcp {
+        outln!("🏠 DHCP {}: client {}", dhcp.message_type.bright_yellow().bold(), dhcp.client_mac.blue());
+        if let Some(ref ip) = dhcp.your_ip {
+            outln!("   Assigned IP: {}", ip.green());
+        }
+        if let Some(ref ip) = dhcp.requested_ip {
+            outln!("   Requested IP: {}", ip.green());
+        }
+        if let Some(ref hostname) = dhcp.hostname {
+            outln!("   Hostname: {}", hostname.cyan());
+        }
+    }
+    
+    if let Some(ref http) = packet_info.http_info {
+        outln!("🌍 HTTP Request: {} {} (host: {})", http.method.bright_yellow().bold(), http.path.cyan(),
+                 http.host.as_deref().unwrap_or("unknown").cyan());
     }
     
-    println!("🔗 Protocols:");
-    for (protocol, count) in protocol_counts {
-        println!("   {} {}: {}", "▶".green(), protocol.yellow(), count);
+    outln!("📊 Size: {} bytes (payload: {} bytes)", packet_info.packet_size, packet_info.payload_size);
+    outln!("💬 Description: {}", packet_info.description.italic());
+    outln!("{}", "─".repeat(80).bright_black());
+}
+
+/// `tcpdump -X` style: offset, 16 hex bytes split in two groups of 8, ASCII.
+fn display_hex_dump(data: &[u8], max_bytes: usize) {
+    let shown = &data[..data.len().min(max_bytes)];
+    
+    for (line, chunk) in shown.chunks(16).enumerate() {
+        let mut hex = String::with_capacity(49);
+        for (i, byte) in chunk.iter().enumerate() {
+            if i == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let ascii: String = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        
+        outln!("  {}  {:<49} |{}|", format!("{:04x}", line * 16).bright_black(), hex, ascii.cyan());
     }
     
-    println!("{}", "═".repeat(50).blue());
-    println!();
+    if data.len() > shown.len() {
+        outln!("  {}", format!("... {} more bytes (raise --hex-dump-bytes to see them)", data.len() - shown.len()).bright_black());
+    }
 }
 
-fn display_final_summary(packets: &[PacketInfo], duration: Duration) {
-    println!("\n{}", "🏁 Capture Complete - Final Summary".bright_green().bold());
-    println!("{}", "═".repeat(80).blue());
+fn display_interim_stats(stats: &NetworkStats) {
+    outln!("\n{}", "📈 Interim Statistics".bright_green().bold());
+    outln!("{}", "═".repeat(50).blue());
     
-    let duration_secs = duration.as_secs();
-    let total_packets = packets.len();
-    let total_bytes: usize = packets.iter().map(|p| p.packet_size).sum();
+    let duration_secs = stats.elapsed().as_secs();
+    
+    let total_packets = stats.total_packets * stats.sample_scale();
+    outln!("⏱️  Duration: {}s | 📦 Packets: {} ({:.1}/s){}", 
+             duration_secs, total_packets, 
+             stats.per_second(total_packets), stats.sampling_note());
+    outln!("📊 Total Data: {}{} | 🔗 Connections: {} | 🚨 Alerts: {}",
+             format_bytes(stats.total_bytes * stats.sample_scale()), stats.sampling_note(), stats.current_connections, stats.threat_alerts.len());
+    
+    outln!("🔗 Protocols:");
+    for (protocol, count) in sorted_counts(&stats.protocol_counts) {
+        let bytes = stats.protocol_bytes.get(protocol).copied().unwrap_or(0);
+        outln!("   {} {}: {} ({})", "▶".green(), protocol.yellow(), count, format_bytes(bytes));
+    }
     
-    println!("⏱️  Total Duration: {}s", duration_secs);
-    println!("📦 Total Packets: {} ({:.2} packets/second)", 
-             total_packets, total_packets as f64 / duration_secs as f64);
-    println!("📊 Total Data: {} ({:.2} bytes/second)", 
-             format_bytes(total_bytes), total_bytes as f64 / duration_secs as f64);
+    outln!("{}", "═".repeat(50).blue());
+    outln!();
+}
+
+/// Highest count first; ties broken by key so the order is stable
+fn sorted_counts<K: Ord>(counts: &HashMap<K, usize>) -> Vec<(&K, usize)> {
+    let mut sorted: Vec<_> = counts.iter().map(|(key, count)| (key, *count)).collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    sorted
+}
+
+fn rank_talkers(talkers: &HashMap<String, TalkerStats>, sort: TalkerSort, limit: usize) -> Vec<(&String, &TalkerStats)> {
+    let mut ranked: Vec<_> = talkers.iter().collect();
+    match sort {
+        TalkerSort::Bytes => ranked.sort_by_key(|t| std::cmp::Reverse(t.1.bytes)),
+        TalkerSort::Packets => ranked.sort_by_key(|t| std::cmp::Reverse(t.1.packets)),
+    }
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Totals come from `stats`, which saw every packet; application protocols
+/// are counted from `packets`, which the dashboard caps to its buffer.
+fn display_final_summary(packets: &[PacketInfo], stats: &NetworkStats, talker_sort: TalkerSort) {
+    outln!("\n{}", "🏁 Capture Complete - Final Summary".bright_green().bold());
+    outln!("{}", "═".repeat(80).blue());
+    
+    let duration_secs = stats.elapsed().as_secs();
+    let total_packets = stats.total_packets * stats.sample_scale();
+    let total_bytes = stats.total_bytes * stats.sample_scale();
+    
+    outln!("⏱️  Total Duration: {}s", duration_secs);
+    outln!("📦 Total Packets: {} ({:.2} packets/second){}", 
+             total_packets, stats.per_second(total_packets), stats.sampling_note());
+    outln!("📊 Total Data: {} ({:.2} bytes/second){}", 
+             format_bytes(total_bytes), stats.per_second(total_bytes), stats.sampling_note());
+    if stats.sample_rate.is_some() {
+        outln!("{}", format!("🎲 Sampled: {} of about {} packets analyzed; the counts below are of sampled packets only, and port scan, SYN flood and ARP spoofing detection were off", stats.total_packets, total_packets).yellow());
+    }
+    if stats.direction_known() {
+        outln!("⇅  Upload / Download: {} / {} ({} / {} packets)",
+                 format_bytes(stats.uploaded.bytes), format_bytes(stats.downloaded.bytes), stats.uploaded.packets, stats.downloaded.packets);
+    } else {
+        outln!("⇅  Upload / Download: unknown, no local addresses to judge direction by");
+    }
+    match stats.connection_idle_timeout {
+        Some(timeout) => outln!("🔗 Connections: {} active, {} closed after {}s idle",
+                                stats.connections.len(), stats.closed_connections.count, timeout.num_seconds()),
+        None => outln!("🔗 Connections: {}", stats.connections.len()),
+    }
+    if let Some((average, longest)) = stats.connection_durations() {
+        outln!("⏳ Connection Duration: {:.1}s average, {:.1}s longest", average, longest);
+    }
+    let retransmissions: usize = stats.connections.values().map(|flow| flow.retransmissions).sum();
+    let out_of_order: usize = stats.connections.values().map(|flow| flow.out_of_order).sum();
+    if retransmissions > 0 || out_of_order > 0 {
+        outln!("{}", format!("🔁 TCP Retransmissions: {} ({} out-of-order segments)", retransmissions, out_of_order).red());
+        let mut lossy: Vec<&ConnectionFlow> = stats.connections.values().filter(|flow| flow.retransmissions > 0).collect();
+        lossy.sort_by_key(|flow| std::cmp::Reverse(flow.retransmissions));
+        for flow in lossy.iter().take(5) {
+            outln!("   {}:{} ↔ {}:{}: {} of {} packets retransmitted", flow.src_ip, flow.src_port.unwrap_or(0),
+                     flow.dst_ip, flow.dst_port.unwrap_or(0), flow.retransmissions, flow.packet_count);
+        }
+    }
+    let (sources, destinations) = stats.unique_hosts();
+    outln!("🖥️  Unique Hosts: {} sources, {} destinations ({} new in the last {}s)",
+             sources, destinations, stats.recent_new_hosts(), NEW_HOST_WINDOW_SECS);
+    if let Some([p50, p90, p99]) = size_percentiles(&stats.packet_sizes) {
+        outln!("📏 Packet Sizes: p50 {}B, p90 {}B, p99 {}B (last {} packets)", p50, p90, p99, stats.packet_sizes.len());
+    }
+    if stats.interface_traffic.len() > 1 {
+        let mut interfaces: Vec<_> = stats.interface_traffic.iter().collect();
+        interfaces.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, traffic) in interfaces {
+            outln!("📡 {}: {} packets, {}", name, traffic.packets, format_bytes(traffic.bytes));
+        }
+    }
+    if stats.dropped_packets > 0 {
+        outln!("{}", format!("🚫 Dropped Packets: {} (over performance.max_packets_per_second, capture is incomplete)", stats.dropped_packets).red().bold());
+    }
+    let (reassembled, discarded) = fragments::totals();
+    if reassembled > 0 || discarded > 0 {
+        outln!("{}", format!("🧩 Fragmented Datagrams: {} reassembled, {} discarded incomplete", reassembled, discarded).yellow());
+    }
+    if stats.capture_overflow > 0 {
+        outln!("{}", format!("🚫 Overload Drops: {} frames arrived faster than they could be analyzed, capture is incomplete", stats.capture_overflow).red().bold());
+    }
+    match stats.kernel_dropped {
+        Some(0) => outln!("🚫 Kernel Drops: none on the capture interfaces"),
+        Some(dropped) => outln!("{}", format!("🚫 Kernel Drops: {} packets dropped by kernel on the capture interfaces, capture is incomplete", dropped).red().bold()),
+        None => {}
+    }
     
     // Detailed protocol statistics
-    let mut protocol_counts = HashMap::new();
     let mut app_protocol_counts = HashMap::new();
-    
     for packet in packets {
-        *protocol_counts.entry(packet.protocol.clone()).or_insert(0) += 1;
         if let Some(ref app_proto) = packet.application_protocol {
             *app_protocol_counts.entry(app_proto.clone()).or_insert(0) += 1;
         }
     }
     
-    println!("\n{}", "🔗 Protocol Distribution:".yellow().bold());
+    outln!("\n{}", "🔗 Protocol Distribution:".yellow().bold());
     let mut protocol_table = Table::new();
     protocol_table.add_row(Row::new(vec![
         Cell::new("Protocol").style_spec("Fb"),
         Cell::new("Packets").style_spec("Fb"),
-        Cell::new("Percentage").style_spec("Fb"),
+        Cell::new("% Packets").style_spec("Fb"),
+        Cell::new("Bytes").style_spec("Fb"),
+        Cell::new("% Bytes").style_spec("Fb"),
     ]));
     
-    for (protocol, count) in protocol_counts {
+    for (protocol, count) in sorted_counts(&stats.protocol_counts) {
         let percentage = (count as f64 / total_packets as f64) * 100.0;
+        let bytes = stats.protocol_bytes.get(protocol).copied().unwrap_or(0);
+        let byte_percentage = (bytes as f64 / stats.total_bytes.max(1) as f64) * 100.0;
         protocol_table.add_row(Row::new(vec![
-            Cell::new(&protocol),
+            Cell::new(protocol),
             Cell::new(&count.to_string()),
             Cell::new(&format!("{:.1}%", percentage)),
+            Cell::new(&format_bytes(bytes)),
+            Cell::new(&format!("{:.1}%", byte_percentage)),
         ]));
     }
     protocol_table.printstd();
     
     if !app_protocol_counts.is_empty() {
-        println!("\n{}", "📱 Application Protocols:".yellow().bold());
+        outln!("\n{}", "📱 Application Protocols:".yellow().bold());
         let mut app_table = Table::new();
         app_table.add_row(Row::new(vec![
             Cell::new("Application").style_spec("Fb"),
@@ -1332,10 +2969,10 @@ fn display_final_summary(packets: &[PacketInfo], duration: Duration) {
             Cell::new("Percentage").style_spec("Fb"),
         ]));
         
-        for (app_proto, count) in app_protocol_counts {
-            let percentage = (count as f64 / total_packets as f64) * 100.0;
+        for (app_proto, count) in sorted_counts(&app_protocol_counts) {
+            let percentage = (count as f64 / packets.len() as f64) * 100.0;
             app_table.add_row(Row::new(vec![
-                Cell::new(&app_proto),
+                Cell::new(app_proto),
                 Cell::new(&count.to_string()),
                 Cell::new(&format!("{:.1}%", percentage)),
             ]));
@@ -1343,33 +2980,128 @@ fn display_final_summary(packets: &[PacketInfo], duration: Duration) {
         app_table.printstd();
     }
     
-    println!("{}", "═".repeat(80).blue());
+    if !stats.top_talkers.is_empty() {
+        outln!("\n{}", "📣 Top Talkers:".yellow().bold());
+        let mut talker_table = Table::new();
+        talker_table.add_row(Row::new(vec![
+            Cell::new("Host").style_spec("Fb"),
+            Cell::new("Sent").style_spec("Fb"),
+            Cell::new("Packets").style_spec("Fb"),
+            Cell::new("Received").style_spec("Fb"),
+            Cell::new("OS (guess)").style_spec("Fb"),
+        ]));
+        
+        for (ip, sent) in rank_talkers(&stats.top_talkers, talker_sort, 5) {
+            let received = stats.top_receivers.get(ip).map_or(0, |r| r.bytes);
+            let os = stats.os_guesses.get(ip).map_or(String::new(), |guess| format!("{} ({} hops)", guess.os, guess.hops));
+            talker_table.add_row(Row::new(vec![
+                Cell::new(ip),
+                Cell::new(&format_bytes(sent.bytes)),
+                Cell::new(&sent.packets.to_string()),
+                Cell::new(&format_bytes(received)),
+                Cell::new(&os),
+            ]));
+        }
+        talker_table.printstd();
+    }
+    
+    let web_destinations = stats.top_web_destinations(10);
+    if !web_destinations.is_empty() {
+        outln!("\n{}", "🌐 Top Web Destinations:".yellow().bold());
+        let mut web_table = Table::new();
+        web_table.add_row(Row::new(vec![
+            Cell::new("Host").style_spec("Fb"),
+            Cell::new("HTTP Requests").style_spec("Fb"),
+            Cell::new("TLS Handshakes").style_spec("Fb"),
+        ]));
+        
+        for (name, requests, handshakes) in web_destinations {
+            web_table.add_row(Row::new(vec![
+                Cell::new(name),
+                Cell::new(&requests.to_string()),
+                Cell::new(&handshakes.to_string()),
+            ]));
+        }
+        web_table.printstd();
+    }
+    
+    outln!("\n{} {}", "🛡️  Threat Alerts:".yellow().bold(), stats.threat_alerts.len());
+    for (ip, ports) in stats.port_scans.active_scanners().iter().take(5) {
+        outln!("   {} {} probed {} ports", "🔭".red(), ip.yellow(), ports);
+    }
+    for (ip, half_open) in stats.syn_floods.flooded_targets().iter().take(5) {
+        outln!("   {} {} has {} half-open connections", "🌊".red(), ip.yellow(), half_open);
+    }
+    for (timestamp, message, level) in stats.threat_alerts.iter().rev().take(5) {
+        let level_icon = match level {
+            ThreatLevel::Low => "🟡",
+            ThreatLevel::Medium => "🟠",
+            ThreatLevel::High => "🔴",
+            ThreatLevel::Critical => "💀",
+            _ => "⚪",
+        };
+        outln!("   {} {} {}", level_icon, style::format_time(timestamp, "%H:%M:%S").bright_black(), message);
+    }
+    
+    outln!("{}", "═".repeat(80).blue());
 }
 
 fn export_to_json(packets: &[PacketInfo], filename: &str) -> Result<()> {
-    let json_data = serde_json::to_string_pretty(packets)
-        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to serialize data: {}", e)))?;
+    write_json(packets, filename)?;
     
-    std::fs::write(filename, json_data)
-        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write JSON file: {}", e)))?;
-    
-    println!("{}", format!("✅ Exported {} packets to {}", packets.len(), filename).green());
+    outln!("{}", format!("✅ Exported {} packets to {}", packets.len(), filename).green());
     Ok(())
 }
 
-fn export_to_csv(packets: &[PacketInfo], filename: &str) -> Result<()> {
-    let mut wtr = csv::Writer::from_path(filename)
+/// Writes the export without reporting it, for callers that own the screen.
+fn write_json<P: Serialize>(packets: &[P], filename: &str) -> Result<()> {
+    write_json_file(packets, filename, "JSON")
+}
+
+/// Pretty-printed JSON through `ExportWriter`; `what` names the export in errors.
+fn write_json_file<T: Serialize + ?Sized>(value: &T, filename: &str, what: &str) -> Result<()> {
+    let mut writer = ExportWriter::create(filename)
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to create {} file: {}", what, e)))?;
+    serde_json::to_writer_pretty(&mut writer, value)
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write {} file: {}", what, e)))?;
+    writer.finish()
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write {} file: {}", what, e)))
+}
+
+fn create_csv_writer(filename: &str) -> Result<csv::Writer<ExportWriter>> {
+    let writer = ExportWriter::create(filename)
         .map_err(|e| PacketSnifferError::ExportError(format!("Failed to create CSV file: {}", e)))?;
+    Ok(csv::Writer::from_writer(writer))
+}
+
+fn finish_csv(wtr: csv::Writer<ExportWriter>) -> Result<()> {
+    let mut writer = wtr.into_inner()
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to flush CSV file: {}", e)))?;
+    writer.finish()
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to flush CSV file: {}", e)))
+}
+
+/// Packet CSV columns, in order. New columns are only ever appended, so
+/// parsers that go by position keep working.
+const CSV_COLUMNS: &[&str] = &[
+    "timestamp", "packet_number", "src_ip", "dst_ip", "protocol",
+    "src_port", "dst_port", "packet_size", "flags", "application_protocol", "description",
+    "http_method", "http_host", "http_path",
+    "threat_level", "payload_size", "src_mac", "dst_mac", "country", "city", "latitude", "longitude", "interface",
+    "direction", "tunnel_src", "tunnel_dst",
+];
+
+fn export_to_csv(packets: &[PacketInfo], filename: &str) -> Result<()> {
+    let mut wtr = create_csv_writer(filename)?;
     
     // Write header
-    wtr.write_record(&["timestamp", "packet_number", "src_ip", "dst_ip", "protocol", 
-                       "src_port", "dst_port", "packet_size", "flags", "application_protocol", "description"])
+    wtr.write_record(CSV_COLUMNS)
         .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV header: {}", e)))?;
     
     // Write data
     for packet in packets {
         let record = vec![
-            packet.timestamp.to_rfc3339(),
+            style::rfc3339(&packet.timestamp),
             packet.packet_number.to_string(),
             packet.src_ip.as_ref().unwrap_or(&"".to_string()).clone(),
             packet.dst_ip.as_ref().unwrap_or(&"".to_string()).clone(),
@@ -1380,80 +3112,452 @@ fn export_to_csv(packets: &[PacketInfo], filename: &str) -> Result<()> {
             packet.flags.as_ref().unwrap_or(&"".to_string()).clone(),
             packet.application_protocol.as_ref().unwrap_or(&"".to_string()).clone(),
             packet.description.clone(),
+            packet.http_info.as_ref().map_or("".to_string(), |h| h.method.clone()),
+            packet.http_info.as_ref().and_then(|h| h.host.clone()).unwrap_or_default(),
+            packet.http_info.as_ref().map_or("".to_string(), |h| h.path.clone()),
+            packet.threat_level.as_str().to_string(),
+            packet.payload_size.to_string(),
+            packet.src_mac.clone(),
+            packet.dst_mac.clone(),
+            packet.geo_info.as_ref().and_then(|g| g.country.clone()).unwrap_or_default(),
+            packet.geo_info.as_ref().and_then(|g| g.city.clone()).unwrap_or_default(),
+            packet.geo_info.as_ref().and_then(|g| g.latitude).map_or("".to_string(), |lat| lat.to_string()),
+            packet.geo_info.as_ref().and_then(|g| g.longitude).map_or("".to_string(), |lon| lon.to_string()),
+            packet.interface.clone(),
+            packet.direction.map_or("".to_string(), |d| d.as_str().to_string()),
+            packet.gre.as_ref().map_or("".to_string(), |g| g.src_ip.clone()),
+            packet.gre.as_ref().map_or("".to_string(), |g| g.dst_ip.clone()),
         ];
         
         wtr.write_record(&record)
             .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV record: {}", e)))?;
     }
     
-    wtr.flush()
-        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to flush CSV file: {}", e)))?;
+    finish_csv(wtr)?;
     
-    println!("{}", format!("✅ Exported {} packets to {}", packets.len(), filename).green());
+    outln!("{}", format!("✅ Exported {} packets to {}", packets.len(), filename).green());
     Ok(())
 }
 
-fn should_capture_packet(packet: &[u8], args: &Args) -> bool {
-    if let Some(ethernet_packet) = EthernetPacket::new(packet) {
-        match ethernet_packet.get_ethertype() {
-            EtherTypes::Ipv4 => {
-                if let Some(ipv4_packet) = Ipv4Packet::new(ethernet_packet.payload()) {
-                    // Check protocol filter
-                    if let Some(ref protocol_filter) = args.protocol {
-                        let protocol_match = match protocol_filter.to_lowercase().as_str() {
-                            "tcp" => ipv4_packet.get_next_level_protocol() == pnet::packet::ip::IpNextHeaderProtocols::Tcp,
-                            "udp" => ipv4_packet.get_next_level_protocol() == pnet::packet::ip::IpNextHeaderProtocols::Udp,
-                            "icmp" => ipv4_packet.get_next_level_protocol() == pnet::packet::ip::IpNextHeaderProtocols::Icmp,
-                            "http" => {
-                                // Check if it's TCP on port 80 or 8080
-                                if ipv4_packet.get_next_level_protocol() == pnet::packet::ip::IpNextHeaderProtocols::Tcp {
-                                    if let Some(tcp_packet) = TcpPacket::new(ipv4_packet.payload()) {
-                                        return tcp_packet.get_destination() == 80 || tcp_packet.get_source() == 80 ||
-                                               tcp_packet.get_destination() == 8080 || tcp_packet.get_source() == 8080;
-                                    }
-                                }
-                                return false;
-                            }
-                            "dns" => {
-                                // Check if it's UDP on port 53
-                                if ipv4_packet.get_next_level_protocol() == pnet::packet::ip::IpNextHeaderProtocols::Udp {
-                                    if let Some(udp_packet) = UdpPacket::new(ipv4_packet.payload()) {
-                                        return udp_packet.get_destination() == 53 || udp_packet.get_source() == 53;
-                                    }
-                                }
-                                return false;
-                            }
-                            _ => true,
-                        };
-                        
-                        if !protocol_match {
-                            return false;
-                        }
-                    }
-                    
-                    // Check port filter
-                    if let Some(port_filter) = args.port {
-                        match ipv4_packet.get_next_level_protocol() {
-                            pnet::packet::ip::IpNextHeaderProtocols::Tcp => {
-                                if let Some(tcp_packet) = TcpPacket::new(ipv4_packet.payload()) {
-                                    return tcp_packet.get_source() == port_filter || tcp_packet.get_destination() == port_filter;
-                                }
-                            }
-                            pnet::packet::ip::IpNextHeaderProtocols::Udp => {
-                                if let Some(udp_packet) = UdpPacket::new(ipv4_packet.payload()) {
-                                    return udp_packet.get_source() == port_filter || udp_packet.get_destination() == port_filter;
-                                }
-                            }
-                            _ => return false,
-                        }
-                    }
-                }
-            }
-            _ => return false,
+
+
+/// `--summary-only`: the `/stats` numbers, with when the capture ran.
+#[derive(Serialize)]
+struct CaptureSummary<'a> {
+    started_at: String,
+    ended_at: String,
+    #[serde(flatten)]
+    stats: api::StatsSummary<'a>,
+}
+
+impl<'a> CaptureSummary<'a> {
+    fn new(stats: &'a NetworkStats) -> Self {
+        CaptureSummary {
+            started_at: style::rfc3339(&stats.started_at),
+            ended_at: style::rfc3339(&Utc::now()),
+            stats: api::StatsSummary::new(stats),
         }
     }
+}
+
+/// One exported flow with the derived columns alongside the raw counters
+#[derive(Serialize)]
+struct FlowRecord<'a> {
+    #[serde(flatten)]
+    flow: &'a ConnectionFlow,
+    duration_secs: f64,
+    avg_bytes_per_packet: f64,
+    rtt: Option<RttStats>,
+}
+
+/// Busiest first, matching the dashboard's connection table
+fn busiest_flows(connections: &HashMap<String, ConnectionFlow>) -> Vec<&ConnectionFlow> {
+    let mut flows: Vec<&ConnectionFlow> = connections.values().collect();
+    flows.sort_by_key(|flow| std::cmp::Reverse(flow.packet_count));
+    flows
+}
+
+fn flow_records<'a>(flows: &[&'a ConnectionFlow]) -> Vec<FlowRecord<'a>> {
+    flows.iter()
+        .map(|flow| FlowRecord {
+            flow,
+            duration_secs: flow.duration_secs(),
+            avg_bytes_per_packet: flow.avg_bytes_per_packet(),
+            rtt: flow.rtt(),
+        })
+        .collect()
+}
+
+fn export_flows(connections: &HashMap<String, ConnectionFlow>, filename: &str) -> Result<()> {
+    let flows = busiest_flows(connections);
+    
+    if is_csv_path(filename) {
+        write_flows_csv(&flows, filename)?;
+    } else {
+        write_json_file(&flow_records(&flows), filename, "flows")?;
+    }
     
-    true
+    outln!("{}", format!("✅ Exported {} flows to {}", flows.len(), filename).green());
+    Ok(())
+}
+
+fn write_flows_csv(flows: &[&ConnectionFlow], filename: &str) -> Result<()> {
+    let mut wtr = create_csv_writer(filename)?;
+    
+    wtr.write_record(["src_ip", "src_port", "dst_ip", "dst_port", "protocol", "packet_count", "total_bytes",
+                       "bytes_from_src", "bytes_from_dst", "first_seen", "last_seen", "duration_secs", "avg_bytes_per_packet", "threat_level", "handshake_state",
+                       "retransmissions", "out_of_order", "rtt_min_ms", "rtt_avg_ms", "rtt_max_ms"])
+        .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV header: {}", e)))?;
+    
+    for flow in flows {
+        let mut record = vec![
+            flow.src_ip.clone(),
+            flow.src_port.map_or("".to_string(), |p| p.to_string()),
+            flow.dst_ip.clone(),
+            flow.dst_port.map_or("".to_string(), |p| p.to_string()),
+            flow.protocol.clone(),
+            flow.packet_count.to_string(),
+            flow.total_bytes.to_string(),
+            flow.bytes_from_src.to_string(),
+            flow.bytes_from_dst.to_string(),
+            style::rfc3339(&flow.first_seen),
+            style::rfc3339(&flow.last_seen),
+            format!("{:.3}", flow.duration_secs()),
+            format!("{:.1}", flow.avg_bytes_per_packet()),
+            flow.threat_level.as_str().to_string(),
+            format!("{:?}", flow.handshake_state),
+            flow.retransmissions.to_string(),
+            flow.out_of_order.to_string(),
+        ];
+        let rtt = flow.rtt();
+        record.extend([rtt.map(|r| r.min_ms), rtt.map(|r| r.avg_ms), rtt.map(|r| r.max_ms)]
+            .map(|ms| ms.map_or("".to_string(), |ms| format!("{:.3}", ms))));
+        
+        wtr.write_record(&record)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV record: {}", e)))?;
+    }
+    
+    finish_csv(wtr)
+}
+
+#[derive(Serialize)]
+struct AlertRecord<'a> {
+    #[serde(serialize_with = "style::serialize_time")]
+    timestamp: DateTime<Utc>,
+    threat_level: &'a ThreatLevel,
+    message: &'a str,
+}
+
+fn alert_records(alerts: &VecDeque<(DateTime<Utc>, String, ThreatLevel)>) -> Vec<AlertRecord<'_>> {
+    alerts.iter()
+        .map(|(timestamp, message, threat_level)| AlertRecord { timestamp: *timestamp, threat_level, message })
+        .collect()
+}
+
+fn export_alerts(alerts: &VecDeque<(DateTime<Utc>, String, ThreatLevel)>, filename: &str) -> Result<()> {
+    if is_csv_path(filename) {
+        let mut wtr = create_csv_writer(filename)?;
+        
+        wtr.write_record(["timestamp", "threat_level", "message"])
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV header: {}", e)))?;
+        
+        for (timestamp, message, level) in alerts {
+            wtr.write_record([style::rfc3339(timestamp).as_str(), level.as_str(), message.as_str()])
+                .map_err(|e| PacketSnifferError::ExportError(format!("Failed to write CSV record: {}", e)))?;
+        }
+        
+        finish_csv(wtr)?;
+    } else {
+        write_json_file(&alert_records(alerts), filename, "alerts")?;
+    }
+    
+    outln!("{}", format!("✅ Exported {} alerts to {}", alerts.len(), filename).green());
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_numbers_are_strictly_increasing() {
+        let config = Config::default();
+        let threat = ThreatRules::new(&config.threat).unwrap();
+        let mut stats = NetworkStats::new(&config.threat, &config.performance);
+
+        let mut numbers = Vec::new();
+        for interface in ["eth0", "wlan0", "eth0"].iter().cycle().take(2500) {
+            let mut packet_info = PacketInfo::new(Utc::now(), interface, 64);
+            stats.record(&mut packet_info, &threat);
+            numbers.push(packet_info.packet_number);
+        }
+
+        assert_eq!(numbers[0], 1);
+        assert!(numbers.windows(2).all(|pair| pair[1] > pair[0]), "packet numbers repeated or went backwards");
+        assert_eq!(*numbers.last().unwrap(), stats.total_packets);
+    }
+
+    /// A mid-sized private TCP packet with no ports: nothing about it scores.
+    fn quiet_packet(dst_ip: &str) -> PacketInfo {
+        let mut packet_info = PacketInfo::new(Utc::now(), "eth0", 512);
+        packet_info.src_ip = Some("192.168.1.10".to_string());
+        packet_info.dst_ip = Some(dst_ip.to_string());
+        packet_info.protocol = "TCP".to_string();
+        packet_info
+    }
+
+    fn rules(configure: impl FnOnce(&mut ThreatConfig)) -> ThreatRules {
+        let mut config = ThreatConfig::default();
+        configure(&mut config);
+        ThreatRules::new(&config).unwrap()
+    }
+
+    #[test]
+    fn score_maps_to_level_at_each_threshold() {
+        let thresholds = ThreatConfig::default().level_thresholds;
+        let cases = [
+            (0, ThreatLevel::Safe),
+            (thresholds.low - 1, ThreatLevel::Safe),
+            (thresholds.low, ThreatLevel::Low),
+            (thresholds.medium - 1, ThreatLevel::Low),
+            (thresholds.medium, ThreatLevel::Medium),
+            (thresholds.high - 1, ThreatLevel::Medium),
+            (thresholds.high, ThreatLevel::High),
+            (thresholds.critical - 1, ThreatLevel::High),
+            (thresholds.critical, ThreatLevel::Critical),
+            (u32::MAX, ThreatLevel::Critical),
+        ];
+        for (score, level) in cases {
+            assert_eq!(threat_level_for(score, &thresholds), level, "score {}", score);
+        }
+    }
+
+    #[test]
+    fn blocklist_wins_over_allowlist() {
+        let rules = rules(|config| {
+            config.allowlist = vec!["192.168.1.0/24".to_string()];
+            config.blocklist = vec!["192.168.1.10".to_string()];
+        });
+        assert_eq!(detect_threat_level(&quiet_packet("192.168.1.20"), &rules), ThreatLevel::Critical);
+    }
+
+    #[test]
+    fn blocklisted_packet_is_critical_whatever_it_scores() {
+        let rules = rules(|config| config.blocklist = vec!["192.168.1.20".to_string()]);
+        let packet_info = quiet_packet("192.168.1.20");
+        assert_eq!(risk_score(&packet_info, &rules.config), 0);
+        assert_eq!(detect_threat_level(&packet_info, &rules), ThreatLevel::Critical);
+    }
+
+    #[test]
+    fn allowlisted_packet_is_safe_on_a_high_risk_port() {
+        let rules = rules(|config| config.allowlist = vec!["192.168.1.10".to_string()]);
+        let mut packet_info = quiet_packet("8.8.8.8");
+        packet_info.dst_port = Some(3389);
+        assert_eq!(detect_threat_level(&packet_info, &rules), ThreatLevel::Safe);
+    }
+
+    #[test]
+    fn private_destinations_score_nothing() {
+        let threat = ThreatConfig::default();
+        for ip in ["10.0.0.5", "10.20.30.40", "192.168.1.1", "172.16.0.1", "127.0.0.1", "::1"] {
+            assert_eq!(address_risk(ip, &threat), 0, "{}", ip);
+        }
+        assert_eq!(detect_threat_level(&quiet_packet("10.0.0.5"), &rules(|_| {})), ThreatLevel::Safe);
+    }
+
+    #[test]
+    fn private_ranges_end_at_their_boundaries() {
+        let is_private_ip = |ip: &str| ip.parse().is_ok_and(is_private_addr);
+        let private = [
+            "10.0.0.0", "10.255.255.255",
+            "172.16.0.0", "172.17.0.1", "172.31.255.255",
+            "192.168.0.0", "192.168.255.255",
+            "127.0.0.1", "127.255.255.255",
+            "::1", "fc00::", "fd12:3456::1", "fdff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+            "::ffff:192.168.1.1",
+        ];
+        for ip in private {
+            assert!(is_private_ip(ip), "{} should be private", ip);
+        }
+
+        let public = [
+            "9.255.255.255", "11.0.0.0",
+            "172.15.255.255", "172.32.0.0", "172.160.0.1",
+            "192.167.255.255", "192.169.0.0",
+            "128.0.0.1", "169.253.255.255", "169.255.0.0",
+            "::2", "fbff::1", "fe00::1", "fec0::1",
+            "2001:4860:4860::8888", "::ffff:8.8.8.8",
+            "10.example.com", "",
+        ];
+        for ip in public {
+            assert!(!is_private_ip(ip), "{} should not be private", ip);
+        }
+    }
+
+    #[test]
+    fn link_local_is_its_own_class_in_both_families() {
+        let link_local = [
+            "169.254.0.0", "169.254.255.255", "::ffff:169.254.0.1",
+            "fe80::", "fe80::1", "febf:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+        ];
+        for ip in link_local {
+            let addr: IpAddr = ip.parse().unwrap();
+            assert!(is_link_local_addr(addr), "{} should be link-local", ip);
+            assert!(!is_private_addr(addr), "{} should not be private", ip);
+            assert!(is_local_ip(ip), "{} should stay local", ip);
+        }
+        for ip in ["169.253.255.255", "169.255.0.0", "fe7f:ffff::1", "fec0::1", "10.0.0.1", "fd00::1"] {
+            assert!(!is_link_local_addr(ip.parse().unwrap()), "{} should not be link-local", ip);
+        }
+    }
+
+    #[test]
+    fn link_local_is_suspicious_but_not_public() {
+        let threat = ThreatConfig::default();
+        for ip in ["169.254.169.254", "fe80::1", "::ffff:169.254.0.1"] {
+            assert_eq!(address_risk(ip, &threat), threat.suspicious_ip_score, "{}", ip);
+        }
+        assert_eq!(address_risk("8.8.8.8", &threat), threat.public_ip_score);
+    }
+
+    #[test]
+    fn oversized_packet_alone_is_low() {
+        let mut packet_info = quiet_packet("10.0.0.5");
+        packet_info.protocol = "GRE".to_string();
+        packet_info.packet_size = 2000;
+        assert_eq!(detect_threat_level(&packet_info, &rules(|_| {})), ThreatLevel::Low);
+    }
+
+    #[test]
+    fn size_bounds_are_inclusive() {
+        let threat = ThreatConfig::default();
+        assert_eq!(size_risk(threat.min_normal_packet_size, &threat), 0);
+        assert_eq!(size_risk(threat.max_normal_packet_size, &threat), 0);
+        assert_eq!(size_risk(threat.min_normal_packet_size - 1, &threat), threat.size_anomaly_score);
+        assert_eq!(size_risk(threat.max_normal_packet_size + 1, &threat), threat.oversize_packet_score);
+    }
+
+    #[test]
+    fn port_scores_use_the_first_matching_band() {
+        let threat = ThreatConfig::default();
+        assert_eq!(port_risk(Some(3389), &threat), threat.high_risk_port_score);
+        assert_eq!(port_risk(Some(25), &threat), threat.medium_risk_port_score);
+        assert_eq!(port_risk(Some(threat.unusual_port_threshold), &threat), 0);
+        assert_eq!(port_risk(Some(threat.unusual_port_threshold + 1), &threat), threat.unusual_port_score);
+        assert_eq!(port_risk(None, &threat), 0);
+    }
 
+    #[test]
+    fn dns_is_not_scored_as_udp() {
+        let threat = ThreatConfig::default();
+        let mut packet_info = quiet_packet("10.0.0.5");
+        packet_info.protocol = "UDP".to_string();
+        packet_info.dst_port = Some(53);
+        assert_eq!(protocol_risk(&packet_info, &threat), 0);
+        packet_info.dst_port = Some(5353);
+        assert_eq!(protocol_risk(&packet_info, &threat), threat.udp_score);
+    }
+    
+    /// A pcap stream of ACK-only TCP segments, each `(seconds in, src port, dst port)`.
+    fn pcap_stream(segments: &[(u32, u16, u16)]) -> Vec<u8> {
+        let mut stream = Vec::new();
+        for word in [0xa1b2_c3d4u32, 0x0004_0002, 0, 0, 65_535, 1] {
+            // Version 2.4 is two u16s, which the second word holds little-endian
+            stream.extend_from_slice(&word.to_le_bytes());
+        }
+        for &(offset, src_port, dst_port) in segments {
+            let mut frame = vec![0x02; 12];
+            frame.extend_from_slice(&[0x08, 0x00]);
+            frame.extend_from_slice(&[0x45, 0, 0, 40, 0, 1, 0, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+            frame.extend_from_slice(&src_port.to_be_bytes());
+            frame.extend_from_slice(&dst_port.to_be_bytes());
+            frame.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 0x50, 0x10, 0x04, 0x00, 0, 0, 0, 0]);
+            for word in [1_700_000_000 + offset, 0, frame.len() as u32, frame.len() as u32] {
+                stream.extend_from_slice(&word.to_le_bytes());
+            }
+            stream.extend_from_slice(&frame);
+        }
+        stream
+    }
+    
+    #[test]
+    fn pcap_replay_is_timed_by_its_packets() {
+        let mut config = Config::default();
+        config.logging.enable_file = false;
+        config.logging.enable_console = false;
+        config.performance.connection_idle_timeout_secs = 60;
+        let args = Args::parse_from(["packet_sniffer", "--read-stdin"]);
+        let mut logger = Logger::new(&config.logging).unwrap();
+        let mut context = AnalysisContext::new(&config, &args, &[], &mut logger).unwrap();
+        let mut stats = start_session(&config, &args).unwrap().stats;
+        
+        // One connection from 0s to 30s, idle by the time a second runs from 100s to 120s
+        let stream = pcap_stream(&[(0, 40000, 8000), (30, 40000, 8000), (100, 40001, 8000), (120, 40001, 8000)]);
+        let mut reader = pcap::PcapReader::new(std::io::Cursor::new(stream)).unwrap();
+        while let Some((timestamp, data)) = reader.next_frame().unwrap() {
+            let frame = CapturedFrame { interface: "stdin".to_string(), timestamp, data };
+            let mut packet_info = analyze_packet_advanced(&frame, &mut context);
+            stats.record(&mut packet_info, &context.threat);
+        }
+        
+        assert_eq!(stats.elapsed(), Duration::from_secs(120));
+        assert_eq!(stats.per_second(stats.total_packets), 4.0 / 120.0);
+        assert_eq!(stats.closed_connections.count, 1);
+        assert_eq!(stats.connections.len(), 1);
+        assert_eq!(stats.connection_durations(), Some((25.0, 30.0)));
+        assert!(!stats.direction_known());
+    }
+    
+    #[test]
+    fn api_cors_origin_is_one_web_origin() {
+        assert_eq!(api::parse_origin("http://localhost:3000").as_deref(), Ok("http://localhost:3000"));
+        assert_eq!(api::parse_origin("https://dash.example.com/").as_deref(), Ok("https://dash.example.com"));
+        for value in ["*", "null", "localhost:3000", "http://localhost:3000/app", "file:///tmp/x.html", "http://a.example\r\nX-Evil: 1"] {
+            assert!(api::parse_origin(value).is_err(), "{:?}", value);
+        }
+        
+        let args = Args::parse_from(["packet_sniffer", "--api-port", "8080", "--api-cors-origin", "http://localhost:3000"]);
+        assert_eq!(args.api_cors_origin.as_deref(), Some("http://localhost:3000"));
+        assert!(Args::try_parse_from(["packet_sniffer", "--api-cors-origin", "http://localhost:3000"]).is_err());
+    }
+    
+    #[test]
+    fn sessions_resume_only_at_their_sample_rate() {
+        let config = Config::default();
+        let path = std::env::temp_dir().join(format!("packet_sniffer-session-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        
+        let saving = Args::parse_from(["packet_sniffer", "--sample", "10", "--save-session", path]);
+        let stats = start_session(&config, &saving).unwrap().stats;
+        save_session::<PacketInfo>(&saving, &stats, &[]).unwrap();
+        
+        let resume = |sample: &[&str]| start_session(&config, &Args::parse_from([&["packet_sniffer", "--load-session", path], sample].concat()));
+        assert_eq!(resume(&["--sample", "10"]).unwrap().stats.sample_rate, Some(10));
+        for sample in [&["--sample", "5"][..], &[]] {
+            assert!(matches!(resume(sample), Err(PacketSnifferError::SessionError(_))), "{:?}", sample);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+    
+    #[test]
+    fn passive_dns_forgets_the_oldest_address() {
+        let rules = rules(|_| {});
+        let mut stats = NetworkStats::new(&rules.config, &PerformanceConfig::default());
+        let answer = |n: usize| DnsAnswer { name: format!("host{}.example", n), address: format!("10.{}.{}.{}", n >> 16, (n >> 8) & 0xFF, n & 0xFF) };
+        let mut packet_info = quiet_packet("192.168.1.20");
+        packet_info.dns_answers = (0..=MAX_DNS_NAMES).map(answer).collect();
+        stats.record(&mut packet_info, &rules);
+        
+        assert_eq!(stats.dns_names.len(), MAX_DNS_NAMES);
+        assert!(!stats.dns_names.contains_key(&answer(0).address));
+        assert_eq!(stats.dns_names.get(&answer(1).address), Some(&answer(1).name));
+        
+        // A fresh answer for a known address renames it without evicting anything
+        let mut packet_info = quiet_packet("192.168.1.20");
+        packet_info.dns_answers = vec![DnsAnswer { name: "renamed.example".to_string(), address: answer(1).address }];
+        stats.record(&mut packet_info, &rules);
+        assert_eq!(stats.dns_names.len(), MAX_DNS_NAMES);
+        assert_eq!(stats.dns_names.get(&answer(1).address).map(String::as_str), Some("renamed.example"));
+    }
+}
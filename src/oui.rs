@@ -0,0 +1,253 @@
+//! MAC address vendor lookup by OUI (the first three octets). A small table
+//! of common vendors is built in; `oui.database_path` can point at the full
+//! IEEE registry for everything else.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+
+use crate::config::OuiConfig;
+use crate::logger::Logger;
+
+type Oui = [u8; 3];
+
+/// Vendors that show up most on home and office networks, plus the virtual
+/// NIC prefixes of common hypervisors.
+const BUILTIN_VENDORS: &[(Oui, &str)] = &[
+    ([0x00, 0x00, 0x0C], "Cisco Systems"),
+    ([0x00, 0x40, 0x96], "Cisco Systems"),
+    ([0x00, 0x18, 0x0A], "Cisco Meraki"),
+    ([0x00, 0x06, 0x25], "Linksys"),
+    ([0x00, 0x12, 0x17], "Cisco-Linksys"),
+    ([0x00, 0x05, 0x85], "Juniper Networks"),
+    ([0x00, 0x09, 0x0F], "Fortinet"),
+    ([0x00, 0x1B, 0x17], "Palo Alto Networks"),
+    ([0x00, 0x1C, 0x73], "Arista Networks"),
+    ([0x00, 0x0B, 0x86], "Aruba Networks"),
+    ([0x00, 0x02, 0xC9], "Mellanox Technologies"),
+    ([0x00, 0x27, 0x22], "Ubiquiti"),
+    ([0x04, 0x18, 0xD6], "Ubiquiti"),
+    ([0x24, 0xA4, 0x3C], "Ubiquiti"),
+    ([0x80, 0x2A, 0xA8], "Ubiquiti"),
+    ([0xF0, 0x9F, 0xC2], "Ubiquiti"),
+    ([0x00, 0x0C, 0x42], "MikroTik"),
+    ([0x4C, 0x5E, 0x0C], "MikroTik"),
+    ([0xD4, 0xCA, 0x6D], "MikroTik"),
+    ([0x14, 0xCC, 0x20], "TP-Link"),
+    ([0x50, 0xC7, 0xBF], "TP-Link"),
+    ([0xF4, 0xF2, 0x6D], "TP-Link"),
+    ([0x00, 0x09, 0x5B], "Netgear"),
+    ([0x00, 0x14, 0x6C], "Netgear"),
+    ([0x00, 0x1B, 0x2F], "Netgear"),
+    ([0x00, 0x1E, 0x2A], "Netgear"),
+    ([0x00, 0x24, 0xB2], "Netgear"),
+    ([0x00, 0x05, 0x5D], "D-Link"),
+    ([0x00, 0x0D, 0x88], "D-Link"),
+    ([0x00, 0x15, 0xE9], "D-Link"),
+    ([0x00, 0xE0, 0xFC], "Huawei"),
+    ([0x00, 0x18, 0x82], "Huawei"),
+    ([0x00, 0x1E, 0x10], "Huawei"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x08, 0x9B], "QNAP Systems"),
+    ([0x24, 0x5E, 0xBE], "QNAP Systems"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x0A, 0x95], "Apple"),
+    ([0x00, 0x17, 0xF2], "Apple"),
+    ([0x00, 0x1B, 0x63], "Apple"),
+    ([0x00, 0x1E, 0xC2], "Apple"),
+    ([0x00, 0x1F, 0xF3], "Apple"),
+    ([0x00, 0x25, 0x00], "Apple"),
+    ([0x00, 0x26, 0xBB], "Apple"),
+    ([0x28, 0xCF, 0xE9], "Apple"),
+    ([0x3C, 0x07, 0x54], "Apple"),
+    ([0xA4, 0x5E, 0x60], "Apple"),
+    ([0xAC, 0xBC, 0x32], "Apple"),
+    ([0xF0, 0x18, 0x98], "Apple"),
+    ([0x00, 0x12, 0xFB], "Samsung Electronics"),
+    ([0x00, 0x15, 0x99], "Samsung Electronics"),
+    ([0x00, 0x16, 0x32], "Samsung Electronics"),
+    ([0x00, 0x21, 0x19], "Samsung Electronics"),
+    ([0x00, 0x23, 0x39], "Samsung Electronics"),
+    ([0x00, 0x1A, 0x11], "Google"),
+    ([0x3C, 0x5A, 0xB4], "Google"),
+    ([0x54, 0x60, 0x09], "Google"),
+    ([0xF4, 0xF5, 0xD8], "Google"),
+    ([0x18, 0xB4, 0x30], "Nest Labs"),
+    ([0x44, 0x65, 0x0D], "Amazon Technologies"),
+    ([0x00, 0x0E, 0x58], "Sonos"),
+    ([0xB8, 0xE9, 0x37], "Sonos"),
+    ([0x64, 0x09, 0x80], "Xiaomi"),
+    ([0x28, 0x6C, 0x07], "Xiaomi"),
+    ([0x24, 0x0A, 0xC4], "Espressif"),
+    ([0x24, 0x6F, 0x28], "Espressif"),
+    ([0x30, 0xAE, 0xA4], "Espressif"),
+    ([0x84, 0xF3, 0xEB], "Espressif"),
+    ([0xA4, 0xCF, 0x12], "Espressif"),
+    ([0xEC, 0xFA, 0xBC], "Espressif"),
+    ([0xB8, 0x27, 0xEB], "Raspberry Pi Foundation"),
+    ([0x28, 0xCD, 0xC1], "Raspberry Pi Trading"),
+    ([0x2C, 0xCF, 0x67], "Raspberry Pi Trading"),
+    ([0xD8, 0x3A, 0xDD], "Raspberry Pi Trading"),
+    ([0xDC, 0xA6, 0x32], "Raspberry Pi Trading"),
+    ([0xE4, 0x5F, 0x01], "Raspberry Pi Trading"),
+    ([0x00, 0x02, 0xB3], "Intel"),
+    ([0x00, 0x1B, 0x21], "Intel"),
+    ([0x00, 0xA0, 0xC9], "Intel"),
+    ([0x3C, 0x97, 0x0E], "Intel"),
+    ([0x00, 0xE0, 0x4C], "Realtek"),
+    ([0x00, 0x10, 0x18], "Broadcom"),
+    ([0x00, 0x04, 0x4B], "NVIDIA"),
+    ([0x48, 0xB0, 0x2D], "NVIDIA"),
+    ([0x00, 0x06, 0x5B], "Dell"),
+    ([0x00, 0x08, 0x74], "Dell"),
+    ([0x00, 0x0B, 0xDB], "Dell"),
+    ([0x00, 0x12, 0x3F], "Dell"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x26, 0xB9], "Dell"),
+    ([0x00, 0xC0, 0x4F], "Dell"),
+    ([0x00, 0x17, 0xA4], "Hewlett Packard"),
+    ([0x00, 0x1B, 0x78], "Hewlett Packard"),
+    ([0x00, 0x1E, 0x0B], "Hewlett Packard"),
+    ([0x3C, 0xD9, 0x2B], "Hewlett Packard"),
+    ([0x00, 0x0D, 0x60], "IBM"),
+    ([0x00, 0x1A, 0x64], "IBM"),
+    ([0x00, 0x25, 0x90], "Super Micro Computer"),
+    ([0x00, 0x30, 0x48], "Super Micro Computer"),
+    ([0x0C, 0xC4, 0x7A], "Super Micro Computer"),
+    ([0xAC, 0x1F, 0x6B], "Super Micro Computer"),
+    ([0x08, 0x00, 0x20], "Oracle (Sun)"),
+    ([0x00, 0x03, 0xBA], "Oracle (Sun)"),
+    ([0x00, 0x14, 0x4F], "Oracle (Sun)"),
+    ([0x00, 0x03, 0xFF], "Microsoft"),
+    ([0x00, 0x0D, 0x3A], "Microsoft"),
+    ([0x00, 0x12, 0x5A], "Microsoft"),
+    ([0x00, 0x17, 0xFA], "Microsoft"),
+    ([0x00, 0x50, 0xF2], "Microsoft"),
+    ([0x28, 0x18, 0x78], "Microsoft"),
+    ([0x7C, 0x1E, 0x52], "Microsoft"),
+    ([0x00, 0x15, 0x5D], "Microsoft (Hyper-V)"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x0C, 0x29], "VMware"),
+    ([0x00, 0x1C, 0x14], "VMware"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x00, 0x1C, 0x42], "Parallels"),
+    ([0x00, 0x16, 0x3E], "Xen"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+    ([0x00, 0x00, 0x5E], "IANA"),
+];
+
+#[derive(Clone)]
+pub struct VendorLookup {
+    /// Entries from `oui.database_path`; the built-in table fills the gaps
+    loaded: Arc<HashMap<Oui, String>>,
+}
+
+impl VendorLookup {
+    pub fn from_config(config: &OuiConfig, logger: &mut Logger) -> Self {
+        let loaded = match config.database_path {
+            Some(ref path) => match fs::read_to_string(path) {
+                Ok(content) => {
+                    let loaded = parse_registry(&content);
+                    logger.log_info(&format!("Loaded {} OUI vendors from {}", loaded.len(), path));
+                    loaded
+                }
+                Err(e) => {
+                    logger.log_warn(&format!("Failed to read OUI database {}: {}, using built-in vendor table", path, e));
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+
+        VendorLookup { loaded: Arc::new(loaded) }
+    }
+
+    /// Vendor for a colon-separated MAC such as `00:1a:2b:3c:4d:5e`.
+    /// Broadcast, multicast and locally administered (often randomized)
+    /// addresses are labeled as such since no vendor owns them.
+    pub fn vendor(&self, mac: &str) -> Option<String> {
+        let octets: Vec<u8> = mac.split(':')
+            .map(|octet| u8::from_str_radix(octet, 16))
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+        if octets.len() != 6 {
+            return None;
+        }
+
+        if octets.iter().all(|&octet| octet == 0xFF) {
+            return Some("Broadcast".to_string());
+        }
+        match octets[..3] {
+            [0x01, 0x00, 0x5E] => return Some("IPv4 multicast".to_string()),
+            [0x33, 0x33, _] => return Some("IPv6 multicast".to_string()),
+            _ => {}
+        }
+
+        let oui = [octets[0], octets[1], octets[2]];
+        if let Some(vendor) = self.loaded.get(&oui) {
+            return Some(vendor.clone());
+        }
+        if let Some((_, vendor)) = BUILTIN_VENDORS.iter().find(|(prefix, _)| *prefix == oui) {
+            return Some(vendor.to_string());
+        }
+
+        if octets[0] & 0x01 != 0 {
+            Some("Multicast".to_string())
+        } else if octets[0] & 0x02 != 0 {
+            Some("Locally administered".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Accepts the IEEE `oui.txt` layout (`00-1A-2B   (hex)\t\tVendor`) and the
+/// Wireshark `manuf` layout (`00:1A:2B\tShort\tLong vendor`). Lines in
+/// neither format, and `manuf` entries for longer prefixes, are skipped.
+fn parse_registry(content: &str) -> HashMap<Oui, String> {
+    let mut vendors = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (prefix, vendor) = if let Some((prefix, vendor)) = line.split_once("(hex)") {
+            (prefix.trim(), vendor.trim())
+        } else {
+            let mut fields = line.split('\t').filter(|field| !field.is_empty());
+            let Some(prefix) = fields.next() else {
+                continue;
+            };
+            // Prefer the long name when the file has one
+            let short = fields.next().unwrap_or("");
+            (prefix.trim(), fields.next().unwrap_or(short).trim())
+        };
+
+        if let Some(oui) = parse_oui(prefix) {
+            if !vendor.is_empty() {
+                vendors.insert(oui, vendor.to_string());
+            }
+        }
+    }
+
+    vendors
+}
+
+fn parse_oui(prefix: &str) -> Option<Oui> {
+    let parts: Vec<&str> = prefix.split(['-', ':']).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let mut oui = [0u8; 3];
+    for (byte, part) in oui.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return None;
+        }
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+    Some(oui)
+}
@@ -0,0 +1,100 @@
+//! A packet socket of our own, for `--bpf` on Linux. pnet opens its socket
+//! inside `datalink::channel` and never hands it out, so nothing can be
+//! attached to it. This opens the same kind of `AF_PACKET` socket, attaches
+//! the compiled program with `SO_ATTACH_FILTER` before binding it to the
+//! interface, so no frame is queued unfiltered and rejected ones never leave
+//! the kernel, and reads it through pnet's `DataLinkReceiver` like any
+//! other channel.
+
+use pnet::datalink::{self, DataLinkReceiver, NetworkInterface};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use crate::bpf::Instruction;
+
+pub struct PacketSocket {
+    fd: OwnedFd,
+    buffer: Vec<u8>,
+    /// For poll(2), in milliseconds; -1 waits indefinitely
+    timeout: libc::c_int,
+}
+
+impl PacketSocket {
+    /// Honours the channel settings pnet would: read buffer size, read
+    /// timeout and promiscuous mode.
+    pub fn open(interface: &NetworkInterface, config: &datalink::Config, program: &[Instruction]) -> io::Result<Self> {
+        // Protocol 0 receives nothing until bind names one
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut filter: Vec<libc::sock_filter> = program.iter()
+            .map(|instruction| libc::sock_filter { code: instruction.code, jt: instruction.jt, jf: instruction.jf, k: instruction.k })
+            .collect();
+        let program = libc::sock_fprog { len: filter.len() as u16, filter: filter.as_mut_ptr() };
+        set_option(&fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &program)?;
+
+        let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as libc::sa_family_t;
+        address.sll_protocol = (libc::ETH_P_ALL as u16).to_be();
+        address.sll_ifindex = interface.index as libc::c_int;
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if bound == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if config.promiscuous {
+            let mut membership: libc::packet_mreq = unsafe { mem::zeroed() };
+            membership.mr_ifindex = interface.index as libc::c_int;
+            membership.mr_type = libc::PACKET_MR_PROMISC as libc::c_ushort;
+            set_option(&fd, libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &membership)?;
+        }
+
+        let timeout = config.read_timeout.map_or(-1, |timeout| timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int);
+        Ok(PacketSocket { fd, buffer: vec![0; config.read_buffer_size], timeout })
+    }
+}
+
+fn set_option<T>(fd: &OwnedFd, level: libc::c_int, name: libc::c_int, value: &T) -> io::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(fd.as_raw_fd(), level, name, value as *const T as *const libc::c_void, mem::size_of::<T>() as libc::socklen_t)
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl DataLinkReceiver for PacketSocket {
+    /// Times out like pnet's receiver, so the reader can check for shutdown;
+    /// a signal interrupting the wait counts as a timeout too.
+    fn next(&mut self) -> io::Result<&[u8]> {
+        let mut poll = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        match unsafe { libc::poll(&mut poll, 1, self.timeout) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, error));
+                }
+                return Err(error);
+            }
+            0 => return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+            _ => {}
+        }
+
+        let received = unsafe { libc::recv(self.fd.as_raw_fd(), self.buffer.as_mut_ptr() as *mut libc::c_void, self.buffer.len(), 0) };
+        if received == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(&self.buffer[..received as usize])
+    }
+}
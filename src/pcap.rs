@@ -0,0 +1,163 @@
+//! Reading the classic pcap format from a stream, for `--read-stdin`. The
+//! magic number gives the byte order and whether timestamps count micro- or
+//! nanoseconds. Frames are handed on as Ethernet, which is what analysis
+//! decodes: raw IP and Linux "cooked" captures (`tcpdump -i any`) get an
+//! Ethernet header made up for them.
+
+use chrono::{DateTime, Utc};
+use std::io::{ErrorKind, Read};
+
+use crate::error::{PacketSnifferError, Result};
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+/// The first block of a pcapng file, which this reader doesn't handle
+const PCAPNG_MAGIC: u32 = 0x0a0d_0d0a;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+
+/// Largest snapshot length tcpdump writes; a bigger record means a corrupt stream
+const MAX_RECORD_BYTES: usize = 262_144;
+
+/// Length of a Linux cooked-capture (SLL) header
+const SLL_HEADER_BYTES: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+enum LinkType {
+    Ethernet,
+    RawIp,
+    LinuxSll,
+}
+
+pub struct PcapReader<R> {
+    input: R,
+    big_endian: bool,
+    nanoseconds: bool,
+    link_type: LinkType,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Reads and checks the global header.
+    pub fn new(mut input: R) -> Result<Self> {
+        let read_error = |e: std::io::Error| match e.kind() {
+            ErrorKind::UnexpectedEof => PacketSnifferError::PcapError("the stream ended before a complete pcap header".to_string()),
+            _ => PacketSnifferError::PcapError(format!("reading the pcap header: {}", e)),
+        };
+        // The magic number alone tells pcapng and other formats apart
+        let mut header = [0u8; 24];
+        input.read_exact(&mut header[..4]).map_err(read_error)?;
+
+        let magic = [header[0], header[1], header[2], header[3]];
+        let (big_endian, nanoseconds) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (MAGIC_MICROS, _) => (false, false),
+            (_, MAGIC_MICROS) => (true, false),
+            (MAGIC_NANOS, _) => (false, true),
+            (_, MAGIC_NANOS) => (true, true),
+            (PCAPNG_MAGIC, _) => {
+                return Err(PacketSnifferError::PcapError("this is pcapng, not pcap; write classic pcap instead (tcpdump does by default, tshark/dumpcap with -F pcap)".to_string()));
+            }
+            _ => return Err(PacketSnifferError::PcapError(format!("not a pcap stream (magic number {:02x?})", magic))),
+        };
+
+        input.read_exact(&mut header[4..]).map_err(read_error)?;
+
+        let mut reader = PcapReader { input, big_endian, nanoseconds, link_type: LinkType::Ethernet };
+        reader.link_type = match reader.u32_at(&header, 20) {
+            LINKTYPE_ETHERNET => LinkType::Ethernet,
+            LINKTYPE_RAW => LinkType::RawIp,
+            LINKTYPE_LINUX_SLL => LinkType::LinuxSll,
+            other => {
+                return Err(PacketSnifferError::PcapError(format!("link type {} isn't supported; only Ethernet (1), raw IP (101) and Linux cooked (113) are", other)));
+            }
+        };
+        Ok(reader)
+    }
+
+    /// The next record as an Ethernet frame with its capture time, or `None`
+    /// once the stream has ended cleanly between records.
+    pub fn next_frame(&mut self) -> Result<Option<(DateTime<Utc>, Vec<u8>)>> {
+        loop {
+            let mut header = [0u8; 16];
+            if !self.fill(&mut header)? {
+                return Ok(None);
+            }
+
+            let seconds = self.u32_at(&header, 0);
+            let fraction = self.u32_at(&header, 4);
+            let length = self.u32_at(&header, 8) as usize;
+            if length > MAX_RECORD_BYTES {
+                return Err(PacketSnifferError::PcapError(format!("a record claims {} bytes, more than any capture writes; the stream is corrupt", length)));
+            }
+
+            let mut data = vec![0u8; length];
+            if !self.fill(&mut data)? {
+                return Err(PacketSnifferError::PcapError("the stream ended in the middle of a record".to_string()));
+            }
+
+            // A cooked record too short for its own header carries nothing
+            let Some(frame) = self.as_ethernet(data) else {
+                continue;
+            };
+            let nanos = if self.nanoseconds { fraction } else { fraction.saturating_mul(1000) };
+            let timestamp = DateTime::from_timestamp(seconds as i64, nanos.min(999_999_999)).unwrap_or_else(Utc::now);
+            return Ok(Some((timestamp, frame)));
+        }
+    }
+
+    /// Fills `buffer` completely; `false` if the stream ended before any of
+    /// it. An empty buffer is always filled.
+    fn fill(&mut self, buffer: &mut [u8]) -> Result<bool> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.input.read(&mut buffer[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(PacketSnifferError::PcapError("the stream ended in the middle of a record".to_string())),
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(PacketSnifferError::PcapError(format!("reading the stream: {}", e))),
+            }
+        }
+        Ok(true)
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let field = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+        if self.big_endian { u32::from_be_bytes(field) } else { u32::from_le_bytes(field) }
+    }
+
+    fn as_ethernet(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        Some(match self.link_type {
+            LinkType::Ethernet => data,
+            LinkType::RawIp => {
+                let ethertype = match data.first().map(|byte| byte >> 4) {
+                    Some(4) => 0x0800,
+                    Some(6) => 0x86dd,
+                    _ => 0,
+                };
+                with_ethernet_header([0; 6], ethertype, &data)
+            }
+            LinkType::LinuxSll if data.len() >= SLL_HEADER_BYTES => {
+                // The sender's link-layer address, when it's a MAC
+                let mut src_mac = [0u8; 6];
+                if u16::from_be_bytes([data[4], data[5]]) == 6 {
+                    src_mac.copy_from_slice(&data[6..12]);
+                }
+                let ethertype = u16::from_be_bytes([data[14], data[15]]);
+                with_ethernet_header(src_mac, ethertype, &data[SLL_HEADER_BYTES..])
+            }
+            LinkType::LinuxSll => return None,
+        })
+    }
+}
+
+/// `payload` behind an Ethernet header with a zero destination MAC.
+fn with_ethernet_header(src_mac: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&src_mac);
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
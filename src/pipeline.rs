@@ -0,0 +1,255 @@
+//! Dashboard-mode capture as stages joined by bounded channels. The dispatch
+//! task deals frames round-robin to a pool of analysis
+//! workers, each decoding with its own `AnalysisContext`. The stats task owns
+//! `NetworkStats` and the packet buffer outright, collects results from the
+//! workers in the same round-robin order, so packets are recorded (and
+//! numbered) in capture order, and publishes a snapshot every refresh for the
+//! dashboard to render. Nothing is shared behind a lock, so analysis never
+//! waits on a redraw.
+//!
+//! Overload is handled by backpressure: full worker queues make dispatch
+//! wait, the frame queue fills up behind it, and the readers then drop and
+//! count frames (see `capture::start_async`).
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::capture::{AsyncCapture, CapturedFrame, KernelDrops};
+use crate::config::Config;
+use crate::reassembly::TcpReassembler;
+use crate::throttle::{RateLimiter, Sampler};
+use crate::session::{self, Session};
+use crate::{AnalysisContext, Args, NetworkStats, PacketInfo, StreamingExports, ThreatRules};
+
+/// Frames waiting for each worker, and results waiting for the stats task
+const WORKER_QUEUE_CAPACITY: usize = 256;
+
+/// What the dashboard and `--api-port` draw from. The packets are shared
+/// with the stats task rather than copied, so a refresh costs a pointer per
+/// retained packet instead of a deep clone of each.
+pub struct Snapshot {
+    pub stats: NetworkStats,
+    pub packets: Vec<Arc<PacketInfo>>,
+}
+
+impl Snapshot {
+    /// The renderer's consistent view of the capture. Only the stats task
+    /// writes `NetworkStats`, and it takes this between two packets, so every
+    /// counter agrees with the others. That is also why the per-packet
+    /// counters need neither a lock nor atomics: readers never touch the
+    /// live struct.
+    pub fn of(stats: &NetworkStats, packets: &VecDeque<Arc<PacketInfo>>) -> Arc<Self> {
+        Arc::new(Snapshot { stats: stats.clone(), packets: packets.iter().cloned().collect() })
+    }
+}
+
+/// Everything the final summary and exports need, once capture has ended.
+pub struct Outcome {
+    pub stats: NetworkStats,
+    pub packets: Vec<PacketInfo>,
+    pub streams: StreamingExports,
+    pub reassembly: Option<TcpReassembler>,
+}
+
+pub struct Pipeline {
+    dispatch: JoinHandle<Option<TcpReassembler>>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+    aggregation: JoinHandle<(NetworkStats, Vec<PacketInfo>, StreamingExports)>,
+}
+
+enum Job {
+    Frame(CapturedFrame),
+    Throttled,
+}
+
+enum Analyzed {
+    Packet(Box<PacketInfo>),
+    /// Over `performance.max_packets_per_second`, so not analyzed
+    Throttled,
+}
+
+impl Pipeline {
+    /// Starts every stage. The snapshot channel closes once capture is over:
+    /// on shutdown, at `--count`/`--duration`, or when every reader stopped.
+    pub fn spawn(runtime: &Runtime, capture: AsyncCapture, args: Args, context: AnalysisContext, streams: StreamingExports, session: Session, config: &Config) -> (Self, watch::Receiver<Arc<Snapshot>>) {
+        let packets = session.packets.into_iter().map(Arc::new).collect();
+        let initial = Snapshot::of(&session.stats, &packets);
+        let (snapshots_tx, snapshots_rx) = watch::channel(initial);
+
+        let worker_count = match config.performance.analysis_workers {
+            0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            count => count,
+        };
+        let mut jobs = Vec::new();
+        let mut results = Vec::new();
+        let mut workers = Vec::new();
+        for _ in 0..worker_count {
+            let (jobs_tx, jobs_rx) = mpsc::channel(WORKER_QUEUE_CAPACITY);
+            let (results_tx, results_rx) = mpsc::channel(WORKER_QUEUE_CAPACITY);
+            let context = context.clone();
+            workers.push(std::thread::spawn(move || analyze(jobs_rx, results_tx, context)));
+            jobs.push(jobs_tx);
+            results.push(results_rx);
+        }
+
+        let aggregator = Aggregator {
+            threat: context.threat,
+            capture_overflow: capture.overflow,
+            kernel_drops: capture.kernel_drops,
+            stats: session.stats,
+            packets,
+            max_retained_packets: config.performance.max_retained_packets,
+            streams,
+            save_args: args.clone(),
+        };
+        let refresh_interval = crate::dashboard_refresh_interval(&config.performance);
+        let aggregation = runtime.spawn(aggregator.run(results, snapshots_tx, refresh_interval));
+        let throttle = RateLimiter::new(config.performance.max_packets_per_second);
+        let reassembly = args.follow_stream.clone().map(TcpReassembler::new);
+        let dispatch = runtime.spawn(dispatch(capture.frames, jobs, args, throttle, reassembly));
+
+        (Pipeline { dispatch, workers, aggregation }, snapshots_rx)
+    }
+
+    /// Waits for every stage to wind down.
+    pub fn finish(self, runtime: &Runtime) -> Outcome {
+        let (reassembly, (stats, packets, streams)) = runtime.block_on(async {
+            let reassembly = self.dispatch.await.unwrap_or_else(|_| {
+                errln!("Dispatch task panicked; the summary may be incomplete");
+                None
+            });
+            (reassembly, self.aggregation.await.expect("stats task panicked"))
+        });
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+        Outcome { stats, packets, streams, reassembly }
+    }
+}
+
+/// Deals frames to the workers in turn, in arrival order.
+/// Rate limiting and stream reassembly depend on that order, so they happen
+/// here rather than in the workers.
+async fn dispatch(mut frames: mpsc::Receiver<CapturedFrame>, workers: Vec<mpsc::Sender<Job>>, args: Args, mut throttle: RateLimiter, mut reassembly: Option<TcpReassembler>) -> Option<TcpReassembler> {
+    let mut packet_count = 0;
+    let mut next = 0;
+    let capture_start = Instant::now();
+    let mut sampler = Sampler::new(args.sample);
+
+    while !crate::shutdown_requested() && !crate::capture_limit_reached(&args, packet_count, capture_start) {
+        // Time out now and then to re-check the shutdown flag and limits
+        let frame = match tokio::time::timeout(crate::CAPTURE_READ_TIMEOUT, frames.recv()).await {
+            Ok(Some(frame)) => frame,
+            Err(_) => continue,
+            // Every reader has stopped
+            Ok(None) => break,
+        };
+        if !sampler.take() {
+            continue;
+        }
+
+        let job = if throttle.allow() {
+            if let Some(ref mut reassembly) = reassembly {
+                crate::follow_stream(reassembly, &frame.data);
+            }
+            packet_count += 1;
+            Job::Frame(frame)
+        } else {
+            Job::Throttled
+        };
+        if workers[next].send(job).await.is_err() {
+            break;
+        }
+        next = (next + 1) % workers.len();
+    }
+
+    reassembly
+}
+
+/// One worker thread: decoding is CPU-bound, so it doesn't run on the runtime.
+fn analyze(mut jobs: mpsc::Receiver<Job>, results: mpsc::Sender<Analyzed>, mut context: AnalysisContext) {
+    while let Some(job) = jobs.blocking_recv() {
+        let analyzed = match job {
+            Job::Frame(frame) => Analyzed::Packet(Box::new(crate::analyze_packet_advanced(&frame, &mut context))),
+            Job::Throttled => Analyzed::Throttled,
+        };
+        if results.blocking_send(analyzed).is_err() {
+            return;
+        }
+    }
+}
+
+struct Aggregator {
+    threat: ThreatRules,
+    capture_overflow: Arc<AtomicUsize>,
+    kernel_drops: KernelDrops,
+    stats: NetworkStats,
+    /// The newest `max_retained_packets`, oldest first
+    packets: VecDeque<Arc<PacketInfo>>,
+    max_retained_packets: usize,
+    streams: StreamingExports,
+    /// For `--save-session`
+    save_args: Args,
+}
+
+impl Aggregator {
+    /// Takes one result from each worker in turn, matching the order frames
+    /// were dealt in. A worker with nothing left means dispatch has stopped,
+    /// since every later frame would have gone to a worker after it.
+    async fn run(mut self, mut results: Vec<mpsc::Receiver<Analyzed>>, snapshots: watch::Sender<Arc<Snapshot>>, refresh_interval: Duration) -> (NetworkStats, Vec<PacketInfo>, StreamingExports) {
+        let mut refresh = tokio::time::interval(refresh_interval);
+        let mut drops_poll = tokio::time::interval(crate::KERNEL_DROPS_POLL_INTERVAL);
+        let mut session_save = tokio::time::interval_at(tokio::time::Instant::now() + session::SAVE_INTERVAL, session::SAVE_INTERVAL);
+        let mut next = 0;
+
+        loop {
+            tokio::select! {
+                message = results[next].recv() => match message {
+                    Some(analyzed) => {
+                        match analyzed {
+                            Analyzed::Packet(packet_info) => self.record(*packet_info),
+                            Analyzed::Throttled => self.stats.dropped_packets += 1,
+                        }
+                        next = (next + 1) % results.len();
+                    }
+                    None => break,
+                },
+                _ = refresh.tick() => self.publish(&snapshots),
+                _ = drops_poll.tick() => self.stats.kernel_dropped = self.kernel_drops.since_start(),
+                _ = session_save.tick() => {
+                    if let Err(e) = crate::save_session(&self.save_args, &self.stats, self.packets.make_contiguous()) {
+                        errln!("{}", e);
+                    }
+                }
+            }
+        }
+
+        self.stats.kernel_dropped = self.kernel_drops.since_start();
+        self.publish(&snapshots);
+        let packets = self.packets.into_iter().map(Arc::unwrap_or_clone).collect();
+        (self.stats, packets, self.streams)
+    }
+
+    fn record(&mut self, mut packet_info: PacketInfo) {
+        self.stats.record(&mut packet_info, &self.threat);
+        if let Err(e) = self.streams.write(&packet_info, &self.stats) {
+            errln!("{}; that export has been stopped", e);
+        }
+
+        self.packets.push_back(Arc::new(packet_info));
+        if self.packets.len() > self.max_retained_packets {
+            self.packets.pop_front();
+        }
+    }
+
+    fn publish(&mut self, snapshots: &watch::Sender<Arc<Snapshot>>) {
+        self.stats.capture_overflow = self.capture_overflow.load(Ordering::Relaxed);
+        // Nobody watching any more is fine; the dashboard may have quit first
+        let _ = snapshots.send(Snapshot::of(&self.stats, &self.packets));
+    }
+}
@@ -0,0 +1,43 @@
+//! QUIC recognition for UDP/443, where HTTP/3 runs. Only the invariant header
+//! bits (RFC 8999) and the version field are checked. The ClientHello in an
+//! Initial packet is encrypted with keys derived from the connection ID, so
+//! pulling the SNI out would need AES-GCM and HKDF; that is left out.
+
+const LONG_HEADER: u8 = 0x80;
+const FIXED_BIT: u8 = 0x40;
+
+/// Version negotiation packets carry version 0
+const VERSION_NEGOTIATION: u32 = 0;
+const VERSION_1: u32 = 0x0000_0001;
+const VERSION_2: u32 = 0x6b33_43cf;
+/// IETF drafts were `0xff0000NN`
+const DRAFT_PREFIX: u32 = 0xff00_0000;
+
+/// Longest connection ID in QUIC v1 and v2
+const MAX_CONNECTION_ID_LEN: usize = 20;
+
+/// Short-header packets carry at least a packet number and an AEAD tag
+const MIN_SHORT_HEADER_LEN: usize = 1 + 1 + 16;
+
+/// Whether a UDP payload to or from port 443 looks like QUIC. Long headers
+/// must name a known version; short headers only have the fixed bit to go
+/// on, so they are accepted when it is set and the packet is long enough.
+pub fn is_quic(payload: &[u8]) -> bool {
+    let Some(&first) = payload.first() else {
+        return false;
+    };
+
+    if first & LONG_HEADER == 0 {
+        return first & FIXED_BIT != 0 && payload.len() >= MIN_SHORT_HEADER_LEN;
+    }
+
+    let Some(version) = payload.get(1..5).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])) else {
+        return false;
+    };
+    let connection_id_ok = payload.get(5).is_some_and(|&len| len as usize <= MAX_CONNECTION_ID_LEN);
+    match version {
+        VERSION_NEGOTIATION => connection_id_ok,
+        VERSION_1 | VERSION_2 => connection_id_ok && first & FIXED_BIT != 0,
+        _ => connection_id_ok && first & FIXED_BIT != 0 && version & 0xffff_ff00 == DRAFT_PREFIX,
+    }
+}
@@ -0,0 +1,104 @@
+//! Background reverse-DNS for `--resolve-names`. PTR lookups can take seconds
+//! and generate traffic of their own, so the capture side only queues
+//! addresses; a small worker pool resolves them into a shared cache that the
+//! display code reads without waiting.
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+// Lookups block for the resolver timeout on unreachable servers, so a few
+// workers keep one slow address from holding up the rest
+const WORKER_COUNT: usize = 4;
+
+// Same bound as the GeoIP cache
+const MAX_CACHED_NAMES: usize = 10_000;
+
+#[derive(Default)]
+struct NameCache {
+    /// `None` records a lookup that found no PTR record
+    names: HashMap<IpAddr, Option<String>>,
+    pending: HashSet<IpAddr>,
+}
+
+/// Cheap to clone; all clones share one cache and worker pool.
+#[derive(Clone)]
+pub struct NameResolver {
+    cache: Arc<Mutex<NameCache>>,
+    requests: Option<Sender<IpAddr>>,
+}
+
+impl NameResolver {
+    /// A disabled resolver never queues lookups and labels every address
+    /// with the bare IP.
+    pub fn new(enabled: bool) -> Self {
+        let cache = Arc::new(Mutex::new(NameCache::default()));
+        if !enabled {
+            return NameResolver { cache, requests: None };
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WORKER_COUNT {
+            let cache = cache.clone();
+            let receiver = receiver.clone();
+            std::thread::spawn(move || resolve_worker(&receiver, &cache));
+        }
+
+        NameResolver { cache, requests: Some(sender) }
+    }
+
+    /// Queues a lookup unless the address is already known or in flight.
+    pub fn resolve(&self, ip: &str) {
+        let Some(ref requests) = self.requests else {
+            return;
+        };
+        let Ok(address) = ip.parse::<IpAddr>() else {
+            return;
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.names.contains_key(&address) || !cache.pending.insert(address) {
+            return;
+        }
+        drop(cache);
+
+        let _ = requests.send(address);
+    }
+
+    pub fn name(&self, ip: &str) -> Option<String> {
+        let address = ip.parse::<IpAddr>().ok()?;
+        self.cache.lock().unwrap().names.get(&address).cloned().flatten()
+    }
+
+    /// `server.example.com (1.2.3.4)` once resolved, otherwise just the IP.
+    pub fn label(&self, ip: &str) -> String {
+        match self.name(ip) {
+            Some(name) => format!("{} ({})", name, ip),
+            None => ip.to_string(),
+        }
+    }
+}
+
+fn resolve_worker(receiver: &Mutex<Receiver<IpAddr>>, cache: &Mutex<NameCache>) {
+    loop {
+        // Only hold the receiver lock while waiting, not during the lookup
+        let address = match receiver.lock().unwrap().recv() {
+            Ok(address) => address,
+            Err(_) => return,
+        };
+
+        // getnameinfo hands back the numeric address when there's no PTR record
+        let name = dns_lookup::lookup_addr(&address)
+            .ok()
+            .filter(|name| name.parse::<IpAddr>().is_err());
+
+        let mut cache = cache.lock().unwrap();
+        cache.pending.remove(&address);
+        if cache.names.len() >= MAX_CACHED_NAMES {
+            cache.names.clear();
+        }
+        cache.names.insert(address, name);
+    }
+}
@@ -0,0 +1,271 @@
+//! `--follow-stream`: TCP payload reassembly. Every TCP stream gets a number
+//! in the order it was first seen, but only the streams the selector names
+//! have their segments buffered. Segments are placed by sequence number, so
+//! out-of-order arrival and retransmissions sort themselves out; anything
+//! never captured shows up as a marked gap instead of holding back the rest.
+
+use colored::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+/// Payload kept per direction of a followed stream; the rest is counted as
+/// truncated rather than buffered
+const MAX_DIRECTION_BYTES: usize = 1024 * 1024;
+
+/// Streams numbered per capture. Past this, new streams are neither numbered
+/// nor followed, so a scan can't grow the table without bound.
+const MAX_TRACKED_STREAMS: usize = 65_536;
+
+/// Which streams to follow: one by number, or every stream with an endpoint.
+#[derive(Debug, Clone)]
+pub enum StreamSelector {
+    Number(usize),
+    Endpoint(SocketAddr),
+}
+
+impl FromStr for StreamSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(number) = s.parse::<usize>() {
+            return Ok(StreamSelector::Number(number));
+        }
+        s.parse::<SocketAddr>().map(StreamSelector::Endpoint).map_err(|_| {
+            format!("'{}' is neither a stream number nor ADDR:PORT ([ADDR]:PORT for IPv6)", s)
+        })
+    }
+}
+
+impl fmt::Display for StreamSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamSelector::Number(number) => write!(f, "stream {}", number),
+            StreamSelector::Endpoint(endpoint) => write!(f, "endpoint {}", endpoint),
+        }
+    }
+}
+
+impl StreamSelector {
+    fn selects(&self, number: usize, a: SocketAddr, b: SocketAddr) -> bool {
+        match self {
+            StreamSelector::Number(selected) => *selected == number,
+            StreamSelector::Endpoint(endpoint) => *endpoint == a || *endpoint == b,
+        }
+    }
+}
+
+/// One TCP segment's worth of what the reassembler needs.
+pub struct Segment<'a> {
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub sequence: u32,
+    pub syn: bool,
+    pub ack: bool,
+    pub fin: bool,
+    pub rst: bool,
+    pub payload: &'a [u8],
+}
+
+pub struct TcpReassembler {
+    selector: StreamSelector,
+    streams: Vec<TcpStream>,
+    /// Latest stream for each endpoint pair, lower address first
+    by_endpoints: HashMap<(SocketAddr, SocketAddr), usize>,
+}
+
+struct TcpStream {
+    number: usize,
+    /// The side that sent the SYN, or else the side seen first
+    client: SocketAddr,
+    server: SocketAddr,
+    followed: bool,
+    closed: bool,
+    to_server: Direction,
+    to_client: Direction,
+}
+
+#[derive(Default)]
+struct Direction {
+    /// Sequence number of the first payload byte
+    base: Option<u32>,
+    /// Payload by offset from `base`
+    segments: BTreeMap<u32, Vec<u8>>,
+    buffered: usize,
+    truncated: usize,
+}
+
+enum Chunk<'a> {
+    Data(&'a [u8]),
+    Missing(u32),
+}
+
+impl TcpReassembler {
+    pub fn new(selector: StreamSelector) -> Self {
+        TcpReassembler {
+            selector,
+            streams: Vec::new(),
+            by_endpoints: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, segment: &Segment) {
+        let src = SocketAddr::new(segment.src_ip, segment.src_port);
+        let dst = SocketAddr::new(segment.dst_ip, segment.dst_port);
+        let key = if src <= dst { (src, dst) } else { (dst, src) };
+
+        // A fresh SYN after the old connection closed starts a new stream
+        let opening = segment.syn && !segment.ack;
+        let existing = self.by_endpoints.get(&key).copied()
+            .filter(|&index| !(opening && self.streams[index].closed));
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                if self.streams.len() >= MAX_TRACKED_STREAMS {
+                    return;
+                }
+                let number = self.streams.len() + 1;
+                // Without a SYN, assume the higher port is the client's ephemeral one
+                let (client, server) = if opening || segment.src_port >= segment.dst_port { (src, dst) } else { (dst, src) };
+                self.streams.push(TcpStream {
+                    number,
+                    client,
+                    server,
+                    followed: self.selector.selects(number, src, dst),
+                    closed: false,
+                    to_server: Direction::default(),
+                    to_client: Direction::default(),
+                });
+                self.by_endpoints.insert(key, self.streams.len() - 1);
+                self.streams.len() - 1
+            }
+        };
+
+        let stream = &mut self.streams[index];
+        if segment.fin || segment.rst {
+            stream.closed = true;
+        }
+        if !stream.followed {
+            return;
+        }
+        let direction = if src == stream.client { &mut stream.to_server } else { &mut stream.to_client };
+        direction.add(segment.sequence, segment.syn, segment.payload);
+    }
+
+    /// Prints every followed stream, or the streams there are to pick from
+    /// when the selector matched none.
+    pub fn print(&self) {
+        let followed: Vec<&TcpStream> = self.streams.iter().filter(|stream| stream.followed).collect();
+        if followed.is_empty() {
+            outln!("\n{}", format!("🧵 No TCP stream matches {}", self.selector).yellow().bold());
+            if !self.streams.is_empty() {
+                outln!("Captured streams:");
+                for stream in &self.streams {
+                    outln!("   {:>5}  {} -> {}", stream.number, stream.client, stream.server);
+                }
+            }
+            return;
+        }
+
+        for stream in followed {
+            outln!("\n{}", format!("🧵 TCP stream {}: {} -> {}", stream.number, stream.client, stream.server).yellow().bold());
+            print_direction(stream.client, stream.server, &stream.to_server);
+            print_direction(stream.server, stream.client, &stream.to_client);
+        }
+    }
+}
+
+impl Direction {
+    fn add(&mut self, sequence: u32, syn: bool, payload: &[u8]) {
+        if syn {
+            self.base = Some(sequence.wrapping_add(1));
+        }
+        if payload.is_empty() {
+            return;
+        }
+        let base = *self.base.get_or_insert(sequence);
+        let data_sequence = if syn { sequence.wrapping_add(1) } else { sequence };
+
+        // Signed distance, so a retransmission from before `base` is trimmed
+        let offset = data_sequence.wrapping_sub(base) as i32 as i64;
+        let skip = (-offset).max(0) as usize;
+        if skip >= payload.len() {
+            return;
+        }
+        let offset = offset.max(0) as u32;
+        let payload = &payload[skip..];
+
+        if let Some(existing) = self.segments.get(&offset) {
+            if existing.len() >= payload.len() {
+                return;
+            }
+        }
+        if self.buffered + payload.len() > MAX_DIRECTION_BYTES {
+            self.truncated += payload.len();
+            return;
+        }
+        let replaced = self.segments.insert(offset, payload.to_vec()).map_or(0, |old| old.len());
+        self.buffered = self.buffered + payload.len() - replaced;
+    }
+
+    /// The stream in order, overlaps removed and holes marked.
+    fn chunks(&self) -> Vec<Chunk<'_>> {
+        let mut chunks = Vec::new();
+        let mut position: u64 = 0;
+        for (&offset, data) in &self.segments {
+            let (offset, end) = (offset as u64, offset as u64 + data.len() as u64);
+            if end <= position {
+                continue;
+            }
+            if offset > position {
+                chunks.push(Chunk::Missing((offset - position) as u32));
+                position = offset;
+            }
+            chunks.push(Chunk::Data(&data[(position - offset) as usize..]));
+            position = end;
+        }
+        chunks
+    }
+}
+
+fn print_direction(from: SocketAddr, to: SocketAddr, direction: &Direction) {
+    let chunks = direction.chunks();
+    let bytes: usize = chunks.iter().map(|chunk| match chunk {
+        Chunk::Data(data) => data.len(),
+        Chunk::Missing(_) => 0,
+    }).sum();
+    let gaps = chunks.iter().filter(|chunk| matches!(chunk, Chunk::Missing(_))).count();
+
+    let mut heading = format!("▶ {} -> {}: {} bytes", from, to, bytes);
+    if gaps > 0 {
+        heading.push_str(&format!(", {} gaps", gaps));
+    }
+    if direction.truncated > 0 {
+        heading.push_str(&format!(", {} bytes past the {} KB limit not kept", direction.truncated, MAX_DIRECTION_BYTES / 1024));
+    }
+    outln!("{}", heading.cyan().bold());
+
+    let mut text = String::new();
+    for chunk in chunks {
+        match chunk {
+            Chunk::Data(data) => text.push_str(&printable(data)),
+            Chunk::Missing(len) => text.push_str(&format!("[{} bytes missing]", len).red().to_string()),
+        }
+    }
+    if !text.is_empty() {
+        outln!("{}", text.trim_end_matches('\n'));
+    }
+}
+
+/// Text with line breaks and tabs kept; other control bytes and invalid
+/// UTF-8 become `.`, so binary payloads can't mess up the terminal.
+fn printable(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .chars()
+        .filter(|&c| c != '\r')
+        .map(|c| if c == '\n' || c == '\t' || !(c.is_control() || c == char::REPLACEMENT_CHARACTER) { c } else { '.' })
+        .collect()
+}
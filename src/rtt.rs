@@ -0,0 +1,107 @@
+//! Passive TCP round-trip estimates. A segment is timed from when it passes
+//! the capture point until the ACK covering it comes back, which measures
+//! the leg between the capture point and the receiver. The handshake times
+//! both legs (SYN → SYN-ACK, then SYN-ACK → ACK) and data keeps them fresh;
+//! the end-to-end RTT is the two legs added up. Retransmitted data is never
+//! timed, since its ACK can't be matched to one transmission (Karn's rule).
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Unacknowledged segments timed per direction; older ones are dropped
+const MAX_PENDING: usize = 32;
+
+/// Weight of a new sample in the smoothed estimate, as in RFC 6298
+const SMOOTHING: f64 = 0.125;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RttStats {
+    pub samples: usize,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub avg_ms: f64,
+    pub smoothed_ms: f64,
+}
+
+impl RttStats {
+    fn new(sample_ms: f64) -> Self {
+        RttStats { samples: 1, min_ms: sample_ms, max_ms: sample_ms, avg_ms: sample_ms, smoothed_ms: sample_ms }
+    }
+
+    fn add(&mut self, sample_ms: f64) {
+        self.samples += 1;
+        self.min_ms = self.min_ms.min(sample_ms);
+        self.max_ms = self.max_ms.max(sample_ms);
+        self.avg_ms += (sample_ms - self.avg_ms) / self.samples as f64;
+        self.smoothed_ms += SMOOTHING * (sample_ms - self.smoothed_ms);
+    }
+
+    /// Both legs added up; with only one leg measured, that leg alone.
+    pub fn end_to_end(a: Option<&RttStats>, b: Option<&RttStats>) -> Option<RttStats> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(RttStats {
+                samples: a.samples + b.samples,
+                min_ms: a.min_ms + b.min_ms,
+                max_ms: a.max_ms + b.max_ms,
+                avg_ms: a.avg_ms + b.avg_ms,
+                smoothed_ms: a.smoothed_ms + b.smoothed_ms,
+            }),
+            (Some(leg), None) | (None, Some(leg)) => Some(*leg),
+            (None, None) => None,
+        }
+    }
+}
+
+/// One direction's segments waiting for an ACK, and the leg they measure.
+#[derive(Debug, Clone, Default)]
+pub struct RttLeg {
+    /// Sequence number just past each timed segment, with when it was seen
+    pending: VecDeque<(u32, DateTime<Utc>)>,
+    pub stats: Option<RttStats>,
+}
+
+impl RttLeg {
+    /// A segment using `len` sequence numbers left toward the receiver.
+    pub fn sent(&mut self, sequence: u32, len: u32, retransmission: bool, timestamp: DateTime<Utc>) {
+        if len == 0 {
+            return;
+        }
+        if retransmission {
+            self.pending.clear();
+            return;
+        }
+        let end = sequence.wrapping_add(len);
+        // The loopback double capture repeats a segment; keep the first sighting
+        if self.pending.back().is_some_and(|&(pending_end, _)| pending_end == end) {
+            return;
+        }
+        self.pending.push_back((end, timestamp));
+        if self.pending.len() > MAX_PENDING {
+            self.pending.pop_front();
+        }
+    }
+
+    /// The receiver acknowledged everything before `ack`. The newest segment
+    /// it covers gives the sample, since its ACK wasn't held back by delayed-
+    /// ACK timers waiting on later data.
+    pub fn acknowledged(&mut self, ack: u32, timestamp: DateTime<Utc>) {
+        let mut newest = None;
+        while let Some(&(end, sent)) = self.pending.front() {
+            if (ack.wrapping_sub(end) as i32) < 0 {
+                break;
+            }
+            newest = Some(sent);
+            self.pending.pop_front();
+        }
+
+        let Some(sent) = newest else {
+            return;
+        };
+        let sample_ms = (timestamp - sent).num_microseconds().unwrap_or(0) as f64 / 1000.0;
+        match self.stats {
+            Some(ref mut stats) => stats.add(sample_ms),
+            None => self.stats = Some(RttStats::new(sample_ms)),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! SCTP (RFC 9260): the common header and the types of the chunks bundled
+//! after it. Chunk contents aren't decoded.
+
+use serde::{Deserialize, Serialize};
+
+pub const COMMON_HEADER_LEN: usize = 12;
+const CHUNK_HEADER_LEN: usize = 4;
+
+/// Chunks listed for one packet; a bundle of many small DATA chunks stops here
+const MAX_CHUNKS: usize = 32;
+
+/// The parts of an SCTP packet besides its ports.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SctpInfo {
+    /// Zero only on a packet carrying INIT
+    pub verification_tag: u32,
+    /// Chunk type names in packet order, e.g. "INIT" or "DATA"
+    pub chunks: Vec<String>,
+}
+
+impl SctpInfo {
+    /// e.g. "SCTP COOKIE ECHO, DATA"
+    pub fn summary(&self) -> String {
+        if self.chunks.is_empty() {
+            "SCTP packet".to_string()
+        } else {
+            format!("SCTP {}", self.chunks.join(", "))
+        }
+    }
+}
+
+/// Source and destination ports, which sit where TCP and UDP keep theirs.
+pub fn ports(payload: &[u8]) -> Option<(u16, u16)> {
+    let header = payload.get(..COMMON_HEADER_LEN)?;
+    Some((u16::from_be_bytes([header[0], header[1]]), u16::from_be_bytes([header[2], header[3]])))
+}
+
+/// Decodes the payload of an IP packet with protocol 132. A chunk cut short
+/// ends the list rather than the packet.
+pub fn parse(payload: &[u8]) -> Option<SctpInfo> {
+    let header = payload.get(..COMMON_HEADER_LEN)?;
+    let verification_tag = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+    let mut chunks = Vec::new();
+    let mut offset = COMMON_HEADER_LEN;
+    while chunks.len() < MAX_CHUNKS {
+        let Some(chunk) = payload.get(offset..offset + CHUNK_HEADER_LEN) else {
+            break;
+        };
+        let length = u16::from_be_bytes([chunk[2], chunk[3]]) as usize;
+        if length < CHUNK_HEADER_LEN {
+            break;
+        }
+        chunks.push(chunk_name(chunk[0]));
+        // Chunks are padded to a multiple of four bytes
+        offset += length.div_ceil(4) * 4;
+    }
+
+    Some(SctpInfo { verification_tag, chunks })
+}
+
+fn chunk_name(chunk_type: u8) -> String {
+    let name = match chunk_type {
+        0 => "DATA",
+        1 => "INIT",
+        2 => "INIT ACK",
+        3 => "SACK",
+        4 => "HEARTBEAT",
+        5 => "HEARTBEAT ACK",
+        6 => "ABORT",
+        7 => "SHUTDOWN",
+        8 => "SHUTDOWN ACK",
+        9 => "ERROR",
+        10 => "COOKIE ECHO",
+        11 => "COOKIE ACK",
+        14 => "SHUTDOWN COMPLETE",
+        15 => "AUTH",
+        64 => "I-DATA",
+        128 => "ASCONF ACK",
+        130 => "RE-CONFIG",
+        132 => "PAD",
+        192 => "FORWARD TSN",
+        193 => "ASCONF",
+        194 => "I-FORWARD TSN",
+        other => return format!("chunk {}", other),
+    };
+    name.to_string()
+}
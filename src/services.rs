@@ -0,0 +1,165 @@
+//! Service names for well-known ports, read once at startup from the system
+//! services file. A short built-in table covers systems without one (or
+//! without a readable one), so the port panel is never just numbers.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::logger::Logger;
+
+#[cfg(windows)]
+const SERVICES_FILE: &str = r"C:\Windows\System32\drivers\etc\services";
+#[cfg(not(windows))]
+const SERVICES_FILE: &str = "/etc/services";
+
+/// The transports service names are looked up for. Other protocols in the
+/// services file, such as `ddp` or `sctp`, are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl Transport {
+    fn parse(protocol: &str) -> Option<Self> {
+        match protocol {
+            "tcp" => Some(Transport::Tcp),
+            "udp" => Some(Transport::Udp),
+            _ => None,
+        }
+    }
+}
+
+/// Used when the services file can't be read. Each name is taken for both
+/// transports, as IANA registers most of them.
+const BUILTIN_SERVICES: &[(u16, &str)] = &[
+    (20, "ftp-data"),
+    (21, "ftp"),
+    (22, "ssh"),
+    (23, "telnet"),
+    (25, "smtp"),
+    (53, "domain"),
+    (67, "bootps"),
+    (68, "bootpc"),
+    (69, "tftp"),
+    (80, "http"),
+    (88, "kerberos"),
+    (110, "pop3"),
+    (123, "ntp"),
+    (137, "netbios-ns"),
+    (138, "netbios-dgm"),
+    (139, "netbios-ssn"),
+    (143, "imap"),
+    (161, "snmp"),
+    (162, "snmp-trap"),
+    (179, "bgp"),
+    (389, "ldap"),
+    (443, "https"),
+    (445, "microsoft-ds"),
+    (465, "submissions"),
+    (514, "syslog"),
+    (546, "dhcpv6-client"),
+    (547, "dhcpv6-server"),
+    (587, "submission"),
+    (631, "ipp"),
+    (636, "ldaps"),
+    (853, "domain-s"),
+    (873, "rsync"),
+    (993, "imaps"),
+    (995, "pop3s"),
+    (1194, "openvpn"),
+    (1433, "ms-sql-s"),
+    (1812, "radius"),
+    (1883, "mqtt"),
+    (1900, "ssdp"),
+    (3306, "mysql"),
+    (3389, "ms-wbt-server"),
+    (5060, "sip"),
+    (5353, "mdns"),
+    (5432, "postgresql"),
+    (5900, "rfb"),
+    (6379, "redis"),
+    (8080, "http-alt"),
+    (8443, "https-alt"),
+    (27017, "mongodb"),
+];
+
+static SERVICES: OnceLock<HashMap<(u16, Transport), String>> = OnceLock::new();
+
+/// Loads the service table. Call once before capture starts; lookups before
+/// that (or without it) fall back to the built-in table.
+pub fn init(logger: &mut Logger) {
+    let services = match fs::read_to_string(SERVICES_FILE) {
+        Ok(content) => {
+            let services = parse_services(&content);
+            logger.log_info(&format!("Loaded {} service names from {}", services.len(), SERVICES_FILE));
+            services
+        }
+        Err(e) => {
+            logger.log_warn(&format!("Failed to read {}: {}, using built-in service names", SERVICES_FILE, e));
+            builtin_services()
+        }
+    };
+    let _ = SERVICES.set(services);
+}
+
+/// Service name for `port` over `transport`, such as `https` for TCP 443.
+pub fn name(port: u16, transport: Transport) -> Option<&'static str> {
+    SERVICES.get_or_init(builtin_services).get(&(port, transport)).map(String::as_str)
+}
+
+fn builtin_services() -> HashMap<(u16, Transport), String> {
+    BUILTIN_SERVICES.iter()
+        .flat_map(|&(port, name)| [Transport::Tcp, Transport::Udp].map(|transport| ((port, transport), name.to_string())))
+        .collect()
+}
+
+/// Parses `name port/protocol [aliases...] [# comment]` lines, keeping tcp and
+/// udp entries. The first name listed for a port and transport wins.
+fn parse_services(content: &str) -> HashMap<(u16, Transport), String> {
+    let mut services = HashMap::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_protocol)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, protocol)) = port_protocol.split_once('/') else {
+            continue;
+        };
+        let (Ok(port), Some(transport)) = (port.parse(), Transport::parse(protocol)) else {
+            continue;
+        };
+        services.entry((port, transport)).or_insert_with(|| name.to_string());
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_kept_apart_by_transport() {
+        let services = parse_services(
+            "tcpmux\t\t1/tcp\t\t\t\t# TCP port service multiplexer\n\
+             nbp\t\t2/ddp\t\t\t\t# Name Binding Protocol\n\
+             bbs\t\t7000/tcp\n\
+             afs3-callback\t7001/udp\n\
+             domain\t\t53/tcp\n\
+             domain\t\t53/udp\n",
+        );
+        let name = |port, transport| services.get(&(port, transport)).map(String::as_str);
+
+        assert_eq!(name(7000, Transport::Tcp), Some("bbs"));
+        assert_eq!(name(7000, Transport::Udp), None);
+        assert_eq!(name(7001, Transport::Udp), Some("afs3-callback"));
+        assert_eq!(name(7001, Transport::Tcp), None);
+        assert_eq!(name(2, Transport::Tcp), None);
+        assert_eq!(name(2, Transport::Udp), None);
+        assert_eq!(name(1, Transport::Tcp), Some("tcpmux"));
+        assert_eq!(name(53, Transport::Tcp), Some("domain"));
+        assert_eq!(name(53, Transport::Udp), Some("domain"));
+    }
+}
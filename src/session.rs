@@ -0,0 +1,94 @@
+//! `--save-session` and `--load-session`: the accumulated stats, and
+//! optionally the retained packets, written to disk now and then so a long
+//! monitoring run can be stopped and picked up again with its totals intact.
+//! Detectors, limits and alert outputs aren't saved; the resuming run builds
+//! them from its own configuration.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use crate::config::{PerformanceConfig, ThreatConfig};
+use crate::error::{PacketSnifferError, Result};
+use crate::{NetworkStats, PacketInfo};
+
+/// Bumped whenever a saved session from an older build would no longer load
+const SESSION_VERSION: u32 = 1;
+
+/// How often a running capture saves, besides once at the end
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What a capture starts from: a resumed session, or nothing yet.
+pub struct Session {
+    pub stats: NetworkStats,
+    /// Oldest first, as the capture loop keeps them
+    pub packets: VecDeque<PacketInfo>,
+}
+
+#[derive(Serialize)]
+struct SavedRef<'a, P> {
+    version: u32,
+    saved_at: DateTime<Utc>,
+    stats: &'a NetworkStats,
+    packets: &'a [P],
+}
+
+#[derive(Deserialize)]
+struct SavedSession {
+    stats: NetworkStats,
+    #[serde(default)]
+    packets: Vec<PacketInfo>,
+}
+
+/// Read first, so a session from another build fails with a clear message
+/// rather than whichever field no longer matches
+#[derive(Deserialize)]
+struct SavedVersion {
+    version: u32,
+}
+
+/// Writes beside `path` and renames over it, so a capture killed mid-save
+/// leaves the previous session intact.
+pub fn save<P: Serialize>(path: &str, stats: &NetworkStats, packets: &[P]) -> Result<()> {
+    let saved = SavedRef { version: SESSION_VERSION, saved_at: Utc::now(), stats, packets };
+    let partial = format!("{}.tmp", path);
+    let failed = |e: &dyn std::fmt::Display| PacketSnifferError::SessionError(format!("Failed to save the session to {}: {}", path, e));
+
+    let file = fs::File::create(&partial).map_err(|e| failed(&e))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, &saved).map_err(|e| failed(&e))?;
+    writer.flush().map_err(|e| failed(&e))?;
+    drop(writer);
+    fs::rename(&partial, path).map_err(|e| failed(&e))
+}
+
+/// Loads a session saved by `save`, keeping at most `max_retained_packets`
+/// of its packets. Its totals count one packet in `sample_rate`, so a run
+/// sampling at another rate can't add to them.
+pub fn load(path: &str, threat: &ThreatConfig, performance: &PerformanceConfig, sample_rate: Option<usize>) -> Result<Session> {
+    let failed = |e: &dyn std::fmt::Display| PacketSnifferError::SessionError(format!("Failed to load the session from {}: {}", path, e));
+
+    let contents = fs::read_to_string(path).map_err(|e| failed(&e))?;
+    let SavedVersion { version } = serde_json::from_str(&contents).map_err(|e| failed(&e))?;
+    if version != SESSION_VERSION {
+        return Err(PacketSnifferError::SessionError(format!(
+            "{} was saved as session version {}, but this build reads version {}", path, version, SESSION_VERSION
+        )));
+    }
+    let saved: SavedSession = serde_json::from_str(&contents).map_err(|e| failed(&e))?;
+    if saved.stats.sample_rate != sample_rate {
+        let describe = |rate: Option<usize>| rate.map_or("unsampled".to_string(), |every| format!("sampled 1:{}", every));
+        return Err(PacketSnifferError::SessionError(format!(
+            "{} holds a session {}, but this run is {}; resume it with the same --sample", path, describe(saved.stats.sample_rate), describe(sample_rate)
+        )));
+    }
+
+    let mut packets = VecDeque::from(saved.packets);
+    while packets.len() > performance.max_retained_packets {
+        packets.pop_front();
+    }
+    Ok(Session { stats: NetworkStats::resume(saved.stats, threat, performance), packets })
+}
@@ -0,0 +1,137 @@
+//! `--export-sqlite`: packets land in a `packets` table as they are captured.
+//! Rows are committed in batches, since a transaction per insert would cap
+//! throughput at a few hundred packets per second.
+
+use rusqlite::{params, Connection};
+use std::time::{Duration, Instant};
+
+use crate::error::{PacketSnifferError, Result};
+use crate::style;
+use crate::PacketInfo;
+
+const COMMIT_EVERY_ROWS: usize = 500;
+const COMMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS packets (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp TEXT NOT NULL,
+        packet_number INTEGER NOT NULL,
+        src_ip TEXT,
+        dst_ip TEXT,
+        protocol TEXT NOT NULL,
+        src_port INTEGER,
+        dst_port INTEGER,
+        packet_size INTEGER NOT NULL,
+        flags TEXT,
+        application_protocol TEXT,
+        description TEXT NOT NULL,
+        http_method TEXT,
+        http_host TEXT,
+        http_path TEXT,
+        threat_level TEXT NOT NULL,
+        country TEXT,
+        city TEXT,
+        latitude REAL,
+        longitude REAL
+    );
+    CREATE INDEX IF NOT EXISTS packets_endpoints ON packets (src_ip, dst_ip);
+    CREATE INDEX IF NOT EXISTS packets_timestamp ON packets (timestamp);
+";
+
+const INSERT: &str = "
+    INSERT INTO packets (
+        timestamp, packet_number, src_ip, dst_ip, protocol, src_port, dst_port, packet_size, flags,
+        application_protocol, description, http_method, http_host, http_path, threat_level,
+        country, city, latitude, longitude
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+";
+
+pub struct SqliteWriter {
+    path: String,
+    connection: Connection,
+    records: usize,
+    uncommitted: usize,
+    last_commit: Instant,
+}
+
+impl SqliteWriter {
+    /// Appends to an existing database, so several sessions can share one
+    /// file; `id` keeps rows unique across them.
+    pub fn open(path: &str) -> Result<Self> {
+        let connection = Connection::open(path)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to open SQLite database {}: {}", path, e)))?;
+        connection.execute_batch(SCHEMA)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to create SQLite schema: {}", e)))?;
+
+        Ok(SqliteWriter {
+            path: path.to_string(),
+            connection,
+            records: 0,
+            uncommitted: 0,
+            last_commit: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn write(&mut self, packet: &PacketInfo) -> Result<()> {
+        if self.uncommitted == 0 {
+            self.execute("BEGIN")?;
+        }
+
+        let http = packet.http_info.as_ref();
+        let geo = packet.geo_info.as_ref();
+        let mut insert = self.connection.prepare_cached(INSERT)
+            .map_err(|e| PacketSnifferError::ExportError(format!("Failed to prepare SQLite insert: {}", e)))?;
+        insert.execute(params![
+            style::rfc3339(&packet.timestamp),
+            packet.packet_number as i64,
+            packet.src_ip,
+            packet.dst_ip,
+            packet.protocol,
+            packet.src_port,
+            packet.dst_port,
+            packet.packet_size as i64,
+            packet.flags,
+            packet.application_protocol,
+            packet.description,
+            http.map(|h| &h.method),
+            http.and_then(|h| h.host.as_ref()),
+            http.map(|h| &h.path),
+            packet.threat_level.as_str(),
+            geo.and_then(|g| g.country.as_ref()),
+            geo.and_then(|g| g.city.as_ref()),
+            geo.and_then(|g| g.latitude),
+            geo.and_then(|g| g.longitude),
+        ]).map_err(|e| PacketSnifferError::ExportError(format!("Failed to insert SQLite row: {}", e)))?;
+        drop(insert);
+
+        self.records += 1;
+        self.uncommitted += 1;
+        if self.uncommitted >= COMMIT_EVERY_ROWS || self.last_commit.elapsed() >= COMMIT_INTERVAL {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        if self.uncommitted > 0 {
+            self.execute("COMMIT")?;
+        }
+        self.uncommitted = 0;
+        self.last_commit = Instant::now();
+        Ok(())
+    }
+
+    fn execute(&self, statement: &str) -> Result<()> {
+        self.connection.execute_batch(statement)
+            .map_err(|e| PacketSnifferError::ExportError(format!("SQLite {} failed: {}", statement, e)))
+    }
+}
@@ -0,0 +1,219 @@
+//! Output styling derived from `UiConfig`. Colors are switched off globally
+//! through `colored`'s override; emoji are swapped for ASCII markers by the
+//! `outln!`/`out!`/`errln!` macros, which every console write goes through.
+//! Under `--quiet` the stdout macros print nothing, leaving stdout to the
+//! per-packet records. Timestamps are shown in `ui.timezone`, and
+//! `ui.timestamp_format` sets how packet times read on the console.
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::config::{UiConfig, DEFAULT_TIMESTAMP_FORMAT};
+
+#[derive(Debug, Clone, Copy)]
+pub struct OutputStyle {
+    pub colors: bool,
+    pub emojis: bool,
+    pub quiet: bool,
+}
+
+static STYLE: OnceLock<OutputStyle> = OnceLock::new();
+
+static TIMESTAMPS: OnceLock<TimestampStyle> = OnceLock::new();
+
+/// `ui.timezone` and `ui.timestamp_format`
+struct TimestampStyle {
+    local: bool,
+    format: String,
+}
+
+impl OutputStyle {
+    /// `NO_COLOR` (any value) wins over the config, per https://no-color.org
+    pub fn from_config(ui: &UiConfig) -> Self {
+        OutputStyle {
+            colors: ui.colors_enabled && std::env::var_os("NO_COLOR").is_none(),
+            emojis: ui.emojis_enabled,
+            quiet: false,
+        }
+    }
+}
+
+pub fn init(style: OutputStyle) {
+    if !style.colors {
+        colored::control::set_override(false);
+    }
+    let _ = STYLE.set(style);
+}
+
+/// Call once the config has been validated, so the format is known to be good.
+pub fn init_timestamps(ui: &UiConfig) {
+    let _ = TIMESTAMPS.set(TimestampStyle {
+        local: ui.timezone.eq_ignore_ascii_case("local"),
+        format: ui.timestamp_format.clone(),
+    });
+}
+
+/// `time` in `ui.timezone`, laid out by `format`.
+pub fn format_time(time: &DateTime<Utc>, format: &str) -> String {
+    if TIMESTAMPS.get().is_some_and(|timestamps| timestamps.local) {
+        time.with_timezone(&Local).format(format).to_string()
+    } else {
+        time.format(format).to_string()
+    }
+}
+
+/// A packet's time as `ui.timestamp_format` has it, for per-packet lines.
+pub fn packet_time(time: &DateTime<Utc>) -> String {
+    format_time(time, TIMESTAMPS.get().map_or(DEFAULT_TIMESTAMP_FORMAT, |timestamps| timestamps.format.as_str()))
+}
+
+/// What to print after a time so the zone is clear: `UTC`, or the local
+/// offset at that time.
+pub fn zone_label(time: &DateTime<Utc>) -> String {
+    if TIMESTAMPS.get().is_some_and(|timestamps| timestamps.local) {
+        time.with_timezone(&Local).format("%:z").to_string()
+    } else {
+        "UTC".to_string()
+    }
+}
+
+/// RFC 3339 in `ui.timezone`, for exports: still machine-readable, and the
+/// offset says which zone it is.
+pub fn rfc3339(time: &DateTime<Utc>) -> String {
+    if TIMESTAMPS.get().is_some_and(|timestamps| timestamps.local) {
+        time.with_timezone(&Local).to_rfc3339()
+    } else {
+        time.to_rfc3339()
+    }
+}
+
+/// `serialize_with` for exported timestamps; reading them back accepts any offset.
+pub fn serialize_time<S: serde::Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(time))
+}
+
+pub fn emojis_enabled() -> bool {
+    STYLE.get().is_none_or(|style| style.emojis)
+}
+
+pub fn quiet() -> bool {
+    STYLE.get().is_some_and(|style| style.quiet)
+}
+
+/// For output that bypasses `colored`, such as the dashboard
+pub fn colors_enabled() -> bool {
+    STYLE.get().is_none_or(|style| style.colors)
+}
+
+/// Replaces emoji with ASCII markers, dropping purely decorative ones. The
+/// padding after an emoji collapses to one space after a marker and none
+/// after a dropped emoji.
+pub fn strip_emojis(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    // Some(keep_one_space) while swallowing the padding after an emoji
+    let mut after_emoji: Option<bool> = None;
+
+    for c in text.chars() {
+        if let Some(keep_space) = after_emoji {
+            if c == ' ' || c == '\u{FE0F}' {
+                continue;
+            }
+            if keep_space && !is_emoji(c) {
+                result.push(' ');
+            }
+            after_emoji = None;
+        }
+
+        match ascii_marker(c) {
+            Some(Some(marker)) => {
+                result.push_str(marker);
+                after_emoji = Some(true);
+            }
+            Some(None) => after_emoji = Some(false),
+            None => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// `Some(Some(marker))` for emoji with a meaningful ASCII stand-in,
+/// `Some(None)` for decorative emoji to drop, `None` for ordinary text.
+fn ascii_marker(c: char) -> Option<Option<&'static str>> {
+    let marker = match c {
+        '✅' => "[OK]",
+        '❌' => "[ERROR]",
+        '⚠' => "[WARN]",
+        '🚨' => "[ALERT]",
+        '🛑' => "[STOP]",
+        'ℹ' => "[INFO]",
+        '🐛' => "[DEBUG]",
+        '🟡' => "[LOW]",
+        '🟠' => "[MED]",
+        '🔴' => "[HIGH]",
+        '💀' => "[CRIT]",
+        '⚪' => "[-]",
+        '🏠' => "[local]",
+        '⬆' => "^",
+        '⬇' => "v",
+        c if is_emoji(c) => return Some(None),
+        _ => return None,
+    };
+    Some(Some(marker))
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF | // pictographs, emoticons, flags
+        0x2300..=0x23FF |   // ⏱ and friends
+        0x2600..=0x27BF |   // misc symbols, dingbats
+        0x2B00..=0x2BFF |   // arrows and stars
+        0xFE0F              // emoji presentation selector
+    )
+}
+
+macro_rules! outln {
+    () => { if !$crate::style::quiet() { println!() } };
+    ($($arg:tt)*) => {
+        if $crate::style::quiet() {
+        } else if $crate::style::emojis_enabled() {
+            println!($($arg)*)
+        } else {
+            println!("{}", $crate::style::strip_emojis(&format!($($arg)*)))
+        }
+    };
+}
+
+macro_rules! out {
+    ($($arg:tt)*) => {
+        if $crate::style::quiet() {
+        } else if $crate::style::emojis_enabled() {
+            print!($($arg)*)
+        } else {
+            print!("{}", $crate::style::strip_emojis(&format!($($arg)*)))
+        }
+    };
+}
+
+/// `outln!` for per-packet records, which `--quiet` keeps.
+macro_rules! recordln {
+    ($($arg:tt)*) => {
+        if $crate::style::emojis_enabled() {
+            println!($($arg)*)
+        } else {
+            println!("{}", $crate::style::strip_emojis(&format!($($arg)*)))
+        }
+    };
+}
+
+macro_rules! errln {
+    () => { eprintln!() };
+    ($($arg:tt)*) => {
+        if $crate::style::emojis_enabled() {
+            eprintln!($($arg)*)
+        } else {
+            eprintln!("{}", $crate::style::strip_emojis(&format!($($arg)*)))
+        }
+    };
+}
@@ -0,0 +1,46 @@
+//! `alerts.syslog`: threat alerts written to the system log, where journald
+//! or an existing log shipper picks them up without parsing our log file.
+//! The severity follows the threat level. Elsewhere than Unix this is a no-op.
+
+use crate::ThreatLevel;
+
+/// Opens the system log once; sending is then just a `syslog(3)` call.
+#[derive(Debug, Clone)]
+pub struct AlertSyslog;
+
+impl AlertSyslog {
+    #[cfg(unix)]
+    pub fn open() -> Self {
+        // openlog keeps the pointer, so the ident has to be static
+        unsafe { libc::openlog(c"packet_sniffer".as_ptr(), libc::LOG_PID, libc::LOG_USER) };
+        AlertSyslog
+    }
+
+    #[cfg(not(unix))]
+    pub fn open() -> Self {
+        AlertSyslog
+    }
+
+    #[cfg(unix)]
+    pub fn send(&self, message: &str, level: &ThreatLevel) {
+        let priority = match level {
+            ThreatLevel::Critical => libc::LOG_CRIT,
+            ThreatLevel::High => libc::LOG_ERR,
+            ThreatLevel::Medium => libc::LOG_WARNING,
+            ThreatLevel::Low => libc::LOG_NOTICE,
+            ThreatLevel::Safe => libc::LOG_INFO,
+        };
+        // Alert text can carry packet data, and a NUL would cut the C string short
+        let message = std::ffi::CString::new(format!("[{}] {}", level.as_str(), message).replace('\0', "")).unwrap_or_default();
+        // Passed through "%s" so a '%' in the message isn't read as a format
+        unsafe { libc::syslog(priority, c"%s".as_ptr(), message.as_ptr()) };
+    }
+
+    #[cfg(not(unix))]
+    pub fn send(&self, _message: &str, _level: &ThreatLevel) {}
+
+    /// Whether alerts actually reach a system log on this platform.
+    pub fn supported() -> bool {
+        cfg!(unix)
+    }
+}
@@ -0,0 +1,93 @@
+//! Per-direction TCP sequence tracking for spotting loss. A segment starting
+//! past the next expected byte leaves a hole; a later segment inside a hole
+//! arrived out of order, while one covering bytes already seen is a
+//! retransmission. Only a handful of holes are remembered at a time.
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Older holes are dropped past this; a segment landing in one is then
+/// counted as a retransmission instead of out-of-order
+const MAX_HOLES: usize = 16;
+
+/// The same segment again this soon is the capture seeing it twice (loopback
+/// delivers every packet once as sent and once as received); no TCP stack
+/// retransmits that fast
+const DUPLICATE_CAPTURE_WINDOW_MICROS: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentKind {
+    InOrder,
+    Retransmission,
+    OutOfOrder,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SequenceTracker {
+    /// Sequence number expected next
+    next: Option<u32>,
+    /// Byte ranges `[start, end)` skipped over so far
+    holes: Vec<(u32, u32)>,
+    /// Sequence, length and time of the last segment with data
+    last: Option<(u32, u32, DateTime<Utc>)>,
+}
+
+impl SequenceTracker {
+    /// `len` is the sequence space the segment uses: its payload, plus one
+    /// for SYN or FIN. Bare ACKs (`len == 0`) say nothing and count as in order.
+    pub fn observe(&mut self, sequence: u32, len: u32, timestamp: DateTime<Utc>) -> SegmentKind {
+        if len == 0 {
+            return SegmentKind::InOrder;
+        }
+        let previous = self.last.replace((sequence, len, timestamp));
+        if previous.is_some_and(|(last_sequence, last_len, seen)| {
+            last_sequence == sequence && last_len == len && timestamp - seen < Duration::microseconds(DUPLICATE_CAPTURE_WINDOW_MICROS)
+        }) {
+            return SegmentKind::InOrder;
+        }
+        let end = sequence.wrapping_add(len);
+        let Some(next) = self.next else {
+            self.next = Some(end);
+            return SegmentKind::InOrder;
+        };
+
+        // Signed distance copes with sequence wraparound
+        let ahead = sequence.wrapping_sub(next) as i32;
+        if ahead >= 0 {
+            if ahead > 0 {
+                self.holes.push((next, sequence));
+                if self.holes.len() > MAX_HOLES {
+                    self.holes.remove(0);
+                }
+            }
+            self.next = Some(end);
+            return SegmentKind::InOrder;
+        }
+
+        // Overlapping the end of what's been seen still moves `next` forward
+        if (end.wrapping_sub(next) as i32) > 0 {
+            self.next = Some(end);
+        }
+        if self.fill_hole(sequence, end) {
+            SegmentKind::OutOfOrder
+        } else {
+            SegmentKind::Retransmission
+        }
+    }
+
+    /// Shrinks or splits the hole containing `sequence`, if any.
+    fn fill_hole(&mut self, sequence: u32, end: u32) -> bool {
+        let within = |start: u32, hole_end: u32, point: u32| point.wrapping_sub(start) < hole_end.wrapping_sub(start);
+        let Some(index) = self.holes.iter().position(|&(start, hole_end)| within(start, hole_end, sequence)) else {
+            return false;
+        };
+
+        let (start, hole_end) = self.holes.remove(index);
+        if sequence != start {
+            self.holes.push((start, sequence));
+        }
+        if within(start, hole_end, end) {
+            self.holes.push((end, hole_end));
+        }
+        true
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+/// Token bucket holding up to one second's worth of packets, so short bursts
+/// under the configured rate pass untouched while sustained overload is shed.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_per_second: usize,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// `max_per_second` of 0 disables the limit.
+    pub fn new(max_per_second: usize) -> Self {
+        RateLimiter {
+            max_per_second,
+            tokens: max_per_second as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token if one is available; `false` means drop the packet.
+    pub fn allow(&mut self) -> bool {
+        if self.max_per_second == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        let capacity = self.max_per_second as f64;
+        self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// `--sample`: passes one packet in every `every`, starting with the first.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    every: usize,
+    /// Packets since the last one passed
+    seen: usize,
+}
+
+impl Sampler {
+    /// `None` passes every packet.
+    pub fn new(every: Option<usize>) -> Self {
+        Sampler { every: every.unwrap_or(1), seen: 0 }
+    }
+
+    /// `false` means skip the packet.
+    pub fn take(&mut self) -> bool {
+        let take = self.seen == 0;
+        self.seen = (self.seen + 1) % self.every;
+        take
+    }
+}
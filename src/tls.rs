@@ -0,0 +1,117 @@
+//! TLS ClientHello parsing for SNI extraction. Every length field is checked
+//! against the remaining bytes, so non-TLS or truncated payloads yield `None`.
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const SERVER_NAME_HOST: u8 = 0x00;
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3).map(|b| ((b[0] as usize) << 16) | ((b[1] as usize) << 8) | b[2] as usize)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+/// Extracts the server_name from a TLS record carrying a ClientHello.
+pub fn parse_sni(payload: &[u8]) -> Option<String> {
+    let mut record = Cursor::new(payload);
+
+    if record.u8()? != CONTENT_TYPE_HANDSHAKE {
+        return None;
+    }
+    let major_version = record.u8()?;
+    let _minor_version = record.u8()?;
+    if major_version != 0x03 {
+        return None;
+    }
+    let record_len = record.u16()? as usize;
+
+    // A ClientHello can span segments; parse whatever part of the record we have
+    let available = record_len.min(record.remaining());
+    parse_client_hello(record.take(available)?)
+}
+
+/// Extracts the server_name from a bare ClientHello handshake message.
+pub fn parse_client_hello(handshake: &[u8]) -> Option<String> {
+    let mut hello = Cursor::new(handshake);
+
+    if hello.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let _handshake_len = hello.u24()?;
+
+    hello.take(2)?; // client_version
+    hello.take(32)?; // random
+    let session_id_len = hello.u8()? as usize;
+    hello.take(session_id_len)?;
+    let cipher_suites_len = hello.u16()? as usize;
+    hello.take(cipher_suites_len)?;
+    let compression_len = hello.u8()? as usize;
+    hello.take(compression_len)?;
+
+    let extensions_len = hello.u16()? as usize;
+    let extensions_len = extensions_len.min(hello.remaining());
+    let mut extensions = Cursor::new(hello.take(extensions_len)?);
+
+    while extensions.remaining() >= 4 {
+        let extension_type = extensions.u16()?;
+        let extension_len = extensions.u16()? as usize;
+        let extension = extensions.take(extension_len)?;
+
+        if extension_type == EXTENSION_SERVER_NAME {
+            return parse_server_name_list(extension);
+        }
+    }
+
+    None
+}
+
+fn parse_server_name_list(extension: &[u8]) -> Option<String> {
+    let mut cursor = Cursor::new(extension);
+    let list_len = cursor.u16()? as usize;
+    let mut list = Cursor::new(cursor.take(list_len)?);
+
+    while list.remaining() >= 3 {
+        let name_type = list.u8()?;
+        let name_len = list.u16()? as usize;
+        let name = list.take(name_len)?;
+
+        if name_type == SERVER_NAME_HOST {
+            let name = std::str::from_utf8(name).ok()?;
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_graphic()) {
+                return None;
+            }
+            return Some(name.to_string());
+        }
+    }
+
+    None
+}
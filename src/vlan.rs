@@ -0,0 +1,48 @@
+//! 802.1Q / 802.1ad tag unwrapping, so trunk-port captures still reach the
+//! IP and ARP decoders.
+
+use pnet::packet::ethernet::{EtherType, EtherTypes};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const TAG_LEN: usize = 4;
+
+// QinQ stacks two tags; anything deeper is left undecoded
+const MAX_TAGS: usize = 2;
+
+/// The ethertype and payload that follow any VLAN tags on a frame.
+pub struct UntaggedFrame<'a> {
+    pub ethertype: EtherType,
+    pub payload: &'a [u8],
+    /// Outermost first
+    pub vlan_ids: Vec<u16>,
+}
+
+/// Returns `None` for frames shorter than an Ethernet header or truncated
+/// inside a tag.
+pub fn untag(frame: &[u8]) -> Option<UntaggedFrame<'_>> {
+    let mut ethertype = read_ethertype(frame, 12)?;
+    let mut offset = ETHERNET_HEADER_LEN;
+    let mut vlan_ids = Vec::new();
+
+    while is_tag(ethertype) && vlan_ids.len() < MAX_TAGS {
+        let tci = read_ethertype(frame, offset)?.0;
+        vlan_ids.push(tci & 0x0FFF);
+        ethertype = read_ethertype(frame, offset + 2)?;
+        offset += TAG_LEN;
+    }
+
+    Some(UntaggedFrame {
+        ethertype,
+        payload: &frame[offset..],
+        vlan_ids,
+    })
+}
+
+fn is_tag(ethertype: EtherType) -> bool {
+    matches!(ethertype, EtherTypes::Vlan | EtherTypes::PBridge | EtherTypes::QinQ)
+}
+
+fn read_ethertype(frame: &[u8], offset: usize) -> Option<EtherType> {
+    let bytes = frame.get(offset..offset + 2)?;
+    Some(EtherType(u16::from_be_bytes([bytes[0], bytes[1]])))
+}
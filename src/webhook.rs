@@ -0,0 +1,204 @@
+//! `alerts.webhook_url`: threat alerts POSTed as JSON from a background
+//! thread, so capture never waits on the network. Alerts are gathered into
+//! batches and posts are spaced out, so a port scan raising hundreds of
+//! alerts turns into a few requests rather than a flood.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{PacketSnifferError, Result};
+use crate::ThreatLevel;
+
+/// Alerts waiting for the sender thread; past this they are counted, not kept
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Alerts in one post; the rest of a burst is only counted
+const MAX_BATCH_ALERTS: usize = 50;
+
+/// How long after the first alert of a batch more are gathered
+const BATCH_DELAY: Duration = Duration::from_secs(2);
+
+/// Least time between two posts
+const MIN_POST_INTERVAL: Duration = Duration::from_secs(10);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the end of a capture waits for the last batch to go out
+const FINISH_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Serialize)]
+struct AlertPost {
+    timestamp: DateTime<Utc>,
+    message: String,
+    level: ThreatLevel,
+    ips: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Batch<'a> {
+    alerts: &'a [AlertPost],
+    /// Alerts left out of this and earlier posts to stay within the limits
+    suppressed: usize,
+}
+
+enum Message {
+    Alert(AlertPost),
+    Finish(mpsc::Sender<WebhookSummary>),
+}
+
+/// What the webhook did over the capture.
+#[derive(Debug, Default)]
+pub struct WebhookSummary {
+    pub posted: usize,
+    pub suppressed: usize,
+    pub failed_posts: usize,
+}
+
+/// Cheap to clone; all clones feed one sender thread.
+#[derive(Debug, Clone)]
+pub struct AlertWebhook {
+    messages: SyncSender<Message>,
+    /// Alerts the queue had no room for
+    overflow: Arc<AtomicUsize>,
+}
+
+impl AlertWebhook {
+    pub fn start(address: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(address)
+            .map_err(|e| PacketSnifferError::ConfigError(format!("Invalid alerts.webhook_url '{}': {}", address, e)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(PacketSnifferError::ConfigError(format!("alerts.webhook_url must be http:// or https://, got '{}'", address)));
+        }
+
+        let (messages, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let overflow = Arc::new(AtomicUsize::new(0));
+        let sender_overflow = overflow.clone();
+        std::thread::spawn(move || post_alerts(url, receiver, sender_overflow));
+        Ok(AlertWebhook { messages, overflow })
+    }
+
+    /// Queues an alert without blocking.
+    pub fn send(&self, timestamp: DateTime<Utc>, message: &str, level: &ThreatLevel) {
+        let alert = AlertPost { timestamp, message: message.to_string(), level: level.clone(), ips: mentioned_ips(message) };
+        if let Err(TrySendError::Full(_)) = self.messages.try_send(Message::Alert(alert)) {
+            self.overflow.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Posts whatever is still batched, ignoring the spacing between posts,
+    /// and stops the sender thread. `None` if it didn't answer in time.
+    pub fn finish(&self) -> Option<WebhookSummary> {
+        let (done, summary) = mpsc::channel();
+        self.messages.send(Message::Finish(done)).ok()?;
+        summary.recv_timeout(FINISH_TIMEOUT).ok()
+    }
+}
+
+/// Addresses named in an alert message; the detectors put them there.
+fn mentioned_ips(message: &str) -> Vec<String> {
+    let mut ips: Vec<String> = Vec::new();
+    for word in message.split_whitespace() {
+        let candidates = [word, word.trim_end_matches([':', ',', '.'])];
+        if let Some(ip) = candidates.iter().find_map(|candidate| candidate.parse::<IpAddr>().ok()) {
+            let ip = ip.to_string();
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+    }
+    ips
+}
+
+/// The sender thread. Each batch starts with the first alert to arrive and
+/// goes out once `BATCH_DELAY` has passed and the last post is at least
+/// `MIN_POST_INTERVAL` old.
+fn post_alerts(url: reqwest::Url, messages: Receiver<Message>, overflow: Arc<AtomicUsize>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            errln!("Alert webhook disabled: {}", e);
+            return;
+        }
+    };
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            errln!("Alert webhook disabled: {}", e);
+            return;
+        }
+    };
+
+    let mut summary = WebhookSummary::default();
+    let mut batch = Vec::new();
+    let mut suppressed = 0;
+    let mut last_post: Option<Instant> = None;
+
+    loop {
+        let finished = match next_batch(&messages, &mut batch, &mut suppressed, last_post) {
+            Some(finished) => finished,
+            // Every handle is gone without asking to finish; nobody wants a summary
+            None => return,
+        };
+        suppressed += overflow.swap(0, Ordering::Relaxed);
+
+        if !batch.is_empty() {
+            let body = Batch { alerts: &batch, suppressed };
+            let result = runtime.block_on(async {
+                client.post(url.clone()).json(&body).send().await?.error_for_status()
+            });
+            match result {
+                Ok(_) => summary.posted += batch.len(),
+                Err(e) => {
+                    errln!("Failed to post {} alerts to the webhook: {}", batch.len(), e);
+                    summary.failed_posts += 1;
+                }
+            }
+            summary.suppressed += suppressed;
+            batch.clear();
+            suppressed = 0;
+            last_post = Some(Instant::now());
+        }
+
+        if let Some(done) = finished {
+            let _ = done.send(summary);
+            return;
+        }
+    }
+}
+
+/// Gathers one batch. Returns the finish request if one arrived, and `None`
+/// if the channel closed.
+fn next_batch(messages: &Receiver<Message>, batch: &mut Vec<AlertPost>, suppressed: &mut usize, last_post: Option<Instant>) -> Option<Option<mpsc::Sender<WebhookSummary>>> {
+    let add = |alert: AlertPost, batch: &mut Vec<AlertPost>, suppressed: &mut usize| {
+        if batch.len() < MAX_BATCH_ALERTS {
+            batch.push(alert);
+        } else {
+            *suppressed += 1;
+        }
+    };
+
+    match messages.recv().ok()? {
+        Message::Alert(alert) => add(alert, batch, suppressed),
+        Message::Finish(done) => return Some(Some(done)),
+    }
+
+    let mut send_at = Instant::now() + BATCH_DELAY;
+    if let Some(last_post) = last_post {
+        send_at = send_at.max(last_post + MIN_POST_INTERVAL);
+    }
+    loop {
+        let wait = send_at.saturating_duration_since(Instant::now());
+        match messages.recv_timeout(wait) {
+            Ok(Message::Alert(alert)) => add(alert, batch, suppressed),
+            Ok(Message::Finish(done)) => return Some(Some(done)),
+            Err(RecvTimeoutError::Timeout) => return Some(None),
+            // Post what's gathered; the next receive then sees the channel closed
+            Err(RecvTimeoutError::Disconnected) => return Some(None),
+        }
+    }
+}